@@ -0,0 +1,36 @@
+// Benchmark of the vendor lookups done after a scan, run with 'cargo bench'.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use arp_scan::{MacAddr, VendorDatabase};
+
+const LOOKUP_COUNT: u64 = 10_000;
+
+fn bench_vendor_lookup(criterion: &mut Criterion) {
+
+    let macs: Vec<MacAddr> = (0..LOOKUP_COUNT).map(|step| {
+        let bytes = (step.wrapping_mul(0x9e3779b97f4a7c15) >> 16).to_be_bytes();
+        MacAddr::new(bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7])
+    }).collect();
+
+    let mut group = criterion.benchmark_group("vendor_lookup");
+    group.throughput(Throughput::Elements(LOOKUP_COUNT));
+
+    for file_path in ["./data/ieee-oui.csv", "./data/ieee-registries.csv", "./data/manuf-sample.txt"] {
+
+        let database = VendorDatabase::load_from_path(file_path).unwrap_or_else(|err| panic!("{}", err));
+        group.bench_with_input(BenchmarkId::from_parameter(file_path), &macs, |bencher, macs| {
+            bencher.iter(|| {
+                for mac in macs.iter() {
+                    black_box(database.lookup(black_box(mac)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_vendor_lookup);
+criterion_main!(benches);
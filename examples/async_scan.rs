@@ -0,0 +1,34 @@
+// Run an ARP scan from a tokio program, with the 'async' feature:
+//
+//   cargo run --example async_scan --features async -- eth0
+//
+// Requests are paced with tokio timers while the responses are read on
+// blocking tasks. Dropping the future before completion stops the scan.
+
+use std::env;
+use std::process;
+use std::time::Duration;
+
+use arp_scan::ArpScanner;
+
+#[tokio::main]
+async fn main() {
+
+    let mut builder = ArpScanner::builder().timeout(Duration::from_millis(500));
+    if let Some(interface_name) = env::args().nth(1) {
+        builder = builder.interface(&interface_name);
+    }
+
+    match builder.build().scan().await {
+        Ok(result) => {
+            for host in result.hosts.iter() {
+                println!("{}\t{}\t{}", host.ipv4, host.mac, host.vendor.as_deref().unwrap_or("-"));
+            }
+            println!("{} hosts found on {}", result.hosts.len(), result.metadata.interface_name);
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
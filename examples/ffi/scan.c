@@ -0,0 +1,45 @@
+/*
+ * Run an ARP scan from C, with the shared library of the arp-scan crate:
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *   cc examples/ffi/scan.c -Iinclude -Ltarget/release -larp_scan -o scan
+ *   LD_LIBRARY_PATH=target/release ./scan eth0 192.168.1.0/24
+ *
+ * Scans require root permissions (or the CAP_NET_RAW capability).
+ */
+
+#include <stdio.h>
+#include "arp_scan.h"
+
+int main(int argc, char **argv) {
+
+    ArpScanConfig config = {
+        .interface = argc > 1 ? argv[1] : NULL,
+        .network = argc > 2 ? argv[2] : NULL,
+        .timeout_ms = 500,
+        .retry_count = 0,
+        .resolve_hostnames = true
+    };
+
+    ArpScanResult *result = NULL;
+    int32_t code = arp_scan_run(&config, &result);
+    if (code != ARP_SCAN_OK) {
+        fprintf(stderr, "Scan failed with code %d\n", code);
+        return code;
+    }
+
+    size_t host_count = arp_scan_result_host_count(result);
+    for (size_t index = 0; index < host_count; index++) {
+        const char *hostname = arp_scan_result_host_hostname(result, index);
+        const char *vendor = arp_scan_result_host_vendor(result, index);
+        printf("%s\t%s\t%s\t%s\n",
+            arp_scan_result_host_ipv4(result, index),
+            arp_scan_result_host_mac(result, index),
+            hostname ? hostname : "-",
+            vendor ? vendor : "-");
+    }
+    printf("%zu hosts found\n", host_count);
+
+    arp_scan_result_free(result);
+    return 0;
+}
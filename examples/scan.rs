@@ -0,0 +1,41 @@
+// Run an ARP scan from another program, with the scanner of the arp-scan
+// library. The interface and the network can be given as arguments:
+//
+//   cargo run --example scan -- eth0 192.168.1.0/24
+//
+// Scans require root permissions (or the CAP_NET_RAW capability).
+
+use std::env;
+use std::process;
+use std::time::Duration;
+
+use arp_scan::ArpScanner;
+
+fn main() {
+
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut builder = ArpScanner::builder()
+        .timeout(Duration::from_millis(500))
+        .resolve_hostnames(true);
+    if let Some(interface_name) = args.first() {
+        builder = builder.interface(interface_name);
+    }
+    if let Some(network) = args.get(1) {
+        let network = network.parse().unwrap_or_else(|err| {
+            eprintln!("Invalid network {} ({})", network, err);
+            process::exit(1);
+        });
+        builder = builder.network(network);
+    }
+
+    let result = builder.build().run().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    for host in result.hosts.iter() {
+        println!("{}\t{}\t{}\t{}", host.ipv4, host.mac, host.hostname.as_deref().unwrap_or("-"), host.vendor.as_deref().unwrap_or("-"));
+    }
+    println!("{} hosts found on {} ({} packets received)", result.hosts.len(), result.metadata.interface_name, result.summary.packet_count);
+}
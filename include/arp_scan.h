@@ -0,0 +1,166 @@
+#ifndef ARP_SCAN_H
+#define ARP_SCAN_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define ARP_SCAN_OK 0
+
+#define ARP_SCAN_ERROR_USAGE 64
+
+#define ARP_SCAN_ERROR_INTERNAL 70
+
+/**
+ * Hosts found by a scan, as an opaque object for C callers. All strings
+ * given by the accessors are owned by the result, and remain valid until
+ * the result is freed with 'arp_scan_result_free'.
+ */
+typedef struct ArpScanResult ArpScanResult;
+
+/**
+ * Scan configuration given by C callers. Strings are optional NUL-terminated
+ * UTF-8 strings: without interface, the default interface is scanned, and
+ * without network the IPv4 networks of the interface are targeted (several
+ * networks are separated by commas). A zero timeout keeps the default
+ * timeout of the CLI.
+ */
+typedef struct ArpScanConfig {
+  const char *interface;
+  const char *network;
+  uint64_t timeout_ms;
+  uint32_t retry_count;
+  bool resolve_hostnames;
+} ArpScanConfig;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Run a single scan round with the given configuration. On success, the
+ * result is written to 'result' and must be freed by the caller. On error, a
+ * non-zero code is returned and 'result' is left untouched. Panics are
+ * caught and reported as internal errors, they never unwind into C code.
+ *
+ * # Safety
+ *
+ * 'config' must be null or point to a valid configuration (with null or
+ * NUL-terminated strings), and 'result' must be null or writable.
+ */
+int32_t arp_scan_run(const struct ArpScanConfig *config, struct ArpScanResult **result);
+
+/**
+ * Free a result returned by 'arp_scan_run', freeing a null result does
+ * nothing.
+ *
+ * # Safety
+ *
+ * 'result' must be null or a result returned by 'arp_scan_run' that has not
+ * been freed yet.
+ */
+void arp_scan_result_free(struct ArpScanResult *result);
+
+/**
+ * Count the hosts of a result (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+size_t arp_scan_result_host_count(const struct ArpScanResult *result);
+
+/**
+ * Count the frames received during the scan (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+size_t arp_scan_result_packet_count(const struct ArpScanResult *result);
+
+/**
+ * Count the ARP packets received during the scan (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+size_t arp_scan_result_arp_count(const struct ArpScanResult *result);
+
+/**
+ * Count the ARP requests sent during the scan, including retries (zero for
+ * a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+size_t arp_scan_result_request_count(const struct ArpScanResult *result);
+
+/**
+ * Count the ARP requests that could not be sent on the interface (zero for
+ * a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+size_t arp_scan_result_send_errors(const struct ArpScanResult *result);
+
+/**
+ * Duration of the scan in milliseconds (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+uint64_t arp_scan_result_duration_ms(const struct ArpScanResult *result);
+
+/**
+ * IPv4 address of a host (such as "192.168.1.1"), or null if the index is
+ * out of range.
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+const char *arp_scan_result_host_ipv4(const struct ArpScanResult *result, size_t index);
+
+/**
+ * MAC address of a host (such as "00:22:72:d7:b5:23"), or null if the index
+ * is out of range.
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+const char *arp_scan_result_host_mac(const struct ArpScanResult *result, size_t index);
+
+/**
+ * Hostname of a host, or null if unknown (or if the index is out of range).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+const char *arp_scan_result_host_hostname(const struct ArpScanResult *result, size_t index);
+
+/**
+ * MAC vendor of a host, or null if unknown (or if the index is out of range).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+const char *arp_scan_result_host_vendor(const struct ArpScanResult *result, size_t index);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ARP_SCAN_H */
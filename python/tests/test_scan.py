@@ -0,0 +1,41 @@
+# Argument validation of the Python module, no raw socket is needed. Build
+# the module with 'maturin develop' and run 'python -m pytest python/tests'.
+
+import pytest
+
+import arp_scan
+
+
+@pytest.mark.parametrize("networks", [["192.168.1.0/33"], ["fe80::/64"], ["192.168.1"]])
+def test_should_reject_invalid_networks(networks):
+
+    with pytest.raises(ValueError):
+        arp_scan.scan(networks=networks)
+
+
+def test_should_reject_invalid_argument_types():
+
+    with pytest.raises(TypeError):
+        arp_scan.scan(networks="192.168.1.0/24")
+    with pytest.raises(TypeError):
+        arp_scan.scan(resolve="yes")
+    with pytest.raises(TypeError):
+        arp_scan.scan(unknown_option=True)
+
+
+def test_should_reject_invalid_options():
+
+    with pytest.raises(ValueError):
+        arp_scan.scan(timeout_ms=0)
+    with pytest.raises(ValueError):
+        arp_scan.scan(interface="")
+    with pytest.raises(ValueError):
+        arp_scan.scan(excluded_networks=["192.168.1.0/33"])
+    with pytest.raises(OverflowError):
+        arp_scan.scan(retry_count=-1)
+
+
+def test_should_raise_os_error_for_missing_interface():
+
+    with pytest.raises(OSError):
+        arp_scan.scan(interface="arp-scan-missing0", networks=["192.168.1.0/24"], timeout_ms=10)
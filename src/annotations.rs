@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::fs;
+
+use pnet_datalink::MacAddr;
+
+/**
+ * Load user annotations, a YAML (or JSON, which is valid YAML) map of MAC
+ * addresses to free text labels. MAC keys are normalized, so that any case
+ * and the usual separators (colons, dashes or Cisco dots) can be used.
+ */
+pub fn load_annotations(file_path: &str) -> Result<HashMap<MacAddr, String>, String> {
+
+    let content = fs::read_to_string(file_path).map_err(|err| format!("Could not open annotations file {} ({})", file_path, err))?;
+    parse_annotations(&content).map_err(|err| format!("Malformed annotations file {} ({})", file_path, err))
+}
+
+fn parse_annotations(content: &str) -> Result<HashMap<MacAddr, String>, String> {
+
+    let raw_annotations: HashMap<String, String> = match content.trim().is_empty() {
+        true => HashMap::new(),
+        false => serde_yaml::from_str(content).map_err(|err| err.to_string())?
+    };
+    raw_annotations.into_iter().map(|(raw_mac, note)| {
+        normalize_mac(&raw_mac).map(|mac| (mac, note)).ok_or_else(|| format!("invalid MAC address '{}'", raw_mac))
+    }).collect()
+}
+
+/**
+ * Normalize a MAC address written as 12 hexadecimal digits with optional
+ * separators, such as '00:22:72:D7:B5:23', '00-22-72-d7-b5-23' or
+ * '0022.72d7.b523'.
+ */
+pub fn normalize_mac(raw_mac: &str) -> Option<MacAddr> {
+
+    let digits: String = raw_mac.trim().chars().filter(|character| !matches!(character, ':' | '-' | '.')).collect();
+    if digits.len() != 12 || !digits.chars().all(|character| character.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut octets = [0u8; 6];
+    for (index, octet) in octets.iter_mut().enumerate() {
+        *octet = u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(MacAddr::new(octets[0], octets[1], octets[2], octets[3], octets[4], octets[5]))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_load_yaml_annotations() {
+
+        let annotations = load_annotations("./data/annotations.yaml").unwrap();
+
+        assert_eq!(annotations.len(), 4);
+        assert_eq!(annotations.get(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)), Some(&"Alice's laptop".to_string()));
+        assert_eq!(annotations.get(&MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b)), Some(&"rack 3 PDU".to_string()));
+        assert_eq!(annotations.get(&MacAddr::new(0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56)), Some(&"Kitchen Raspberry Pi".to_string()));
+    }
+
+    #[test]
+    fn should_load_json_annotations() {
+
+        let annotations = load_annotations("./data/annotations.json").unwrap();
+
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations.get(&MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b)), Some(&"rack 3 PDU".to_string()));
+    }
+
+    #[test]
+    fn should_normalize_mac_keys() {
+
+        let mac = Some(MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23));
+
+        assert_eq!(normalize_mac("00:22:72:D7:B5:23"), mac);
+        assert_eq!(normalize_mac("00-22-72-d7-b5-23"), mac);
+        assert_eq!(normalize_mac("0022.72d7.b523"), mac);
+        assert_eq!(normalize_mac("002272d7b523"), mac);
+        assert_eq!(normalize_mac("00:22:72:d7:b5"), None);
+        assert_eq!(normalize_mac("00:22:72:d7:b5:zz"), None);
+    }
+
+    #[test]
+    fn should_reject_malformed_annotations() {
+
+        assert!(parse_annotations("00:22:72:d7:b5:23: [laptop").is_err());
+        assert!(parse_annotations("laptop: Alice's laptop").is_err());
+        assert!(parse_annotations("- 00:22:72:d7:b5:23").is_err());
+        assert_eq!(parse_annotations(""), Ok(HashMap::new()));
+    }
+
+    #[test]
+    fn should_reject_missing_annotations_file() {
+
+        assert!(load_annotations("./data/missing-annotations.yaml").is_err());
+    }
+
+}
@@ -1,23 +1,50 @@
 use std::str::FromStr;
-use std::net::Ipv4Addr;
-use std::process;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, OnceLock};
 use std::path::Path;
 use std::fs;
-
-use clap::{Arg, ArgMatches, Command};
-use ipnetwork::IpNetwork;
-use pnet_datalink::MacAddr;
-use pnet::packet::arp::{ArpHardwareType, ArpOperation};
+use std::io::{self, Read};
+use std::error::Error;
+use std::fmt;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use ipnetwork::{IpNetwork, Ipv4Network};
+use pnet_datalink::{MacAddr, NetworkInterface};
+use pnet::packet::arp::{ArpHardwareType, ArpOperation, ArpOperations};
 use pnet::packet::ethernet::EtherType;
 
+use crate::annotations;
+use crate::cache;
+use crate::dhcp;
+use crate::dns;
+use crate::error::ScanError;
+use crate::mdns;
+use crate::netbios;
+use crate::network::HostnameSource;
+use crate::respond::SimulatedHost;
 use crate::time::parse_to_milliseconds;
+use crate::vendor;
+use crate::utils::{self, parse_template, TemplatePart, HOST_TEMPLATE_FIELDS, SUMMARY_TEMPLATE_FIELDS};
 
 const TIMEOUT_MS_FAST: u64 = 800;
 const TIMEOUT_MS_DEFAULT: u64 = 2000;
 
-const HOST_RETRY_DEFAULT: usize = 1;
+const HOST_RETRY_DEFAULT: usize = 0;
 const REQUEST_MS_INTERVAL: u64 = 10;
+const LOOP_INTERVAL_MS_DEFAULT: u64 = 5 * 60 * 1000;
+
+const ANNOUNCE_COUNT_DEFAULT: usize = 3;
+const ANNOUNCE_INTERVAL_MS_DEFAULT: u64 = 1000;
+
+const WAKE_GRACE_MS_DEFAULT: u64 = 10 * 1000;
+
+const RESOLVE_CONCURRENCY_DEFAULT: usize = 20;
+const DNS_TIMEOUT_MS_DEFAULT: u64 = 1000;
+const RESOLVE_TIMEOUT_MS_MAX: u64 = 60_000;
+const RESOLVE_CACHE_TTL_MS_DEFAULT: u64 = 24 * 60 * 60 * 1000;
+
+const STDIN_FILE_PATH: &str = "-";
 
 const CLI_VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -35,6 +62,21 @@ const EXAMPLES_HELP: &str = "EXAMPLES:
     # Launch a scan on VLAN 45 with JSON output
     arp-scan -Q 45 -o json
 
+    # Scan the hosts found in a previous JSON scan
+    jq -r '.results[].ipv4' scan.json | arp-scan -i eth0 --stdin
+
+    # Check that an IPv4 address is free before assigning it
+    arp-scan -i eth0 --probe 192.168.1.50
+
+    # Check that the known neighbors of eth0 are still alive
+    arp-scan -i eth0 --verify
+
+    # Wake two sleeping hosts, then scan after a 30 seconds grace period
+    arp-scan -i eth0 --wake 00:22:72:d7:b5:23,40:55:82:c3:e5:5b --wake-grace 30s
+
+    # Announce a failed-over virtual IP with 5 gratuitous ARP frames
+    arp-scan -i eth0 --gratuitous 192.168.1.250 --announce-count 5
+
 ";
 
 /**
@@ -43,18 +85,46 @@ const EXAMPLES_HELP: &str = "EXAMPLES:
  */
 pub fn build_args<'a>() -> Command<'a> {
 
-    Command::new("arp-scan")
+    let command = Command::new("arp-scan")
         .version(CLI_VERSION)
         .about("A minimalistic ARP scan tool written in Rust")
         .arg(
             Arg::new("profile").short('p').long("profile")
                 .takes_value(true).value_name("PROFILE_NAME")
-                .help("Scan profile")
+                .help("Scan profile (default, fast, stealth, chaos) or name of a saved profile")
+        )
+        .arg(
+            Arg::new("save_profile").long("save-profile")
+                .takes_value(true).value_name("PROFILE_NAME")
+                .help("Save the given options as a named profile, for use with --profile")
+        )
+        .arg(
+            Arg::new("list_profiles").long("list-profiles")
+                .takes_value(false)
+                .help("List the profiles of the config file and the saved profiles")
+        )
+        .arg(
+            Arg::new("config").long("config")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("Read default options from this TOML config file (instead of ~/.config/arp-scan/config.toml)")
+        )
+        .arg(
+            Arg::new("no_config").long("no-config")
+                .takes_value(false)
+                .conflicts_with("config")
+                .help("Ignore the config file, for reproducible runs")
         )
         .arg(
             Arg::new("interface").short('i').long("interface")
                 .takes_value(true).value_name("INTERFACE_NAME")
-                .help("Network interface")
+                .action(ArgAction::Append)
+                .help("Network interface (can be repeated)")
+        )
+        .arg(
+            Arg::new("all_interfaces").long("all-interfaces")
+                .takes_value(false)
+                .conflicts_with_all(&["interface", "network", "file", "stdin"])
+                .help("Scan the networks of all eligible interfaces")
         )
         .arg(
             Arg::new("network").short('n').long("network")
@@ -64,8 +134,23 @@ pub fn build_args<'a>() -> Command<'a> {
         .arg(
             Arg::new("file").short('f').long("file")
                 .takes_value(true).value_name("FILE_PATH")
-                .conflicts_with("network")
-                .help("Read IPv4 addresses from a file")
+                .help("Read IPv4 targets from a file (merged with --network, '-' for stdin)")
+        )
+        .arg(
+            Arg::new("stdin").long("stdin")
+                .takes_value(false)
+                .conflicts_with("file")
+                .help("Read IPv4 targets from stdin (same as '--file -')")
+        )
+        .arg(
+            Arg::new("exclude").long("exclude")
+                .takes_value(true).value_name("IPS_OR_NETWORKS")
+                .help("Exclude IPv4 addresses or networks from the targets")
+        )
+        .arg(
+            Arg::new("exclude_file").long("exclude-file")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("Read excluded IPv4 addresses or networks from a file")
         )
         .arg(
             Arg::new("timeout").short('t').long("timeout")
@@ -85,33 +170,267 @@ pub fn build_args<'a>() -> Command<'a> {
         .arg(
             Arg::new("source_mac").long("source-mac")
                 .takes_value(true).value_name("SOURCE_MAC")
-                .help("Source MAC address for requests")
+                .help("Source MAC address for requests (Ethernet & ARP sender)")
+        )
+        .arg(
+            Arg::new("arp_sha").long("arp-sha")
+                .takes_value(true).value_name("SENDER_MAC")
+                .help("ARP sender hardware address, if different from the Ethernet source MAC")
         )
         .arg(
             Arg::new("numeric").long("numeric")
                 .takes_value(false)
                 .help("Numeric mode, no hostname resolution")
         )
+        .arg(
+            Arg::new("dns_server").long("dns-server")
+                .takes_value(true).value_name("IP[:PORT]")
+                .conflicts_with("numeric")
+                .help("DNS servers for reverse lookups, tried in order (comma-separated)")
+        )
+        .arg(
+            Arg::new("hosts_file").long("hosts-file")
+                .takes_value(true).value_name("FILE_PATH")
+                .conflicts_with("numeric")
+                .help("Hosts file with static hostnames, checked before DNS")
+        )
+        .arg(
+            Arg::new("dhcp_leases").long("dhcp-leases")
+                .takes_value(true).value_name("FILE_PATH")
+                .conflicts_with("numeric")
+                .help("DHCP lease file (dnsmasq or ISC dhcpd) with hostnames of MAC addresses")
+        )
+        .arg(
+            Arg::new("annotations").long("annotations")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("YAML or JSON file with notes of MAC addresses")
+        )
+        .arg(
+            Arg::new("resolve_cache_ttl").long("resolve-cache-ttl")
+                .takes_value(true).value_name("TTL_DURATION")
+                .conflicts_with("numeric")
+                .help("Reuse hostnames resolved by previous scans for this duration (default 24h)")
+        )
+        .arg(
+            Arg::new("no_resolve_cache").long("no-resolve-cache")
+                .takes_value(false)
+                .conflicts_with_all(&["numeric", "resolve_cache_ttl"])
+                .help("Do not cache resolved hostnames between scans")
+        )
+        .arg(
+            Arg::new("resolve_order").long("resolve-order")
+                .takes_value(true).value_name("METHODS")
+                .conflicts_with("numeric")
+                .help("Hostname resolution methods, tried in order (hosts, dns, mdns, netbios, dhcp)")
+        )
+        .arg(
+            Arg::new("resolve_mdns").long("resolve-mdns")
+                .takes_value(false)
+                .conflicts_with("numeric")
+                .help("Resolve hostnames missing from reverse DNS with mDNS")
+        )
+        .arg(
+            Arg::new("resolve_netbios").long("resolve-netbios")
+                .takes_value(false)
+                .conflicts_with("numeric")
+                .help("Resolve missing hostnames with NetBIOS name queries")
+        )
+        .arg(
+            Arg::new("resolver").long("resolver")
+                .takes_value(true).value_name("RESOLVER")
+                .conflicts_with_all(&["numeric", "dns_server"])
+                .help("Reverse DNS backend, systemd-resolved is used when running (system/systemd)")
+        )
+        .arg(
+            Arg::new("hostname_style").long("hostname-style")
+                .takes_value(true).value_name("STYLE")
+                .conflicts_with("numeric")
+                .help("Keep fully qualified hostnames or only the first label (full/short)")
+        )
+        .arg(
+            Arg::new("resolve_concurrency").long("resolve-concurrency")
+                .takes_value(true).value_name("WORKERS")
+                .conflicts_with("numeric")
+                .help("Hostname lookups running at the same time (default 20)")
+        )
+        .arg(
+            Arg::new("dns_timeout").long("dns-timeout").alias("resolve-timeout")
+                .takes_value(true).value_name("TIMEOUT_DURATION")
+                .conflicts_with("numeric")
+                .help("Timeout of each reverse DNS lookup (default 1s)")
+        )
+        .arg(
+            Arg::new("mdns_timeout").long("mdns-timeout")
+                .takes_value(true).value_name("TIMEOUT_DURATION")
+                .conflicts_with("numeric")
+                .help("Time waiting for mDNS answers (default 1s)")
+        )
+        .arg(
+            Arg::new("netbios_timeout").long("netbios-timeout")
+                .takes_value(true).value_name("TIMEOUT_DURATION")
+                .conflicts_with("numeric")
+                .help("Time waiting for NetBIOS answers (default 800ms)")
+        )
         .arg(
             Arg::new("vlan").short('Q').long("vlan")
-                .takes_value(true).value_name("VLAN_ID")
-                .help("Send using 802.1Q with VLAN ID")
+                .takes_value(true).value_name("VLAN_IDS")
+                .help("Send using 802.1Q with VLAN IDs (comma-separated)")
+        )
+        .arg(
+            Arg::new("vlan_priority").long("vlan-priority")
+                .takes_value(true).value_name("PCP")
+                .requires("vlan")
+                .help("802.1Q priority code point (0-7, default 0)")
         )
         .arg(
             Arg::new("retry_count").short('r').long("retry")
                 .takes_value(true).value_name("RETRY_COUNT")
-                .help("Host retry attempt count")
+                .help("Retry count for hosts that did not answer")
+        )
+        .arg(
+            Arg::new("max_duration").long("max-duration")
+                .takes_value(true).value_name("SECONDS")
+                .help("Stop the scan after a maximum duration (in seconds)")
+        )
+        .arg(
+            Arg::new("max_hosts").long("max-hosts")
+                .takes_value(true).value_name("COUNT")
+                .help("Stop the scan once a number of hosts have answered")
+        )
+        .arg(
+            Arg::new("strict").long("strict")
+                .takes_value(false)
+                .requires("max_duration")
+                .help("Exit with an error if the scan has been truncated")
+        )
+        .arg(
+            Arg::new("fail_on_conflict").long("fail-on-conflict")
+                .takes_value(false)
+                .help("Exit with an error if an IPv4 is answered by several MAC addresses")
+        )
+        .arg(
+            Arg::new("loop").long("loop")
+                .takes_value(false)
+                .help("Repeat the scan until a halt signal is received")
+        )
+        .arg(
+            Arg::new("loop_interval").long("loop-interval")
+                .takes_value(true).value_name("LOOP_DURATION")
+                .requires("loop")
+                .help("Delay between two scan rounds in loop mode (default 5m)")
+        )
+        .arg(
+            Arg::new("state_file").long("state-file")
+                .takes_value(true).value_name("FILE_PATH")
+                .conflicts_with_all(&["loop", "passive", "verify"])
+                .help("Checkpoint the scan progress to resume an interrupted scan")
+        )
+        .arg(
+            Arg::new("passive").long("passive")
+                .takes_value(false)
+                .help("Only listen to ARP traffic, without sending requests")
+        )
+        .arg(
+            Arg::new("dry_run").long("dry-run")
+                .takes_value(false)
+                .conflicts_with_all(&["passive", "loop", "state_file", "verify", "wake", "wake_file"])
+                .help("Print the planned ARP requests and an example frame, without sending anything")
+        )
+        .arg(
+            Arg::new("read_pcap").long("read-pcap")
+                .takes_value(true).value_name("FILE_PATH")
+                .conflicts_with_all(&["dry_run", "loop", "state_file", "verify", "include_arp_cache", "all_interfaces", "wake", "wake_file", "capture"])
+                .help("Replay the frames of a pcap file instead of scanning, no request is sent")
+        )
+        .arg(
+            Arg::new("gratuitous").long("gratuitous")
+                .takes_value(true).value_name("IPV4_ADDRESS")
+                .conflicts_with_all(&["passive", "loop", "all_interfaces", "dry_run", "read_pcap"])
+                .help("Announce an IPv4 address with gratuitous ARP frames")
+        )
+        .arg(
+            Arg::new("probe").long("probe")
+                .takes_value(true).value_name("IPV4_ADDRESS")
+                .conflicts_with_all(&["gratuitous", "passive", "loop", "all_interfaces", "dry_run", "read_pcap"])
+                .help("Check if an IPv4 address is already in use (RFC 5227 ARP probe)")
+        )
+        .arg(
+            Arg::new("verify").long("verify")
+                .takes_value(false)
+                .conflicts_with_all(&["gratuitous", "probe", "passive", "network", "file", "stdin"])
+                .help("Verify kernel neighbor entries with unicast ARP requests")
+        )
+        .arg(
+            Arg::new("include_arp_cache").long("include-arp-cache")
+                .takes_value(false)
+                .conflicts_with_all(&["verify", "state_file"])
+                .help("Add kernel ARP cache entries of the interface to the results")
+        )
+        .arg(
+            Arg::new("include_unsolicited").long("include-unsolicited")
+                .takes_value(false)
+                .help("Merge hosts answering for IPv4 addresses that were not targeted into the results")
+        )
+        .arg(
+            Arg::new("wake").long("wake")
+                .takes_value(true).value_name("MAC_ADDRESSES")
+                .help("Send Wake-on-LAN magic packets to MAC addresses before scanning")
+        )
+        .arg(
+            Arg::new("wake_file").long("wake-file")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("Read MAC addresses to wake from a file")
+        )
+        .arg(
+            Arg::new("wake_grace").long("wake-grace")
+                .takes_value(true).value_name("GRACE_DURATION")
+                .help("Delay between magic packets and the scan (default 10s)")
+        )
+        .arg(
+            Arg::new("wake_only").long("wake-only")
+                .takes_value(false)
+                .conflicts_with_all(&["wake_grace", "loop", "passive", "verify"])
+                .help("Only send Wake-on-LAN magic packets, without scanning")
+        )
+        .arg(
+            Arg::new("announce_count").long("announce-count")
+                .takes_value(true).value_name("COUNT")
+                .requires("gratuitous")
+                .help("Number of gratuitous ARP frames to send (default 3)")
+        )
+        .arg(
+            Arg::new("announce_interval").long("announce-interval")
+                .takes_value(true).value_name("INTERVAL_DURATION")
+                .requires("gratuitous")
+                .help("Delay between gratuitous ARP frames (default 1s)")
+        )
+        .arg(
+            Arg::new("force").long("force")
+                .takes_value(false)
+                .help("Announce an IPv4 address not owned by the interface, or send unusual requests (foreign source IPv4, unicast destination MAC, multicast source MAC)")
         )
         .arg(
             Arg::new("random").short('R').long("random")
+                .alias("random-order")
                 .takes_value(false)
                 .help("Randomize the target list")
         )
+        .arg(
+            Arg::new("seed").long("seed")
+                .takes_value(true).value_name("SEED")
+                .help("Seed of the random target order (implies --random)")
+        )
         .arg(
             Arg::new("interval").short('I').long("interval")
                 .takes_value(true).value_name("INTERVAL_DURATION")
                 .help("Milliseconds between ARP requests")
         )
+        .arg(
+            Arg::new("rate").long("rate")
+                .takes_value(true).value_name("PACKETS_PER_SECOND")
+                .conflicts_with("bandwidth")
+                .help("Send ARP requests at a given rate (overrides interval)")
+        )
         .arg(
             Arg::new("bandwidth").short('B').long("bandwidth")
                 .takes_value(true).value_name("BITS")
@@ -119,20 +438,135 @@ pub fn build_args<'a>() -> Command<'a> {
                 .help("Limit scan bandwidth (bits/second)")
         )
         .arg(
-            Arg::new("oui-file").long("oui-file")
+            Arg::new("oui-file").long("vendor-file")
+                .alias("oui-file")
                 .takes_value(true).value_name("FILE_PATH")
                 .help("Path to custom IEEE OUI CSV file")
         )
+        .arg(
+            Arg::new("top_vendors").long("top-vendors")
+                .takes_value(true).value_name("COUNT")
+                .help("Show the host count of the top vendors (JSON & YAML export as well)")
+        )
+        .arg(
+            Arg::new("group_by_mac").long("group-by-mac")
+                .takes_value(false)
+                .help("Show one block per MAC address with all its IPv4 addresses (JSON & YAML export as well)")
+        )
+        .arg(
+            Arg::new("update_vendors").long("update-vendors")
+                .takes_value(false)
+                .help("Download the latest IEEE OUI database for the current user")
+        )
+        .arg(
+            Arg::new("offline").long("offline")
+                .takes_value(false)
+                .conflicts_with_all(&["oui-file", "update_vendors"])
+                .help("Use the system vendor database instead of the downloaded one")
+        )
         .arg(
             Arg::new("list").short('l').long("list")
                 .takes_value(false)
                 .help("List network interfaces")
         )
+        .arg(
+            Arg::new("schema").long("schema")
+                .takes_value(false)
+                .help("Print the JSON Schema of the JSON & YAML exports and exit")
+        )
+        .arg(
+            Arg::new("verbose").short('v').long("verbose")
+                .takes_value(false)
+                .help("Print resolution details on stderr")
+        )
+        .arg(
+            Arg::new("progress").long("progress")
+                .takes_value(false)
+                .help("Display a progress bar on stderr while scanning")
+        )
         .arg(
             Arg::new("output").short('o').long("output")
                 .takes_value(true).value_name("FORMAT")
                 .help("Define output format")
         )
+        .arg(
+            Arg::new("format_string").long("format-string")
+                .takes_value(true).value_name("TEMPLATE")
+                .conflicts_with("output")
+                .help("Output template for each host, such as '{ipv4}\\t{mac}'")
+        )
+        .arg(
+            Arg::new("summary_format").long("summary-format")
+                .takes_value(true).value_name("TEMPLATE")
+                .requires("format_string")
+                .help("Output template for the summary line, such as '{host_count} hosts'")
+        )
+        .arg(
+            Arg::new("output_file").long("output-file")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("Write the output to a file ('-' for stdout)")
+        )
+        .arg(
+            Arg::new("pretty").long("pretty")
+                .takes_value(false)
+                .help("Pretty-print JSON output")
+        )
+        .arg(
+            Arg::new("columns").long("columns")
+                .takes_value(true).value_name("COLUMNS")
+                .help("Columns of the plain table & CSV output (ipv4,mac,hostname,vendor,response_time_ms,interface,status,vlan)")
+        )
+        .arg(
+            Arg::new("csv_headers").long("csv-headers")
+                .takes_value(false)
+                .conflicts_with("no_csv_headers")
+                .help("Include a header row in CSV output (default)")
+        )
+        .arg(
+            Arg::new("no_csv_headers").long("no-csv-headers")
+                .takes_value(false)
+                .help("Remove the header row from CSV output")
+        )
+        .arg(
+            Arg::new("csv_delimiter").long("csv-delimiter")
+                .takes_value(true).value_name("CHARACTER")
+                .help("Field delimiter of CSV output (default ',')")
+        )
+        .arg(
+            Arg::new("csv_quote_all").long("csv-quote-all")
+                .takes_value(false)
+                .help("Quote all fields in CSV output")
+        )
+        .arg(
+            Arg::new("csv_summary_file").long("csv-summary-file")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("Write the CSV scan summary to a file")
+        )
+        .arg(
+            Arg::new("allowlist").long("allowlist")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("Expected IPv4 or MAC addresses for JUnit output")
+        )
+        .arg(
+            Arg::new("only_resolved").long("only-resolved")
+                .takes_value(false)
+                .help("Skip hosts without resolved hostname (hosts & dnsmasq outputs)")
+        )
+        .arg(
+            Arg::new("ansible_group_by").long("ansible-group-by")
+                .takes_value(true).value_name("GROUPING")
+                .help("Group hosts of the Ansible inventory (vendor/subnet)")
+        )
+        .arg(
+            Arg::new("gelf").long("gelf")
+                .takes_value(true).value_name("HOST:PORT")
+                .help("Send results to a Graylog GELF UDP input")
+        )
+        .arg(
+            Arg::new("capture").long("capture")
+                .takes_value(true).value_name("FILE_PATH")
+                .help("Save received frames to a pcap file")
+        )
         .arg(
             Arg::new("hw_type").long("hw-type")
                 .takes_value(true).value_name("HW_TYPE")
@@ -155,19 +589,128 @@ pub fn build_args<'a>() -> Command<'a> {
         )
         .arg(
             Arg::new("arp_operation").long("arp-op")
-                .takes_value(true).value_name("OPERATION_ID")
-                .help("Custom ARP operation ID")
+                .takes_value(true).value_name("OPERATION")
+                .help("ARP operation (request, reply or numeric ID)")
+        )
+        .subcommand(
+            Command::new("respond")
+                .hide(true)
+                .about("Answer ARP requests for simulated hosts, to test scans end-to-end")
+                .arg(
+                    Arg::new("interface").short('i').long("interface")
+                        .takes_value(true).value_name("INTERFACE_NAME")
+                        .required(true)
+                        .help("Network interface receiving the requests")
+                )
+                .arg(
+                    Arg::new("host").long("host")
+                        .takes_value(true).value_name("IPV4[=MAC]")
+                        .action(ArgAction::Append)
+                        .required(true)
+                        .help("Simulated host (can be repeated, a MAC is generated if omitted)")
+                )
+                .arg(
+                    Arg::new("delay").long("delay")
+                        .takes_value(true).value_name("DELAY_DURATION")
+                        .help("Delay before each reply")
+                )
+                .arg(
+                    Arg::new("loss").long("loss")
+                        .takes_value(true).value_name("PERCENT")
+                        .help("Share of requests left unanswered (0-100)")
+                )
+                .arg(
+                    Arg::new("seed").long("seed")
+                        .takes_value(true).value_name("SEED")
+                        .help("Seed of the random losses")
+                )
+                .arg(
+                    Arg::new("vlan").short('Q').long("vlan")
+                        .takes_value(true).value_name("VLAN_ID")
+                        .help("Only answer 802.1Q requests of this VLAN")
+                )
         )
-        .after_help(EXAMPLES_HELP)
+        .after_help(EXAMPLES_HELP);
+
+    // The SQLite export is only available in builds with the 'sqlite' feature.
+    #[cfg(feature = "sqlite")]
+    let command = command.arg(
+        Arg::new("output_sqlite").long("output-sqlite")
+            .takes_value(true).value_name("FILE_PATH")
+            .help("Append scan results to a SQLite database")
+    );
+
+    command
 }
 
+#[derive(Clone)]
 pub enum OutputFormat {
     Plain,
     Json,
     Yaml,
-    Csv
+    Csv,
+    Xml,
+    Ndjson,
+    Html,
+    Markdown,
+    Prometheus,
+    NmapXml,
+    MessagePack,
+    Toml,
+    Grep,
+    Junit,
+    Dot,
+    Ansible,
+    Hosts,
+    Dnsmasq,
+    Influx,
+    Template
+}
+
+/**
+ * Backend of the reverse DNS lookups. The automatic backend picks
+ * systemd-resolved when it is running, and the system resolver otherwise
+ * (this check happens when hostnames are resolved).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResolverBackend {
+    Auto,
+    System,
+    Systemd
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostnameStyle {
+    Full,
+    Short
+}
+
+#[derive(Clone)]
+pub enum AnsibleGrouping {
+    Vendor,
+    Subnet
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputColumn {
+    Ipv4,
+    Mac,
+    FrameSrcMac,
+    Hostname,
+    Vendor,
+    ResponseTime,
+    ReplyCount,
+    Interface,
+    Status,
+    Vlan,
+    Origin,
+    MacType,
+    Virtualization,
+    Note
+}
+
+pub const OUTPUT_COLUMN_NAMES: [&str; 14] = ["ipv4", "mac", "frame_src_mac", "hostname", "vendor", "response_time_ms", "reply_count", "interface", "status", "vlan", "origin", "mac_type", "virtualization", "note"];
+
 pub enum ProfileType {
     Default,
     Fast,
@@ -175,26 +718,99 @@ pub enum ProfileType {
     Chaos
 }
 
+#[derive(Clone)]
 pub enum ScanTiming {
     Interval(u64),
-    Bandwidth(u64)
+    Bandwidth(u64),
+    Rate(u64)
+}
+
+/**
+ * A hostname given as scan target, with the IPv4 addresses it resolved to
+ * (these addresses are the actual scan targets).
+ */
+#[derive(Clone)]
+pub struct TargetHostname {
+    pub name: String,
+    pub addresses: Vec<Ipv4Addr>
 }
 
+#[derive(Clone)]
 pub struct ScanOptions {
-    pub profile: ProfileType,
-    pub interface_name: Option<String>,
+    pub interface_names: Vec<String>,
+    pub all_interfaces: bool,
     pub network_range: Option<Vec<ipnetwork::IpNetwork>>,
+    pub excluded_networks: Vec<Ipv4Network>,
+    pub target_hostnames: Vec<TargetHostname>,
     pub timeout_ms: u64,
     pub resolve_hostname: bool,
+    pub resolve_order: Vec<HostnameSource>,
+    pub resolver: ResolverBackend,
+    pub hostname_style: HostnameStyle,
+    pub dns_servers: Vec<SocketAddr>,
+    pub hosts_file: Option<String>,
+    pub static_hostnames: OnceLock<HashMap<Ipv4Addr, String>>,
+    pub dhcp_hostnames: HashMap<MacAddr, String>,
+    pub annotations: HashMap<MacAddr, String>,
+    pub resolve_cache_file: Option<String>,
+    pub resolve_cache_ttl_ms: u64,
+    pub resolve_concurrency: usize,
+    pub dns_timeout_ms: u64,
+    pub mdns_timeout_ms: u64,
+    pub netbios_timeout_ms: u64,
+    pub verbose: bool,
+    pub progress: bool,
     pub source_ipv4: Option<Ipv4Addr>,
     pub source_mac: Option<MacAddr>,
+    pub arp_sender_mac: Option<MacAddr>,
     pub destination_mac: Option<MacAddr>,
-    pub vlan_id: Option<u16>,
+    pub vlan_ids: Vec<u16>,
+    pub vlan_priority: u8,
     pub retry_count: usize,
     pub scan_timing: ScanTiming,
-    pub randomize_targets: bool,
+    pub random_seed: Option<u64>,
+    pub passive: bool,
+    pub dry_run: bool,
+    pub read_pcap: Option<String>,
+    pub max_duration_ms: Option<u64>,
+    pub max_hosts: Option<usize>,
+    pub strict: bool,
+    pub fail_on_conflict: bool,
+    pub loop_mode: bool,
+    pub state_file: Option<String>,
+    pub loop_interval_ms: u64,
+    pub gratuitous_ipv4: Option<Ipv4Addr>,
+    pub probe_ipv4: Option<Ipv4Addr>,
+    pub verify: bool,
+    pub include_arp_cache: bool,
+    pub include_unsolicited: bool,
+    pub wake_macs: Vec<MacAddr>,
+    pub wake_grace_ms: u64,
+    pub wake_only: bool,
+    pub announce_count: usize,
+    pub announce_interval_ms: u64,
+    pub force: bool,
     pub output: OutputFormat,
-    pub oui_file: String,
+    pub output_file: Option<String>,
+    pub host_template: Option<Vec<TemplatePart>>,
+    pub summary_template: Option<Vec<TemplatePart>>,
+    pub pretty: bool,
+    pub columns: Vec<OutputColumn>,
+    pub csv_headers: bool,
+    pub csv_delimiter: u8,
+    pub csv_quote_all: bool,
+    pub csv_summary_file: Option<String>,
+    #[cfg(feature = "sqlite")]
+    pub output_sqlite: Option<String>,
+    pub capture_file: Option<String>,
+    pub gelf_address: Option<String>,
+    pub allowlist: Option<Vec<String>>,
+    pub ansible_group_by: Option<AnsibleGrouping>,
+    pub only_resolved: bool,
+    pub oui_file: Option<String>,
+    pub top_vendors: Option<usize>,
+    pub group_by_mac: bool,
+    pub custom_oui_file: bool,
     pub hw_type: Option<ArpHardwareType>,
     pub hw_addr: Option<u8>,
     pub proto_type: Option<EtherType>,
@@ -202,90 +818,502 @@ pub struct ScanOptions {
     pub arp_operation: Option<ArpOperation>
 }
 
-impl ScanOptions {
-
-    fn list_required_networks(file_value: Option<&String>, network_value: Option<&String>) -> Result<Option<Vec<String>>, String> {
+/**
+ * The default options are the options of a CLI run without any argument, as
+ * used by the scanner builder of the library. They are built without any I/O:
+ * hosts files, the resolver backend and the vendor file are only looked up
+ * when the scan runs. Unlike the CLI, resolved hostnames are not cached.
+ */
+impl Default for ScanOptions {
+
+    fn default() -> Self {
+
+        ScanOptions {
+            interface_names: vec![],
+            all_interfaces: false,
+            network_range: None,
+            excluded_networks: vec![],
+            target_hostnames: vec![],
+            timeout_ms: TIMEOUT_MS_DEFAULT,
+            resolve_hostname: true,
+            resolve_order: vec![HostnameSource::HostsFile, HostnameSource::Dns],
+            resolver: ResolverBackend::Auto,
+            hostname_style: HostnameStyle::Full,
+            dns_servers: vec![],
+            hosts_file: None,
+            static_hostnames: OnceLock::new(),
+            dhcp_hostnames: HashMap::new(),
+            annotations: HashMap::new(),
+            resolve_cache_file: None,
+            resolve_cache_ttl_ms: RESOLVE_CACHE_TTL_MS_DEFAULT,
+            resolve_concurrency: RESOLVE_CONCURRENCY_DEFAULT,
+            dns_timeout_ms: DNS_TIMEOUT_MS_DEFAULT,
+            mdns_timeout_ms: mdns::MDNS_TIMEOUT_MS,
+            netbios_timeout_ms: netbios::NETBIOS_TIMEOUT_MS,
+            verbose: false,
+            progress: false,
+            source_ipv4: None,
+            source_mac: None,
+            arp_sender_mac: None,
+            destination_mac: None,
+            vlan_ids: vec![],
+            vlan_priority: 0,
+            retry_count: HOST_RETRY_DEFAULT,
+            scan_timing: ScanTiming::Interval(REQUEST_MS_INTERVAL),
+            random_seed: None,
+            passive: false,
+            dry_run: false,
+            read_pcap: None,
+            max_duration_ms: None,
+            max_hosts: None,
+            strict: false,
+            fail_on_conflict: false,
+            loop_mode: false,
+            state_file: None,
+            loop_interval_ms: LOOP_INTERVAL_MS_DEFAULT,
+            gratuitous_ipv4: None,
+            probe_ipv4: None,
+            verify: false,
+            include_arp_cache: false,
+            include_unsolicited: false,
+            wake_macs: vec![],
+            wake_grace_ms: WAKE_GRACE_MS_DEFAULT,
+            wake_only: false,
+            announce_count: ANNOUNCE_COUNT_DEFAULT,
+            announce_interval_ms: ANNOUNCE_INTERVAL_MS_DEFAULT,
+            force: false,
+            output: OutputFormat::Plain,
+            output_file: None,
+            host_template: None,
+            summary_template: None,
+            pretty: false,
+            columns: vec![OutputColumn::Ipv4, OutputColumn::Mac, OutputColumn::Hostname, OutputColumn::Vendor, OutputColumn::ResponseTime],
+            csv_headers: true,
+            csv_delimiter: b',',
+            csv_quote_all: false,
+            csv_summary_file: None,
+            #[cfg(feature = "sqlite")]
+            output_sqlite: None,
+            capture_file: None,
+            gelf_address: None,
+            allowlist: None,
+            ansible_group_by: None,
+            only_resolved: false,
+            oui_file: None,
+            top_vendors: None,
+            group_by_mac: false,
+            custom_oui_file: false,
+            hw_type: None,
+            hw_addr: None,
+            proto_type: None,
+            proto_addr: None,
+            arp_operation: None
+        }
+    }
+}
 
-        let network_options = (file_value, network_value);
-        match network_options {
-            (Some(file_path), None) => {
+impl ScanOptions {
 
-                let path = Path::new(file_path);
-                fs::read_to_string(path).map(|content| {
-                    Some(content.lines().map(|line| line.to_string()).collect())
-                }).map_err(|err| {
-                    format!("Could not open file {} - {}", file_path, err)
-                })
+    /**
+     * Remove duplicate interface names (when the same interface is given
+     * several times), keeping the order of the first occurrences.
+     */
+    fn dedup_interface_names<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
 
-            },
-            (None, Some(raw_ranges)) => {
-                Ok(Some(raw_ranges.split(',').map(|line| line.to_string()).collect()))
-            },
-            _ => Ok(None)
+        let mut interface_names: Vec<String> = vec![];
+        for name in names {
+            if !interface_names.contains(name) {
+                interface_names.push(name.clone());
+            }
         }
+        interface_names
     }
 
     /**
-     * Computes the whole network range requested by the user through CLI
-     * arguments or files. This method will fail of a failure has been detected
-     * (either on the IO level or the network syntax parsing)
+     * Parse a comma-separated list of output columns, keeping the user order.
+     * Unknown column names are rejected with the list of valid names.
      */
-    fn compute_networks(file_value: Option<&String>, network_value: Option<&String>) -> Result<Option<Vec<IpNetwork>>, String> {
-
-        let required_networks: Option<Vec<String>> = ScanOptions::list_required_networks(file_value, network_value)?;
-        if required_networks.is_none() {
-            return Ok(None);
-        }
+    fn parse_columns(raw_columns: &str) -> Result<Vec<OutputColumn>, String> {
+
+        raw_columns.split(',').map(|name| {
+            match name.trim() {
+                "ipv4" => Ok(OutputColumn::Ipv4),
+                "mac" => Ok(OutputColumn::Mac),
+                "frame_src_mac" => Ok(OutputColumn::FrameSrcMac),
+                "hostname" => Ok(OutputColumn::Hostname),
+                "vendor" => Ok(OutputColumn::Vendor),
+                "response_time_ms" => Ok(OutputColumn::ResponseTime),
+                "reply_count" => Ok(OutputColumn::ReplyCount),
+                "interface" => Ok(OutputColumn::Interface),
+                "status" => Ok(OutputColumn::Status),
+                "vlan" => Ok(OutputColumn::Vlan),
+                "origin" => Ok(OutputColumn::Origin),
+                "mac_type" => Ok(OutputColumn::MacType),
+                "virtualization" => Ok(OutputColumn::Virtualization),
+                "note" => Ok(OutputColumn::Note),
+                unknown => Err(format!("Unknown column '{}' (expected {})", unknown, OUTPUT_COLUMN_NAMES.join(", ")))
+            }
+        }).collect()
+    }
 
-        let mut networks: Vec<IpNetwork> = vec![];
-        for network_text in required_networks.unwrap() {
+    /**
+     * Parse the timeout of a resolution protocol, which must be strictly
+     * positive and at most one minute (longer timeouts would stall the end of
+     * the scan).
+     */
+    fn parse_resolution_timeout(matches: &ArgMatches, arg_name: &str, protocol: &str, default_timeout_ms: u64) -> Result<u64, String> {
 
-            match IpNetwork::from_str(&network_text) {
-                Ok(parsed_network) => {
-                    networks.push(parsed_network);
-                    Ok(())
-                },
-                Err(err) => {
-                    Err(format!("Expected valid IPv4 network range ({})", err))
-                }
-            }?;
+        let timeout_ms = match matches.get_one::<String>(arg_name) {
+            Some(raw_timeout) => parse_to_milliseconds(raw_timeout).map_err(|err| format!("Expected correct {} timeout, {}", protocol, err))?,
+            None => default_timeout_ms
+        };
+        match (1..=RESOLVE_TIMEOUT_MS_MAX).contains(&timeout_ms) {
+            true => Ok(timeout_ms),
+            false => Err(format!("Expected {} timeout between 1ms and {}s", protocol, RESOLVE_TIMEOUT_MS_MAX / 1000))
         }
-        Ok(Some(networks))
     }
 
     /**
-     * Computes scan timing constraints, as requested by the user through CLI
-     * arguments. The scan timing constraints will be either expressed in bandwidth
-     * (bits per second) or interval between ARP requests (in milliseconds).
+     * Parse a comma-separated list of hostname resolution methods, keeping
+     * the user order. Duplicated methods are only tried once.
      */
-    fn compute_scan_timing(matches: &ArgMatches, profile: &ProfileType) -> ScanTiming {
-
-        match (matches.get_one::<String>("bandwidth"), matches.get_one::<String>("interval")) {
-            (Some(bandwidth_text), None) => {
-                let bits_second: u64 = bandwidth_text.parse().unwrap_or_else(|err| {
-                    eprintln!("Expected positive number, {}", err);
-                    process::exit(1);
-                });
-                ScanTiming::Bandwidth(bits_second)
-            },
-            (None, Some(interval_text)) => parse_to_milliseconds(interval_text).map(ScanTiming::Interval).unwrap_or_else(|err| {
-                eprintln!("Expected correct interval, {}", err);
-                process::exit(1);
-            }),
-            _ => match profile {
-                ProfileType::Stealth => ScanTiming::Interval(REQUEST_MS_INTERVAL * 2),
-                ProfileType::Fast => ScanTiming::Interval(0),
-                _ => ScanTiming::Interval(REQUEST_MS_INTERVAL)
+    fn parse_resolve_order(raw_order: &str) -> Result<Vec<HostnameSource>, String> {
+
+        let mut resolve_order: Vec<HostnameSource> = vec![];
+        for name in raw_order.split(',') {
+            let method = HostnameSource::parse(name.trim()).ok_or_else(|| {
+                format!("Unknown resolution method '{}' (expected hosts, dns, mdns, netbios, dhcp)", name.trim())
+            })?;
+            if !resolve_order.contains(&method) {
+                resolve_order.push(method);
             }
         }
+        Ok(resolve_order)
     }
-    
+
     /**
-     * Build a new 'ScanOptions' struct that will be used in the whole CLI such
-     * as the network level, the display details and more. The scan options reflect
+     * The CSV writer only handles single-byte delimiters, so multi-byte
+     * characters (such as '§' in UTF-8) are rejected.
+     */
+    fn parse_csv_delimiter(raw_delimiter: &str) -> Result<u8, String> {
+
+        match raw_delimiter.as_bytes() {
+            [delimiter] => Ok(*delimiter),
+            _ => Err(format!("Expected a single-byte CSV delimiter (got '{}')", raw_delimiter))
+        }
+    }
+
+    /**
+     * Parse a forced source MAC address. Multicast addresses (including the
+     * broadcast address) are rejected, unless the 'force' flag is given.
+     */
+    fn parse_source_mac(mac_address: &str, force: bool) -> Result<MacAddr, String> {
+
+        let parsed_mac = mac_address.parse::<MacAddr>().map_err(|_| format!("invalid address: {}", mac_address))?;
+        match parsed_mac.0 & 0x01 == 0x01 && !force {
+            true => Err(format!("multicast or broadcast address {}, use --force to send it anyway", parsed_mac)),
+            false => Ok(parsed_mac)
+        }
+    }
+
+    /**
+     * Parse an ARP operation, either by name ('request' or 'reply') or by its
+     * numeric identifier for custom operations.
+     */
+    fn parse_arp_operation(arp_op_text: &str) -> Result<ArpOperation, String> {
+
+        match arp_op_text.to_lowercase().as_ref() {
+            "request" => Ok(ArpOperations::Request),
+            "reply" => Ok(ArpOperations::Reply),
+            op_text => op_text.parse::<u16>().map(ArpOperation::new).map_err(|_| format!("unknown operation '{}'", arp_op_text))
+        }
+    }
+
+    /**
+     * Parse a comma-separated list of 802.1Q VLAN identifiers. The identifiers
+     * 0 and 4095 are reserved, duplicates are only scanned once.
+     */
+    fn parse_vlan_ids(raw_vlan_ids: &str) -> Result<Vec<u16>, String> {
+
+        let mut vlan_ids: Vec<u16> = vec![];
+        for vlan_text in raw_vlan_ids.split(',') {
+            let vlan_id = match vlan_text.trim().parse::<u16>() {
+                Ok(vlan_id) if (1..=4094).contains(&vlan_id) => vlan_id,
+                _ => return Err(format!("Expected valid VLAN identifier between 1 and 4094 (got '{}')", vlan_text.trim()))
+            };
+            if !vlan_ids.contains(&vlan_id) {
+                vlan_ids.push(vlan_id);
+            }
+        }
+        Ok(vlan_ids)
+    }
+
+    /**
+     * A target is handled as a hostname if it has a valid hostname syntax and
+     * contains at least a letter (IPv4 addresses and networks do not).
+     */
+    fn is_hostname(target_text: &str) -> bool {
+
+        let has_letter = target_text.chars().any(|character| character.is_ascii_alphabetic());
+        let has_valid_labels = target_text.trim_end_matches('.').split('.').all(|label| {
+            !label.is_empty() && !label.starts_with('-') && !label.ends_with('-') && label.chars().all(|character| character.is_ascii_alphanumeric() || character == '-')
+        });
+        has_letter && has_valid_labels
+    }
+
+    /**
+     * Resolve a target hostname with the system resolver, only IPv4 addresses
+     * are kept as ARP only works with IPv4.
+     */
+    fn resolve_target_hostname(hostname: &str) -> Result<Vec<Ipv4Addr>, String> {
+
+        let addresses = dns_lookup::lookup_host(hostname).map_err(|err| {
+            format!("could not resolve hostname {}: {}", hostname, err)
+        })?;
+
+        let mut ipv4_addresses: Vec<Ipv4Addr> = vec![];
+        for address in addresses {
+            if let IpAddr::V4(ipv4_address) = address {
+                if !ipv4_addresses.contains(&ipv4_address) {
+                    ipv4_addresses.push(ipv4_address);
+                }
+            }
+        }
+        match ipv4_addresses.is_empty() {
+            true => Err(format!("hostname {} has no IPv4 address", hostname)),
+            false => Ok(ipv4_addresses)
+        }
+    }
+
+    /**
+     * Parse a single target, either an IPv4 address, a network in CIDR
+     * notation, an address range ('192.168.1.10-192.168.1.20') or a hostname.
+     * Address ranges are converted to the smallest list of covering networks,
+     * while hostnames are resolved to all their IPv4 addresses.
+     */
+    fn parse_target(target_text: &str, target_hostnames: &mut Vec<TargetHostname>) -> Result<Vec<IpNetwork>, String> {
+
+        if ScanOptions::is_hostname(target_text) {
+            let addresses = ScanOptions::resolve_target_hostname(target_text)?;
+            let networks = addresses.iter().map(|address| IpNetwork::V4(Ipv4Network::from(*address))).collect();
+            target_hostnames.push(TargetHostname {
+                name: target_text.to_string(),
+                addresses
+            });
+            return Ok(networks);
+        }
+
+        let range_bounds = target_text.split_once('-').and_then(|(start_text, end_text)| {
+            Some((start_text.trim().parse::<Ipv4Addr>().ok()?, end_text.trim().parse::<Ipv4Addr>().ok()?))
+        });
+        let (start, end) = match range_bounds {
+            Some(bounds) => bounds,
+            None => return IpNetwork::from_str(target_text).map(|network| vec![network]).map_err(|err| err.to_string())
+        };
+
+        let (mut current, end) = (u64::from(u32::from(start)), u64::from(u32::from(end)));
+        if current > end {
+            return Err(format!("invalid range: {}", target_text));
+        }
+
+        let mut networks: Vec<IpNetwork> = vec![];
+        while current <= end {
+            let mut block_size: u64 = 1 << current.trailing_zeros().min(32);
+            while current + block_size - 1 > end {
+                block_size >>= 1;
+            }
+            let prefix = 32 - block_size.trailing_zeros() as u8;
+            let network = Ipv4Network::new(Ipv4Addr::from(current as u32), prefix).map_err(|err| err.to_string())?;
+            networks.push(IpNetwork::V4(network));
+            current += block_size;
+        }
+        Ok(networks)
+    }
+
+    /**
+     * Read the allowlist file, with one IPv4 or MAC address per line. Empty
+     * lines and comments (starting with '#') are ignored, MAC addresses are
+     * normalized to lowercase to match the exported MAC format.
+     */
+    fn read_allowlist(file_path: &str) -> Result<Vec<String>, String> {
+
+        fs::read_to_string(file_path).map(|content| {
+            content.lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .collect()
+        }).map_err(|err| {
+            format!("Could not open allowlist file {} - {}", file_path, err)
+        })
+    }
+
+    /**
+     * Computes the whole network range requested by the user through CLI
+     * arguments or files. This method will fail of a failure has been detected
+     * (either on the IO level or the network syntax parsing)
+     */
+    fn compute_networks(file_value: Option<&String>, network_value: Option<&String>, target_hostnames: &mut Vec<TargetHostname>) -> Result<Option<Vec<IpNetwork>>, String> {
+
+        if file_value.is_none() && network_value.is_none() {
+            return Ok(None);
+        }
+
+        let mut networks: Vec<IpNetwork> = vec![];
+        if let Some(raw_ranges) = network_value {
+            for network_text in raw_ranges.split(',') {
+                let parsed_networks = ScanOptions::parse_target(network_text, target_hostnames).map_err(|err| {
+                    format!("Expected valid IPv4 network range ({})", err)
+                })?;
+                networks.extend(parsed_networks);
+            }
+        }
+
+        // Targets read from stdin follow the same rules as target files, but
+        // an empty input is an error (a failed upstream command for instance).
+        if let Some(file_path) = file_value {
+            match file_path.as_str() {
+                STDIN_FILE_PATH => {
+                    let mut content = String::new();
+                    io::stdin().read_to_string(&mut content).map_err(|err| {
+                        format!("Could not read targets from standard input - {}", err)
+                    })?;
+                    let stdin_networks = ScanOptions::parse_target_lines(&content, "standard input", target_hostnames)?;
+                    if stdin_networks.is_empty() {
+                        return Err("No targets read from standard input".to_string());
+                    }
+                    networks.extend(stdin_networks);
+                },
+                _ => {
+                    let content = fs::read_to_string(Path::new(file_path)).map_err(|err| {
+                        format!("Could not open file {} - {}", file_path, err)
+                    })?;
+                    networks.extend(ScanOptions::parse_target_lines(&content, file_path, target_hostnames)?);
+                }
+            }
+        }
+        Ok(Some(networks))
+    }
+
+    /**
+     * Parse the content of a target file, with one target per line. Blank
+     * lines & comments are ignored, malformed lines are reported with their
+     * line number.
+     */
+    fn parse_target_lines(content: &str, source_name: &str, target_hostnames: &mut Vec<TargetHostname>) -> Result<Vec<IpNetwork>, String> {
+
+        let mut networks: Vec<IpNetwork> = vec![];
+        for (line_index, line) in content.lines().enumerate() {
+            let target_text = line.trim();
+            if target_text.is_empty() || target_text.starts_with('#') {
+                continue;
+            }
+            let parsed_networks = ScanOptions::parse_target(target_text, target_hostnames).map_err(|err| {
+                format!("Expected valid IPv4 network range on line {} of {} ({})", line_index + 1, source_name, err)
+            })?;
+            networks.extend(parsed_networks);
+        }
+        Ok(networks)
+    }
+
+    /**
+     * Computes the MAC addresses to wake, from both a comma-separated list and
+     * a file (one MAC address per line, blank lines and '#' comments are
+     * ignored). Duplicate addresses only receive a single magic packet.
+     */
+    fn compute_wake_macs(file_value: Option<&String>, wake_value: Option<&String>) -> Result<Vec<MacAddr>, String> {
+
+        let mut wake_texts: Vec<String> = vec![];
+        if let Some(wake_list) = wake_value {
+            wake_texts.extend(wake_list.split(',').map(|text| text.trim().to_string()));
+        }
+        if let Some(file_path) = file_value {
+            let content = fs::read_to_string(file_path).map_err(|err| {
+                format!("Could not open file {} - {}", file_path, err)
+            })?;
+            wake_texts.extend(content.lines().map(|line| line.trim().to_string()).filter(|line| !line.is_empty() && !line.starts_with('#')));
+        }
+
+        let mut wake_macs: Vec<MacAddr> = vec![];
+        for text in wake_texts.iter() {
+            let mac = text.parse::<MacAddr>().map_err(|_| {
+                format!("Expected valid MAC address to wake (got '{}')", text)
+            })?;
+            if !wake_macs.contains(&mac) {
+                wake_macs.push(mac);
+            }
+        }
+        Ok(wake_macs)
+    }
+
+    /**
+     * Computes the excluded IPv4 networks, from both a comma-separated list and
+     * a file (one address or network per line, blank lines and '#' comments
+     * are ignored). Single addresses are handled as /32 networks.
+     */
+    fn compute_excluded_networks(file_value: Option<&String>, exclude_value: Option<&String>) -> Result<Vec<Ipv4Network>, String> {
+
+        let mut excluded_texts: Vec<String> = vec![];
+        if let Some(exclude_list) = exclude_value {
+            excluded_texts.extend(exclude_list.split(',').map(|text| text.trim().to_string()));
+        }
+        if let Some(file_path) = file_value {
+            let content = fs::read_to_string(file_path).map_err(|err| {
+                format!("Could not open file {} - {}", file_path, err)
+            })?;
+            excluded_texts.extend(content.lines().map(|line| line.trim().to_string()).filter(|line| !line.is_empty() && !line.starts_with('#')));
+        }
+
+        excluded_texts.iter().map(|text| {
+            Ipv4Network::from_str(text).map_err(|err| {
+                format!("Expected valid excluded IPv4 address or network ({})", err)
+            })
+        }).collect()
+    }
+
+    /**
+     * Computes scan timing constraints, as requested by the user through CLI
+     * arguments. The scan timing constraints will be either expressed in bandwidth
+     * (bits per second) or interval between ARP requests (in milliseconds).
+     */
+    fn compute_scan_timing(matches: &ArgMatches, profile: &ProfileType) -> Result<ScanTiming, String> {
+
+        // The packets-per-second rate takes the priority over the interval
+        if let Some(rate_text) = matches.get_one::<String>("rate") {
+            return match rate_text.parse::<u64>() {
+                Ok(rate) if rate > 0 => Ok(ScanTiming::Rate(rate)),
+                _ => Err("Expected strictly positive number for packets per second rate".to_string())
+            };
+        }
+
+        match (matches.get_one::<String>("bandwidth"), matches.get_one::<String>("interval")) {
+            (Some(bandwidth_text), None) => bandwidth_text.parse().map(ScanTiming::Bandwidth).map_err(|err| format!("Expected positive number, {}", err)),
+            (None, Some(interval_text)) => parse_to_milliseconds(interval_text).map(ScanTiming::Interval).map_err(|err| format!("Expected correct interval, {}", err)),
+            _ => match profile {
+                ProfileType::Stealth => Ok(ScanTiming::Interval(REQUEST_MS_INTERVAL * 2)),
+                ProfileType::Fast => Ok(ScanTiming::Interval(0)),
+                _ => Ok(ScanTiming::Interval(REQUEST_MS_INTERVAL))
+            }
+        }
+    }
+    
+    /**
+     * Build a new 'ScanOptions' struct that will be used in the whole CLI such
+     * as the network level, the display details and more. The scan options reflect
      * user requests for the CLI and should not be mutated.
      */
-    pub fn new(matches: &ArgMatches) -> Arc<Self> {
+    pub fn new(matches: &ArgMatches) -> Result<Arc<Self>, ScanError> {
+
+        ScanOptions::from_matches(matches).map(Arc::new)
+    }
+
+    /**
+     * Parse the scan options of the command line. Invalid targets are
+     * reported as such, every other invalid value is listed in a single
+     * options error (like the violated option rules).
+     */
+    fn from_matches(matches: &ArgMatches) -> Result<Self, ScanError> {
+
+        let mut violations: Vec<String> = vec![];
 
         let profile = match matches.get_one::<String>("profile") {
             Some(output_request) => {
@@ -296,30 +1324,36 @@ impl ScanOptions {
                     "stealth" | "s" => ProfileType::Stealth,
                     "chaos" | "c" => ProfileType::Chaos,
                     _ => {
-                        eprintln!("Expected correct profile name (default/fast/stealth/chaos)");
-                        process::exit(1);
+                        violations.push("Expected correct profile name (default/fast/stealth/chaos)".to_string());
+                        ProfileType::Default
                     }
                 }
             },
             None => ProfileType::Default
         };
 
-        let interface_name = matches.get_one::<String>("interface").cloned();
+        let interface_names = match matches.get_many::<String>("interface") {
+            Some(names) => ScanOptions::dedup_interface_names(names),
+            None => vec![]
+        };
+        let all_interfaces = matches.contains_id("all_interfaces");
 
-        let file_option = matches.get_one::<String>("file");
+        let stdin_file_path = STDIN_FILE_PATH.to_string();
+        let file_option = match matches.contains_id("stdin") {
+            true => Some(&stdin_file_path),
+            false => matches.get_one::<String>("file")
+        };
         let network_option = matches.get_one::<String>("network");
 
-        let network_range = ScanOptions::compute_networks(file_option, network_option).unwrap_or_else(|err| {
-            eprintln!("Could not compute requested network range to scan");
-            eprintln!("{}", err);
-            process::exit(1);
-        });
+        let mut target_hostnames: Vec<TargetHostname> = vec![];
+        let network_range = ScanOptions::compute_networks(file_option, network_option, &mut target_hostnames).map_err(|err| {
+            ScanError::InvalidTarget(format!("Could not compute requested network range to scan\n{}", err))
+        })?;
+
+        let excluded_networks = ScanOptions::compute_excluded_networks(matches.get_one::<String>("exclude_file"), matches.get_one::<String>("exclude")).map_err(ScanError::InvalidTarget)?;
 
         let timeout_ms: u64 = match matches.get_one::<String>("timeout") {
-            Some(timeout_text) => parse_to_milliseconds(timeout_text).unwrap_or_else(|err| {
-                eprintln!("Expected correct timeout, {}", err);
-                process::exit(1);
-            }),
+            Some(timeout_text) => parse_to_milliseconds(timeout_text).map_err(|err| violations.push(format!("Expected correct timeout, {}", err))).unwrap_or_default(),
             None => match profile {
                 ProfileType::Fast => TIMEOUT_MS_FAST,
                 _ => TIMEOUT_MS_DEFAULT
@@ -328,6 +1362,76 @@ impl ScanOptions {
 
         // Hostnames will not be resolved in numeric mode or stealth profile
         let resolve_hostname = !matches.contains_id("numeric") && !matches!(profile, ProfileType::Stealth);
+        // Hosts files are checked before reverse DNS by default, the mDNS and
+        // NetBIOS flags append their method to the resolution order.
+        let mut resolve_order = match matches.get_one::<String>("resolve_order") {
+            Some(raw_order) => ScanOptions::parse_resolve_order(raw_order).map_err(|err| violations.push(err)).unwrap_or_default(),
+            None => vec![HostnameSource::HostsFile, HostnameSource::Dns]
+        };
+        let enabled_methods = [("resolve_mdns", HostnameSource::Mdns), ("resolve_netbios", HostnameSource::Netbios), ("dhcp_leases", HostnameSource::Dhcp)];
+        for (arg_name, method) in enabled_methods {
+            if matches.contains_id(arg_name) && !resolve_order.contains(&method) {
+                resolve_order.push(method);
+            }
+        }
+        // Reverse lookups go through systemd-resolved when it is running (and
+        // supported by the build), so that per-link DNS settings are used.
+        let resolver = match matches.get_one::<String>("resolver").map(|resolver| resolver.as_str()) {
+            None => ResolverBackend::Auto,
+            Some("system") => ResolverBackend::System,
+            Some("systemd") if cfg!(all(feature = "systemd-resolved", target_os = "linux")) => ResolverBackend::Systemd,
+            Some("systemd") => {
+                violations.push("This build does not support systemd-resolved (see the 'systemd-resolved' feature)".to_string());
+                ResolverBackend::Auto
+            },
+            Some(_) => {
+                violations.push("Expected correct resolver (system/systemd)".to_string());
+                ResolverBackend::Auto
+            }
+        };
+        let hostname_style = match matches.get_one::<String>("hostname_style").map(|style| style.as_str()) {
+            None | Some("full") => HostnameStyle::Full,
+            Some("short") => HostnameStyle::Short,
+            Some(_) => {
+                violations.push("Expected correct hostname style (full/short)".to_string());
+                HostnameStyle::Full
+            }
+        };
+        // Static hostnames are loaded once, when hostnames are resolved.
+        let hosts_file = matches.get_one::<String>("hosts_file").cloned();
+        let dhcp_hostnames = match (resolve_hostname, matches.get_one::<String>("dhcp_leases")) {
+            (true, Some(file_path)) => dhcp::load_lease_hostnames(file_path).map_err(|err| violations.push(err)).unwrap_or_default(),
+            _ => HashMap::new()
+        };
+        // Resolved hostnames are cached in the user cache directory, unless
+        // the cache is disabled or no cache directory can be found.
+        let resolve_cache_file = match resolve_hostname && !matches.contains_id("no_resolve_cache") {
+            true => cache::hostname_cache_path().map(|path| path.to_string_lossy().to_string()),
+            false => None
+        };
+        let resolve_cache_ttl_ms: u64 = match matches.get_one::<String>("resolve_cache_ttl") {
+            Some(cache_ttl) => parse_to_milliseconds(cache_ttl).map_err(|err| violations.push(format!("Expected correct hostname cache TTL, {}", err))).unwrap_or_default(),
+            None => RESOLVE_CACHE_TTL_MS_DEFAULT
+        };
+        let resolve_concurrency: usize = match matches.get_one::<String>("resolve_concurrency") {
+            Some(concurrency) => match concurrency.parse::<usize>() {
+                Ok(worker_count) if worker_count > 0 => worker_count,
+                _ => {
+                    violations.push("Expected strictly positive number of resolution workers".to_string());
+                    RESOLVE_CONCURRENCY_DEFAULT
+                }
+            },
+            None => RESOLVE_CONCURRENCY_DEFAULT
+        };
+        let dns_timeout_ms = ScanOptions::parse_resolution_timeout(matches, "dns_timeout", "DNS", DNS_TIMEOUT_MS_DEFAULT).map_err(|err| violations.push(err)).unwrap_or_default();
+        let mdns_timeout_ms = ScanOptions::parse_resolution_timeout(matches, "mdns_timeout", "mDNS", mdns::MDNS_TIMEOUT_MS).map_err(|err| violations.push(err)).unwrap_or_default();
+        let netbios_timeout_ms = ScanOptions::parse_resolution_timeout(matches, "netbios_timeout", "NetBIOS", netbios::NETBIOS_TIMEOUT_MS).map_err(|err| violations.push(err)).unwrap_or_default();
+        let verbose = matches.contains_id("verbose");
+        let progress = matches.contains_id("progress");
+        let dns_servers: Vec<SocketAddr> = match matches.get_one::<String>("dns_server") {
+            Some(raw_servers) => raw_servers.split(',').map(dns::parse_dns_server).collect::<Result<Vec<SocketAddr>, String>>().map_err(|err| violations.push(format!("Expected valid DNS servers ({})", err))).unwrap_or_default(),
+            None => vec![]
+        };
 
         let source_ipv4: Option<Ipv4Addr> = match matches.get_one::<String>("source_ip") {
             Some(source_ip) => {
@@ -335,8 +1439,8 @@ impl ScanOptions {
                 match source_ip.parse::<Ipv4Addr>() {
                     Ok(parsed_ipv4) => Some(parsed_ipv4),
                     Err(_) => {
-                        eprintln!("Expected valid IPv4 as source IP");
-                        process::exit(1);
+                        violations.push("Expected valid IPv4 as source IP".to_string());
+                        None
                     }
                 }
             }, 
@@ -349,40 +1453,37 @@ impl ScanOptions {
                 match mac_address.parse::<MacAddr>() {
                     Ok(parsed_mac) => Some(parsed_mac),
                     Err(_) => {
-                        eprintln!("Expected valid MAC address as destination");
-                        process::exit(1);
+                        violations.push("Expected valid MAC address as destination".to_string());
+                        None
                     }
                 }
             },
             None => None
         };
 
-        let source_mac: Option<MacAddr> = match matches.get_one::<String>("source_mac") {
-            Some(mac_address) => {
-                
-                match mac_address.parse::<MacAddr>() {
-                    Ok(parsed_mac) => Some(parsed_mac),
-                    Err(_) => {
-                        eprintln!("Expected valid MAC address as source");
-                        process::exit(1);
-                    }
-                }
-            },
-            None => None
-        };
-    
-        let vlan_id: Option<u16> = match matches.get_one::<String>("vlan") {
-            Some(vlan) => {
+        // Multicast & broadcast source MACs are invalid for most switches, and
+        // are only allowed with the '--force' flag.
+        let force = matches.contains_id("force");
+        let source_mac: Option<MacAddr> = matches.get_one::<String>("source_mac").map(|mac_address| {
+            ScanOptions::parse_source_mac(mac_address, force).map_err(|err| format!("Expected valid MAC address as source ({})", err))
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let arp_sender_mac: Option<MacAddr> = matches.get_one::<String>("arp_sha").map(|mac_address| {
+            ScanOptions::parse_source_mac(mac_address, force).map_err(|err| format!("Expected valid MAC address as ARP sender ({})", err))
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
     
-                match vlan.parse::<u16>() {
-                    Ok(vlan_number) => Some(vlan_number),
-                    Err(_) => {
-                        eprintln!("Expected valid VLAN identifier");
-                        process::exit(1);
-                    }
+        let vlan_ids: Vec<u16> = match matches.get_one::<String>("vlan") {
+            Some(raw_vlan_ids) => ScanOptions::parse_vlan_ids(raw_vlan_ids).map_err(|err| violations.push(err)).unwrap_or_default(),
+            None => vec![]
+        };
+        let vlan_priority: u8 = match matches.get_one::<String>("vlan_priority") {
+            Some(raw_priority) => match raw_priority.parse::<u8>() {
+                Ok(priority) if priority <= 7 => priority,
+                _ => {
+                    violations.push("Expected VLAN priority code point between 0 and 7".to_string());
+                    0
                 }
             },
-            None => None
+            None => 0
         };
 
         let retry_count = match matches.get_one::<String>("retry_count") {
@@ -391,41 +1492,231 @@ impl ScanOptions {
                 match retry_count.parse::<usize>() {
                     Ok(retry_number) => retry_number,
                     Err(_) => {
-                        eprintln!("Expected positive number for host retry count");
-                        process::exit(1);
+                        violations.push("Expected positive number for host retry count".to_string());
+                        HOST_RETRY_DEFAULT
                     }
                 }
             },
             None => match profile {
-                ProfileType::Chaos => HOST_RETRY_DEFAULT * 2,
+                ProfileType::Chaos => 1,
                 _ => HOST_RETRY_DEFAULT
             }
         };
 
-        let scan_timing: ScanTiming = ScanOptions::compute_scan_timing(matches, &profile);
+        let scan_timing: ScanTiming = ScanOptions::compute_scan_timing(matches, &profile).map_err(|err| violations.push(err)).unwrap_or(ScanTiming::Interval(REQUEST_MS_INTERVAL));
 
         let output = match matches.get_one::<String>("output") {
             Some(output_request) => {
 
                 match output_request.as_ref() {
-                    "json" => OutputFormat::Json,
+                    "json" | "json-pretty" => OutputFormat::Json,
                     "yaml" => OutputFormat::Yaml,
                     "plain" | "text" => OutputFormat::Plain,
                     "csv" => OutputFormat::Csv,
+                    "xml" => OutputFormat::Xml,
+                    "ndjson" => OutputFormat::Ndjson,
+                    "html" => OutputFormat::Html,
+                    "markdown" | "md" => OutputFormat::Markdown,
+                    "prom" | "prometheus" => OutputFormat::Prometheus,
+                    "nmap" => OutputFormat::NmapXml,
+                    "msgpack" => OutputFormat::MessagePack,
+                    "toml" => OutputFormat::Toml,
+                    "grep" => OutputFormat::Grep,
+                    "junit" => OutputFormat::Junit,
+                    "dot" => OutputFormat::Dot,
+                    "ansible" => OutputFormat::Ansible,
+                    "hosts" => OutputFormat::Hosts,
+                    "dnsmasq" => OutputFormat::Dnsmasq,
+                    "influx" => OutputFormat::Influx,
                     _ => {
-                        eprintln!("Expected correct output format (json/yaml/plain)");
-                        process::exit(1);
+                        violations.push("Expected correct output format (json/yaml/plain/csv/xml/ndjson/html/markdown/prom/nmap/msgpack/toml/grep/junit/dot/ansible/hosts/dnsmasq/influx)".to_string());
+                        OutputFormat::Plain
                     }
                 }
             },
             None => OutputFormat::Plain
         };
 
-        let randomize_targets = matches.contains_id("random") || matches!(profile, ProfileType::Stealth | ProfileType::Chaos);
+        // Name mapping outputs are useless without hostnames, this is rejected
+        // early instead of producing empty (or nameless) entries.
+        let only_resolved = matches.contains_id("only_resolved");
+        let is_name_mapping = matches!(output, OutputFormat::Hosts | OutputFormat::Dnsmasq);
+        if is_name_mapping && !resolve_hostname {
+            violations.push("The hosts & dnsmasq outputs require hostname resolution (not available with --numeric or the stealth profile)".to_string());
+        }
+        if only_resolved && !is_name_mapping {
+            violations.push("The --only-resolved option is only available with hosts & dnsmasq outputs".to_string());
+        }
+
+        // User-defined templates are validated before the scan starts, unknown
+        // placeholders should not be discovered when printing results.
+        let host_template = matches.get_one::<String>("format_string").map(|template| {
+            parse_template(template, &HOST_TEMPLATE_FIELDS).map_err(|err| format!("Expected correct format string, {}", err))
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let summary_template = matches.get_one::<String>("summary_format").map(|template| {
+            parse_template(template, &SUMMARY_TEMPLATE_FIELDS).map_err(|err| format!("Expected correct summary format, {}", err))
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let output = match host_template {
+            Some(_) => OutputFormat::Template,
+            None => output
+        };
+
+        let output_file = matches.get_one::<String>("output_file").filter(|path| *path != "-").cloned();
+
+        let pretty = matches.contains_id("pretty") || matches.get_one::<String>("output").map(|output| output == "json-pretty").unwrap_or(false);
+
+        let verify = matches.contains_id("verify");
+        let include_arp_cache = matches.contains_id("include_arp_cache");
+        let include_unsolicited = matches.contains_id("include_unsolicited");
+
+        // Annotations are loaded before the scan starts, so that a malformed
+        // file is reported early.
+        let annotations = match matches.get_one::<String>("annotations") {
+            Some(file_path) => annotations::load_annotations(file_path).map_err(|err| violations.push(err)).unwrap_or_default(),
+            None => HashMap::new()
+        };
 
-        let oui_file: String = match matches.get_one::<String>("oui-file") {
-            Some(file) => file.to_string(),
-            None => "/usr/share/arp-scan/ieee-oui.csv".to_string()
+        let columns = match matches.get_one::<String>("columns") {
+            Some(raw_columns) => ScanOptions::parse_columns(raw_columns).map_err(|err| violations.push(err)).unwrap_or_default(),
+            None => {
+                let mut default_columns = vec![OutputColumn::Ipv4, OutputColumn::Mac, OutputColumn::Hostname, OutputColumn::Vendor, OutputColumn::ResponseTime];
+                if verify {
+                    default_columns.push(OutputColumn::Status);
+                }
+                else if all_interfaces {
+                    default_columns.push(OutputColumn::Interface);
+                }
+                if vlan_ids.len() > 1 {
+                    default_columns.push(OutputColumn::Vlan);
+                }
+                if include_arp_cache {
+                    default_columns.push(OutputColumn::Origin);
+                }
+                if !annotations.is_empty() {
+                    default_columns.push(OutputColumn::Note);
+                }
+                default_columns
+            }
+        };
+
+        let csv_headers = !matches.contains_id("no_csv_headers");
+        let csv_delimiter = match matches.get_one::<String>("csv_delimiter") {
+            Some(raw_delimiter) => ScanOptions::parse_csv_delimiter(raw_delimiter).map_err(|err| violations.push(err)).unwrap_or(b','),
+            None => b','
+        };
+        let csv_quote_all = matches.contains_id("csv_quote_all");
+        let csv_summary_file = matches.get_one::<String>("csv_summary_file").cloned();
+
+        #[cfg(feature = "sqlite")]
+        let output_sqlite = matches.get_one::<String>("output_sqlite").cloned();
+
+        let ansible_group_by = matches.get_one::<String>("ansible_group_by").map(|grouping| {
+            match grouping.as_ref() {
+                "vendor" => Ok(AnsibleGrouping::Vendor),
+                "subnet" => Ok(AnsibleGrouping::Subnet),
+                _ => Err("Expected correct Ansible grouping (vendor/subnet)".to_string())
+            }
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+
+        let gelf_address = matches.get_one::<String>("gelf").map(|address| {
+            let is_valid = match address.rsplit_once(':') {
+                Some((host, port)) => !host.is_empty() && port.parse::<u16>().is_ok(),
+                None => false
+            };
+            match is_valid {
+                true => Ok(address.clone()),
+                false => Err(format!("Expected GELF address as host:port (got {})", address))
+            }
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+
+        let allowlist = matches.get_one::<String>("allowlist").map(|file_path| ScanOptions::read_allowlist(file_path)).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+
+        let capture_file = matches.get_one::<String>("capture").cloned();
+
+        // A random seed is always used when randomizing targets, so that a
+        // given target order can be reproduced with the '--seed' option.
+        let random_seed: Option<u64> = match matches.get_one::<String>("seed") {
+            Some(seed) => seed.parse::<u64>().map_err(|_| violations.push("Expected positive number for random seed".to_string())).ok(),
+            None if matches.contains_id("random") || matches!(profile, ProfileType::Stealth | ProfileType::Chaos) => Some(rand::random::<u64>()),
+            None => None
+        };
+
+        let passive = matches.contains_id("passive");
+        let dry_run = matches.contains_id("dry_run");
+        let read_pcap = matches.get_one::<String>("read_pcap").cloned();
+
+        let max_duration_ms: Option<u64> = matches.get_one::<String>("max_duration").map(|max_duration| {
+            match max_duration.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Ok(seconds * 1000),
+                _ => Err("Expected strictly positive number of seconds for maximum scan duration".to_string())
+            }
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let max_hosts: Option<usize> = matches.get_one::<String>("max_hosts").map(|max_hosts| {
+            match max_hosts.parse::<usize>() {
+                Ok(host_count) if host_count > 0 => Ok(host_count),
+                _ => Err("Expected strictly positive number of hosts".to_string())
+            }
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let strict = matches.contains_id("strict");
+        let fail_on_conflict = matches.contains_id("fail_on_conflict");
+
+        let loop_mode = matches.contains_id("loop");
+        let loop_interval_ms: u64 = match matches.get_one::<String>("loop_interval") {
+            Some(loop_interval) => parse_to_milliseconds(loop_interval).map_err(|err| violations.push(format!("Expected correct loop interval, {}", err))).unwrap_or_default(),
+            None => LOOP_INTERVAL_MS_DEFAULT
+        };
+
+        let state_file = matches.get_one::<String>("state_file").cloned();
+        if state_file.as_deref() == Some("-") {
+            violations.push("Expected a file path for the scan state file".to_string());
+        }
+
+        let gratuitous_ipv4: Option<Ipv4Addr> = matches.get_one::<String>("gratuitous").map(|raw_ipv4| {
+            raw_ipv4.parse::<Ipv4Addr>().map_err(|err| format!("Expected valid IPv4 address to announce ({})", err))
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let probe_ipv4: Option<Ipv4Addr> = matches.get_one::<String>("probe").map(|raw_ipv4| {
+            raw_ipv4.parse::<Ipv4Addr>().map_err(|err| format!("Expected valid IPv4 address to probe ({})", err))
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let announce_count: usize = match matches.get_one::<String>("announce_count") {
+            Some(raw_count) => match raw_count.parse::<usize>() {
+                Ok(count) if count > 0 => count,
+                _ => {
+                    violations.push("Expected strictly positive number of gratuitous ARP frames".to_string());
+                    ANNOUNCE_COUNT_DEFAULT
+                }
+            },
+            None => ANNOUNCE_COUNT_DEFAULT
+        };
+        let announce_interval_ms: u64 = match matches.get_one::<String>("announce_interval") {
+            Some(announce_interval) => parse_to_milliseconds(announce_interval).map_err(|err| violations.push(format!("Expected correct announce interval, {}", err))).unwrap_or_default(),
+            None => ANNOUNCE_INTERVAL_MS_DEFAULT
+        };
+
+        let wake_macs = ScanOptions::compute_wake_macs(matches.get_one::<String>("wake_file"), matches.get_one::<String>("wake")).map_err(|err| violations.push(err)).unwrap_or_default();
+        let wake_only = matches.contains_id("wake_only");
+        if wake_only && wake_macs.is_empty() {
+            violations.push("The --wake-only option requires MAC addresses (see --wake and --wake-file)".to_string());
+        }
+        let wake_grace_ms: u64 = match matches.get_one::<String>("wake_grace") {
+            Some(wake_grace) => parse_to_milliseconds(wake_grace).map_err(|err| violations.push(format!("Expected correct wake grace period, {}", err))).unwrap_or_default(),
+            None => WAKE_GRACE_MS_DEFAULT
+        };
+
+        let top_vendors: Option<usize> = matches.get_one::<String>("top_vendors").map(|top_vendors| {
+            match top_vendors.parse::<usize>() {
+                Ok(vendor_count) if vendor_count > 0 => Ok(vendor_count),
+                _ => Err("Expected strictly positive number of vendors".to_string())
+            }
+        }).transpose().map_err(|err| violations.push(err)).unwrap_or_default();
+        let group_by_mac = matches.contains_id("group_by_mac");
+        let custom_oui_file = matches.contains_id("oui-file");
+        // The database downloaded with '--update-vendors' is preferred over
+        // the system one (when no file is given), unless offline mode is
+        // requested.
+        let oui_file: Option<String> = match (matches.get_one::<String>("oui-file"), matches.contains_id("offline")) {
+            (Some(file), _) => Some(file.to_string()),
+            (None, true) => Some(vendor::SYSTEM_VENDOR_FILE_PATH.to_string()),
+            (None, false) => None
         };
 
         let hw_type = match matches.get_one::<String>("hw_type") {
@@ -434,8 +1725,8 @@ impl ScanOptions {
                 match hw_type_text.parse::<u16>() {
                     Ok(type_number) => Some(ArpHardwareType::new(type_number)),
                     Err(_) => {
-                        eprintln!("Expected valid ARP hardware type number");
-                        process::exit(1);
+                        violations.push("Expected valid ARP hardware type number".to_string());
+                        None
                     }
                 }
             },
@@ -448,8 +1739,8 @@ impl ScanOptions {
                 match hw_addr_text.parse::<u8>() {
                     Ok(addr_length) => Some(addr_length),
                     Err(_) => {
-                        eprintln!("Expected valid ARP hardware address length");
-                        process::exit(1);
+                        violations.push("Expected valid ARP hardware address length".to_string());
+                        None
                     }
                 }
             },
@@ -462,8 +1753,8 @@ impl ScanOptions {
                 match proto_type_text.parse::<u16>() {
                     Ok(type_number) => Some(EtherType::new(type_number)),
                     Err(_) => {
-                        eprintln!("Expected valid ARP proto type number");
-                        process::exit(1);
+                        violations.push("Expected valid ARP proto type number".to_string());
+                        None
                     }
                 }
             },
@@ -476,8 +1767,8 @@ impl ScanOptions {
                 match proto_addr_text.parse::<u8>() {
                     Ok(addr_length) => Some(addr_length),
                     Err(_) => {
-                        eprintln!("Expected valid ARP hardware address length");
-                        process::exit(1);
+                        violations.push("Expected valid ARP hardware address length".to_string());
+                        None
                     }
                 }
             },
@@ -487,32 +1778,96 @@ impl ScanOptions {
         let arp_operation = match matches.get_one::<String>("arp_operation") {
             Some(arp_op_text) => {
     
-                match arp_op_text.parse::<u16>() {
-                    Ok(op_number) => Some(ArpOperation::new(op_number)),
-                    Err(_) => {
-                        eprintln!("Expected valid ARP operation number");
-                        process::exit(1);
+                match ScanOptions::parse_arp_operation(arp_op_text) {
+                    Ok(arp_operation) => Some(arp_operation),
+                    Err(err) => {
+                        violations.push(format!("Expected valid ARP operation ({})", err));
+                        None
                     }
                 }
             },
             None => None
         };
-    
-        Arc::new(ScanOptions {
-            profile,
-            interface_name,
+
+        if !violations.is_empty() {
+            return Err(ScanError::InvalidOptions(OptionsError(violations)));
+        }
+
+        Ok(ScanOptions {
+            interface_names,
+            all_interfaces,
             network_range,
+            excluded_networks,
+            target_hostnames,
             timeout_ms,
             resolve_hostname,
+            resolve_order,
+            resolver,
+            hostname_style,
+            dns_servers,
+            hosts_file,
+            static_hostnames: OnceLock::new(),
+            dhcp_hostnames,
+            annotations,
+            resolve_cache_file,
+            resolve_cache_ttl_ms,
+            resolve_concurrency,
+            dns_timeout_ms,
+            mdns_timeout_ms,
+            netbios_timeout_ms,
+            verbose,
+            progress,
             source_ipv4,
             destination_mac,
             source_mac,
-            vlan_id,
+            arp_sender_mac,
+            vlan_ids,
+            vlan_priority,
             retry_count,
             scan_timing,
-            randomize_targets,
+            random_seed,
+            passive,
+            dry_run,
+            read_pcap,
+            max_duration_ms,
+            max_hosts,
+            strict,
+            fail_on_conflict,
+            loop_mode,
+            state_file,
+            loop_interval_ms,
+            gratuitous_ipv4,
+            probe_ipv4,
+            verify,
+            include_arp_cache,
+            include_unsolicited,
+            wake_macs,
+            wake_grace_ms,
+            wake_only,
+            announce_count,
+            announce_interval_ms,
+            force,
             output,
+            output_file,
+            host_template,
+            summary_template,
+            pretty,
+            columns,
+            csv_headers,
+            csv_delimiter,
+            csv_quote_all,
+            csv_summary_file,
+            #[cfg(feature = "sqlite")]
+            output_sqlite,
+            capture_file,
+            gelf_address,
+            allowlist,
+            ansible_group_by,
+            only_resolved,
             oui_file,
+            top_vendors,
+            group_by_mac,
+            custom_oui_file,
             hw_type,
             hw_addr,
             proto_type,
@@ -526,11 +1881,191 @@ impl ScanOptions {
         matches!(&self.output, OutputFormat::Plain)
     }
 
+    /**
+     * NDJSON hosts are streamed as they answer, except when replaying a
+     * capture (which is read at once).
+     */
+    pub fn is_streaming_output(&self) -> bool {
+
+        matches!(&self.output, OutputFormat::Ndjson) && self.output_file.is_none() && self.read_pcap.is_none()
+    }
+
     pub fn has_vlan(&self) -> bool {
 
-        matches!(&self.vlan_id, Some(_)) 
+        !self.vlan_ids.is_empty()
+    }
+
+}
+
+/**
+ * Options of the ARP responder (see the hidden 'respond' subcommand), which
+ * answers requests for simulated hosts instead of scanning.
+ */
+#[derive(Debug)]
+pub struct ResponderOptions {
+    pub interface_name: String,
+    pub hosts: Vec<SimulatedHost>,
+    pub vlan_id: Option<u16>,
+    pub delay_ms: u64,
+    pub loss_percent: u8,
+    pub seed: Option<u64>
+}
+
+impl ResponderOptions {
+
+    pub fn from_matches(matches: &ArgMatches) -> Result<ResponderOptions, OptionsError> {
+
+        let mut violations: Vec<String> = vec![];
+
+        let hosts: Vec<SimulatedHost> = matches.get_many::<String>("host").into_iter().flatten().filter_map(|value| {
+            SimulatedHost::parse(value).map_err(|err| violations.push(err)).ok()
+        }).collect();
+        let delay_ms = matches.get_one::<String>("delay").map_or(Ok(0), |value| parse_to_milliseconds(value)).unwrap_or_else(|err| {
+            violations.push(format!("Expected correct reply delay, {}", err));
+            0
+        });
+        let loss_percent = matches.get_one::<String>("loss").map_or(Ok(0), |value| value.parse::<u8>()).ok().filter(|loss| *loss <= 100).unwrap_or_else(|| {
+            violations.push("Expected loss percentage between 0 and 100".to_string());
+            0
+        });
+        let seed = matches.get_one::<String>("seed").and_then(|value| value.parse::<u64>().map_err(|err| {
+            violations.push(format!("Expected numeric loss seed ({})", err));
+        }).ok());
+        let vlan_id = matches.get_one::<String>("vlan").and_then(|value| value.parse::<u16>().ok().filter(|vlan_id| (1..=4094).contains(vlan_id)).or_else(|| {
+            violations.push(format!("Expected valid VLAN identifier between 1 and 4094 (got '{}')", value));
+            None
+        }));
+
+        match violations.is_empty() {
+            true => Ok(ResponderOptions {
+                interface_name: matches.get_one::<String>("interface").cloned().unwrap_or_default(),
+                hosts,
+                vlan_id,
+                delay_ms,
+                loss_percent,
+                seed
+            }),
+            false => Err(OptionsError(violations))
+        }
+    }
+}
+
+/**
+ * Violated option rules, reported all at once so that users can fix every
+ * invalid combination in a single pass.
+ */
+#[derive(Debug, PartialEq)]
+pub struct OptionsError(pub Vec<String>);
+
+impl fmt::Display for OptionsError {
+
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+
+        write!(formatter, "Invalid scan options:")?;
+        for violation in &self.0 {
+            write!(formatter, "\n  - {}", violation)?;
+        }
+        Ok(())
     }
+}
+
+impl Error for OptionsError {}
 
+/**
+ * Builder of scan options checking the constraints between several options
+ * (which can not be expressed as CLI argument conflicts), such as a source
+ * IPv4 outside of the selected interfaces. The interfaces of the host are
+ * used by default, other interfaces can be given for tests.
+ */
+pub struct ScanOptionsBuilder {
+    options: ScanOptions,
+    interfaces: Vec<NetworkInterface>
+}
+
+impl ScanOptionsBuilder {
+
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, ScanError> {
+
+        Ok(ScanOptionsBuilder {
+            options: ScanOptions::from_matches(matches)?,
+            interfaces: pnet_datalink::interfaces()
+        })
+    }
+
+    pub fn interfaces(mut self, interfaces: Vec<NetworkInterface>) -> Self {
+
+        self.interfaces = interfaces;
+        self
+    }
+
+    /**
+     * Check all cross-option rules, the error lists every violated rule. The
+     * '--force' flag allows unusual (but valid) packets to be sent anyway.
+     */
+    pub fn validate(&self) -> Result<(), OptionsError> {
+
+        let options = &self.options;
+        let mut violations: Vec<String> = vec![];
+
+        if options.timeout_ms == 0 {
+            violations.push("The timeout should be strictly positive, no response could be received".to_string());
+        }
+
+        // Gratuitous ARP announcements may impersonate another host with the
+        // source IPv4, which is then not expected on the interface. Replays
+        // identify the scanner of the capture, not a local address.
+        if let (Some(source_ipv4), false, None, None) = (options.source_ipv4, options.force, options.gratuitous_ipv4, &options.read_pcap) {
+            for interface in self.selected_interfaces() {
+                let is_local = interface.ips.iter().any(|ip_network| ip_network.is_ipv4() && ip_network.contains(IpAddr::V4(source_ipv4)));
+                if !is_local {
+                    violations.push(format!("Source IPv4 {} is not in the networks of interface {}, use --force to send it anyway", source_ipv4, interface.name));
+                }
+            }
+        }
+
+        // A unicast destination MAC only reaches a single host, scanning a
+        // whole network with it would miss every other host.
+        if let (Some(destination_mac), false) = (options.destination_mac, options.force) {
+            let is_unicast = destination_mac.0 & 0x01 == 0x00;
+            if is_unicast && !self.has_single_target() {
+                violations.push(format!("Destination MAC {} is unicast but several IPv4 addresses are scanned, use --force to send it anyway", destination_mac));
+            }
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(OptionsError(violations))
+        }
+    }
+
+    pub fn build(self) -> Result<Arc<ScanOptions>, OptionsError> {
+
+        self.validate()?;
+        Ok(Arc::new(self.options))
+    }
+
+    /**
+     * Interfaces used by the scan, unknown interface names are ignored (they
+     * are reported when the network configuration is computed).
+     */
+    fn selected_interfaces(&self) -> Vec<&NetworkInterface> {
+
+        if self.options.all_interfaces {
+            return self.interfaces.iter().filter(|interface| utils::is_eligible_interface(interface)).collect();
+        }
+        match self.options.interface_names.is_empty() {
+            true => self.interfaces.iter().find(|interface| utils::is_eligible_interface(interface)).into_iter().collect(),
+            false => self.interfaces.iter().filter(|interface| self.options.interface_names.contains(&interface.name)).collect()
+        }
+    }
+
+    fn has_single_target(&self) -> bool {
+
+        match &self.options.network_range {
+            Some(network_range) => matches!(network_range.as_slice(), [IpNetwork::V4(network)] if network.prefix() == 32),
+            None => false
+        }
+    }
 }
 
 
@@ -543,14 +2078,14 @@ mod tests {
     #[test]
     fn should_have_no_network_default() {
         
-        let networks = ScanOptions::compute_networks(None, None);
+        let networks = ScanOptions::compute_networks(None, None, &mut vec![]);
         assert_eq!(networks, Ok(None));
     }
 
     #[test]
     fn should_handle_single_ipv4_arg() {
         
-        let networks = ScanOptions::compute_networks(None, Some(&"192.168.1.20".to_string()));
+        let networks = ScanOptions::compute_networks(None, Some(&"192.168.1.20".to_string()), &mut vec![]);
 
         let target_network: Vec<IpNetwork> = vec![
             IpNetwork::V4(
@@ -564,7 +2099,7 @@ mod tests {
     #[test]
     fn should_handle_multiple_ipv4_arg() {
         
-        let networks = ScanOptions::compute_networks(None, Some(&"192.168.1.20,192.168.1.50".to_string()));
+        let networks = ScanOptions::compute_networks(None, Some(&"192.168.1.20,192.168.1.50".to_string()), &mut vec![]);
 
         let target_network: Vec<IpNetwork> = vec![
             IpNetwork::V4(
@@ -581,7 +2116,7 @@ mod tests {
     #[test]
     fn should_handle_single_network_arg() {
         
-        let networks = ScanOptions::compute_networks(None, Some(&"192.168.1.0/24".to_string()));
+        let networks = ScanOptions::compute_networks(None, Some(&"192.168.1.0/24".to_string()), &mut vec![]);
 
         let target_network: Vec<IpNetwork> = vec![
             IpNetwork::V4(
@@ -595,7 +2130,7 @@ mod tests {
     #[test]
     fn should_handle_network_mix_arg() {
         
-        let networks = ScanOptions::compute_networks(None, Some(&"192.168.20.1,192.168.1.0/24,192.168.5.4/28".to_string()));
+        let networks = ScanOptions::compute_networks(None, Some(&"192.168.20.1,192.168.1.0/24,192.168.5.4/28".to_string()), &mut vec![]);
 
         let target_network: Vec<IpNetwork> = vec![
             IpNetwork::V4(
@@ -615,7 +2150,7 @@ mod tests {
     #[test]
     fn should_handle_file_input() {
         
-        let networks = ScanOptions::compute_networks(Some(&"./data/ip-list.txt".to_string()), None);
+        let networks = ScanOptions::compute_networks(Some(&"./data/ip-list.txt".to_string()), None, &mut vec![]);
 
         let target_network: Vec<IpNetwork> = vec![
             IpNetwork::V4(
@@ -632,20 +2167,368 @@ mod tests {
         assert_eq!(networks, Ok(Some(target_network)));
     }
 
+    #[test]
+    fn should_merge_file_and_network_inputs() {
+
+        let networks = ScanOptions::compute_networks(Some(&"./data/target-list.txt".to_string()), Some(&"10.0.0.1".to_string()), &mut vec![]);
+
+        let target_network: Vec<IpNetwork> = vec![
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(10, 0, 0, 1), 32).unwrap()
+            ),
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 1), 32).unwrap()
+            ),
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(192, 168, 2, 0), 29).unwrap()
+            ),
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(192, 168, 3, 10), 31).unwrap()
+            ),
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(192, 168, 3, 12), 32).unwrap()
+            )
+        ];
+
+        assert_eq!(networks, Ok(Some(target_network)));
+    }
+
+    #[test]
+    fn should_report_malformed_file_line() {
+
+        let networks = ScanOptions::compute_networks(Some(&"./data/target-list-invalid.txt".to_string()), None, &mut vec![]);
+
+        assert_eq!(networks, Err("Expected valid IPv4 network range on line 3 of ./data/target-list-invalid.txt (invalid address: 192.168.1.300)".to_string()));
+    }
+
+    #[test]
+    fn should_resolve_hostname_target() {
+
+        let mut target_hostnames: Vec<TargetHostname> = vec![];
+        let networks = ScanOptions::compute_networks(None, Some(&"localhost,192.168.1.1".to_string()), &mut target_hostnames);
+
+        let target_network: Vec<IpNetwork> = vec![
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(127, 0, 0, 1), 32).unwrap()
+            ),
+            IpNetwork::V4(
+                Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 1), 32).unwrap()
+            )
+        ];
+
+        assert_eq!(networks, Ok(Some(target_network)));
+        assert_eq!(target_hostnames.len(), 1);
+        assert_eq!(target_hostnames[0].name, "localhost");
+        assert_eq!(target_hostnames[0].addresses, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+    }
+
+    #[test]
+    fn should_detect_hostname_targets() {
+
+        assert!(ScanOptions::is_hostname("printer1"));
+        assert!(ScanOptions::is_hostname("printer-2.office.local"));
+        assert!(!ScanOptions::is_hostname("192.168.1.1"));
+        assert!(!ScanOptions::is_hostname("192.168.1.0/24"));
+        assert!(!ScanOptions::is_hostname("192.168.1.1-192.168.1.9"));
+    }
+
+    #[test]
+    fn should_parse_address_range() {
+
+        let networks = ScanOptions::parse_target("192.168.1.0-192.168.1.255", &mut vec![]);
+        assert_eq!(networks, Ok(vec![IpNetwork::V4(Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 0), 24).unwrap())]));
+
+        let networks = ScanOptions::parse_target("0.0.0.0-255.255.255.255", &mut vec![]);
+        assert_eq!(networks, Ok(vec![IpNetwork::V4(Ipv4Network::new(Ipv4Addr::new(0, 0, 0, 0), 0).unwrap())]));
+
+        assert!(ScanOptions::parse_target("192.168.1.20-192.168.1.10", &mut vec![]).is_err());
+    }
+
     #[test]
     fn should_fail_incorrect_network() {
         
-        let networks = ScanOptions::compute_networks(None, Some(&"500.10.10.10/24".to_string()));
+        let networks = ScanOptions::compute_networks(None, Some(&"500.10.10.10/24".to_string()), &mut vec![]);
 
         assert_eq!(networks, Err("Expected valid IPv4 network range (invalid address: 500.10.10.10/24)".to_string()));
     }
 
+    #[test]
+    fn should_compute_excluded_networks() {
+
+        let excluded = ScanOptions::compute_excluded_networks(None, Some(&"192.168.1.1, 10.0.0.0/30".to_string()));
+
+        assert_eq!(excluded, Ok(vec![
+            Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 1), 32).unwrap(),
+            Ipv4Network::new(Ipv4Addr::new(10, 0, 0, 0), 30).unwrap()
+        ]));
+    }
+
+    #[test]
+    fn should_compute_wake_macs() {
+
+        let wake_macs = ScanOptions::compute_wake_macs(None, Some(&"00:22:72:d7:b5:23, 40:55:82:c3:e5:5b,00:22:72:d7:b5:23".to_string()));
+
+        assert_eq!(wake_macs, Ok(vec![
+            MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23),
+            MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b)
+        ]));
+        assert!(ScanOptions::compute_wake_macs(None, Some(&"00:22:72".to_string())).is_err());
+    }
+
+    #[test]
+    fn should_fail_incorrect_excluded_network() {
+
+        let excluded = ScanOptions::compute_excluded_networks(None, Some(&"192.168.1.1,fe80::1".to_string()));
+
+        assert!(excluded.is_err());
+    }
+
     #[test]
     fn should_fail_unreadable_network() {
         
-        let networks = ScanOptions::compute_networks(None, Some(&"no-network".to_string()));
+        let networks = ScanOptions::compute_networks(None, Some(&"no-network".to_string()), &mut vec![]);
+
+        assert!(networks.unwrap_err().starts_with("Expected valid IPv4 network range (could not resolve hostname no-network"));
+    }
+
+    #[test]
+    fn should_read_allowlist_file() {
+
+        let allowlist = ScanOptions::read_allowlist("./data/allowlist.txt");
+
+        assert_eq!(allowlist, Ok(vec![
+            "192.168.1.1".to_string(),
+            "40:55:82:c3:e5:5b".to_string()
+        ]));
+    }
+
+    #[test]
+    fn should_parse_ordered_columns() {
+
+        let columns = ScanOptions::parse_columns("mac,ipv4, vendor");
+
+        assert_eq!(columns, Ok(vec![OutputColumn::Mac, OutputColumn::Ipv4, OutputColumn::Vendor]));
+    }
+
+    #[test]
+    fn should_parse_resolve_order() {
+
+        let resolve_order = ScanOptions::parse_resolve_order("mdns, dns,mdns");
+
+        assert_eq!(resolve_order, Ok(vec![HostnameSource::Mdns, HostnameSource::Dns]));
+    }
+
+    #[test]
+    fn should_fail_unknown_resolve_method() {
+
+        let resolve_order = ScanOptions::parse_resolve_order("dns,llmnr");
+
+        assert_eq!(resolve_order, Err("Unknown resolution method 'llmnr' (expected hosts, dns, mdns, netbios, dhcp)".to_string()));
+    }
+
+    #[test]
+    fn should_parse_csv_delimiter() {
+
+        assert_eq!(ScanOptions::parse_csv_delimiter(";"), Ok(b';'));
+        assert_eq!(ScanOptions::parse_csv_delimiter("\t"), Ok(b'\t'));
+    }
+
+    #[test]
+    fn should_fail_multibyte_csv_delimiter() {
+
+        assert_eq!(ScanOptions::parse_csv_delimiter("§"), Err("Expected a single-byte CSV delimiter (got '§')".to_string()));
+        assert!(ScanOptions::parse_csv_delimiter(";;").is_err());
+        assert!(ScanOptions::parse_csv_delimiter("").is_err());
+    }
+
+    #[test]
+    fn should_parse_source_mac() {
+
+        assert_eq!(ScanOptions::parse_source_mac("00:22:72:d7:b5:23", false), Ok(MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)));
+        assert!(ScanOptions::parse_source_mac("00:22:72", false).is_err());
+    }
+
+    #[test]
+    fn should_reject_multicast_source_mac_without_force() {
+
+        assert_eq!(ScanOptions::parse_source_mac("ff:ff:ff:ff:ff:ff", false), Err("multicast or broadcast address ff:ff:ff:ff:ff:ff, use --force to send it anyway".to_string()));
+        assert!(ScanOptions::parse_source_mac("01:00:5e:00:00:fb", false).is_err());
+        assert_eq!(ScanOptions::parse_source_mac("01:00:5e:00:00:fb", true), Ok(MacAddr::new(0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb)));
+    }
+
+    #[test]
+    fn should_parse_vlan_ids() {
+
+        assert_eq!(ScanOptions::parse_vlan_ids("10, 20,30,10"), Ok(vec![10, 20, 30]));
+        assert_eq!(ScanOptions::parse_vlan_ids("4094"), Ok(vec![4094]));
+    }
+
+    #[test]
+    fn should_fail_reserved_vlan_ids() {
+
+        assert_eq!(ScanOptions::parse_vlan_ids("0"), Err("Expected valid VLAN identifier between 1 and 4094 (got '0')".to_string()));
+        assert!(ScanOptions::parse_vlan_ids("10,4095").is_err());
+        assert!(ScanOptions::parse_vlan_ids("ten").is_err());
+    }
+
+    #[test]
+    fn should_parse_target_lines() {
+
+        let content = "# Targets from a previous scan\n192.168.1.20\n\n  10.0.0.0/30  \n";
+        let networks = ScanOptions::parse_target_lines(content, "standard input", &mut vec![]);
+
+        assert_eq!(networks, Ok(vec![
+            IpNetwork::V4(Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 20), 32).unwrap()),
+            IpNetwork::V4(Ipv4Network::new(Ipv4Addr::new(10, 0, 0, 0), 30).unwrap())
+        ]));
+        assert_eq!(ScanOptions::parse_target_lines("", "standard input", &mut vec![]), Ok(vec![]));
+    }
+
+    #[test]
+    fn should_report_invalid_target_line() {
+
+        let networks = ScanOptions::parse_target_lines("192.168.1.20\nnot an ip\n", "standard input", &mut vec![]);
+
+        assert!(networks.unwrap_err().starts_with("Expected valid IPv4 network range on line 2 of standard input"));
+    }
+
+    #[test]
+    fn should_parse_arp_operations() {
+
+        assert_eq!(ScanOptions::parse_arp_operation("request"), Ok(ArpOperations::Request));
+        assert_eq!(ScanOptions::parse_arp_operation("Reply"), Ok(ArpOperations::Reply));
+        assert_eq!(ScanOptions::parse_arp_operation("8"), Ok(ArpOperation::new(8)));
+        assert_eq!(ScanOptions::parse_arp_operation("answer"), Err("unknown operation 'answer'".to_string()));
+    }
+
+    #[test]
+    fn should_dedup_interface_names() {
+
+        let names = ["eth1".to_string(), "eth0".to_string(), "eth1".to_string()];
+
+        assert_eq!(ScanOptions::dedup_interface_names(names.iter()), vec!["eth1".to_string(), "eth0".to_string()]);
+    }
+
+    #[test]
+    fn should_defer_lookups_of_default_options() {
+
+        let options = ScanOptions::default();
+
+        assert_eq!(options.resolver, ResolverBackend::Auto);
+        assert!(options.static_hostnames.get().is_none());
+        assert_eq!(options.oui_file, None);
+        assert_eq!(options.resolve_cache_file, None);
+        assert_eq!(options.timeout_ms, TIMEOUT_MS_DEFAULT);
+    }
+
+    #[test]
+    fn should_fail_unknown_column() {
+
+        let columns = ScanOptions::parse_columns("ipv4,latency");
+
+        assert_eq!(columns, Err("Unknown column 'latency' (expected ipv4, mac, frame_src_mac, hostname, vendor, response_time_ms, reply_count, interface, status, vlan, origin, mac_type, virtualization, note)".to_string()));
+    }
+
+    #[test]
+    fn should_report_every_invalid_option_value() {
+
+        let invalid_matches = build_args().get_matches_from(["arp-scan", "--rate", "0", "--retry", "-", "-o", "pdf"]);
+        let error = ScanOptions::new(&invalid_matches).err().unwrap();
+        assert!(matches!(&error, ScanError::InvalidOptions(OptionsError(violations)) if violations.len() == 3));
+
+        let network_matches = build_args().get_matches_from(["arp-scan", "-n", "500.10.10.10/24"]);
+        assert!(matches!(ScanOptions::new(&network_matches), Err(ScanError::InvalidTarget(_))));
+    }
+
+    fn build_options(arguments: &[&str]) -> ScanOptionsBuilder {
+
+        let interface = NetworkInterface {
+            name: "eth0".to_string(),
+            description: String::new(),
+            index: 2,
+            mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            ips: vec!["192.168.1.21/24".parse().unwrap()],
+            flags: 0
+        };
+        let matches = build_args().get_matches_from([&["arp-scan", "-i", "eth0", "--numeric"], arguments].concat());
+        ScanOptionsBuilder::from_matches(&matches).unwrap().interfaces(vec![interface])
+    }
+
+    #[test]
+    fn should_accept_consistent_options() {
+
+        assert!(build_options(&[]).build().is_ok());
+        assert!(build_options(&["-S", "192.168.1.200", "-t", "500ms"]).validate().is_ok());
+        assert!(build_options(&["-M", "d2:71:d8:29:a8:01", "-n", "192.168.1.1"]).validate().is_ok());
+        assert!(build_options(&["-M", "ff:ff:ff:ff:ff:ff"]).validate().is_ok());
+    }
+
+    #[test]
+    fn should_reject_zero_timeout() {
+
+        let validation = build_options(&["-t", "0"]).validate();
+
+        assert_eq!(validation, Err(OptionsError(vec!["The timeout should be strictly positive, no response could be received".to_string()])));
+    }
+
+    #[test]
+    fn should_reject_source_ip_outside_interface() {
+
+        let validation = build_options(&["-S", "10.0.0.5"]).validate();
+
+        assert_eq!(validation, Err(OptionsError(vec!["Source IPv4 10.0.0.5 is not in the networks of interface eth0, use --force to send it anyway".to_string()])));
+    }
+
+    #[test]
+    fn should_reject_unicast_destination_for_network_scan() {
+
+        let validation = build_options(&["-M", "d2:71:d8:29:a8:01", "-n", "192.168.1.0/24"]).validate();
+
+        assert_eq!(validation, Err(OptionsError(vec!["Destination MAC d2:71:d8:29:a8:01 is unicast but several IPv4 addresses are scanned, use --force to send it anyway".to_string()])));
+    }
+
+    #[test]
+    fn should_report_every_violated_rule() {
+
+        let error = build_options(&["-t", "0", "-S", "10.0.0.5", "-M", "d2:71:d8:29:a8:01"]).build().err().unwrap();
+
+        assert_eq!(error.0.len(), 3);
+        assert!(error.to_string().starts_with("Invalid scan options:\n  - The timeout should be strictly positive"));
+    }
+
+    #[test]
+    fn should_allow_forced_options() {
+
+        assert!(build_options(&["-S", "10.0.0.5", "-M", "d2:71:d8:29:a8:01", "--force"]).validate().is_ok());
+        assert!(build_options(&["-S", "10.0.0.5", "--gratuitous", "192.168.1.21"]).validate().is_ok());
+    }
+
+    #[test]
+    fn should_parse_responder_options() {
+
+        let matches = build_args().get_matches_from(["arp-scan", "respond", "-i", "veth1", "--host", "10.10.0.5", "--host", "10.10.0.9=00:22:72:d7:b5:23", "--delay", "20ms", "--loss", "25"]);
+        let responder_options = ResponderOptions::from_matches(matches.subcommand_matches("respond").unwrap()).unwrap();
+
+        assert_eq!(responder_options.interface_name, "veth1");
+        assert_eq!(responder_options.hosts.len(), 2);
+        assert_eq!(responder_options.hosts[1].mac, MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23));
+        assert_eq!((responder_options.delay_ms, responder_options.loss_percent, responder_options.seed), (20, 25, None));
+
+        let matches = build_args().get_matches_from(["arp-scan", "respond", "-i", "veth1", "--host", "10.10.0.300", "--loss", "150"]);
+        let error = ResponderOptions::from_matches(matches.subcommand_matches("respond").unwrap()).err().unwrap();
+        assert_eq!(error.0.len(), 2);
+    }
+
+    #[test]
+    fn should_reject_responder_vlan_zero() {
+
+        let matches = build_args().get_matches_from(["arp-scan", "respond", "-i", "veth1", "--host", "10.10.0.5", "--vlan", "0"]);
+        let error = ResponderOptions::from_matches(matches.subcommand_matches("respond").unwrap()).err().unwrap();
+        assert_eq!(error.0, ["Expected valid VLAN identifier between 1 and 4094 (got '0')"]);
 
-        assert_eq!(networks, Err("Expected valid IPv4 network range (invalid address: no-network)".to_string()));
+        let matches = build_args().get_matches_from(["arp-scan", "respond", "-i", "veth1", "--host", "10.10.0.5", "--vlan", "4094"]);
+        let responder_options = ResponderOptions::from_matches(matches.subcommand_matches("respond").unwrap()).unwrap();
+        assert_eq!(responder_options.vlan_id, Some(4094));
     }
 
 }
@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use hickory_resolver::TokioResolver;
+use ipnetwork::IpNetwork;
+use pnet_datalink::{MacAddr, NetworkInterface};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::{self, JoinError, JoinSet};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use crate::args::{OptionsError, ScanOptions, ScanTiming};
+use crate::dns;
+use crate::error::ScanError;
+use crate::network::{self, CancellationToken, HostCallback, HostOrigin, HostnameSource, NetworkIterator, ResponseSummary, ScanEvent, ScanMetadata, ScanPass, TargetDetails};
+use crate::resolve::{self, ResolverChain};
+use crate::resolver::{HostnameResolver, StaticResolver};
+use crate::scanner::{ArpScanner, ScanResult, ScanSession};
+use crate::vendor::VendorDatabase;
+
+// Waits are split in steps of this duration, so that a halted scan does not
+// wait for the whole timeout (as 'wait_until_halted' does for blocking scans).
+const HALT_CHECK_MS: u64 = 100;
+
+impl ArpScanner {
+
+    /**
+     * Run a single scan round on the tokio runtime, for async programs.
+     * Requests are paced with tokio timers, the blocking datalink reads run
+     * on blocking tasks and reverse DNS lookups go through the tokio resolver
+     * of hickory (see 'ScanSession::run_round_async').
+     *
+     * Dropping the future before completion cancels the scan: the receive
+     * tasks stop shortly after, and the results are discarded.
+     */
+    pub async fn scan(self) -> Result<ScanResult, ScanError> {
+
+        let cancel_on_drop = CancelOnDrop(Some(self.cancellation_token()));
+
+        let mut session = task::spawn_blocking(move || self.open()).await.map_err(join_error)??;
+        let scan_result = session.run_round_async().await;
+        task::spawn_blocking(move || session.close()).await.map_err(join_error)??;
+
+        cancel_on_drop.disarm();
+        scan_result
+    }
+}
+
+impl ScanSession {
+
+    /**
+     * Run a scan round on the tokio runtime, following the blocking round
+     * (see 'ScanSession::run_round'). The ARP responses of each scan pass
+     * are received on a blocking task, which forwards new hosts through a
+     * channel: their reverse DNS lookups start while the scan goes on, the
+     * other resolution methods are applied once the pass is done. Scans
+     * resumed from a state file are not supported.
+     */
+    pub async fn run_round_async(&mut self) -> Result<ScanResult, ScanError> {
+
+        if self.checkpoint.is_some() {
+            return Err(ScanError::InvalidOptions(OptionsError(vec!["Async scans can not be resumed from a state file".to_string()])));
+        }
+
+        let options = Arc::clone(&self.options);
+        let scan_state = self.scan_state.clone();
+        scan_state.reset_round();
+
+        // Hostnames are resolved by the round itself, the receive tasks only
+        // report the hosts found (enriched with their vendor).
+        let receive_options = Arc::new(ScanOptions {
+            resolve_hostname: false,
+            ..options.as_ref().clone()
+        });
+        let dns_lookup = DnsLookup::from_options(&options, self.resolvers.is_some());
+
+        let scanned_interfaces: Vec<&NetworkInterface> = self.channels.iter().map(|channel| &channel.interface).collect();
+        let scanned_networks: Vec<&IpNetwork> = self.channels.iter().flat_map(|channel| channel.ip_networks.iter()).collect();
+        let scan_metadata = ScanMetadata::new(&scanned_interfaces, &scanned_networks, self.channels[0].source_ip);
+
+        let interval_ms = network::compute_scan_estimation(self.target_count, &options).interval_ms;
+        let is_halted = || scan_state.cancellation.is_cancelled() || scan_state.has_reached_host_limit(options.max_hosts);
+        let attempt_count = match options.passive {
+            true => 0,
+            false => options.retry_count + 1
+        };
+
+        // With a packets-per-second rate, a single timer paces all requests of
+        // the round. Otherwise, a fixed interval is applied after each request.
+        let mut rate_interval: Option<Interval> = match options.scan_timing {
+            ScanTiming::Rate(rate) => {
+                let period = Duration::from_secs_f64(1_f64 / rate.max(1) as f64).max(Duration::from_nanos(1));
+                let mut rate_interval = time::interval(period);
+                rate_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                Some(rate_interval)
+            },
+            _ => None
+        };
+
+        let progress = self.progress.clone();
+        let request_interval_us = match options.scan_timing {
+            ScanTiming::Rate(rate) => 1_000_000 / rate.max(1),
+            _ => interval_ms * 1000
+        };
+        let window_count = (self.scan_passes.len() * attempt_count.max(1)) as u64;
+        let progress_target_count = match options.passive {
+            true => 0,
+            false => self.target_count
+        };
+        progress.start_round(progress_target_count, request_interval_us, options.timeout_ms, window_count);
+
+        let event_sender = self.event_sender.clone();
+        let send_event = |event: ScanEvent| {
+            if let Some(event_sender) = &event_sender {
+                let _ = event_sender.send(event);
+            }
+        };
+        send_event(ScanEvent::Started { targets: progress_target_count });
+
+        let mut requests_sent: usize = 0;
+        let mut send_errors: usize = 0;
+        let mut send_started_at: Option<Instant> = None;
+        let mut send_duration_ms: u128 = 0;
+
+        let mut response_summary = ResponseSummary::default();
+        let mut target_details: Vec<TargetDetails> = vec![];
+
+        for (pass_index, (channel_index, vlan_id)) in self.scan_passes.clone().into_iter().enumerate() {
+
+            if pass_index > 0 && is_halted() {
+                break;
+            }
+
+            let channel = &self.channels[channel_index];
+            scan_state.reset();
+
+            // New hosts are forwarded by the receive task, and reported (with
+            // their reverse DNS lookup started) as they arrive.
+            let (host_sender, host_receiver) = mpsc::unbounded_channel::<TargetDetails>();
+            let forward_host = move |target_detail: &TargetDetails| {
+                let _ = host_sender.send(target_detail.clone());
+            };
+            let host_reports = task::spawn(report_hosts(host_receiver, self.host_callback.clone(), self.event_sender.clone(), dns_lookup.clone()));
+
+            let scan_pass = ScanPass {
+                interface_name: channel.interface.name.clone(),
+                own_mac: options.arp_sender_mac.or(options.source_mac).or(channel.interface.mac),
+                source_ipv4: channel.source_ip,
+                vlan_id,
+                host_sender: None,
+                host_callback: Some(Arc::new(Mutex::new(forward_host))),
+                event_sender: None,
+                frame_filter: self.frame_filter.clone(),
+                resolvers: None,
+                targets: match options.passive || options.include_unsolicited {
+                    true => None,
+                    false => Some(channel.scan_targets(&options))
+                }
+            };
+            let link = Arc::clone(&channel.link);
+            let cloned_options = Arc::clone(&receive_options);
+            let cloned_scan_state = scan_state.clone();
+            let cloned_capture_sender = self.capture_sender.clone();
+            let mut vendor_list = mem::replace(&mut self.vendor_list, VendorDatabase::disabled());
+            let arp_responses = task::spawn_blocking(move || {
+                let scan_results = network::receive_arp_responses(link.as_ref(), cloned_options, scan_pass, cloned_scan_state, cloned_capture_sender, &mut vendor_list);
+                (scan_results, vendor_list)
+            });
+
+            let mut responses_per_attempt: Vec<usize> = vec![];
+            let mut send_error: Option<ScanError> = None;
+
+            for attempt_index in 0..attempt_count {
+
+                if is_halted() {
+                    break;
+                }
+                if attempt_index > 0 {
+                    send_event(ScanEvent::RetryRound(attempt_index.try_into().unwrap_or(u8::MAX)));
+                }
+
+                let ip_networks: Vec<&IpNetwork> = channel.ip_networks.iter().collect();
+                let targets: Box<dyn Iterator<Item = (IpAddr, Option<MacAddr>)> + Send> = match &channel.neighbors {
+                    Some(neighbors) => Box::new(neighbors.clone().into_iter().map(|entry| (IpAddr::V4(entry.ipv4), Some(entry.mac)))),
+                    None => Box::new(NetworkIterator::new(&ip_networks, options.random_seed).map(|ip_address| (ip_address, None)))
+                };
+
+                for (ip_address, unicast_mac) in targets {
+
+                    if is_halted() {
+                        break;
+                    }
+
+                    let ipv4_address = match ip_address {
+                        IpAddr::V4(ipv4_address) => ipv4_address,
+                        IpAddr::V6(_) => continue
+                    };
+                    if network::is_excluded_target(ipv4_address, &options.excluded_networks) {
+                        continue;
+                    }
+                    if network::has_responded(&scan_state.responded_hosts, ipv4_address) {
+                        if attempt_index == 0 {
+                            progress.record_sent();
+                        }
+                        continue;
+                    }
+                    if let Some(rate_interval) = rate_interval.as_mut() {
+                        rate_interval.tick().await;
+                    }
+                    if let Ok(mut times) = scan_state.request_times.lock() {
+                        times.insert(ipv4_address, Instant::now());
+                    }
+                    let send_result = match unicast_mac {
+                        Some(target_mac) => network::send_unicast_arp_request(channel.link.as_ref(), &channel.interface, channel.source_ip, ipv4_address, target_mac, vlan_id, Arc::clone(&options)),
+                        None => network::send_arp_request(channel.link.as_ref(), &channel.interface, channel.source_ip, ipv4_address, vlan_id, Arc::clone(&options))
+                    };
+                    match send_result {
+                        Ok(true) => {
+                            requests_sent += 1;
+                            send_event(ScanEvent::PacketSent { ip: ipv4_address });
+                        },
+                        Ok(false) => send_errors += 1,
+                        Err(error) => {
+                            send_error = Some(error);
+                            break;
+                        }
+                    }
+                    if attempt_index == 0 {
+                        progress.record_sent();
+                    }
+                    let send_started_at = *send_started_at.get_or_insert_with(Instant::now);
+                    send_duration_ms = send_started_at.elapsed().as_millis();
+                    if rate_interval.is_none() {
+                        sleep_until_halted(&is_halted, interval_ms).await;
+                    }
+                }
+
+                if send_error.is_some() {
+                    break;
+                }
+
+                progress.start_window();
+                sleep_until_halted(&is_halted, options.timeout_ms).await;
+                progress.complete_window();
+
+                let responded_count = scan_state.responded_hosts.lock().map(|hosts| hosts.len()).unwrap_or(0);
+                let previous_count: usize = responses_per_attempt.iter().sum();
+                responses_per_attempt.push(responded_count.saturating_sub(previous_count));
+            }
+            if attempt_count == 0 {
+                progress.start_window();
+                sleep_until_halted(&is_halted, options.timeout_ms).await;
+                progress.complete_window();
+            }
+
+            // The receive task stops at its next read timeout, the channel of
+            // new hosts is then closed and the pending lookups are settled.
+            scan_state.timed_out.store(true, Ordering::Relaxed);
+
+            let (scan_results, returned_vendor_list) = arp_responses.await.map_err(join_error)?;
+            self.vendor_list = returned_vendor_list;
+            let dns_hostnames = host_reports.await.map_err(join_error)?;
+            if let Some(error) = send_error {
+                progress.finish_round();
+                return Err(error);
+            }
+            let (mut interface_summary, interface_details) = scan_results?;
+            interface_summary.responses_per_attempt = responses_per_attempt;
+            response_summary.merge(interface_summary);
+
+            let vendor_list = &mut self.vendor_list;
+            let interface_details: Vec<TargetDetails> = match options.include_arp_cache && vlan_id == self.first_vlan_id {
+                true => network::merge_cached_neighbors(interface_details, &channel.cached_neighbors, &channel.interface.name).into_iter().map(|mut detail| {
+                    if detail.origin == Some(HostOrigin::Cache) {
+                        network::enrich_target_details(&mut detail, vendor_list, &options.annotations);
+                    }
+                    detail
+                }).collect(),
+                false => interface_details
+            };
+
+            let interface_details = resolve_hostnames(interface_details, Arc::clone(&options), self.resolvers.clone(), dns_hostnames).await?;
+
+            match &channel.neighbors {
+                Some(neighbors) => target_details.extend(network::apply_verify_statuses(interface_details, neighbors, &channel.interface.name)),
+                None => target_details.extend(interface_details)
+            }
+        }
+
+        progress.finish_round();
+        response_summary.requests_sent = requests_sent;
+        response_summary.send_errors = send_errors;
+        response_summary.send_duration_ms = send_duration_ms;
+        response_summary.host_limit_reached = scan_state.has_reached_host_limit(options.max_hosts);
+        response_summary.truncated = self.truncated.load(Ordering::Relaxed) || response_summary.host_limit_reached;
+        response_summary.cancelled = scan_state.cancellation.is_cancelled() && !self.truncated.load(Ordering::Relaxed);
+        send_event(ScanEvent::Finished(response_summary.clone()));
+
+        Ok(ScanResult {
+            summary: response_summary,
+            hosts: target_details,
+            metadata: scan_metadata
+        })
+    }
+}
+
+/**
+ * Cancel a scan when its future is dropped before completion.
+ */
+struct CancelOnDrop(Option<CancellationToken>);
+
+impl CancelOnDrop {
+
+    fn disarm(mut self) {
+
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+
+    fn drop(&mut self) {
+
+        if let Some(cancellation) = self.0.take() {
+            cancellation.cancel();
+        }
+    }
+}
+
+/**
+ * Reverse DNS lookups of an async scan: the DNS servers given by the user
+ * are tried in order, the DNS configuration of the system is used otherwise.
+ */
+#[derive(Clone)]
+enum DnsLookup {
+    Disabled,
+    Servers(Vec<SocketAddr>, u64),
+    System(Arc<TokioResolver>, u64)
+}
+
+impl DnsLookup {
+
+    /**
+     * Reverse DNS is only looked up when it is part of the resolution order
+     * (and not replaced by a custom resolution chain). A system DNS
+     * configuration that can not be read only disables reverse DNS.
+     */
+    fn from_options(options: &ScanOptions, has_custom_resolvers: bool) -> Self {
+
+        if !options.resolve_hostname || has_custom_resolvers || !options.resolve_order.contains(&HostnameSource::Dns) {
+            return DnsLookup::Disabled;
+        }
+        if !options.dns_servers.is_empty() {
+            return DnsLookup::Servers(options.dns_servers.clone(), options.dns_timeout_ms);
+        }
+        match dns::build_system_resolver(options.dns_timeout_ms) {
+            Ok(resolver) => DnsLookup::System(Arc::new(resolver), options.dns_timeout_ms),
+            Err(err) => {
+                eprintln!("[warn] {}, hostnames will not be resolved with DNS", err);
+                DnsLookup::Disabled
+            }
+        }
+    }
+
+    async fn lookup(self, ipv4: Ipv4Addr) -> Option<(Ipv4Addr, String)> {
+
+        let mut hostnames: HashMap<Ipv4Addr, String> = match &self {
+            DnsLookup::Disabled => HashMap::new(),
+            DnsLookup::Servers(servers, timeout_ms) => dns::resolve_hostnames_async(&[ipv4], servers, *timeout_ms, false).await,
+            DnsLookup::System(resolver, timeout_ms) => dns::reverse_lookup_all(resolver, &[ipv4], *timeout_ms).await.into_iter().filter_map(|(ipv4, hostname)| {
+                hostname.map(|hostname| (ipv4, hostname))
+            }).collect()
+        };
+        hostnames.remove(&ipv4).map(|hostname| (ipv4, hostname))
+    }
+}
+
+/**
+ * Report the hosts forwarded by a receive task until it stops, and start the
+ * reverse DNS lookup of each host. The hostnames found are returned once all
+ * lookups are settled.
+ */
+async fn report_hosts(mut host_receiver: UnboundedReceiver<TargetDetails>, host_callback: Option<HostCallback>, event_sender: Option<std::sync::mpsc::Sender<ScanEvent>>, dns_lookup: DnsLookup) -> HashMap<Ipv4Addr, String> {
+
+    let mut lookups = JoinSet::new();
+    while let Some(target_detail) = host_receiver.recv().await {
+
+        if let Some(host_callback) = &host_callback {
+            let mut callback = host_callback.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            callback(&target_detail);
+        }
+        if let Some(event_sender) = &event_sender {
+            let _ = event_sender.send(ScanEvent::HostFound(target_detail.clone()));
+        }
+        if !matches!(dns_lookup, DnsLookup::Disabled) {
+            lookups.spawn(dns_lookup.clone().lookup(target_detail.ipv4));
+        }
+    }
+
+    let mut hostnames: HashMap<Ipv4Addr, String> = HashMap::new();
+    while let Some(lookup) = lookups.join_next().await {
+        if let Ok(Some((ipv4, hostname))) = lookup {
+            hostnames.insert(ipv4, hostname);
+        }
+    }
+    hostnames
+}
+
+/**
+ * Resolve the hostnames of a scan pass on a blocking task, with the custom
+ * resolution chain or the chain of the scan options. Reverse DNS answers come
+ * from the lookups started while scanning.
+ */
+async fn resolve_hostnames(mut target_details: Vec<TargetDetails>, options: Arc<ScanOptions>, resolvers: Option<ResolverChain>, dns_hostnames: HashMap<Ipv4Addr, String>) -> Result<Vec<TargetDetails>, ScanError> {
+
+    if !options.resolve_hostname || target_details.is_empty() {
+        return Ok(target_details);
+    }
+
+    task::spawn_blocking(move || {
+        let resolvers: ResolverChain = match resolvers {
+            Some(resolvers) => resolvers,
+            None => resolve::build_resolvers(&target_details, &options)?.into_iter().map(|(method, resolver)| match method {
+                HostnameSource::Dns => (method, Arc::new(StaticResolver::new(dns_hostnames.clone())) as Arc<dyn HostnameResolver>),
+                _ => (method, resolver)
+            }).collect()
+        };
+        resolve::resolve_hostnames_with(&mut target_details, &options, &resolvers);
+        Ok(target_details)
+    }).await.map_err(join_error)?
+}
+
+/**
+ * Sleep on the tokio timer for the given duration (by steps of 100ms), unless
+ * the scan is halted while waiting.
+ */
+async fn sleep_until_halted(is_halted: impl Fn() -> bool, duration_ms: u64) {
+
+    let mut slept_ms: u64 = 0;
+    while !is_halted() && slept_ms < duration_ms {
+
+        let sleep_ms = (duration_ms - slept_ms).min(HALT_CHECK_MS);
+        time::sleep(Duration::from_millis(sleep_ms)).await;
+        slept_ms += sleep_ms;
+    }
+}
+
+fn join_error(error: JoinError) -> ScanError {
+
+    ScanError::Thread(format!("Failed to join scan task ({})", error))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::datalink::FakeDataLink;
+
+    const ROUTER_MAC: MacAddr = MacAddr(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+
+    fn build_interface() -> NetworkInterface {
+
+        NetworkInterface {
+            name: "eth0".to_string(),
+            description: String::new(),
+            index: 2,
+            mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            ips: vec!["192.168.1.21/24".parse().unwrap()],
+            flags: 1
+        }
+    }
+
+    fn build_runtime() -> tokio::runtime::Runtime {
+
+        tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap()
+    }
+
+    fn open_session(scanner: &ArpScanner, link: &FakeDataLink) -> ScanSession {
+
+        scanner.open_with_links(vec![(build_interface(), Arc::new(link.clone()))]).unwrap()
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn should_scan_on_the_tokio_runtime() {
+
+        let link = FakeDataLink::new();
+        let (router_ipv4, printer_ipv4) = (Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 30));
+        link.reply_to(router_ipv4, vec![FakeDataLink::arp_reply(ROUTER_MAC, router_ipv4, Ipv4Addr::new(192, 168, 1, 21))]);
+
+        let reported_hosts = Arc::new(Mutex::new(vec![]));
+        let cloned_reported_hosts = Arc::clone(&reported_hosts);
+        let scanner = ArpScanner::builder()
+            .interface("eth0")
+            .network(IpNetwork::from(IpAddr::V4(router_ipv4)))
+            .network(IpNetwork::from(IpAddr::V4(printer_ipv4)))
+            .timeout(Duration::from_millis(100))
+            .interval(Duration::ZERO)
+            .retries(1)
+            .resolve_hostnames(false)
+            .on_host_found(move |host| cloned_reported_hosts.lock().unwrap().push(host.ipv4))
+            .build();
+        let mut session = open_session(&scanner, &link);
+
+        let round = session.run_round_async();
+        assert_send(&round);
+        let scan_result = build_runtime().block_on(round).unwrap();
+
+        let hosts: Vec<(Ipv4Addr, MacAddr)> = scan_result.hosts.iter().map(|host| (host.ipv4, host.mac)).collect();
+        assert_eq!(hosts, [(router_ipv4, ROUTER_MAC)]);
+        assert_eq!(*reported_hosts.lock().unwrap(), [router_ipv4]);
+        assert_eq!(link.sent_requests(), [router_ipv4, printer_ipv4, printer_ipv4]);
+        assert_eq!(scan_result.summary.responses_per_attempt, [1, 0]);
+
+        let progress = scanner.progress().snapshot();
+        assert_eq!((progress.sent_count, progress.target_count), (2, 2));
+        assert!(progress.finished);
+    }
+
+    #[test]
+    fn should_stop_async_round_when_cancelled() {
+
+        let link = FakeDataLink::new();
+        let scanner = ArpScanner::builder()
+            .interface("eth0")
+            .network("192.168.1.0/24".parse().unwrap())
+            .timeout(Duration::from_secs(30))
+            .interval(Duration::ZERO)
+            .retries(0)
+            .resolve_hostnames(false)
+            .build();
+        let mut session = open_session(&scanner, &link);
+        scanner.cancellation_token().cancel();
+
+        let started_at = Instant::now();
+        let scan_result = build_runtime().block_on(session.run_round_async()).unwrap();
+
+        assert!(scan_result.summary.cancelled);
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pnet_datalink::MacAddr;
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+// Cache files written by another version are discarded, the version should be
+// increased on each incompatible change of the cache structure.
+pub const HOSTNAME_CACHE_VERSION: u32 = 1;
+
+const HOSTNAME_CACHE_FILE_NAME: &str = "hostnames.json";
+
+/**
+ * A hostname resolved by a previous scan, with the resolution method (such as
+ * 'dns' or 'mdns') and the UNIX timestamp of the resolution (in seconds).
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CachedHostname {
+    pub hostname: String,
+    pub source: String,
+    pub resolved_at: u64
+}
+
+/**
+ * Hostnames resolved by previous scans, keyed by MAC address so that hosts
+ * keep their name when their IPv4 address changes. Entries older than the
+ * cache TTL are resolved again.
+ */
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct HostnameCache {
+    version: u32,
+    entries: HashMap<String, CachedHostname>
+}
+
+impl Default for HostnameCache {
+
+    fn default() -> Self {
+
+        HostnameCache {
+            version: HOSTNAME_CACHE_VERSION,
+            entries: HashMap::new()
+        }
+    }
+}
+
+impl HostnameCache {
+
+    /**
+     * Load the hostname cache file. A missing, unreadable or corrupted cache
+     * is discarded (the cache is rebuilt by the next save), it never stops
+     * the scan.
+     */
+    pub fn load(file_path: &str) -> HostnameCache {
+
+        match fs::read_to_string(file_path) {
+            Ok(content) => HostnameCache::parse(&content),
+            Err(err) if err.kind() == ErrorKind::NotFound => HostnameCache::default(),
+            Err(err) => {
+                eprintln!("[warn] Could not read hostname cache {} ({}), the cache is rebuilt", file_path, err);
+                HostnameCache::default()
+            }
+        }
+    }
+
+    fn parse(content: &str) -> HostnameCache {
+
+        serde_json::from_str::<HostnameCache>(content).ok()
+            .filter(|cache| cache.version == HOSTNAME_CACHE_VERSION)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), String> {
+
+        if let Some(directory) = PathBuf::from(file_path).parent() {
+            fs::create_dir_all(directory).map_err(|err| format!("Could not create directory {} ({})", directory.display(), err))?;
+        }
+        let content = serde_json::to_string(self).map_err(|err| format!("Could not serialize hostname cache ({})", err))?;
+        utils::write_output(Some(file_path), content.as_bytes())
+    }
+
+    /**
+     * Find the cached hostname of a MAC address, if it has been resolved less
+     * than 'ttl_seconds' ago.
+     */
+    pub fn lookup(&self, mac: &MacAddr, now: u64, ttl_seconds: u64) -> Option<&CachedHostname> {
+
+        self.entries.get(&mac.to_string()).filter(|entry| now.saturating_sub(entry.resolved_at) < ttl_seconds)
+    }
+
+    /**
+     * Record a freshly resolved hostname, which replaces any previous hostname
+     * of the MAC address.
+     */
+    pub fn record(&mut self, mac: &MacAddr, hostname: &str, source: &str, now: u64) {
+
+        self.entries.insert(mac.to_string(), CachedHostname {
+            hostname: hostname.to_string(),
+            source: source.to_string(),
+            resolved_at: now
+        });
+    }
+}
+
+/**
+ * Path of the hostname cache, in the per-user cache directory (the XDG base
+ * directory is used on Linux).
+ */
+pub fn hostname_cache_path() -> Option<PathBuf> {
+
+    let cache_directory = if cfg!(target_os = "windows") {
+        env::var_os("LOCALAPPDATA").map(|path| PathBuf::from(path).join("arp-scan").join("cache"))
+    }
+    else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|path| PathBuf::from(path).join("Library/Caches/arp-scan"))
+    }
+    else {
+        env::var_os("XDG_CACHE_HOME").map(PathBuf::from).filter(|path| path.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|path| PathBuf::from(path).join(".cache")))
+            .map(|path| path.join("arp-scan"))
+    };
+    cache_directory.map(|directory| directory.join(HOSTNAME_CACHE_FILE_NAME))
+}
+
+pub fn unix_timestamp() -> u64 {
+
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const TTL_SECONDS: u64 = 24 * 60 * 60;
+
+    #[test]
+    fn should_expire_old_entries() {
+
+        let mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let mut cache = HostnameCache::default();
+        cache.record(&mac, "router.home", "dns", 1_000_000);
+
+        assert_eq!(cache.lookup(&mac, 1_000_000 + TTL_SECONDS - 1, TTL_SECONDS).map(|entry| entry.hostname.as_str()), Some("router.home"));
+        assert_eq!(cache.lookup(&mac, 1_000_000 + TTL_SECONDS, TTL_SECONDS), None);
+        assert_eq!(cache.lookup(&MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b), 1_000_000, TTL_SECONDS), None);
+    }
+
+    #[test]
+    fn should_replace_changed_hostnames() {
+
+        let mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let mut cache = HostnameCache::default();
+        cache.record(&mac, "old-name.home", "dns", 1_000_000);
+        cache.record(&mac, "new-name.home", "mdns", 1_000_000 + TTL_SECONDS);
+
+        let entry = cache.lookup(&mac, 1_000_000 + TTL_SECONDS, TTL_SECONDS).unwrap();
+        assert_eq!(entry.hostname, "new-name.home");
+        assert_eq!(entry.source, "mdns");
+    }
+
+    #[test]
+    fn should_reload_saved_cache() {
+
+        let mut cache = HostnameCache::default();
+        cache.record(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23), "router.home", "dns", 1_000_000);
+
+        let content = serde_json::to_string(&cache).unwrap();
+        assert_eq!(HostnameCache::parse(&content), cache);
+    }
+
+    #[test]
+    fn should_discard_corrupted_cache() {
+
+        assert_eq!(HostnameCache::parse("{\"version\":1,\"entries\":{\"00:22"), HostnameCache::default());
+        assert_eq!(HostnameCache::parse("{\"version\":0,\"entries\":{}}"), HostnameCache::default());
+        assert_eq!(HostnameCache::parse(""), HostnameCache::default());
+    }
+
+}
@@ -0,0 +1,293 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PCAP_MAGIC_NUMBER: u32 = 0xa1b2_c3d4;
+const PCAP_NANOSECOND_MAGIC_NUMBER: u32 = 0xa1b2_3c4d;
+const PCAPNG_MAGIC_NUMBER: u32 = 0x0a0d_0d0a;
+const PCAP_HEADER_SIZE: usize = 24;
+const PCAP_RECORD_HEADER_SIZE: usize = 16;
+const PCAP_VERSION_MAJOR: u16 = 2;
+const PCAP_VERSION_MINOR: u16 = 4;
+const PCAP_SNAPSHOT_LENGTH: u32 = 65535;
+const PCAP_LINKTYPE_ETHERNET: u32 = 1;
+
+/**
+ * A single Ethernet frame seen by the response thread, with the timestamp at
+ * which it has been received (since the UNIX epoch).
+ */
+pub struct CapturedFrame {
+    pub timestamp: Duration,
+    pub data: Vec<u8>
+}
+
+impl CapturedFrame {
+
+    pub fn new(data: &[u8]) -> CapturedFrame {
+
+        CapturedFrame {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+            data: data.to_vec()
+        }
+    }
+}
+
+pub type CaptureThread = JoinHandle<Result<(), String>>;
+
+/**
+ * Start a capture thread that writes all received frames to a pcap file. The
+ * frames are sent through the returned channel, which keeps file writes out
+ * of the receive loop. The capture thread stops (and flushes the file) once
+ * all senders have been dropped.
+ */
+pub fn start_capture(file_path: &str) -> Result<(Sender<CapturedFrame>, CaptureThread), String> {
+
+    let file = File::create(file_path).map_err(|err| {
+        format!("Could not create capture file {} ({})", file_path, err)
+    })?;
+
+    let mut writer = BufWriter::new(file);
+    write_pcap_header(&mut writer).map_err(|err| {
+        format!("Could not write capture file header ({})", err)
+    })?;
+
+    let (sender, receiver) = mpsc::channel();
+    let capture_thread = thread::spawn(move || write_captured_frames(writer, receiver));
+
+    Ok((sender, capture_thread))
+}
+
+fn write_captured_frames(mut writer: BufWriter<File>, receiver: Receiver<CapturedFrame>) -> Result<(), String> {
+
+    for frame in receiver {
+        write_pcap_record(&mut writer, &frame).map_err(|err| {
+            format!("Could not write frame to capture file ({})", err)
+        })?;
+    }
+
+    writer.flush().map_err(|err| format!("Could not flush capture file ({})", err))
+}
+
+/**
+ * Write the pcap global header (microsecond timestamps, Ethernet link-layer)
+ * in the native byte order, as expected by the pcap file format.
+ */
+fn write_pcap_header(writer: &mut impl Write) -> std::io::Result<()> {
+
+    writer.write_all(&PCAP_MAGIC_NUMBER.to_ne_bytes())?;
+    writer.write_all(&PCAP_VERSION_MAJOR.to_ne_bytes())?;
+    writer.write_all(&PCAP_VERSION_MINOR.to_ne_bytes())?;
+    writer.write_all(&0i32.to_ne_bytes())?;
+    writer.write_all(&0u32.to_ne_bytes())?;
+    writer.write_all(&PCAP_SNAPSHOT_LENGTH.to_ne_bytes())?;
+    writer.write_all(&PCAP_LINKTYPE_ETHERNET.to_ne_bytes())
+}
+
+fn write_pcap_record(writer: &mut impl Write, frame: &CapturedFrame) -> std::io::Result<()> {
+
+    let captured_length = frame.data.len().min(PCAP_SNAPSHOT_LENGTH as usize);
+    let seconds = u32::try_from(frame.timestamp.as_secs()).unwrap_or(u32::MAX);
+
+    writer.write_all(&seconds.to_ne_bytes())?;
+    writer.write_all(&frame.timestamp.subsec_micros().to_ne_bytes())?;
+    writer.write_all(&(captured_length as u32).to_ne_bytes())?;
+    writer.write_all(&(frame.data.len() as u32).to_ne_bytes())?;
+    writer.write_all(&frame.data[..captured_length])
+}
+
+/**
+ * Read all frames of a pcap file, with their timestamps. Files may have been
+ * written on a host with another byte order, with microsecond or nanosecond
+ * timestamps. Only Ethernet captures are supported (as written by the
+ * '--capture' option), since the frames are parsed as received by the scanner.
+ */
+pub fn read_pcap(file_path: &str) -> Result<Vec<CapturedFrame>, String> {
+
+    let content = fs::read(file_path).map_err(|err| {
+        format!("Could not read capture file {} ({})", file_path, err)
+    })?;
+
+    parse_pcap(&content).map_err(|err| format!("Could not replay capture file {} ({})", file_path, err))
+}
+
+fn parse_pcap(content: &[u8]) -> Result<Vec<CapturedFrame>, String> {
+
+    let header = content.get(..PCAP_HEADER_SIZE).ok_or_else(|| "file too short for a pcap header".to_string())?;
+    let magic_bytes: [u8; 4] = [header[0], header[1], header[2], header[3]];
+
+    // The magic number gives both the byte order of the file and the
+    // precision of the timestamps.
+    let (is_big_endian, is_nanosecond) = match (u32::from_le_bytes(magic_bytes), u32::from_be_bytes(magic_bytes)) {
+        (PCAP_MAGIC_NUMBER, _) => (false, false),
+        (PCAP_NANOSECOND_MAGIC_NUMBER, _) => (false, true),
+        (_, PCAP_MAGIC_NUMBER) => (true, false),
+        (_, PCAP_NANOSECOND_MAGIC_NUMBER) => (true, true),
+        (PCAPNG_MAGIC_NUMBER, _) => return Err("pcapng files are not supported, convert it with 'editcap -F pcap'".to_string()),
+        _ => return Err("not a pcap file".to_string())
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match is_big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes)
+        }
+    };
+
+    let link_type = read_u32(&header[20..24]);
+    if link_type != PCAP_LINKTYPE_ETHERNET {
+        return Err(format!("unsupported link type {}, only Ethernet captures (link type {}) can be replayed", link_type, PCAP_LINKTYPE_ETHERNET));
+    }
+
+    let mut frames: Vec<CapturedFrame> = vec![];
+    let mut offset = PCAP_HEADER_SIZE;
+    while offset < content.len() {
+
+        let record_header = content.get(offset..offset + PCAP_RECORD_HEADER_SIZE).ok_or_else(|| {
+            format!("truncated record header at byte {}", offset)
+        })?;
+        let seconds = read_u32(&record_header[0..4]);
+        let fraction = read_u32(&record_header[4..8]);
+        let captured_length = read_u32(&record_header[8..12]) as usize;
+
+        let data_offset = offset + PCAP_RECORD_HEADER_SIZE;
+        let data = content.get(data_offset..data_offset + captured_length).ok_or_else(|| {
+            format!("truncated frame at byte {}", data_offset)
+        })?;
+        let nanoseconds = match is_nanosecond {
+            true => fraction,
+            false => fraction.saturating_mul(1000)
+        };
+
+        frames.push(CapturedFrame {
+            timestamp: Duration::new(seconds.into(), nanoseconds.min(999_999_999)),
+            data: data.to_vec()
+        });
+        offset = data_offset + captured_length;
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn should_write_pcap_header() {
+
+        let mut buffer: Vec<u8> = vec![];
+        write_pcap_header(&mut buffer).unwrap();
+
+        assert_eq!(buffer.len(), 24);
+        assert_eq!(&buffer[0..4], &PCAP_MAGIC_NUMBER.to_ne_bytes());
+        assert_eq!(&buffer[20..24], &PCAP_LINKTYPE_ETHERNET.to_ne_bytes());
+    }
+
+    #[test]
+    fn should_write_pcap_record() {
+
+        let frame = CapturedFrame {
+            timestamp: Duration::new(1663408800, 250_000_000),
+            data: vec![0xff; 42]
+        };
+
+        let mut buffer: Vec<u8> = vec![];
+        write_pcap_record(&mut buffer, &frame).unwrap();
+
+        assert_eq!(buffer.len(), 16 + 42);
+        assert_eq!(&buffer[0..4], &1663408800u32.to_ne_bytes());
+        assert_eq!(&buffer[4..8], &250_000u32.to_ne_bytes());
+        assert_eq!(&buffer[8..12], &42u32.to_ne_bytes());
+        assert_eq!(&buffer[12..16], &42u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn should_capture_frames_to_file() {
+
+        let file_path = env::temp_dir().join(format!("arp-scan-capture-{}.pcap", std::process::id()));
+        let file_path = file_path.to_str().unwrap();
+
+        let (sender, capture_thread) = start_capture(file_path).unwrap();
+        sender.send(CapturedFrame::new(&[0x01; 60])).unwrap();
+        sender.send(CapturedFrame::new(&[0x02; 42])).unwrap();
+        drop(sender);
+        capture_thread.join().unwrap().unwrap();
+
+        let content = fs::read(file_path).unwrap();
+        fs::remove_file(file_path).unwrap();
+
+        assert_eq!(content.len(), 24 + (16 + 60) + (16 + 42));
+    }
+
+    #[test]
+    fn should_read_written_frames() {
+
+        let frames = [
+            CapturedFrame { timestamp: Duration::new(1663408800, 250_000_000), data: vec![0x01; 60] },
+            CapturedFrame { timestamp: Duration::new(1663408801, 0), data: vec![0x02; 42] }
+        ];
+        let mut buffer: Vec<u8> = vec![];
+        write_pcap_header(&mut buffer).unwrap();
+        for frame in frames.iter() {
+            write_pcap_record(&mut buffer, frame).unwrap();
+        }
+
+        let read_frames = parse_pcap(&buffer).unwrap();
+
+        assert_eq!(read_frames.len(), 2);
+        assert_eq!(read_frames[0].timestamp, Duration::new(1663408800, 250_000_000));
+        assert_eq!(read_frames[1].data, vec![0x02; 42]);
+    }
+
+    #[test]
+    fn should_read_big_endian_nanosecond_captures() {
+
+        let mut buffer: Vec<u8> = vec![];
+        buffer.extend_from_slice(&PCAP_NANOSECOND_MAGIC_NUMBER.to_be_bytes());
+        buffer.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff]);
+        buffer.extend_from_slice(&PCAP_LINKTYPE_ETHERNET.to_be_bytes());
+        buffer.extend_from_slice(&1663408800u32.to_be_bytes());
+        buffer.extend_from_slice(&123u32.to_be_bytes());
+        buffer.extend_from_slice(&4u32.to_be_bytes());
+        buffer.extend_from_slice(&4u32.to_be_bytes());
+        buffer.extend_from_slice(&[0xaa; 4]);
+
+        let frames = parse_pcap(&buffer).unwrap();
+
+        assert_eq!(frames[0].timestamp, Duration::new(1663408800, 123));
+        assert_eq!(frames[0].data, vec![0xaa; 4]);
+    }
+
+    #[test]
+    fn should_reject_unsupported_captures() {
+
+        let mut buffer: Vec<u8> = vec![];
+        write_pcap_header(&mut buffer).unwrap();
+        buffer[20..24].copy_from_slice(&113u32.to_ne_bytes());
+        assert_eq!(parse_pcap(&buffer).err().unwrap(), "unsupported link type 113, only Ethernet captures (link type 1) can be replayed");
+
+        assert!(parse_pcap(&PCAPNG_MAGIC_NUMBER.to_le_bytes().repeat(6)).err().unwrap().starts_with("pcapng files are not supported"));
+        assert_eq!(parse_pcap(&[0u8; 8]).err().unwrap(), "file too short for a pcap header");
+
+        let mut truncated: Vec<u8> = vec![];
+        write_pcap_header(&mut truncated).unwrap();
+        write_pcap_record(&mut truncated, &CapturedFrame { timestamp: Duration::ZERO, data: vec![0x01; 60] }).unwrap();
+        truncated.truncate(truncated.len() - 10);
+        assert_eq!(parse_pcap(&truncated).err().unwrap(), "truncated frame at byte 40");
+    }
+
+    #[test]
+    fn should_fail_unwritable_capture_file() {
+
+        let result = start_capture("/nonexistent-directory/capture.pcap");
+
+        assert!(result.is_err());
+    }
+
+}
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind};
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use pnet_datalink::{DataLinkReceiver, DataLinkSender, MacAddr, NetworkInterface};
+use pnet::packet::Packet;
+use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+use pnet::packet::vlan::VlanPacket;
+
+use crate::error::ScanError;
+use crate::network;
+
+/**
+ * A datalink layer sending & receiving raw Ethernet frames on a single
+ * interface. The scan engine only uses this trait, so that it can run on a
+ * real pnet channel or on an in-memory fake (see 'FakeDataLink'). Frames are
+ * sent from the scan thread while the response thread is receiving, both
+ * methods therefore take a shared reference.
+ */
+pub trait DataLink: Send + Sync {
+
+    /**
+     * Send a single Ethernet frame. A frame that could not be handed to the
+     * interface is reported as an error.
+     */
+    fn send(&self, frame: &[u8]) -> io::Result<()>;
+
+    /**
+     * Receive the next Ethernet frame, or 'None' if no frame has been
+     * received before the timeout.
+     */
+    fn recv(&self, timeout: Duration) -> io::Result<Option<Vec<u8>>>;
+}
+
+/**
+ * The datalink layer of a network interface, backed by a pnet Ethernet
+ * channel. The channel reads are stopped every 'DATALINK_RCV_TIMEOUT'
+ * milliseconds, receive timeouts are therefore rounded up to this interval.
+ */
+pub struct PnetDataLink {
+    interface: NetworkInterface,
+    tx: Mutex<Box<dyn DataLinkSender>>,
+    rx: Mutex<Box<dyn DataLinkReceiver>>
+}
+
+impl PnetDataLink {
+
+    /**
+     * Open an Ethernet channel on a network interface, see
+     * 'network::open_ethernet_channel' for the reported errors.
+     */
+    pub fn open(interface: &NetworkInterface) -> Result<PnetDataLink, ScanError> {
+
+        let channel_config = pnet_datalink::Config {
+            read_timeout: Some(Duration::from_millis(network::DATALINK_RCV_TIMEOUT)),
+            ..pnet_datalink::Config::default()
+        };
+        let (tx, rx) = network::open_ethernet_channel(interface, channel_config)?;
+
+        Ok(PnetDataLink {
+            interface: interface.clone(),
+            tx: Mutex::new(tx),
+            rx: Mutex::new(rx)
+        })
+    }
+}
+
+impl DataLink for PnetDataLink {
+
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+
+        let mut tx = lock_ignoring_poison(&self.tx);
+        match tx.send_to(frame, Some(self.interface.clone())) {
+            Some(result) => result,
+            None => Err(io::Error::other("no buffer available"))
+        }
+    }
+
+    fn recv(&self, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+
+        let started_at = Instant::now();
+        let mut rx = lock_ignoring_poison(&self.rx);
+        loop {
+            match rx.next() {
+                Ok(frame) => return Ok(Some(frame.to_vec())),
+                Err(error) if error.kind() == ErrorKind::TimedOut => {
+                    if started_at.elapsed() >= timeout {
+                        return Ok(None);
+                    }
+                },
+                Err(error) => return Err(error)
+            }
+        }
+    }
+}
+
+/**
+ * An in-memory datalink layer, scripted with the frames received by the
+ * scanner. Canned replies are queued when an ARP request is sent for their
+ * target, other frames (garbage, truncated frames, unsolicited replies, ...)
+ * can be queued at any time. Clones share the same state, so that sent
+ * frames can be inspected once the link has been given to a scanner. Two
+ * links can also be connected as the ends of a cable (see 'pair').
+ */
+#[derive(Clone, Default)]
+pub struct FakeDataLink {
+    state: Arc<(Mutex<FakeState>, Condvar)>
+}
+
+#[derive(Default)]
+struct FakeState {
+    pending_frames: VecDeque<Vec<u8>>,
+    scripted_replies: HashMap<Ipv4Addr, VecDeque<Vec<Vec<u8>>>>,
+    sent_frames: Vec<Vec<u8>>,
+    peer: Option<Weak<(Mutex<FakeState>, Condvar)>>
+}
+
+impl FakeDataLink {
+
+    pub fn new() -> Self {
+
+        FakeDataLink::default()
+    }
+
+    /**
+     * Build two connected links, each frame sent on a link being received
+     * on the other one (as with a veth pair).
+     */
+    pub fn pair() -> (FakeDataLink, FakeDataLink) {
+
+        let (first_link, second_link) = (FakeDataLink::new(), FakeDataLink::new());
+        lock_ignoring_poison(&first_link.state.0).peer = Some(Arc::downgrade(&second_link.state));
+        lock_ignoring_poison(&second_link.state.0).peer = Some(Arc::downgrade(&first_link.state));
+        (first_link, second_link)
+    }
+
+    /**
+     * Queue a frame, received by the scanner as soon as possible.
+     */
+    pub fn push_frame(&self, frame: Vec<u8>) {
+
+        let (state, frame_added) = &*self.state;
+        lock_ignoring_poison(state).pending_frames.push_back(frame);
+        frame_added.notify_all();
+    }
+
+    /**
+     * Script the frames answering the next ARP request for an IPv4 address.
+     * Each call scripts one more request: a host answering only the second
+     * request is scripted with an empty answer, then with its reply. Requests
+     * without scripted answer are not answered.
+     */
+    pub fn reply_to(&self, target_ipv4: Ipv4Addr, frames: Vec<Vec<u8>>) {
+
+        let (state, _) = &*self.state;
+        lock_ignoring_poison(state).scripted_replies.entry(target_ipv4).or_default().push_back(frames);
+    }
+
+    /**
+     * All frames sent on the link, in the sending order.
+     */
+    pub fn sent_frames(&self) -> Vec<Vec<u8>> {
+
+        let (state, _) = &*self.state;
+        lock_ignoring_poison(state).sent_frames.clone()
+    }
+
+    /**
+     * Target IPv4 addresses of the ARP requests sent on the link, in the
+     * sending order.
+     */
+    pub fn sent_requests(&self) -> Vec<Ipv4Addr> {
+
+        self.sent_frames().iter().filter_map(|frame| find_requested_ipv4(frame)).collect()
+    }
+
+    /**
+     * Build a broadcast Ethernet frame holding an ARP reply from a host.
+     */
+    pub fn arp_reply(sender_mac: MacAddr, sender_ipv4: Ipv4Addr, target_ipv4: Ipv4Addr) -> Vec<u8> {
+
+        let header_size = MutableEthernetPacket::minimum_packet_size();
+        let mut frame = vec![0u8; header_size + MutableArpPacket::minimum_packet_size()];
+
+        if let Some(mut ethernet_packet) = MutableEthernetPacket::new(&mut frame) {
+            ethernet_packet.set_destination(MacAddr::broadcast());
+            ethernet_packet.set_source(sender_mac);
+            ethernet_packet.set_ethertype(EtherTypes::Arp);
+        }
+        if let Some(mut arp_packet) = MutableArpPacket::new(&mut frame[header_size..]) {
+            arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
+            arp_packet.set_protocol_type(EtherTypes::Ipv4);
+            arp_packet.set_hw_addr_len(6);
+            arp_packet.set_proto_addr_len(4);
+            arp_packet.set_operation(ArpOperations::Reply);
+            arp_packet.set_sender_hw_addr(sender_mac);
+            arp_packet.set_sender_proto_addr(sender_ipv4);
+            arp_packet.set_target_hw_addr(MacAddr::broadcast());
+            arp_packet.set_target_proto_addr(target_ipv4);
+        }
+        frame
+    }
+}
+
+impl DataLink for FakeDataLink {
+
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+
+        let (state, frame_added) = &*self.state;
+        let mut state = lock_ignoring_poison(state);
+        state.sent_frames.push(frame.to_vec());
+
+        let scripted_reply = find_requested_ipv4(frame).and_then(|target_ipv4| {
+            state.scripted_replies.get_mut(&target_ipv4).and_then(|replies| replies.pop_front())
+        });
+        if let Some(reply_frames) = scripted_reply {
+            state.pending_frames.extend(reply_frames);
+            frame_added.notify_all();
+        }
+
+        // The peer is locked once this link is released, so that both ends
+        // can send at the same time.
+        let peer = state.peer.as_ref().and_then(Weak::upgrade);
+        drop(state);
+        if let Some(peer) = peer {
+            FakeDataLink { state: peer }.push_frame(frame.to_vec());
+        }
+        Ok(())
+    }
+
+    fn recv(&self, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+
+        let (state, frame_added) = &*self.state;
+        let state = lock_ignoring_poison(state);
+        let (mut state, _) = frame_added.wait_timeout_while(state, timeout, |state| state.pending_frames.is_empty()).unwrap_or_else(|poisoned| poisoned.into_inner());
+        Ok(state.pending_frames.pop_front())
+    }
+}
+
+/**
+ * A datalink layer replaying the frames of a capture file, see
+ * 'ArpScanner::replay'. Sent frames are discarded, and the receiver gets an
+ * 'UnexpectedEof' error once all frames have been replayed (which ends the
+ * receive loop of the scanner).
+ */
+pub struct ReplayDataLink {
+    frames: Mutex<VecDeque<Vec<u8>>>
+}
+
+impl ReplayDataLink {
+
+    pub fn new(frames: Vec<Vec<u8>>) -> Self {
+
+        ReplayDataLink {
+            frames: Mutex::new(frames.into())
+        }
+    }
+}
+
+impl DataLink for ReplayDataLink {
+
+    fn send(&self, _frame: &[u8]) -> io::Result<()> {
+
+        Ok(())
+    }
+
+    fn recv(&self, _timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+
+        match lock_ignoring_poison(&self.frames).pop_front() {
+            Some(frame) => Ok(Some(frame)),
+            None => Err(io::Error::new(ErrorKind::UnexpectedEof, "no more frames to replay"))
+        }
+    }
+}
+
+/**
+ * Find the target IPv4 of an ARP request frame (with an optional VLAN tag).
+ */
+fn find_requested_ipv4(frame: &[u8]) -> Option<Ipv4Addr> {
+
+    let ethernet_packet = EthernetPacket::new(frame)?;
+    let vlan_packet;
+    let arp_payload = match ethernet_packet.get_ethertype() {
+        EtherTypes::Arp => ethernet_packet.payload(),
+        EtherTypes::Vlan => {
+            vlan_packet = VlanPacket::new(ethernet_packet.payload())?;
+            vlan_packet.payload()
+        },
+        _ => return None
+    };
+
+    let arp_packet = ArpPacket::new(arp_payload)?;
+    match arp_packet.get_operation() {
+        ArpOperations::Request => Some(arp_packet.get_target_proto_addr()),
+        _ => None
+    }
+}
+
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_answer_scripted_requests_in_order() {
+
+        let link = FakeDataLink::new();
+        let router_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let router_reply = FakeDataLink::arp_reply(router_mac, Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 21));
+        link.reply_to(Ipv4Addr::new(192, 168, 1, 1), vec![]);
+        link.reply_to(Ipv4Addr::new(192, 168, 1, 1), vec![router_reply.clone()]);
+
+        let mut request = router_reply.clone();
+        MutableArpPacket::new(&mut request[14..]).unwrap().set_operation(ArpOperations::Request);
+        MutableArpPacket::new(&mut request[14..]).unwrap().set_target_proto_addr(Ipv4Addr::new(192, 168, 1, 1));
+
+        link.send(&request).unwrap();
+        assert_eq!(link.recv(Duration::from_millis(10)).unwrap(), None);
+
+        link.send(&request).unwrap();
+        assert_eq!(link.recv(Duration::from_millis(10)).unwrap(), Some(router_reply));
+
+        link.push_frame(vec![0u8; 4]);
+        assert_eq!(link.recv(Duration::from_millis(10)).unwrap(), Some(vec![0u8; 4]));
+        assert_eq!(link.sent_requests(), [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 1)]);
+    }
+
+    #[test]
+    fn should_end_replays_after_the_last_frame() {
+
+        let link = ReplayDataLink::new(vec![vec![0x01; 4]]);
+        link.send(&[0x02; 4]).unwrap();
+
+        assert_eq!(link.recv(Duration::ZERO).unwrap(), Some(vec![0x01; 4]));
+        assert_eq!(link.recv(Duration::ZERO).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs;
+
+use pnet_datalink::MacAddr;
+
+// Hostname set by dnsmasq when the DHCP client did not send any name.
+const DNSMASQ_UNKNOWN_HOSTNAME: &str = "*";
+
+/**
+ * Load the hostnames of a DHCP lease file, keyed by MAC address. Both the
+ * dnsmasq leases format and the ISC dhcpd format (also written by Kea in its
+ * legacy mode) are supported, the format is detected from the content.
+ */
+pub fn load_lease_hostnames(file_path: &str) -> Result<HashMap<MacAddr, String>, String> {
+
+    let content = fs::read_to_string(file_path).map_err(|err| format!("Could not open DHCP lease file {} ({})", file_path, err))?;
+    match is_dhcpd_format(&content) {
+        true => Ok(parse_dhcpd_leases(&content)),
+        false => Ok(parse_dnsmasq_leases(&content))
+    }
+}
+
+/**
+ * ISC dhcpd lease files are made of 'lease <ip> { ... }' blocks, while
+ * dnsmasq writes one lease per line.
+ */
+fn is_dhcpd_format(content: &str) -> bool {
+
+    content.lines().any(|line| {
+        let line = line.trim();
+        line.starts_with("lease ") && line.ends_with('{')
+    })
+}
+
+/**
+ * Parse dnsmasq leases, each line holds the expiry time, the MAC address, the
+ * IP address, the hostname and the client ID. Leases without hostname ('*')
+ * are skipped, as well as the DUID line of DHCPv6 servers. The last lease of
+ * a MAC address wins, since it is the most recent one.
+ */
+pub fn parse_dnsmasq_leases(content: &str) -> HashMap<MacAddr, String> {
+
+    let mut hostnames: HashMap<MacAddr, String> = HashMap::new();
+    for line in content.lines() {
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (mac, hostname) = match fields.as_slice() {
+            [_, mac, _, hostname, ..] => (mac.parse::<MacAddr>(), *hostname),
+            _ => continue
+        };
+        if let Ok(mac) = mac {
+            if hostname != DNSMASQ_UNKNOWN_HOSTNAME {
+                hostnames.insert(mac, hostname.to_string());
+            }
+        }
+    }
+    hostnames
+}
+
+/**
+ * Parse ISC dhcpd leases, the hostname is given by the 'client-hostname'
+ * statement and the MAC address by the 'hardware ethernet' statement of a
+ * lease block. Leases are appended to the file when renewed, the last lease
+ * of a MAC address therefore wins.
+ */
+pub fn parse_dhcpd_leases(content: &str) -> HashMap<MacAddr, String> {
+
+    let mut hostnames: HashMap<MacAddr, String> = HashMap::new();
+    let mut lease_mac: Option<MacAddr> = None;
+    let mut lease_hostname: Option<String> = None;
+    for line in content.lines() {
+
+        let statement = line.trim().trim_end_matches(';');
+        if statement.starts_with("lease ") {
+            lease_mac = None;
+            lease_hostname = None;
+        }
+        else if let Some(mac) = statement.strip_prefix("hardware ethernet ") {
+            lease_mac = mac.trim().parse::<MacAddr>().ok();
+        }
+        else if let Some(hostname) = statement.strip_prefix("client-hostname ") {
+            lease_hostname = Some(hostname.trim().trim_matches('"').to_string()).filter(|hostname| !hostname.is_empty());
+        }
+        else if statement == "}" {
+            if let (Some(mac), Some(hostname)) = (lease_mac.take(), lease_hostname.take()) {
+                hostnames.insert(mac, hostname);
+            }
+        }
+    }
+    hostnames
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_parse_dnsmasq_leases_fixture() {
+
+        let hostnames = load_lease_hostnames("./data/dnsmasq-leases.txt").unwrap();
+
+        assert_eq!(hostnames.len(), 2);
+        assert_eq!(hostnames.get(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)), Some(&"printer".to_string()));
+        assert_eq!(hostnames.get(&MacAddr::new(0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56)), Some(&"pi-kitchen".to_string()));
+    }
+
+    #[test]
+    fn should_skip_dnsmasq_leases_without_hostname() {
+
+        let hostnames = parse_dnsmasq_leases("1760605200 40:55:82:c3:e5:5b 192.168.1.34 * *\n");
+
+        assert!(hostnames.is_empty());
+    }
+
+    #[test]
+    fn should_parse_dhcpd_leases_fixture() {
+
+        let hostnames = load_lease_hostnames("./data/dhcpd-leases.txt").unwrap();
+
+        assert_eq!(hostnames.len(), 2);
+        assert_eq!(hostnames.get(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)), Some(&"printer".to_string()));
+        assert_eq!(hostnames.get(&MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b)), None);
+        assert_eq!(hostnames.get(&MacAddr::new(0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56)), Some(&"pi-kitchen".to_string()));
+    }
+
+    #[test]
+    fn should_reject_missing_lease_file() {
+
+        assert!(load_lease_hostnames("./data/missing-leases.txt").is_err());
+    }
+
+}
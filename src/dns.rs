@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use hickory_resolver::{Resolver, TokioResolver};
+use hickory_resolver::config::{NameServerConfig, ResolveHosts, ResolverConfig, ResolverOpts};
+use hickory_resolver::net::runtime::TokioRuntimeProvider;
+use hickory_resolver::proto::rr::RData;
+use tokio::task::JoinSet;
+
+pub const DNS_PORT: u16 = 53;
+
+const DNS_HEADER_SIZE: usize = 12;
+const DNS_TYPE_PTR: u16 = 12;
+const DNS_CLASS_IN: u16 = 1;
+// The top bit of the class field is the cache-flush bit in mDNS answers.
+const DNS_CLASS_MASK: u16 = 0x7fff;
+const DNS_FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const DNS_RESPONSE_CODE_MASK: u16 = 0x000f;
+const DNS_POINTER_MASK: u8 = 0xc0;
+const DNS_MAX_POINTER_JUMPS: usize = 16;
+
+const REVERSE_DOMAIN_SUFFIX: &str = ".in-addr.arpa";
+
+/**
+ * A parsed DNS response, with the name of the first question and the PTR
+ * answers (as pairs of owner name and target hostname).
+ */
+#[derive(Debug, PartialEq)]
+pub struct DnsResponse {
+    pub response_code: u8,
+    pub question_name: Option<String>,
+    pub ptr_answers: Vec<(String, String)>
+}
+
+/**
+ * Build the reverse DNS name of an IPv4 address, as used in PTR questions
+ * (192.168.1.20 becomes 20.1.168.192.in-addr.arpa).
+ */
+pub fn build_reverse_name(ipv4: Ipv4Addr) -> String {
+
+    let [a, b, c, d] = ipv4.octets();
+    format!("{}.{}.{}.{}{}", d, c, b, a, REVERSE_DOMAIN_SUFFIX)
+}
+
+/**
+ * Find the IPv4 address of a reverse DNS name, names outside of the
+ * 'in-addr.arpa' domain are ignored.
+ */
+pub fn parse_reverse_name(name: &str) -> Option<Ipv4Addr> {
+
+    let reversed_octets = name.trim_end_matches('.').strip_suffix(REVERSE_DOMAIN_SUFFIX)?;
+    let mut octets = reversed_octets.split('.').map(|octet| octet.parse::<u8>().ok()).collect::<Option<Vec<u8>>>()?;
+    if octets.len() != 4 {
+        return None;
+    }
+    octets.reverse();
+    Some(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+}
+
+/**
+ * Build a DNS query message with a single PTR question. Recursion is only
+ * requested from DNS servers, mDNS responders answer for their own names.
+ */
+pub fn build_ptr_query(query_id: u16, name: &str, recursion_desired: bool) -> Vec<u8> {
+
+    let flags: u16 = match recursion_desired {
+        true => DNS_FLAG_RECURSION_DESIRED,
+        false => 0
+    };
+
+    let mut message: Vec<u8> = Vec::with_capacity(DNS_HEADER_SIZE + name.len() + 6);
+    message.extend_from_slice(&query_id.to_be_bytes());
+    message.extend_from_slice(&flags.to_be_bytes());
+    message.extend_from_slice(&1u16.to_be_bytes());
+    message.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+    for label in name.trim_end_matches('.').split('.') {
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&DNS_TYPE_PTR.to_be_bytes());
+    message.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    message
+}
+
+/**
+ * Read a (possibly compressed) domain name at the given offset. The returned
+ * offset is the position right after the name in the message, pointer loops
+ * are stopped after a fixed amount of jumps.
+ */
+fn read_name(message: &[u8], offset: usize) -> Option<(String, usize)> {
+
+    let mut labels: Vec<String> = vec![];
+    let mut position = offset;
+    let mut next_offset: Option<usize> = None;
+    let mut jump_count = 0;
+
+    loop {
+        let length = *message.get(position)?;
+        match length {
+            0 => {
+                return Some((labels.join("."), next_offset.unwrap_or(position + 1)));
+            },
+            _ if length & DNS_POINTER_MASK == DNS_POINTER_MASK => {
+                let pointer = (usize::from(length & !DNS_POINTER_MASK) << 8) | usize::from(*message.get(position + 1)?);
+                next_offset.get_or_insert(position + 2);
+                jump_count += 1;
+                if jump_count > DNS_MAX_POINTER_JUMPS {
+                    return None;
+                }
+                position = pointer;
+            },
+            _ => {
+                let label = message.get(position + 1..position + 1 + usize::from(length))?;
+                labels.push(String::from_utf8_lossy(label).to_string());
+                position += 1 + usize::from(length);
+            }
+        }
+    }
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+
+    message.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/**
+ * Parse a DNS response. Malformed answers stop the parsing, the answers read
+ * before are kept.
+ */
+pub fn parse_response(message: &[u8]) -> Option<DnsResponse> {
+
+    let flags = read_u16(message, 2)?;
+    let question_count = read_u16(message, 4)?;
+    let answer_count = read_u16(message, 6)?;
+
+    let mut response = DnsResponse {
+        response_code: (flags & DNS_RESPONSE_CODE_MASK) as u8,
+        question_name: None,
+        ptr_answers: vec![]
+    };
+
+    let mut offset = DNS_HEADER_SIZE;
+    for _ in 0..question_count {
+        let (question_name, name_end) = read_name(message, offset)?;
+        response.question_name.get_or_insert(question_name);
+        offset = name_end + 4;
+    }
+
+    for _ in 0..answer_count {
+        let (owner_name, name_end) = match read_name(message, offset) {
+            Some(name) => name,
+            None => break
+        };
+        let (record_type, record_class, data_length) = match (read_u16(message, name_end), read_u16(message, name_end + 2), read_u16(message, name_end + 8)) {
+            (Some(record_type), Some(record_class), Some(data_length)) => (record_type, record_class, usize::from(data_length)),
+            _ => break
+        };
+        let data_offset = name_end + 10;
+        if record_type == DNS_TYPE_PTR && record_class & DNS_CLASS_MASK == DNS_CLASS_IN {
+            if let Some((target_name, _)) = read_name(message, data_offset) {
+                response.ptr_answers.push((owner_name, target_name));
+            }
+        }
+        offset = data_offset + data_length;
+    }
+    Some(response)
+}
+
+/**
+ * Parse the PTR answers of a DNS response, as pairs of reverse name and
+ * target hostname. Other records and malformed messages are ignored.
+ */
+pub fn parse_ptr_answers(message: &[u8]) -> Vec<(String, String)> {
+
+    parse_response(message).map(|response| response.ptr_answers).unwrap_or_default()
+}
+
+/**
+ * Parse a DNS server address, with an optional port (53 by default). IPv6
+ * addresses with a port should be enclosed in brackets.
+ */
+pub fn parse_dns_server(server_text: &str) -> Result<SocketAddr, String> {
+
+    let server_text = server_text.trim();
+    server_text.parse::<SocketAddr>()
+        .or_else(|_| server_text.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, DNS_PORT)))
+        .map_err(|_| format!("invalid DNS server '{}'", server_text))
+}
+
+/**
+ * Build a resolver sending its queries to a single DNS server only, without
+ * cache nor hosts file (those are handled by the other resolution sources).
+ */
+fn build_server_resolver(server: SocketAddr, timeout_ms: u64) -> Result<TokioResolver, String> {
+
+    let mut name_server = NameServerConfig::udp_and_tcp(server.ip());
+    for connection in name_server.connections.iter_mut() {
+        connection.port = server.port();
+    }
+
+    let mut resolver_options = ResolverOpts::default();
+    resolver_options.timeout = Duration::from_millis(timeout_ms);
+    resolver_options.attempts = 1;
+    resolver_options.cache_size = 0;
+    resolver_options.use_hosts_file = ResolveHosts::Never;
+
+    Resolver::builder_with_config(ResolverConfig::from_name_servers(vec![name_server]), TokioRuntimeProvider::default())
+        .with_options(resolver_options)
+        .build()
+        .map_err(|err| format!("Could not build the resolver of DNS server {} ({})", server, err))
+}
+
+/**
+ * Send reverse lookups for all IPv4 addresses at once with the given
+ * resolver, each lookup being bounded by the timeout. Settled lookups are
+ * returned with their hostname, or without hostname if there is no PTR
+ * record.
+ */
+pub async fn reverse_lookup_all(resolver: &TokioResolver, ipv4s: &[Ipv4Addr], timeout_ms: u64) -> HashMap<Ipv4Addr, Option<String>> {
+
+    let mut lookups = JoinSet::new();
+    for ipv4 in ipv4s {
+        let (ipv4, resolver) = (*ipv4, resolver.clone());
+        lookups.spawn(async move {
+            let lookup = tokio::time::timeout(Duration::from_millis(timeout_ms), resolver.reverse_lookup(IpAddr::V4(ipv4))).await;
+            (ipv4, lookup)
+        });
+    }
+
+    let mut settled_lookups: HashMap<Ipv4Addr, Option<String>> = HashMap::new();
+    while let Some(joined_lookup) = lookups.join_next().await {
+        match joined_lookup {
+            Ok((ipv4, Ok(Ok(lookup)))) => {
+                let hostname = lookup.answers().iter().find_map(|record| match &record.data {
+                    RData::PTR(ptr) => Some(ptr.0.to_string().trim_end_matches('.').to_string()),
+                    _ => None
+                });
+                settled_lookups.insert(ipv4, hostname);
+            },
+            Ok((ipv4, Ok(Err(err)))) if err.is_no_records_found() => {
+                settled_lookups.insert(ipv4, None);
+            },
+            _ => {}
+        }
+    }
+    settled_lookups
+}
+
+/**
+ * Resolve the hostnames of several IPv4 addresses with the given DNS servers,
+ * tried in order, on the current tokio runtime. Each server receives all
+ * lookups that are not settled yet at once, so that a dead server only
+ * delays the resolution by one timeout. Servers without a valid resolver
+ * are skipped.
+ */
+pub async fn resolve_hostnames_async(ipv4s: &[Ipv4Addr], servers: &[SocketAddr], timeout_ms: u64, verbose: bool) -> HashMap<Ipv4Addr, String> {
+
+    let mut hostnames: HashMap<Ipv4Addr, String> = HashMap::new();
+    let mut pending_ipv4s: Vec<Ipv4Addr> = ipv4s.to_vec();
+
+    for server in servers {
+
+        if pending_ipv4s.is_empty() {
+            break;
+        }
+        let resolver = match build_server_resolver(*server, timeout_ms) {
+            Ok(resolver) => resolver,
+            Err(err) => {
+                eprintln!("[warn] {}, skipping this server", err);
+                continue;
+            }
+        };
+        let settled_lookups = reverse_lookup_all(&resolver, &pending_ipv4s, timeout_ms).await;
+        if verbose {
+            let resolved_count = settled_lookups.values().filter(|hostname| hostname.is_some()).count();
+            eprintln!("DNS server {} answered {} of {} reverse lookups ({} hostnames)", server, settled_lookups.len(), pending_ipv4s.len(), resolved_count);
+        }
+        pending_ipv4s.retain(|ipv4| !settled_lookups.contains_key(ipv4));
+        hostnames.extend(settled_lookups.into_iter().filter_map(|(ipv4, hostname)| hostname.map(|hostname| (ipv4, hostname))));
+    }
+    hostnames
+}
+
+/**
+ * Resolve the hostnames of several IPv4 addresses with the given DNS servers,
+ * see 'resolve_hostnames_async'. The lookups run on a dedicated runtime, this
+ * function blocks until they are settled.
+ */
+pub fn resolve_hostnames(ipv4s: &[Ipv4Addr], servers: &[SocketAddr], timeout_ms: u64, verbose: bool) -> HashMap<Ipv4Addr, String> {
+
+    match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime.block_on(resolve_hostnames_async(ipv4s, servers, timeout_ms, verbose)),
+        Err(err) => {
+            eprintln!("[warn] Could not start DNS resolver ({})", err);
+            HashMap::new()
+        }
+    }
+}
+
+/**
+ * Build a resolver with the DNS configuration of the system (such as
+ * '/etc/resolv.conf'), for async scans. Hosts files and the cache are left to
+ * the other resolution sources.
+ */
+#[cfg(feature = "async")]
+pub fn build_system_resolver(timeout_ms: u64) -> Result<TokioResolver, String> {
+
+    let mut builder = TokioResolver::builder_tokio().map_err(|err| format!("Could not read the DNS configuration of the system ({})", err))?;
+    let resolver_options = builder.options_mut();
+    resolver_options.timeout = Duration::from_millis(timeout_ms);
+    resolver_options.attempts = 1;
+    resolver_options.cache_size = 0;
+    resolver_options.use_hosts_file = ResolveHosts::Never;
+    builder.build().map_err(|err| format!("Could not build the DNS resolver ({})", err))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::net::UdpSocket;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+
+    const DNS_RESPONSE_CODE_SERVFAIL: u16 = 2;
+
+    /**
+     * A DNS server on the loopback interface, answering every PTR question
+     * with the same hostname (or with the given error code). The server
+     * counts the queries it received, and stops after 5 seconds without any
+     * query.
+     */
+    struct StubServer {
+        address: SocketAddr,
+        query_count: Arc<AtomicUsize>
+    }
+
+    impl StubServer {
+
+        fn start(answer: Result<&str, u16>) -> StubServer {
+
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let address = socket.local_addr().unwrap();
+            let query_count = Arc::new(AtomicUsize::new(0));
+
+            let answer = answer.map(str::to_string);
+            let cloned_query_count = Arc::clone(&query_count);
+            thread::spawn(move || {
+                let mut buffer = [0u8; 512];
+                while let Ok((length, client)) = socket.recv_from(&mut buffer) {
+                    cloned_query_count.fetch_add(1, Ordering::SeqCst);
+                    if let Some(response) = build_stub_response(&buffer[..length], &answer) {
+                        let _ = socket.send_to(&response, client);
+                    }
+                }
+            });
+
+            StubServer { address, query_count }
+        }
+
+        fn query_count(&self) -> usize {
+
+            self.query_count.load(Ordering::SeqCst)
+        }
+    }
+
+    /**
+     * Answer a query with its question only, the additional records (such as
+     * EDNS options) are dropped.
+     */
+    fn build_stub_response(query: &[u8], answer: &Result<String, u16>) -> Option<Vec<u8>> {
+
+        let (_, name_end) = read_name(query, DNS_HEADER_SIZE)?;
+        let question = query.get(DNS_HEADER_SIZE..name_end + 4)?;
+        let (response_code, answer_count) = match answer {
+            Ok(_) => (0, 1u16),
+            Err(response_code) => (*response_code, 0)
+        };
+
+        let mut response: Vec<u8> = query[0..2].to_vec();
+        response.extend_from_slice(&(0x8180 | response_code).to_be_bytes());
+        response.extend_from_slice(&1u16.to_be_bytes());
+        response.extend_from_slice(&answer_count.to_be_bytes());
+        response.extend_from_slice(&[0, 0, 0, 0]);
+        response.extend_from_slice(question);
+
+        if let Ok(hostname) = answer {
+            let mut target_data: Vec<u8> = vec![];
+            for label in hostname.split('.') {
+                target_data.push(label.len() as u8);
+                target_data.extend_from_slice(label.as_bytes());
+            }
+            target_data.push(0);
+
+            response.extend_from_slice(&[0xc0, 0x0c]);
+            response.extend_from_slice(&DNS_TYPE_PTR.to_be_bytes());
+            response.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+            response.extend_from_slice(&120u32.to_be_bytes());
+            response.extend_from_slice(&(target_data.len() as u16).to_be_bytes());
+            response.extend_from_slice(&target_data);
+        }
+        Some(response)
+    }
+
+    fn build_ptr_response(reverse_name: &str, target_name: &str) -> Vec<u8> {
+
+        // The response repeats the question, then points to it in the answer
+        // owner name (compression pointer to offset 12).
+        let mut message = build_ptr_query(7, reverse_name, false);
+        message[2] = 0x84;
+        message[7] = 1;
+
+        let mut target_data: Vec<u8> = vec![];
+        for label in target_name.split('.') {
+            target_data.push(label.len() as u8);
+            target_data.extend_from_slice(label.as_bytes());
+        }
+        target_data.push(0);
+
+        message.extend_from_slice(&[0xc0, 0x0c]);
+        message.extend_from_slice(&DNS_TYPE_PTR.to_be_bytes());
+        message.extend_from_slice(&(0x8000 | DNS_CLASS_IN).to_be_bytes());
+        message.extend_from_slice(&120u32.to_be_bytes());
+        message.extend_from_slice(&(target_data.len() as u16).to_be_bytes());
+        message.extend_from_slice(&target_data);
+        message
+    }
+
+    #[test]
+    fn should_build_reverse_names() {
+
+        assert_eq!(build_reverse_name(Ipv4Addr::new(192, 168, 1, 20)), "20.1.168.192.in-addr.arpa");
+        assert_eq!(parse_reverse_name("20.1.168.192.in-addr.arpa."), Some(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(parse_reverse_name("printer.local"), None);
+        assert_eq!(parse_reverse_name("1.168.192.in-addr.arpa"), None);
+    }
+
+    #[test]
+    fn should_build_ptr_query() {
+
+        let query = build_ptr_query(0x0102, "1.0.0.10.in-addr.arpa", false);
+
+        assert_eq!(&query[0..12], &[0x01, 0x02, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&query[12..16], &[1, b'1', 1, b'0']);
+        assert_eq!(&query[query.len() - 5..], &[0, 0, 12, 0, 1]);
+
+        let recursive_query = build_ptr_query(0x0102, "1.0.0.10.in-addr.arpa", true);
+        assert_eq!(&recursive_query[2..4], &[0x01, 0x00]);
+    }
+
+    #[test]
+    fn should_parse_compressed_ptr_answer() {
+
+        let response = build_ptr_response("20.1.168.192.in-addr.arpa", "living-room-tv.local");
+
+        assert_eq!(parse_ptr_answers(&response), vec![("20.1.168.192.in-addr.arpa".to_string(), "living-room-tv.local".to_string())]);
+    }
+
+    #[test]
+    fn should_parse_negative_response() {
+
+        let mut response = build_ptr_query(7, "20.1.168.192.in-addr.arpa", true);
+        response[2] = 0x81;
+        response[3] = 0x83;
+
+        assert_eq!(parse_response(&response), Some(DnsResponse {
+            response_code: 3,
+            question_name: Some("20.1.168.192.in-addr.arpa".to_string()),
+            ptr_answers: vec![]
+        }));
+    }
+
+    #[test]
+    fn should_replace_invalid_utf8_labels() {
+
+        let message = [4, b'c', b'a', 0xff, b'e', 4, b'h', b'o', b'm', b'e', 0];
+
+        assert_eq!(read_name(&message, 0), Some(("ca\u{fffd}e.home".to_string(), message.len())));
+    }
+
+    #[test]
+    fn should_ignore_malformed_responses() {
+
+        let response = build_ptr_response("20.1.168.192.in-addr.arpa", "living-room-tv.local");
+
+        assert!(parse_ptr_answers(&response[..response.len() - 8]).is_empty());
+        assert!(parse_ptr_answers(&[0, 1, 2]).is_empty());
+
+        // A compression pointer to itself should not loop forever.
+        let mut looping_response = response.clone();
+        let answer_offset = build_ptr_query(7, "20.1.168.192.in-addr.arpa", false).len();
+        looping_response[answer_offset + 1] = answer_offset as u8;
+        assert!(parse_ptr_answers(&looping_response).is_empty());
+    }
+
+    #[test]
+    fn should_parse_dns_servers() {
+
+        assert_eq!(parse_dns_server("10.0.0.53"), Ok("10.0.0.53:53".parse().unwrap()));
+        assert_eq!(parse_dns_server(" 10.0.0.53:5353"), Ok("10.0.0.53:5353".parse().unwrap()));
+        assert_eq!(parse_dns_server("fd00::53"), Ok("[fd00::53]:53".parse().unwrap()));
+        assert_eq!(parse_dns_server("dns.lab"), Err("invalid DNS server 'dns.lab'".to_string()));
+    }
+
+    #[test]
+    fn should_try_dns_servers_in_order() {
+
+        let (first_server, second_server) = (StubServer::start(Ok("printer.lab")), StubServer::start(Ok("nas.lab")));
+        let printer_ipv4 = Ipv4Addr::new(192, 168, 1, 20);
+
+        let hostnames = resolve_hostnames(&[printer_ipv4], &[first_server.address, second_server.address], 1000, false);
+
+        assert_eq!(hostnames, HashMap::from([(printer_ipv4, "printer.lab".to_string())]));
+        assert_eq!(first_server.query_count(), 1);
+        assert_eq!(second_server.query_count(), 0);
+    }
+
+    #[test]
+    fn should_fall_back_after_server_failure() {
+
+        let (failing_server, second_server) = (StubServer::start(Err(DNS_RESPONSE_CODE_SERVFAIL)), StubServer::start(Ok("printer.lab")));
+        let printer_ipv4 = Ipv4Addr::new(192, 168, 1, 20);
+
+        let hostnames = resolve_hostnames(&[printer_ipv4], &[failing_server.address, second_server.address], 1000, false);
+
+        assert_eq!(hostnames, HashMap::from([(printer_ipv4, "printer.lab".to_string())]));
+        assert!(failing_server.query_count() > 0);
+        assert_eq!(second_server.query_count(), 1);
+    }
+
+    #[test]
+    fn should_bound_dead_server_by_timeout() {
+
+        // The socket of the dead server is bound but never read.
+        let dead_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let second_server = StubServer::start(Ok("printer.lab"));
+        let ipv4s = [Ipv4Addr::new(192, 168, 1, 20), Ipv4Addr::new(192, 168, 1, 21), Ipv4Addr::new(192, 168, 1, 22)];
+
+        let started_at = Instant::now();
+        let hostnames = resolve_hostnames(&ipv4s, &[dead_socket.local_addr().unwrap(), second_server.address], 300, false);
+
+        assert_eq!(hostnames.len(), 3);
+        assert!(started_at.elapsed() >= Duration::from_millis(300));
+        assert!(started_at.elapsed() < Duration::from_millis(1500));
+    }
+
+}
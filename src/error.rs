@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::args::OptionsError;
+use crate::utils::ExportError;
+
+// Exit codes of the CLI for each error category, based on the BSD sysexits
+// values so that scripts can tell user errors from system failures. Failed
+// result checks (such as '--strict') keep the generic failure code.
+pub const EXIT_CHECK_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_UNAVAILABLE: i32 = 69;
+pub const EXIT_SOFTWARE: i32 = 70;
+pub const EXIT_IO: i32 = 74;
+pub const EXIT_NO_PERMISSION: i32 = 77;
+
+/**
+ * Errors preventing a scan from running (or from completing). The message of
+ * each error is ready to be displayed to users, and the CLI exits with the
+ * code of the error category.
+ */
+#[derive(Debug)]
+pub enum ScanError {
+    InterfaceNotFound(String),
+    Interface(String),
+    PermissionDenied,
+    InvalidTarget(String),
+    InvalidOptions(OptionsError),
+    Profile(String),
+    Channel(io::Error),
+    Packet(String),
+    NeighborTable(String),
+    Capture(String),
+    Resolution(String),
+    Checkpoint(String),
+    Export(ExportError),
+    Output(String),
+    Thread(String),
+    CheckFailed(String)
+}
+
+impl ScanError {
+
+    /**
+     * Exit code of the CLI for this error, see the 'Exit codes' section of
+     * the README.
+     */
+    pub fn exit_code(&self) -> i32 {
+
+        match self {
+            ScanError::InvalidTarget(_) | ScanError::InvalidOptions(_) | ScanError::Profile(_) => EXIT_USAGE,
+            ScanError::InterfaceNotFound(_) | ScanError::Interface(_) => EXIT_UNAVAILABLE,
+            ScanError::PermissionDenied => EXIT_NO_PERMISSION,
+            ScanError::Channel(_) | ScanError::Packet(_) | ScanError::NeighborTable(_) | ScanError::Capture(_) | ScanError::Resolution(_)
+                | ScanError::Checkpoint(_) | ScanError::Export(_) | ScanError::Output(_) => EXIT_IO,
+            ScanError::Thread(_) => EXIT_SOFTWARE,
+            ScanError::CheckFailed(_) => EXIT_CHECK_FAILED
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+
+        match self {
+            ScanError::InterfaceNotFound(interface_name) => write!(formatter, "Could not find interface with name {}\nMake sure the interface is up, not loopback and has a valid IPv4", interface_name),
+            ScanError::PermissionDenied => write!(formatter, "Should run this binary as root or use --help for options"),
+            ScanError::Channel(error) => write!(formatter, "Datalink channel creation failed ({})", error),
+            ScanError::Export(error) => write!(formatter, "{}", error),
+            ScanError::InvalidOptions(error) => write!(formatter, "{}", error),
+            ScanError::Interface(message) | ScanError::InvalidTarget(message) | ScanError::Profile(message) | ScanError::Packet(message)
+                | ScanError::NeighborTable(message) | ScanError::Capture(message) | ScanError::Resolution(message) | ScanError::Checkpoint(message)
+                | ScanError::Output(message) | ScanError::Thread(message) | ScanError::CheckFailed(message) => write!(formatter, "{}", message)
+        }
+    }
+}
+
+impl Error for ScanError {
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+
+        match self {
+            ScanError::Channel(error) => Some(error),
+            ScanError::Export(error) => Some(error),
+            ScanError::InvalidOptions(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<OptionsError> for ScanError {
+
+    fn from(error: OptionsError) -> Self {
+
+        ScanError::InvalidOptions(error)
+    }
+}
+
+impl From<ExportError> for ScanError {
+
+    fn from(error: ExportError) -> Self {
+
+        ScanError::Export(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_map_errors_to_distinct_exit_codes() {
+
+        assert_eq!(ScanError::InvalidTarget("fe80::/64".to_string()).exit_code(), EXIT_USAGE);
+        assert_eq!(ScanError::from(OptionsError(vec!["Zero timeout".to_string()])).exit_code(), EXIT_USAGE);
+        assert_eq!(ScanError::InterfaceNotFound("eth9".to_string()).exit_code(), EXIT_UNAVAILABLE);
+        assert_eq!(ScanError::PermissionDenied.exit_code(), EXIT_NO_PERMISSION);
+        assert_eq!(ScanError::Channel(io::Error::from(io::ErrorKind::NotFound)).exit_code(), EXIT_IO);
+        assert_eq!(ScanError::from(ExportError("Could not export".to_string())).exit_code(), EXIT_IO);
+        assert_eq!(ScanError::Thread("Failed".to_string()).exit_code(), EXIT_SOFTWARE);
+        assert_eq!(ScanError::CheckFailed("Scan truncated".to_string()).exit_code(), EXIT_CHECK_FAILED);
+    }
+
+    #[test]
+    fn should_keep_error_source() {
+
+        let error = ScanError::Channel(io::Error::other("No such device"));
+
+        assert_eq!(error.to_string(), "Datalink channel creation failed (No such device)");
+        assert_eq!(error.source().map(|source| source.to_string()).as_deref(), Some("No such device"));
+    }
+
+}
@@ -0,0 +1,376 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::Duration;
+
+use ipnetwork::IpNetwork;
+
+use crate::error::{ScanError, EXIT_SOFTWARE, EXIT_USAGE};
+use crate::network::{ResponseSummary, TargetDetails};
+use crate::scanner::ArpScanner;
+
+// Return codes of the C interface. Scan errors return the exit code of the
+// CLI for the same error (see 'ScanError::exit_code').
+pub const ARP_SCAN_OK: i32 = 0;
+pub const ARP_SCAN_ERROR_USAGE: i32 = EXIT_USAGE;
+pub const ARP_SCAN_ERROR_INTERNAL: i32 = EXIT_SOFTWARE;
+
+/**
+ * Scan configuration given by C callers. Strings are optional NUL-terminated
+ * UTF-8 strings: without interface, the default interface is scanned, and
+ * without network the IPv4 networks of the interface are targeted (several
+ * networks are separated by commas). A zero timeout keeps the default
+ * timeout of the CLI.
+ */
+#[repr(C)]
+pub struct ArpScanConfig {
+    pub interface: *const c_char,
+    pub network: *const c_char,
+    pub timeout_ms: u64,
+    pub retry_count: u32,
+    pub resolve_hostnames: bool
+}
+
+/**
+ * Hosts found by a scan, as an opaque object for C callers. All strings
+ * given by the accessors are owned by the result, and remain valid until
+ * the result is freed with 'arp_scan_result_free'.
+ */
+pub struct ArpScanResult {
+    hosts: Vec<ResultHost>,
+    packet_count: usize,
+    arp_count: usize,
+    requests_sent: usize,
+    send_errors: usize,
+    duration_ms: u64
+}
+
+struct ResultHost {
+    ipv4: CString,
+    mac: CString,
+    hostname: Option<CString>,
+    vendor: Option<CString>
+}
+
+impl ArpScanResult {
+
+    fn new(hosts: &[TargetDetails], summary: &ResponseSummary) -> ArpScanResult {
+
+        // Addresses never hold a NUL byte, while names holding one are
+        // dropped as they could not be read by C callers.
+        let hosts = hosts.iter().map(|host| ResultHost {
+            ipv4: CString::new(host.ipv4.to_string()).unwrap_or_default(),
+            mac: CString::new(host.mac.to_string()).unwrap_or_default(),
+            hostname: host.hostname.as_ref().and_then(|hostname| CString::new(hostname.as_str()).ok()),
+            vendor: host.vendor.as_ref().and_then(|vendor| CString::new(vendor.as_str()).ok())
+        }).collect();
+
+        ArpScanResult {
+            hosts,
+            packet_count: summary.packet_count,
+            arp_count: summary.arp_count,
+            requests_sent: summary.requests_sent,
+            send_errors: summary.send_errors,
+            duration_ms: summary.duration_ms.try_into().unwrap_or(u64::MAX)
+        }
+    }
+}
+
+/**
+ * Run a single scan round with the given configuration. On success, the
+ * result is written to 'result' and must be freed by the caller. On error, a
+ * non-zero code is returned and 'result' is left untouched. Panics are
+ * caught and reported as internal errors, they never unwind into C code.
+ *
+ * # Safety
+ *
+ * 'config' must be null or point to a valid configuration (with null or
+ * NUL-terminated strings), and 'result' must be null or writable.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_run(config: *const ArpScanConfig, result: *mut *mut ArpScanResult) -> i32 {
+
+    if config.is_null() || result.is_null() {
+        return ARP_SCAN_ERROR_USAGE;
+    }
+
+    let scan_result = panic::catch_unwind(AssertUnwindSafe(|| run_scan(&*config)));
+    match scan_result {
+        Ok(Ok(scan_result)) => {
+            *result = Box::into_raw(Box::new(scan_result));
+            ARP_SCAN_OK
+        },
+        Ok(Err(error)) => error.exit_code(),
+        Err(_) => ARP_SCAN_ERROR_INTERNAL
+    }
+}
+
+unsafe fn run_scan(config: &ArpScanConfig) -> Result<ArpScanResult, ScanError> {
+
+    let mut builder = ArpScanner::builder()
+        .retries(config.retry_count as usize)
+        .resolve_hostnames(config.resolve_hostnames);
+    if config.timeout_ms > 0 {
+        builder = builder.timeout(Duration::from_millis(config.timeout_ms));
+    }
+    if let Some(interface_name) = read_config_string(config.interface)? {
+        builder = builder.interface(interface_name);
+    }
+    if let Some(networks) = read_config_string(config.network)? {
+        for network in networks.split(',').map(str::trim) {
+            let network: IpNetwork = network.parse().map_err(|err| ScanError::InvalidTarget(format!("Invalid network {} ({})", network, err)))?;
+            builder = builder.network(network);
+        }
+    }
+
+    let scan_result = builder.build().run()?;
+    Ok(ArpScanResult::new(&scan_result.hosts, &scan_result.summary))
+}
+
+unsafe fn read_config_string<'a>(raw_string: *const c_char) -> Result<Option<&'a str>, ScanError> {
+
+    match raw_string.is_null() {
+        true => Ok(None),
+        false => CStr::from_ptr(raw_string).to_str().map(Some).map_err(|_| {
+            ScanError::InvalidTarget("Configuration strings should be valid UTF-8".to_string())
+        })
+    }
+}
+
+/**
+ * Free a result returned by 'arp_scan_run', freeing a null result does
+ * nothing.
+ *
+ * # Safety
+ *
+ * 'result' must be null or a result returned by 'arp_scan_run' that has not
+ * been freed yet.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_free(result: *mut ArpScanResult) {
+
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/**
+ * Count the hosts of a result (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_host_count(result: *const ArpScanResult) -> usize {
+
+    result.as_ref().map_or(0, |result| result.hosts.len())
+}
+
+/**
+ * Count the frames received during the scan (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_packet_count(result: *const ArpScanResult) -> usize {
+
+    result.as_ref().map_or(0, |result| result.packet_count)
+}
+
+/**
+ * Count the ARP packets received during the scan (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_arp_count(result: *const ArpScanResult) -> usize {
+
+    result.as_ref().map_or(0, |result| result.arp_count)
+}
+
+/**
+ * Count the ARP requests sent during the scan, including retries (zero for
+ * a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_request_count(result: *const ArpScanResult) -> usize {
+
+    result.as_ref().map_or(0, |result| result.requests_sent)
+}
+
+/**
+ * Count the ARP requests that could not be sent on the interface (zero for
+ * a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_send_errors(result: *const ArpScanResult) -> usize {
+
+    result.as_ref().map_or(0, |result| result.send_errors)
+}
+
+/**
+ * Duration of the scan in milliseconds (zero for a null result).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_duration_ms(result: *const ArpScanResult) -> u64 {
+
+    result.as_ref().map_or(0, |result| result.duration_ms)
+}
+
+/**
+ * IPv4 address of a host (such as "192.168.1.1"), or null if the index is
+ * out of range.
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_host_ipv4(result: *const ArpScanResult, index: usize) -> *const c_char {
+
+    host_string(result, index, |host| Some(&host.ipv4))
+}
+
+/**
+ * MAC address of a host (such as "00:22:72:d7:b5:23"), or null if the index
+ * is out of range.
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_host_mac(result: *const ArpScanResult, index: usize) -> *const c_char {
+
+    host_string(result, index, |host| Some(&host.mac))
+}
+
+/**
+ * Hostname of a host, or null if unknown (or if the index is out of range).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_host_hostname(result: *const ArpScanResult, index: usize) -> *const c_char {
+
+    host_string(result, index, |host| host.hostname.as_ref())
+}
+
+/**
+ * MAC vendor of a host, or null if unknown (or if the index is out of range).
+ *
+ * # Safety
+ *
+ * 'result' must be null or a live result returned by 'arp_scan_run'.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn arp_scan_result_host_vendor(result: *const ArpScanResult, index: usize) -> *const c_char {
+
+    host_string(result, index, |host| host.vendor.as_ref())
+}
+
+unsafe fn host_string(result: *const ArpScanResult, index: usize, select: impl Fn(&ResultHost) -> Option<&CString>) -> *const c_char {
+
+    result.as_ref()
+        .and_then(|result| result.hosts.get(index))
+        .and_then(select)
+        .map_or(ptr::null(), |value| value.as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::net::Ipv4Addr;
+    use pnet_datalink::MacAddr;
+
+    fn read_string(raw_string: *const c_char) -> Option<String> {
+
+        match raw_string.is_null() {
+            true => None,
+            false => Some(unsafe { CStr::from_ptr(raw_string) }.to_str().unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn should_read_hosts_of_result() {
+
+        let host = TargetDetails {
+            hostname: Some("router.lab".to_string()),
+            response_time_ms: Some(3),
+            ..TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, 1), MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23))
+        };
+        let summary = ResponseSummary {
+            packet_count: 12,
+            arp_count: 4,
+            requests_sent: 254,
+            send_errors: 2,
+            duration_ms: 2100,
+            ..ResponseSummary::default()
+        };
+        let result = Box::into_raw(Box::new(ArpScanResult::new(&[host], &summary)));
+
+        unsafe {
+            assert_eq!(arp_scan_result_host_count(result), 1);
+            assert_eq!(read_string(arp_scan_result_host_ipv4(result, 0)).as_deref(), Some("192.168.1.1"));
+            assert_eq!(read_string(arp_scan_result_host_mac(result, 0)).as_deref(), Some("00:22:72:d7:b5:23"));
+            assert_eq!(read_string(arp_scan_result_host_hostname(result, 0)).as_deref(), Some("router.lab"));
+            assert_eq!(read_string(arp_scan_result_host_vendor(result, 0)), None);
+            assert_eq!(read_string(arp_scan_result_host_ipv4(result, 1)), None);
+            assert_eq!((arp_scan_result_packet_count(result), arp_scan_result_arp_count(result)), (12, 4));
+            assert_eq!((arp_scan_result_request_count(result), arp_scan_result_duration_ms(result)), (254, 2100));
+            assert_eq!(arp_scan_result_send_errors(result), 2);
+            arp_scan_result_free(result);
+
+            assert_eq!(arp_scan_result_host_count(ptr::null()), 0);
+            assert!(arp_scan_result_host_mac(ptr::null(), 0).is_null());
+            arp_scan_result_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn should_return_error_codes() {
+
+        let interface = CString::new("arp-scan-missing0").unwrap();
+        let network = CString::new("192.168.1.0/24, 192.168.1.0/33").unwrap();
+        let mut config = ArpScanConfig {
+            interface: interface.as_ptr(),
+            network: ptr::null(),
+            timeout_ms: 100,
+            retry_count: 0,
+            resolve_hostnames: false
+        };
+        let mut result: *mut ArpScanResult = ptr::null_mut();
+
+        unsafe {
+            assert_eq!(arp_scan_run(ptr::null(), &mut result), ARP_SCAN_ERROR_USAGE);
+            assert_eq!(arp_scan_run(&config, ptr::null_mut()), ARP_SCAN_ERROR_USAGE);
+            assert_eq!(arp_scan_run(&config, &mut result), crate::error::EXIT_UNAVAILABLE);
+
+            config.network = network.as_ptr();
+            assert_eq!(arp_scan_run(&config, &mut result), ARP_SCAN_ERROR_USAGE);
+        }
+        assert!(result.is_null());
+    }
+
+}
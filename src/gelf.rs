@@ -0,0 +1,219 @@
+use std::net::UdpSocket;
+
+use serde_json::{json, Value};
+
+use crate::network::{ResponseSummary, ScanMetadata, TargetDetails};
+
+const GELF_VERSION: &str = "1.1";
+const GELF_LEVEL_INFO: u8 = 6;
+
+// Graylog accepts chunks of up to 8192 bytes, each chunk starts with a 12
+// bytes header (magic bytes, message ID, sequence number & count).
+const GELF_CHUNK_MAX_SIZE: usize = 8192;
+const GELF_CHUNK_HEADER_SIZE: usize = 12;
+const GELF_CHUNK_MAX_COUNT: usize = 128;
+const GELF_CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+
+/**
+ * Build the GELF messages for a scan: one message for each discovered host
+ * and a final summary message. The scanning host is identified by its source
+ * IPv4 and all messages use the scan start date as timestamp.
+ */
+pub fn build_gelf_messages(response_summary: &ResponseSummary, target_details: &[TargetDetails], metadata: &ScanMetadata) -> Vec<Value> {
+
+    let host = metadata.source_ipv4.to_string();
+    let timestamp = metadata.started_at.timestamp_millis() as f64 / 1000_f64;
+
+    let mut sorted_details: Vec<&TargetDetails> = target_details.iter().collect();
+    sorted_details.sort_by_key(|detail| detail.ipv4);
+
+    let mut messages: Vec<Value> = sorted_details.iter().map(|detail| {
+        json!({
+            "version": GELF_VERSION,
+            "host": host,
+            "short_message": format!("ARP host {} ({})", detail.ipv4, detail.mac),
+            "timestamp": timestamp,
+            "level": GELF_LEVEL_INFO,
+            "_ipv4": detail.ipv4.to_string(),
+            "_mac": detail.mac.to_string(),
+            "_vendor": detail.vendor.as_deref().unwrap_or(""),
+            "_hostname": detail.hostname.as_deref().unwrap_or(""),
+            "_note": detail.note.as_deref().unwrap_or(""),
+            "_scan_duration_ms": response_summary.duration_ms as u64
+        })
+    }).collect();
+
+    messages.push(json!({
+        "version": GELF_VERSION,
+        "host": host,
+        "short_message": format!("ARP scan finished, {} hosts found on {}", target_details.len(), metadata.interface_name),
+        "timestamp": timestamp,
+        "level": GELF_LEVEL_INFO,
+        "_hosts_found": target_details.len(),
+        "_packets_received": response_summary.packet_count,
+        "_arp_packets": response_summary.arp_count,
+        "_requests_sent": response_summary.requests_sent,
+        "_send_errors": response_summary.send_errors,
+        "_truncated": response_summary.truncated,
+        "_cancelled": response_summary.cancelled,
+        "_scan_duration_ms": response_summary.duration_ms as u64
+    }));
+
+    messages
+}
+
+/**
+ * Split a GELF message into UDP datagrams. Small messages are sent as-is, while
+ * larger messages are chunked with the GELF chunk header (magic bytes, message
+ * ID, sequence number and sequence count).
+ */
+pub fn chunk_gelf_message(message: &[u8], message_id: [u8; 8]) -> Result<Vec<Vec<u8>>, String> {
+
+    if message.len() <= GELF_CHUNK_MAX_SIZE {
+        return Ok(vec![message.to_vec()]);
+    }
+
+    let chunks: Vec<&[u8]> = message.chunks(GELF_CHUNK_MAX_SIZE - GELF_CHUNK_HEADER_SIZE).collect();
+    if chunks.len() > GELF_CHUNK_MAX_COUNT {
+        return Err(format!("GELF message is too large ({} bytes)", message.len()));
+    }
+
+    let chunk_count = chunks.len() as u8;
+    Ok(chunks.iter().enumerate().map(|(index, chunk)| {
+        let mut datagram = Vec::with_capacity(GELF_CHUNK_HEADER_SIZE + chunk.len());
+        datagram.extend_from_slice(&GELF_CHUNK_MAGIC);
+        datagram.extend_from_slice(&message_id);
+        datagram.push(index as u8);
+        datagram.push(chunk_count);
+        datagram.extend_from_slice(chunk);
+        datagram
+    }).collect())
+}
+
+/**
+ * Send all GELF messages of a scan to a Graylog UDP input. Sending failures do
+ * not stop the other messages, all errors are returned to be reported once
+ * the scan results have been printed.
+ */
+pub fn send_gelf_messages(address: &str, messages: &[Value]) -> Vec<String> {
+
+    let socket = match UdpSocket::bind("0.0.0.0:0").and_then(|socket| socket.connect(address).map(|_| socket)) {
+        Ok(socket) => socket,
+        Err(err) => return vec![format!("Could not connect to GELF address {} ({})", address, err)]
+    };
+
+    let mut errors: Vec<String> = vec![];
+    for message in messages.iter() {
+
+        let payload = message.to_string().into_bytes();
+        let datagrams = match chunk_gelf_message(&payload, rand::random::<[u8; 8]>()) {
+            Ok(datagrams) => datagrams,
+            Err(err) => {
+                errors.push(err);
+                continue;
+            }
+        };
+
+        for datagram in datagrams.iter() {
+            if let Err(err) = socket.send(datagram) {
+                errors.push(format!("Could not send GELF message to {} ({})", address, err));
+                break;
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use pnet_datalink::MacAddr;
+
+    fn build_metadata() -> ScanMetadata {
+
+        ScanMetadata {
+            started_at: DateTime::parse_from_rfc3339("2022-09-17T10:00:00+00:00").unwrap(),
+            interface_name: "eth0".to_string(),
+            interface_mac: None,
+            source_ipv4: Ipv4Addr::new(192, 168, 1, 21),
+            target_networks: vec![]
+        }
+    }
+
+    fn build_target_details() -> Vec<TargetDetails> {
+
+        vec![TargetDetails {
+            hostname: Some("router.home".to_string()),
+            response_time_ms: Some(14),
+            ..TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, 1), MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23))
+        }]
+    }
+
+    #[test]
+    fn should_build_host_and_summary_messages() {
+
+        let summary = ResponseSummary { packet_count: 12, arp_count: 2, duration_ms: 2500, responses_per_attempt: vec![], requests_sent: 0, send_errors: 0, send_duration_ms: 0, truncated: false, host_limit_reached: false, cancelled: false, unsolicited_hosts: vec![] };
+        let messages = build_gelf_messages(&summary, &build_target_details(), &build_metadata());
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["short_message"], "ARP host 192.168.1.1 (00:22:72:d7:b5:23)");
+        assert_eq!(messages[0]["_hostname"], "router.home");
+        assert_eq!(messages[0]["_vendor"], "");
+        assert_eq!(messages[0]["timestamp"], 1663408800.0);
+        assert_eq!(messages[1]["_hosts_found"], 1);
+        assert_eq!(messages[1]["_scan_duration_ms"], 2500);
+    }
+
+    #[test]
+    fn should_not_chunk_small_message() {
+
+        let datagrams = chunk_gelf_message(b"{}", [0; 8]).unwrap();
+
+        assert_eq!(datagrams, vec![b"{}".to_vec()]);
+    }
+
+    #[test]
+    fn should_chunk_large_message() {
+
+        let message = vec![b'a'; 20000];
+        let datagrams = chunk_gelf_message(&message, [7; 8]).unwrap();
+
+        assert_eq!(datagrams.len(), 3);
+        assert!(datagrams.iter().all(|datagram| datagram.len() <= GELF_CHUNK_MAX_SIZE));
+        assert_eq!(&datagrams[1][0..12], &[0x1e, 0x0f, 7, 7, 7, 7, 7, 7, 7, 7, 1, 3]);
+
+        let payload: Vec<u8> = datagrams.iter().flat_map(|datagram| datagram[12..].to_vec()).collect();
+        assert_eq!(payload, message);
+    }
+
+    #[test]
+    fn should_deny_oversized_message() {
+
+        let message = vec![b'a'; 129 * GELF_CHUNK_MAX_SIZE];
+
+        assert!(chunk_gelf_message(&message, [0; 8]).is_err());
+    }
+
+    #[test]
+    fn should_send_messages_over_udp() {
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let address = receiver.local_addr().unwrap().to_string();
+
+        let errors = send_gelf_messages(&address, &[json!({"short_message": "test"})]);
+        assert!(errors.is_empty());
+
+        let mut buffer = [0u8; 1024];
+        let size = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..size], br#"{"short_message":"test"}"#);
+    }
+
+}
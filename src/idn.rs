@@ -0,0 +1,135 @@
+// Internationalized domain names are sent on the wire as ASCII labels with
+// the 'xn--' prefix, the rest of the label being encoded with Punycode (RFC
+// 3492). Only the decoding is needed to display resolved hostnames.
+const ACE_PREFIX: &str = "xn--";
+
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/**
+ * Convert the internationalized labels of a hostname to their Unicode form,
+ * such as 'xn--mnchen-3ya.example' to 'münchen.example'. Labels that are not
+ * valid Punycode are kept in their ASCII form.
+ */
+pub fn to_unicode(hostname: &str) -> String {
+
+    hostname.split('.').map(|label| {
+        match label.get(..ACE_PREFIX.len()) {
+            Some(prefix) if prefix.eq_ignore_ascii_case(ACE_PREFIX) => {
+                decode_punycode(&label[ACE_PREFIX.len()..].to_ascii_lowercase()).unwrap_or_else(|| label.to_string())
+            },
+            _ => label.to_string()
+        }
+    }).collect::<Vec<String>>().join(".")
+}
+
+/**
+ * Decode a Punycode string (without the 'xn--' prefix), as described in the
+ * section 6.2 of RFC 3492. Invalid or overflowing inputs are rejected.
+ */
+fn decode_punycode(input: &str) -> Option<String> {
+
+    let (basic, extended) = match input.rfind('-') {
+        Some(delimiter_index) => (&input[..delimiter_index], &input[delimiter_index + 1..]),
+        None => ("", input)
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.bytes().peekable();
+
+    while digits.peek().is_some() {
+
+        let previous_i = i;
+        let mut weight: u32 = 1;
+        let mut k = BASE;
+        loop {
+            let digit = decode_digit(digits.next()?)?;
+            i = i.checked_add(digit.checked_mul(weight)?)?;
+            let threshold = match k {
+                k if k <= bias => TMIN,
+                k if k >= bias + TMAX => TMAX,
+                k => k - bias
+            };
+            if digit < threshold {
+                break;
+            }
+            weight = weight.checked_mul(BASE - threshold)?;
+            k += BASE;
+        }
+
+        let point_count = output.len() as u32 + 1;
+        bias = adapt_bias(i - previous_i, point_count, previous_i == 0);
+        n = n.checked_add(i / point_count)?;
+        i %= point_count;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+fn decode_digit(digit: u8) -> Option<u32> {
+
+    match digit {
+        b'a'..=b'z' => Some((digit - b'a') as u32),
+        b'0'..=b'9' => Some((digit - b'0') as u32 + 26),
+        _ => None
+    }
+}
+
+fn adapt_bias(delta: u32, point_count: u32, is_first: bool) -> u32 {
+
+    let mut delta = match is_first {
+        true => delta / DAMP,
+        false => delta / 2
+    };
+    delta += delta / point_count;
+
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta /= BASE - TMIN;
+        k += BASE;
+    }
+    k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_decode_punycode_labels() {
+
+        assert_eq!(decode_punycode("mnchen-3ya"), Some("münchen".to_string()));
+        assert_eq!(decode_punycode("bcher-caf-j4a4r"), Some("bücher-café".to_string()));
+        assert_eq!(decode_punycode("fiq228c"), Some("中文".to_string()));
+        assert_eq!(decode_punycode("eckwd4c7cu47r2wf"), Some("ドメイン名例".to_string()));
+    }
+
+    #[test]
+    fn should_convert_hostnames_to_unicode() {
+
+        assert_eq!(to_unicode("printer.xn--mnchen-3ya.example"), "printer.münchen.example");
+        assert_eq!(to_unicode("XN--BCHER-KVA.local"), "bücher.local");
+        assert_eq!(to_unicode("router.home"), "router.home");
+    }
+
+    #[test]
+    fn should_keep_invalid_punycode_labels() {
+
+        assert_eq!(to_unicode("xn--a%b.home"), "xn--a%b.home");
+        assert_eq!(to_unicode("xn--99999999999999.home"), "xn--99999999999999.home");
+    }
+
+}
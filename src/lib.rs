@@ -0,0 +1,51 @@
+//! Reusable parts of arp-scan. The scanner runs ARP scans on the local
+//! network and returns the hosts found, with their vendor (based on the IEEE
+//! registries or a Wireshark 'manuf' file) and their hostname. Hostname
+//! resolvers find the names of the scanned hosts, custom resolvers can be
+//! plugged in the resolution chain.
+//!
+//! The other modules hold the building blocks of the arp-scan CLI (argument
+//! parsing, packet crafting & exports), they are exposed for the binary.
+
+pub mod annotations;
+#[cfg(feature = "async")]
+pub mod async_scan;
+pub mod args;
+pub mod cache;
+pub mod capture;
+pub mod datalink;
+pub mod dhcp;
+pub mod dns;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod gelf;
+pub mod idn;
+pub mod mdns;
+pub mod netbios;
+pub mod network;
+pub mod profile;
+#[cfg(feature = "python")]
+mod python;
+pub mod progress;
+pub mod resolve;
+#[cfg(all(feature = "systemd-resolved", target_os = "linux"))]
+pub mod resolved;
+pub mod resolver;
+pub mod respond;
+pub mod resume;
+pub mod scanner;
+pub mod time;
+pub mod utils;
+pub mod vendor;
+pub mod wol;
+
+pub use ipnetwork::IpNetwork;
+pub use pnet_datalink::MacAddr;
+pub use datalink::{DataLink, FakeDataLink, ReplayDataLink};
+pub use network::{CancellationToken, ResponseSummary, ScanEvent, TargetDetails};
+pub use progress::{ProgressTracker, ScanProgress};
+pub use resolver::{HostnameResolver, StaticResolver};
+pub use error::ScanError;
+pub use scanner::{ArpScanner, ArpScannerBuilder, ScanResult};
+pub use vendor::VendorDatabase;
@@ -1,19 +1,37 @@
-mod args;
-mod network;
-mod time;
-mod utils;
-mod vendor;
-
-use std::net::IpAddr;
+use std::io::{self, IsTerminal};
+use std::net::Ipv4Addr;
 use std::process;
 use std::thread;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::time::Duration;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::args::{ScanOptions, OutputFormat};
-use crate::network::NetworkIterator;
-use crate::vendor::Vendor;
+use chrono::Local;
+use clap::ArgMatches;
+use pnet_datalink::NetworkInterface;
+use rand::Rng;
+
+use arp_scan::args::{self, OptionsError, ResponderOptions, ScanOptions, ScanOptionsBuilder, OutputFormat};
+use arp_scan::datalink::PnetDataLink;
+use arp_scan::error::ScanError;
+use arp_scan::gelf;
+use arp_scan::profile;
+use arp_scan::respond::Responder;
+use arp_scan::network::{self, CancellationToken, ResponseSummary, ScanEvent, ScanMetadata, TargetDetails};
+use arp_scan::progress::ProgressTracker;
+use arp_scan::scanner::{load_vendor_list, wait_until_halted, ArpScanner, ScanResult};
+use arp_scan::time;
+use arp_scan::utils::{self, ExportError};
+use arp_scan::vendor;
+use arp_scan::wol;
+
+const PROBE_EXIT_AVAILABLE: i32 = 0;
+const PROBE_EXIT_IN_USE: i32 = 1;
+const PROBE_EXIT_ERROR: i32 = 2;
+
+const PROGRESS_REFRESH_MS: u64 = 200;
 
 fn main() {
     
@@ -32,127 +50,579 @@ fn main() {
         process::exit(0);
     }
 
+    // The export schema is printed without scanning, so that parsers can
+    // check the shape of the results they consume.
+    if matches.contains_id("schema") {
+        print!("{}", utils::EXPORT_SCHEMA);
+        process::exit(0);
+    }
+
+    // Profiles are listed from the config file and the profiles directory,
+    // without scanning.
+    if matches.contains_id("list_profiles") {
+        let config = profile::load_selected_config(&args::build_args(), &matches).unwrap_or_else(|err| exit_with_error(ScanError::Profile(err)));
+        for (name, summary) in profile::list_profiles(&config) {
+            println!("{}: {}", name, summary);
+        }
+        process::exit(0);
+    }
+
+    // The vendor database update does not perform any scan, and does not
+    // require a root account either.
+    if matches.contains_id("update_vendors") {
+        update_vendors().unwrap_or_else(|err| exit_with_error(err));
+        process::exit(0);
+    }
+
+    // The hidden responder answers ARP requests for simulated hosts instead
+    // of scanning, so that scans can be tested end-to-end (on a veth pair).
+    if let Some(respond_matches) = matches.subcommand_matches("respond") {
+        respond_to_requests(respond_matches, &interfaces);
+        process::exit(0);
+    }
+
     // Assert requirements for a local network scan
     // --------------------------------------------
     // Ensure all requirements are met to perform an ARP scan on the local
     // network for the given interface. ARP scans require an active interface
     // with an IPv4 address and root permissions (for crafting ARP packets).
 
-    let scan_options = ScanOptions::new(&matches);
-    
-    if !utils::is_root_user() {
-        eprintln!("Should run this binary as root or use --help for options");
-        process::exit(1);
+    // The config file and saved profiles are merged with the options of the
+    // command line (which take precedence), the interfaces of the profile are
+    // checked before any channel is opened.
+    let save_profile_name = matches.get_one::<String>("save_profile").cloned();
+    let (matches, scan_profile) = profile::apply_saved_options(&args::build_args(), matches, &interfaces).unwrap_or_else(|err| exit_with_error(err));
+    if let Some(profile_name) = save_profile_name {
+        match profile::save_profile(&profile_name, &scan_profile) {
+            Ok(file_path) => eprintln!("Saved profile {} to {}", profile_name, file_path.display()),
+            Err(err) => exit_with_error(ScanError::Profile(err))
+        }
     }
 
-    let (selected_interface, ip_networks) = network::compute_network_configuration(&interfaces, &scan_options);
+    let scan_options = ScanOptionsBuilder::from_matches(&matches)
+        .and_then(|builder| Ok(builder.interfaces(interfaces.clone()).build()?))
+        .unwrap_or_else(|err| match matches.contains_id("probe") {
+            true => exit_probe_with_error(err),
+            false => exit_with_error(err)
+        });
+
+    // Binary outputs are not printed on a terminal, the scan is refused
+    // before sending any packet if the standard output is not redirected.
+    if matches!(scan_options.output, OutputFormat::MessagePack) && scan_options.output_file.is_none() && io::stdout().is_terminal() {
+        exit_with_error(ScanError::InvalidOptions(OptionsError(vec![
+            "Refusing to print binary MessagePack output on a terminal, redirect stdout or use --output-file".to_string()
+        ])));
+    }
+    
+    // Dry runs and replays never open a datalink channel, they do not
+    // require a root account.
+    if !scan_options.dry_run && scan_options.read_pcap.is_none() && !utils::is_root_user() {
+        match scan_options.probe_ipv4 {
+            Some(_) => exit_probe_with_error(ScanError::PermissionDenied),
+            None => exit_with_error(ScanError::PermissionDenied)
+        }
+    }
 
-    if scan_options.is_plain_output() {
-        utils::display_prescan_details(&ip_networks, selected_interface, scan_options.clone());
+    // Captures are replayed through the receive pipeline of a scan, without
+    // any interface (the capture may come from another host).
+    if let Some(pcap_file) = &scan_options.read_pcap {
+        let scan_result = ArpScanner::new(Arc::clone(&scan_options)).replay(pcap_file).unwrap_or_else(|err| exit_with_error(err));
+        let conflict_found = !network::find_address_conflicts(&scan_result.hosts).is_empty();
+        export_scan_results(&scan_options, &scan_result.metadata, scan_result.summary, scan_result.hosts).unwrap_or_else(|err| exit_with_error(err));
+        if scan_options.fail_on_conflict && conflict_found {
+            exit_with_error(ScanError::CheckFailed("IPv4 address conflicts found (answered by several MAC addresses)".to_string()));
+        }
+        process::exit(0);
     }
 
-    // Start ARP scan operation
-    // ------------------------
-    // ARP responses on the interface will be collected in a separate thread,
-    // while the main thread sends a batch of ARP requests for each IP in the
-    // local network.
+    let network_configurations = match scan_options.all_interfaces {
+        true => network::compute_all_interfaces_configuration(&interfaces),
+        false => network::compute_network_configuration(&interfaces, &scan_options)
+    }.unwrap_or_else(|err| exit_with_error(err));
 
-    let channel_config = pnet_datalink::Config {
-        read_timeout: Some(Duration::from_millis(network::DATALINK_RCV_TIMEOUT)), 
-        ..pnet_datalink::Config::default()
-    };
+    // Dry runs print the planned requests with an example frame for each
+    // network, and stop before sending any packet.
+    if scan_options.dry_run {
+        let scan_plans = network::plan_scan(&network_configurations, &scan_options).unwrap_or_else(|err| exit_with_error(err));
+        print!("{}", utils::format_scan_plans(&scan_plans));
+        process::exit(0);
+    }
 
-    let (mut tx, mut rx) = match pnet_datalink::channel(selected_interface, channel_config) {
-        Ok(pnet_datalink::Channel::Ethernet(tx, rx)) => (tx, rx),
-        Ok(_) => {
-            eprintln!("Expected an Ethernet datalink channel");
-            process::exit(1);
-        },
-        Err(error) => {
-            eprintln!("Datalink channel creation failed ({})", error);
-            process::exit(1);
-        }
-    };
+    // Gratuitous ARP announcements do not perform any scan, the frames are
+    // sent on the first selected interface and the program stops.
+    if let Some(announced_ipv4) = scan_options.gratuitous_ipv4 {
+        announce_ipv4(network_configurations[0].0, announced_ipv4, &scan_options);
+        process::exit(0);
+    }
 
-    // The 'timed_out' mutex is shared accross the main thread (which performs
-    // ARP packet sending) and the response thread (which receives and stores
-    // all ARP responses).
-    let timed_out = Arc::new(AtomicBool::new(false));
-    let cloned_timed_out = Arc::clone(&timed_out);
+    // ARP probes only check a single address, the exit code gives the probe
+    // result (0 for an available address, 1 if in use, 2 on errors).
+    if let Some(candidate_ipv4) = scan_options.probe_ipv4 {
+        let probe_code = probe_ipv4(network_configurations[0].0, candidate_ipv4, &scan_options).unwrap_or_else(|err| exit_probe_with_error(err));
+        process::exit(probe_code);
+    }
 
-    let mut vendor_list = Vendor::new(&scan_options.oui_file);
+    // Wake-on-LAN magic packets are sent on the first selected interface, the
+    // scan starts after a grace period (so that hosts have time to boot).
+    if !scan_options.wake_macs.is_empty() {
+        wake_hosts(network_configurations[0].0, &scan_options);
+        if scan_options.wake_only {
+            process::exit(0);
+        }
+        if scan_options.is_plain_output() {
+            println!("Waiting {} before scanning woken hosts", time::format_milliseconds(scan_options.wake_grace_ms.into()));
+        }
+        thread::sleep(Duration::from_millis(scan_options.wake_grace_ms));
+    }
 
-    let cloned_options = Arc::clone(&scan_options);
-    let arp_responses = thread::spawn(move || network::receive_arp_responses(&mut rx, cloned_options, cloned_timed_out, &mut vendor_list));
+    if scan_options.is_plain_output() {
+        utils::display_prescan_details(&network_configurations, scan_options.clone());
+    }
 
-    let network_size = utils::compute_network_size(&ip_networks);
+    // Start ARP scan operation
+    // ------------------------
+    // The scan session opens a datalink channel on each scanned interface,
+    // which is reused by all scan rounds. The scanner halt flag is raised by
+    // a halt signal, the scan then ends with partial results.
+
+    // In verbose mode, scan events are printed as they happen: each new host
+    // as soon as it answers, and each retry (the results are still displayed
+    // once the scan is done). The end of each round is acknowledged by the
+    // event thread, so that all events are printed before the results.
+    let (scanner, round_finished) = match scan_options.verbose && scan_options.is_plain_output() {
+        true => {
+            let (event_sender, event_receiver) = mpsc::channel();
+            let (finished_sender, finished_receiver) = mpsc::channel();
+            thread::spawn(move || print_verbose_events(event_receiver, finished_sender));
+            (ArpScanner::new(Arc::clone(&scan_options)).events(event_sender), Some(finished_receiver))
+        },
+        false => (ArpScanner::new(Arc::clone(&scan_options)), None)
+    };
+    let mut session = scanner.open().unwrap_or_else(|err| exit_with_error(err));
 
+    let network_size = session.target_count();
     let estimations = network::compute_scan_estimation(network_size, &scan_options);
-    let interval_ms = estimations.interval_ms;
 
-    if scan_options.is_plain_output() {
+    if scan_options.is_plain_output() && scan_options.passive {
+        println!("Passive mode, listening to ARP traffic for {} (no ARP request will be sent)", time::format_milliseconds(scan_options.timeout_ms.into()));
+    }
+    else if scan_options.is_plain_output() && scan_options.verify {
+        println!("Verifying {} neighbor entries from {} with unicast ARP requests", network_size, network::NEIGHBOR_TABLE_PATH);
+    }
+    else if scan_options.is_plain_output() {
 
         let formatted_ms = time::format_milliseconds(estimations.duration_ms);
         println!("Estimated scan time {} ({} bytes, {} bytes/s)", formatted_ms, estimations.request_size, estimations.bandwidth);
-        println!("Sending {} ARP requests (waiting at least {}ms, {}ms request interval)", network_size, scan_options.timeout_ms, interval_ms);
+        println!("Sending {} ARP requests (waiting at least {}ms, {}ms request interval)", network_size, scan_options.timeout_ms, estimations.interval_ms);
     }
 
-    let has_reached_timeout = Arc::new(AtomicBool::new(false));
-    let cloned_reached_timeout = Arc::clone(&has_reached_timeout);
+    // A halt signal cancels the scan, the partial results are still printed
+    // (or exported) as usual.
+    let cancellation = scanner.cancellation_token();
+    let cloned_cancellation = cancellation.clone();
 
     ctrlc::set_handler(move || {
         eprintln!("[warn] Receiving halt signal, ending scan with partial results");
-        cloned_reached_timeout.store(true, Ordering::Relaxed);
-    }).unwrap_or_else(|err| {
-        eprintln!("Could not set CTRL+C handler ({})", err);
-        process::exit(1);
-    });
+        cloned_cancellation.cancel();
+    }).unwrap_or_else(|err| exit_with_error(ScanError::Thread(format!("Could not set CTRL+C handler ({})", err))));
 
-    let source_ip = network::find_source_ip(selected_interface, scan_options.source_ipv4);
+    if let (Some(state_file), Some(host_count), true) = (&scan_options.state_file, session.resumed_host_count(), scan_options.is_plain_output()) {
+        println!("Resuming scan from {} ({} hosts already recorded)", state_file, host_count);
+    }
 
-    // The retry count does right now use a 'brute-force' strategy without
-    // synchronization process with the already known hosts.
-    for _ in 0..scan_options.retry_count {
+    // The progress bar is only displayed on terminals, since it is redrawn
+    // in place.
+    let progress = scanner.progress();
+    let show_progress = scan_options.progress && io::stderr().is_terminal();
 
-        if has_reached_timeout.load(Ordering::Relaxed) {
-            break;
-        }
+    // In loop mode, the whole scan cycle is repeated until a halt signal is
+    // received. Only aggregated counters are kept between rounds, while the
+    // scan session is reused by each round.
+    let mut round_count: usize = 0;
+    let mut packet_total: usize = 0;
+    let mut seen_hosts: HashSet<Ipv4Addr> = HashSet::new();
+    let mut conflict_found = false;
+
+    loop {
 
-        let ip_addresses = NetworkIterator::new(&ip_networks, scan_options.randomize_targets);
+        round_count += 1;
 
-        for ip_address in ip_addresses {
+        if scan_options.loop_mode && scan_options.is_plain_output() {
+            println!();
+            println!("Scan round {} started at {}", round_count, Local::now().fixed_offset().to_rfc3339());
+        }
 
-            if has_reached_timeout.load(Ordering::Relaxed) {
-                break;
+        // Streaming outputs (NDJSON) print each new host as soon as it has
+        // been confirmed, the host channel is closed at the end of the round
+        // (before the summary is printed).
+        //
+        // The progress bar is redrawn on stderr during the round, and cleared
+        // before the results are printed.
+        let round_done = AtomicBool::new(false);
+        let round_result = thread::scope(|scope| {
+            if show_progress {
+                scope.spawn(|| display_progress_bar(&progress, &round_done));
             }
+            let round_result = match scan_options.is_streaming_output() {
+                true => thread::scope(|scope| {
+                    let (host_sender, host_receiver) = mpsc::channel::<TargetDetails>();
+                    scope.spawn(move || {
+                        for host in host_receiver {
+                            match utils::export_host_to_ndjson(&host) {
+                                Ok(line) => println!("{}", line),
+                                Err(err) => eprintln!("{}", err)
+                            }
+                        }
+                    });
+                    session.run_round_streaming(host_sender)
+                }),
+                false => session.run_round()
+            };
+            round_done.store(true, Ordering::Relaxed);
+            round_result
+        });
+        let ScanResult { summary: response_summary, hosts: target_details, metadata: scan_metadata } = round_result.unwrap_or_else(|err| exit_with_error(err));
+        if let Some(round_finished) = &round_finished {
+            let _ = round_finished.recv();
+        }
+
+        packet_total += response_summary.packet_count;
+        seen_hosts.extend(target_details.iter().map(|detail| detail.ipv4));
+        conflict_found = conflict_found || !network::find_address_conflicts(&target_details).is_empty();
+
+        let wake_report = match scan_options.wake_macs.is_empty() {
+            true => None,
+            false => Some(utils::format_wake_report(&scan_options.wake_macs, &target_details))
+        };
+
+        export_scan_results(&scan_options, &scan_metadata, response_summary, target_details).unwrap_or_else(|err| exit_with_error(err));
 
-            if let IpAddr::V4(ipv4_address) = ip_address {
-                network::send_arp_request(&mut tx, selected_interface, source_ip, ipv4_address, Arc::clone(&scan_options));
-                thread::sleep(Duration::from_millis(interval_ms));
+        if let Some(wake_report) = wake_report {
+            match scan_options.is_plain_output() {
+                true => print!("{}", wake_report),
+                false => eprint!("{}", wake_report)
             }
         }
+
+        // The maximum scan duration stops the session, and therefore the loop.
+        if !scan_options.loop_mode || cancellation.is_cancelled() || session.has_reached_max_duration() {
+            break;
+        }
+
+        // The next round starts after the loop interval, unless a halt signal
+        // is received (or the maximum scan duration is reached) while waiting.
+        let is_halted = || cancellation.is_cancelled() || session.has_reached_max_duration();
+        wait_until_halted(is_halted, scan_options.loop_interval_ms);
+        if is_halted() {
+            break;
+        }
     }
 
-    // Once the ARP packets are sent, the main thread will sleep for T seconds
-    // (where T is the timeout option). After the sleep phase, the response
-    // thread will receive a stop request through the 'timed_out' mutex.
-    let mut sleep_ms_mount: u64 = 0;
-    while !has_reached_timeout.load(Ordering::Relaxed) && sleep_ms_mount < scan_options.timeout_ms {
-        
-        thread::sleep(Duration::from_millis(100));
-        sleep_ms_mount += 100;
+    let scan_truncated = session.has_reached_max_duration();
+    session.close().unwrap_or_else(|err| exit_with_error(err));
+
+    if scan_options.strict && scan_truncated {
+        exit_with_error(ScanError::CheckFailed("Scan truncated after the maximum scan duration".to_string()));
     }
-    timed_out.store(true, Ordering::Relaxed);
 
-    let (response_summary, target_details) = arp_responses.join().unwrap_or_else(|error| {
-        eprintln!("Failed to close receive thread ({:?})", error);
-        process::exit(1);
+    if scan_options.fail_on_conflict && conflict_found {
+        exit_with_error(ScanError::CheckFailed("IPv4 address conflicts found (answered by several MAC addresses)".to_string()));
+    }
+
+    if scan_options.loop_mode {
+        let aggregate_summary = format!("Monitoring stopped after {} rounds, {} distinct hosts seen, {} packets received", round_count, seen_hosts.len(), packet_total);
+        match scan_options.is_plain_output() {
+            true => println!("{}", aggregate_summary),
+            false => eprintln!("{}", aggregate_summary)
+        }
+    }
+}
+
+/**
+ * Print the scan events in verbose mode, and acknowledge the end of each
+ * scan round.
+ */
+fn print_verbose_events(events: Receiver<ScanEvent>, round_finished: Sender<()>) {
+
+    for event in events {
+        match event {
+            ScanEvent::HostFound(host) => eprintln!("Found {} at {}", host.ipv4, host.mac),
+            ScanEvent::RetryRound(retry) => eprintln!("Retry {}, requesting unanswered hosts again", retry),
+            ScanEvent::Finished(_) => {
+                let _ = round_finished.send(());
+            },
+            ScanEvent::Started { .. } | ScanEvent::PacketSent { .. } => {}
+        }
+    }
+}
+
+/**
+ * Redraw the progress bar of the current scan round until the round is done,
+ * the line is then cleared.
+ */
+fn display_progress_bar(progress: &ProgressTracker, round_done: &AtomicBool) {
+
+    while !round_done.load(Ordering::Relaxed) {
+        eprint!("\r{}", utils::format_progress_bar(&progress.snapshot()));
+        thread::sleep(Duration::from_millis(PROGRESS_REFRESH_MS));
+    }
+    eprint!("\r\x1b[2K");
+}
+
+/**
+ * Send gratuitous ARP frames for an IPv4 address on the selected interface,
+ * so that switches and neighbors update their ARP cache (after a failover for
+ * example). The address should be owned by the interface or forced with the
+ * source IPv4 option, unless the '--force' flag is given.
+ */
+fn announce_ipv4(selected_interface: &NetworkInterface, announced_ipv4: Ipv4Addr, scan_options: &ScanOptions) {
+
+    let is_impersonated = scan_options.source_ipv4 == Some(announced_ipv4);
+    if !network::is_interface_ipv4(selected_interface, announced_ipv4) && !is_impersonated && !scan_options.force {
+        exit_with_error(ScanError::InvalidTarget(format!(
+            "Interface {} does not own the IPv4 address {}\nUse --source-ip {} or --force to announce it anyway",
+            selected_interface.name, announced_ipv4, announced_ipv4
+        )));
+    }
+
+    let link = PnetDataLink::open(selected_interface).unwrap_or_else(|err| exit_with_error(err));
+
+    let mut sent_count: usize = 0;
+    for index in 0..scan_options.announce_count {
+
+        if index > 0 {
+            thread::sleep(Duration::from_millis(scan_options.announce_interval_ms));
+        }
+
+        match network::send_gratuitous_arp(&link, selected_interface, announced_ipv4, scan_options) {
+            Ok(()) => sent_count += 1,
+            Err(err) => eprintln!("{}", err)
+        }
+    }
+
+    println!("Sent {} of {} gratuitous ARP frames for {} on {}", sent_count, scan_options.announce_count, announced_ipv4, selected_interface.name);
+    if sent_count == 0 {
+        exit_with_error(ScanError::Packet(format!("Could not send any gratuitous ARP frame for {}", announced_ipv4)));
+    }
+}
+
+/**
+ * Run the 'respond' testing subcommand: answer the ARP requests received on
+ * the given interface for the simulated hosts (with the optional delay and
+ * loss percentage), until a halt signal is received.
+ */
+fn respond_to_requests(matches: &ArgMatches, interfaces: &[NetworkInterface]) {
+
+    let responder_options = ResponderOptions::from_matches(matches).unwrap_or_else(|err| exit_with_error(err.into()));
+    if !utils::is_root_user() {
+        exit_with_error(ScanError::PermissionDenied);
+    }
+
+    let selected_interface = interfaces.iter().find(|interface| interface.name == responder_options.interface_name).unwrap_or_else(|| {
+        exit_with_error(ScanError::InterfaceNotFound(responder_options.interface_name.clone()))
     });
+    let link = PnetDataLink::open(selected_interface).unwrap_or_else(|err| exit_with_error(err));
 
-    match &scan_options.output {
-        OutputFormat::Plain => utils::display_scan_results(response_summary, target_details, &scan_options),
-        OutputFormat::Json => println!("{}", utils::export_to_json(response_summary, target_details)),
-        OutputFormat::Yaml => println!("{}", utils::export_to_yaml(response_summary, target_details)),
-        OutputFormat::Csv => print!("{}", utils::export_to_csv(response_summary, target_details))
+    let cancellation = CancellationToken::new();
+    let cloned_cancellation = cancellation.clone();
+    ctrlc::set_handler(move || cloned_cancellation.cancel()).unwrap_or_else(|err| {
+        exit_with_error(ScanError::Thread(format!("Could not set CTRL+C handler ({})", err)))
+    });
+
+    println!("Answering ARP requests for {} simulated hosts on {} (Ctrl-C to stop)", responder_options.hosts.len(), selected_interface.name);
+    let mut responder = Responder::new(selected_interface, &responder_options.hosts, responder_options.vlan_id, Duration::from_millis(responder_options.delay_ms), responder_options.loss_percent, responder_options.seed);
+    let reply_count = responder.run(&link, &cancellation).unwrap_or_else(|err| exit_with_error(err));
+    println!("Sent {} ARP replies", reply_count);
+}
+
+/**
+ * Send a Wake-on-LAN magic packet to each requested MAC address on the
+ * selected interface. Send failures are reported for each MAC address, the
+ * program only stops if no magic packet could be sent.
+ */
+fn wake_hosts(selected_interface: &NetworkInterface, scan_options: &ScanOptions) {
+
+    let source_mac = scan_options.source_mac.or(selected_interface.mac).unwrap_or_else(|| {
+        exit_with_error(ScanError::Interface(format!("Interface {} should have a MAC address", selected_interface.name)))
+    });
+
+    let link = PnetDataLink::open(selected_interface).unwrap_or_else(|err| exit_with_error(err));
+
+    let mut sent_count: usize = 0;
+    for wake_mac in scan_options.wake_macs.iter() {
+        match wol::send_magic_packet(&link, source_mac, wake_mac) {
+            Ok(()) => sent_count += 1,
+            Err(err) => eprintln!("{}", err)
+        }
+    }
+
+    let wake_summary = format!("Sent {} of {} Wake-on-LAN magic packets on {}", sent_count, scan_options.wake_macs.len(), selected_interface.name);
+    match scan_options.is_plain_output() || scan_options.wake_only {
+        true => println!("{}", wake_summary),
+        false => eprintln!("{}", wake_summary)
     }
+    if sent_count == 0 {
+        exit_with_error(ScanError::Packet("Could not send any Wake-on-LAN magic packet".to_string()));
+    }
+}
+
+/**
+ * Check if an IPv4 address is already in use with ARP probes, as described in
+ * RFC 5227: after a random delay, probes are sent at random intervals and the
+ * responses are collected until the announce delay. Any reply from the address
+ * (or a probe for the same address by another host) is a conflict. The probe
+ * result is printed and returned as an exit code.
+ */
+fn probe_ipv4(selected_interface: &NetworkInterface, candidate_ipv4: Ipv4Addr, scan_options: &ScanOptions) -> Result<i32, ScanError> {
+
+    let link = Arc::new(PnetDataLink::open(selected_interface)?);
+
+    let own_mac = scan_options.source_mac.or(selected_interface.mac);
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let cloned_timed_out = Arc::clone(&timed_out);
+    let receiving_link = Arc::clone(&link);
+    let probe_responses = thread::spawn(move || {
+        network::receive_probe_conflict(receiving_link.as_ref(), candidate_ipv4, own_mac, cloned_timed_out)
+    });
+
+    let mut rng = rand::thread_rng();
+    thread::sleep(Duration::from_millis(rng.gen_range(0..=network::PROBE_WAIT_MS)));
+
+    for probe_index in 0..network::PROBE_NUM {
+
+        if probe_responses.is_finished() {
+            break;
+        }
+
+        if let Err(err) = network::send_arp_probe(link.as_ref(), selected_interface, candidate_ipv4, scan_options) {
+            timed_out.store(true, Ordering::Relaxed);
+            return Err(err);
+        }
+
+        let wait_ms = match probe_index + 1 < network::PROBE_NUM {
+            true => rng.gen_range(network::PROBE_MIN_MS..=network::PROBE_MAX_MS),
+            false => network::ANNOUNCE_WAIT_MS
+        };
+        wait_until_halted(|| timed_out.load(Ordering::Relaxed), wait_ms);
+    }
+
+    timed_out.store(true, Ordering::Relaxed);
+    let probe_result = probe_responses.join().map_err(|error| {
+        ScanError::Thread(format!("Failed to close receive thread ({:?})", error))
+    })?;
+
+    match probe_result.map_err(ScanError::Packet)? {
+        Some(conflicting_mac) => {
+            let vendor_list = load_vendor_list(scan_options);
+            let vendor = match vendor_list.has_vendor_db() {
+                true => vendor_list.lookup(&conflicting_mac),
+                false => None
+            };
+            println!("{} is in use by {} ({})", candidate_ipv4, conflicting_mac, vendor.unwrap_or("unknown vendor"));
+            Ok(PROBE_EXIT_IN_USE)
+        },
+        None => {
+            println!("{} is available", candidate_ipv4);
+            Ok(PROBE_EXIT_AVAILABLE)
+        }
+    }
+}
+
+/**
+ * Download the latest IEEE registries to the user data directory, which is
+ * then preferred by the next scans.
+ */
+fn update_vendors() -> Result<(), ScanError> {
+
+    let vendor_file_path = vendor::user_vendor_file_path().ok_or_else(|| {
+        ScanError::Output("Could not find the user data directory to store the vendor database".to_string())
+    })?;
+
+    let update = vendor::update_vendor_file(&vendor_file_path).map_err(|err| {
+        ScanError::Output(format!("{}\nThe previous vendor database has been kept", err))
+    })?;
+
+    println!("Downloaded {} vendor prefixes to {}", update.prefix_count, vendor_file_path.display());
+    match update.previous_age {
+        Some(previous_age) => println!("Replaced a vendor database downloaded {} ago", time::format_milliseconds(previous_age.as_millis())),
+        None => println!("No previous vendor database was downloaded")
+    }
+    Ok(())
+}
+
+/**
+ * Export the results of a scan round: the optional side exports (SQLite, CSV
+ * summary & GELF) are done first, then the main output is written.
+ */
+fn export_scan_results(scan_options: &ScanOptions, scan_metadata: &ScanMetadata, response_summary: ResponseSummary, target_details: Vec<TargetDetails>) -> Result<(), ScanError> {
+
+    #[cfg(feature = "sqlite")]
+    if let Some(database_path) = &scan_options.output_sqlite {
+        utils::export_to_sqlite(database_path, scan_metadata, &response_summary, &target_details)?;
+    }
+
+    if let Some(summary_path) = &scan_options.csv_summary_file {
+        let summary = utils::export_summary_to_csv(&response_summary, scan_metadata)?;
+        utils::write_output(Some(summary_path), summary.as_bytes()).map_err(ScanError::Output)?;
+    }
+
+    // GELF failures are only reported after the scan results, so that a
+    // missing Graylog server does not prevent the scan output.
+    let gelf_errors = match &scan_options.gelf_address {
+        Some(address) => gelf::send_gelf_messages(address, &gelf::build_gelf_messages(&response_summary, &target_details, scan_metadata)),
+        None => vec![]
+    };
+
+    let output: Result<Vec<u8>, ExportError> = match &scan_options.output {
+        OutputFormat::Plain => Ok(utils::format_scan_results(response_summary, target_details, scan_options, scan_options.output_file.is_none()).into_bytes()),
+        OutputFormat::Json => utils::export_to_json(response_summary, target_details, scan_metadata, scan_options.pretty && !scan_options.loop_mode, scan_options.top_vendors, scan_options.group_by_mac).map(|json| format!("{}\n", json).into_bytes()),
+        OutputFormat::Yaml => utils::export_to_yaml(response_summary, target_details, scan_metadata, scan_options.top_vendors, scan_options.group_by_mac).map(|yaml| format!("{}\n", yaml).into_bytes()),
+        OutputFormat::Csv => utils::export_to_csv(response_summary, target_details, scan_options.csv_headers, &scan_options.columns, scan_options.csv_delimiter, scan_options.csv_quote_all).map(String::into_bytes),
+        OutputFormat::Xml => utils::export_to_xml(response_summary, target_details, scan_metadata).map(|xml| format!("{}\n", xml).into_bytes()),
+        OutputFormat::Ndjson if scan_options.is_streaming_output() => utils::export_summary_to_ndjson(response_summary).map(|summary| format!("{}\n", summary).into_bytes()),
+        OutputFormat::Ndjson => utils::export_to_ndjson(response_summary, target_details).map(String::into_bytes),
+        OutputFormat::Html => Ok(utils::export_to_html(response_summary, target_details, scan_metadata).into_bytes()),
+        OutputFormat::Markdown => Ok(utils::export_to_markdown(response_summary, target_details).into_bytes()),
+        OutputFormat::Prometheus => Ok(utils::export_to_prometheus(response_summary, target_details).into_bytes()),
+        OutputFormat::NmapXml => Ok(utils::export_to_nmap_xml(response_summary, target_details, scan_metadata).into_bytes()),
+        OutputFormat::MessagePack => utils::export_to_msgpack(response_summary, target_details, scan_metadata),
+        OutputFormat::Toml => utils::export_to_toml(response_summary, target_details, scan_metadata).map(String::into_bytes),
+        OutputFormat::Grep => Ok(utils::export_to_grep(response_summary, target_details).into_bytes()),
+        OutputFormat::Junit => Ok(utils::export_to_junit(response_summary, target_details, scan_options.allowlist.as_deref()).into_bytes()),
+        OutputFormat::Dot => Ok(utils::export_to_dot(response_summary, target_details, scan_metadata).into_bytes()),
+        OutputFormat::Ansible => utils::export_to_ansible(response_summary, target_details, scan_metadata, scan_options.ansible_group_by.as_ref()).map(String::into_bytes),
+        OutputFormat::Hosts => Ok(utils::export_to_hosts(response_summary, target_details).into_bytes()),
+        OutputFormat::Dnsmasq => Ok(utils::export_to_dnsmasq(response_summary, target_details, scan_options.only_resolved).into_bytes()),
+        OutputFormat::Influx => Ok(utils::export_to_influx(response_summary, target_details, scan_metadata).into_bytes()),
+        OutputFormat::Template => match &scan_options.host_template {
+            Some(host_template) => Ok(utils::export_to_template(response_summary, target_details, host_template, scan_options.summary_template.as_deref()).into_bytes()),
+            None => Ok(vec![])
+        }
+    };
+    utils::write_output(scan_options.output_file.as_deref(), &output?).map_err(ScanError::Output)?;
+
+    for gelf_error in gelf_errors.iter() {
+        eprintln!("{}", gelf_error);
+    }
+    Ok(())
+}
+
+/**
+ * Print an error preventing the scan, and stop the program with the exit code
+ * of the error category.
+ */
+fn exit_with_error(error: ScanError) -> ! {
+
+    eprintln!("{}", error);
+    process::exit(error.exit_code());
+}
+
+/**
+ * Print an error preventing an ARP probe, probes exit with their own error
+ * code whatever the error category.
+ */
+fn exit_probe_with_error(error: ScanError) -> ! {
+
+    eprintln!("{}", error);
+    process::exit(PROBE_EXIT_ERROR);
 }
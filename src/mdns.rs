@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::dns;
+
+pub const MDNS_TIMEOUT_MS: u64 = 1000;
+
+const MDNS_MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const MDNS_MULTICAST_TTL: u32 = 255;
+const MDNS_RECEIVE_STEP_MS: u64 = 50;
+
+/**
+ * Resolve the hostnames of several IPv4 addresses with mDNS. All PTR queries
+ * are sent at once from a single socket, and answers are collected until the
+ * timeout is reached (or all addresses are resolved). Queries are sent from
+ * an ephemeral port (legacy unicast queries), responders therefore answer
+ * directly to the querier instead of the multicast group. Addresses without
+ * answer are not part of the returned map.
+ */
+pub fn resolve_hostnames(ipv4s: &[Ipv4Addr], timeout_ms: u64) -> Result<HashMap<Ipv4Addr, String>, String> {
+
+    let mut hostnames: HashMap<Ipv4Addr, String> = HashMap::new();
+    if ipv4s.is_empty() {
+        return Ok(hostnames);
+    }
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|err| format!("Could not open mDNS socket ({})", err))?;
+    socket.set_multicast_ttl_v4(MDNS_MULTICAST_TTL).map_err(|err| format!("Could not configure mDNS socket ({})", err))?;
+    socket.set_read_timeout(Some(Duration::from_millis(MDNS_RECEIVE_STEP_MS))).map_err(|err| format!("Could not configure mDNS socket ({})", err))?;
+
+    let destination = SocketAddrV4::new(MDNS_MULTICAST_ADDR, MDNS_PORT);
+    for (query_index, ipv4) in ipv4s.iter().enumerate() {
+        let query = dns::build_ptr_query(query_index as u16, &dns::build_reverse_name(*ipv4), false);
+        socket.send_to(&query, destination).map_err(|err| format!("Could not send mDNS query ({})", err))?;
+    }
+
+    let started_at = Instant::now();
+    let mut buffer = [0u8; 9000];
+    while started_at.elapsed() < Duration::from_millis(timeout_ms) && hostnames.len() < ipv4s.len() {
+
+        let received_size = match socket.recv_from(&mut buffer) {
+            Ok((received_size, _)) => received_size,
+            Err(_) => continue
+        };
+        for (owner_name, target_name) in dns::parse_ptr_answers(&buffer[..received_size]) {
+            if let Some(ipv4) = dns::parse_reverse_name(&owner_name).filter(|ipv4| ipv4s.contains(ipv4)) {
+                hostnames.entry(ipv4).or_insert(target_name);
+            }
+        }
+    }
+    Ok(hostnames)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_skip_empty_resolution() {
+
+        assert_eq!(resolve_hostnames(&[], MDNS_TIMEOUT_MS), Ok(HashMap::new()));
+    }
+
+}
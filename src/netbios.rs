@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+
+pub const NETBIOS_TIMEOUT_MS: u64 = 800;
+
+const NETBIOS_NAME_PORT: u16 = 137;
+const NETBIOS_RECEIVE_STEP_MS: u64 = 50;
+
+const NBSTAT_HEADER_SIZE: usize = 12;
+const NBSTAT_TYPE: u16 = 0x0021;
+const NBSTAT_CLASS_IN: u16 = 0x0001;
+const NBSTAT_NAME_ENTRY_SIZE: usize = 18;
+const NETBIOS_NAME_SIZE: usize = 16;
+const NETBIOS_ENCODED_NAME_SIZE: u8 = 32;
+const NETBIOS_GROUP_FLAG: u16 = 0x8000;
+// Names registered with the 0x00 suffix are the workstation (computer) names.
+const NETBIOS_WORKSTATION_SUFFIX: u8 = 0x00;
+
+/**
+ * Encode a NetBIOS name with the first-level encoding (RFC 1002): the name
+ * is padded to 16 bytes, then each half-byte is mapped to a letter from 'A'.
+ */
+fn encode_netbios_name(name: &[u8]) -> Vec<u8> {
+
+    let mut padded_name = [0u8; NETBIOS_NAME_SIZE];
+    padded_name[..name.len().min(NETBIOS_NAME_SIZE)].copy_from_slice(&name[..name.len().min(NETBIOS_NAME_SIZE)]);
+
+    padded_name.iter().flat_map(|byte| [b'A' + (byte >> 4), b'A' + (byte & 0x0f)]).collect()
+}
+
+/**
+ * Build a NBSTAT query for the wildcard name '*', which is answered by NetBIOS
+ * hosts with the list of all their registered names.
+ */
+pub fn build_nbstat_query(transaction_id: u16) -> Vec<u8> {
+
+    let mut message: Vec<u8> = Vec::with_capacity(NBSTAT_HEADER_SIZE + 38);
+    message.extend_from_slice(&transaction_id.to_be_bytes());
+    message.extend_from_slice(&[0, 0]);
+    message.extend_from_slice(&1u16.to_be_bytes());
+    message.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+    message.push(NETBIOS_ENCODED_NAME_SIZE);
+    message.extend_from_slice(&encode_netbios_name(b"*"));
+    message.push(0);
+    message.extend_from_slice(&NBSTAT_TYPE.to_be_bytes());
+    message.extend_from_slice(&NBSTAT_CLASS_IN.to_be_bytes());
+    message
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+
+    message.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/**
+ * Skip an encoded name in a NetBIOS message, which is either a sequence of
+ * labels or a compression pointer.
+ */
+fn skip_name(message: &[u8], offset: usize) -> Option<usize> {
+
+    let mut position = offset;
+    loop {
+        let length = *message.get(position)?;
+        match length {
+            0 => return Some(position + 1),
+            _ if length & 0xc0 == 0xc0 => return Some(position + 2),
+            _ => position += 1 + usize::from(length)
+        }
+    }
+}
+
+/**
+ * Parse a NBSTAT response and return the workstation name of the host: the
+ * first unique name registered with the 0x00 suffix (group names are domain
+ * or workgroup names). Padding spaces are removed.
+ */
+pub fn parse_nbstat_response(message: &[u8]) -> Option<String> {
+
+    let question_count = read_u16(message, 4)?;
+    let answer_count = read_u16(message, 6)?;
+    if answer_count == 0 {
+        return None;
+    }
+
+    let mut offset = NBSTAT_HEADER_SIZE;
+    for _ in 0..question_count {
+        offset = skip_name(message, offset)? + 4;
+    }
+
+    let name_end = skip_name(message, offset)?;
+    if read_u16(message, name_end)? != NBSTAT_TYPE {
+        return None;
+    }
+    let data_offset = name_end + 10;
+    let name_count = usize::from(*message.get(data_offset)?);
+
+    (0..name_count).find_map(|name_index| {
+        let entry_offset = data_offset + 1 + name_index * NBSTAT_NAME_ENTRY_SIZE;
+        let entry = message.get(entry_offset..entry_offset + NBSTAT_NAME_ENTRY_SIZE)?;
+        let flags = u16::from_be_bytes([entry[16], entry[17]]);
+        let name = String::from_utf8_lossy(&entry[..15]).trim_end().to_string();
+        match entry[15] == NETBIOS_WORKSTATION_SUFFIX && flags & NETBIOS_GROUP_FLAG == 0 && !name.is_empty() {
+            true => Some(name),
+            false => None
+        }
+    })
+}
+
+/**
+ * Resolve the NetBIOS names of several IPv4 addresses. NBSTAT queries are sent
+ * to all hosts at once from a single socket, and answers are collected until
+ * the timeout is reached (or all hosts answered), so that silent hosts do not
+ * add a timeout each.
+ */
+pub fn resolve_names(ipv4s: &[Ipv4Addr], timeout_ms: u64) -> Result<HashMap<Ipv4Addr, String>, String> {
+
+    let mut names: HashMap<Ipv4Addr, String> = HashMap::new();
+    if ipv4s.is_empty() {
+        return Ok(names);
+    }
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|err| format!("Could not open NetBIOS socket ({})", err))?;
+    socket.set_read_timeout(Some(Duration::from_millis(NETBIOS_RECEIVE_STEP_MS))).map_err(|err| format!("Could not configure NetBIOS socket ({})", err))?;
+
+    for (query_index, ipv4) in ipv4s.iter().enumerate() {
+        let query = build_nbstat_query(query_index as u16);
+        socket.send_to(&query, SocketAddrV4::new(*ipv4, NETBIOS_NAME_PORT)).map_err(|err| format!("Could not send NetBIOS query ({})", err))?;
+    }
+
+    let started_at = Instant::now();
+    let mut buffer = [0u8; 1500];
+    while started_at.elapsed() < Duration::from_millis(timeout_ms) && names.len() < ipv4s.len() {
+
+        let (received_size, source) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(_) => continue
+        };
+        if let IpAddr::V4(source_ipv4) = source.ip() {
+            if let Some(name) = ipv4s.contains(&source_ipv4).then(|| parse_nbstat_response(&buffer[..received_size])).flatten() {
+                names.entry(source_ipv4).or_insert(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn should_build_nbstat_query() {
+
+        let query = build_nbstat_query(0x1234);
+
+        assert_eq!(query.len(), 50);
+        assert_eq!(&query[0..12], &[0x12, 0x34, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&query[12..15], &[0x20, b'C', b'K']);
+        assert_eq!(&query[15..45], "A".repeat(30).as_bytes());
+        assert_eq!(&query[45..], &[0, 0x00, 0x21, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn should_parse_nbstat_response_fixture() {
+
+        let response = fs::read("./data/netbios-nbstat-response.bin").unwrap();
+
+        assert_eq!(parse_nbstat_response(&response), Some("DESKTOP-4F2K9Q".to_string()));
+    }
+
+    #[test]
+    fn should_ignore_truncated_nbstat_response() {
+
+        let response = fs::read("./data/netbios-nbstat-response.bin").unwrap();
+
+        assert_eq!(parse_nbstat_response(&response[..60]), None);
+        assert_eq!(parse_nbstat_response(&build_nbstat_query(1)), None);
+    }
+
+    #[test]
+    fn should_skip_group_names() {
+
+        let mut response = fs::read("./data/netbios-nbstat-response.bin").unwrap();
+
+        // Flag the workstation name as a group name, no unique name is left
+        // with the workstation suffix.
+        let first_entry_flags = NBSTAT_HEADER_SIZE + 34 + 10 + 1 + 16;
+        response[first_entry_flags] |= 0x80;
+        assert_eq!(parse_nbstat_response(&response), None);
+    }
+
+}
@@ -1,35 +1,57 @@
-use std::process;
+use std::fs;
 use std::net::{IpAddr, Ipv4Addr};
-use std::time::Instant;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::io::ErrorKind::TimedOut;
+use std::slice;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::io::ErrorKind;
 use std::convert::TryInto;
+use std::sync::mpsc::Sender;
 
+use chrono::{DateTime, FixedOffset, Local};
 use dns_lookup::lookup_addr;
-use ipnetwork::IpNetwork;
+use ipnetwork::{IpNetwork, Ipv4Network};
 use pnet_datalink::{MacAddr, NetworkInterface, DataLinkSender, DataLinkReceiver};
-use pnet::packet::{MutablePacket, Packet};
+use pnet::packet::MutablePacket;
 use pnet::packet::ethernet::{EthernetPacket, MutableEthernetPacket, EtherTypes};
-use pnet::packet::arp::{MutableArpPacket, ArpOperations, ArpHardwareTypes, ArpPacket};
-use pnet::packet::vlan::{ClassOfService, MutableVlanPacket};
+use pnet::packet::arp::{MutableArpPacket, ArpOperation, ArpOperations, ArpHardwareTypes, ArpPacket};
+use pnet::packet::vlan::{ClassOfService, MutableVlanPacket, VlanPacket};
 use rand::prelude::*;
 
 use crate::args::ScanOptions;
-use crate::vendor::Vendor;
+use crate::capture::CapturedFrame;
+use crate::datalink::DataLink;
+use crate::error::ScanError;
+use crate::resolve::{self, ResolverChain};
+use crate::vendor::VendorDatabase;
 use crate::utils;
 use crate::args::ScanTiming;
 
 pub const DATALINK_RCV_TIMEOUT: u64 = 500;
 
-const VLAN_QOS_DEFAULT: u8 = 1;
 const ARP_PACKET_SIZE: usize = 28;
 const VLAN_PACKET_SIZE: usize = 32;
 
 const ETHERNET_STD_PACKET_SIZE: usize = 42;
 const ETHERNET_VLAN_PACKET_SIZE: usize = 46;
 
+// ARP probe timings from RFC 5227 (section 1.1), all durations are given in
+// milliseconds.
+pub const PROBE_WAIT_MS: u64 = 1000;
+pub const PROBE_NUM: usize = 3;
+pub const PROBE_MIN_MS: u64 = 1000;
+pub const PROBE_MAX_MS: u64 = 2000;
+pub const ANNOUNCE_WAIT_MS: u64 = 2000;
+
+pub const NEIGHBOR_TABLE_PATH: &str = "/proc/net/arp";
+
+// Kernel ARP entry flag for completed entries (see 'ATF_COM' in if_arp.h),
+// incomplete entries have no usable MAC address.
+const NEIGHBOR_FLAG_COMPLETE: u32 = 0x02;
+
 /**
  * Contains scan estimation records. This will be computed before the scan
  * starts and should give insights about the scan.
@@ -43,66 +65,787 @@ pub struct ScanEstimation {
 
 /**
  * Gives high-level details about the scan response. This may include Ethernet
- * details (packet count, size, ...) and other technical network aspects.
+ * details (packet count, size, ...) and other technical network aspects. Hosts
+ * answering for an IPv4 that was not targeted are kept apart from the results
+ * as unsolicited hosts.
  */
+#[derive(Clone, Default)]
 pub struct ResponseSummary {
     pub packet_count: usize,
     pub arp_count: usize,
-    pub duration_ms: u128
+    pub duration_ms: u128,
+    pub responses_per_attempt: Vec<usize>,
+    pub requests_sent: usize,
+    pub send_errors: usize,
+    pub send_duration_ms: u128,
+    pub truncated: bool,
+    pub host_limit_reached: bool,
+    pub cancelled: bool,
+    pub unsolicited_hosts: Vec<TargetDetails>
+}
+
+impl ResponseSummary {
+
+    /**
+     * Compute the achieved ARP request rate (packets per second), between the
+     * first and the last sent request. Failed sends are paced as any other
+     * request, they are therefore counted as well.
+     */
+    pub fn achieved_send_rate(&self) -> Option<f64> {
+
+        let attempt_count = self.requests_sent + self.send_errors;
+        match attempt_count > 1 && self.send_duration_ms > 0 {
+            true => Some((attempt_count - 1) as f64 * 1000_f64 / self.send_duration_ms as f64),
+            false => None
+        }
+    }
+
+    /**
+     * Merge the response summary of another interface scan. Interfaces are
+     * scanned one after another, durations & counters are therefore added.
+     */
+    pub fn merge(&mut self, other: ResponseSummary) {
+
+        self.packet_count += other.packet_count;
+        self.arp_count += other.arp_count;
+        self.duration_ms += other.duration_ms;
+        self.requests_sent += other.requests_sent;
+        self.send_errors += other.send_errors;
+        self.send_duration_ms += other.send_duration_ms;
+        self.truncated = self.truncated || other.truncated;
+        self.host_limit_reached = self.host_limit_reached || other.host_limit_reached;
+        self.cancelled = self.cancelled || other.cancelled;
+        self.unsolicited_hosts.extend(other.unsolicited_hosts);
+
+        for (index, response_count) in other.responses_per_attempt.into_iter().enumerate() {
+            match self.responses_per_attempt.get_mut(index) {
+                Some(total_count) => *total_count += response_count,
+                None => self.responses_per_attempt.push(response_count)
+            }
+        }
+    }
+}
+
+/**
+ * Identifies a scan with its start date and network context (interface, source
+ * IPv4 and target networks). This is embedded in exports for archiving.
+ */
+pub struct ScanMetadata {
+    pub started_at: DateTime<FixedOffset>,
+    pub interface_name: String,
+    pub interface_mac: Option<MacAddr>,
+    pub source_ipv4: Ipv4Addr,
+    pub target_networks: Vec<IpNetwork>
+}
+
+impl ScanMetadata {
+
+    /**
+     * Scans performed on several interfaces are identified by all interface
+     * names, while the MAC address is the one of the first interface.
+     */
+    pub fn new(interfaces: &[&NetworkInterface], ip_networks: &[&IpNetwork], source_ipv4: Ipv4Addr) -> ScanMetadata {
+
+        let interface_names: Vec<&str> = interfaces.iter().map(|interface| interface.name.as_str()).collect();
+
+        ScanMetadata {
+            started_at: Local::now().fixed_offset(),
+            interface_name: interface_names.join(","),
+            interface_mac: interfaces.first().and_then(|interface| interface.mac),
+            source_ipv4,
+            target_networks: ip_networks.iter().map(|network| *(*network)).collect()
+        }
+    }
 }
 
 /**
  * A target detail represents a single host on the local network with an IPv4
  * address and a linked MAC address. Hostnames are optional since some hosts
  * does not respond to the resolve call (or the numeric mode may be enabled).
+ * The response time is only known for hosts that answered to a sent request.
+ * The interface is the network interface that received the host answer, and
+ * the VLAN identifier is set for 802.1Q scans. The origin is only set when
+ * the kernel ARP cache is included in the results, and the hostname source
+ * only when a fallback resolution (mDNS or NetBIOS) is enabled. The MAC
+ * address is the ARP sender hardware address, while the Ethernet source of
+ * the answering frame is kept apart for hosts seen in ARP packets. The reply
+ * count includes the duplicate answers of a host (retransmissions, mirrored
+ * ports, ...), only the first answer sets the other details.
  */
+#[derive(Clone, Debug)]
 pub struct TargetDetails {
     pub ipv4: Ipv4Addr,
     pub mac: MacAddr,
     pub hostname: Option<String>,
-    pub vendor: Option<String>
+    pub vendor: Option<String>,
+    pub response_time_ms: Option<u64>,
+    pub interface: String,
+    pub vlan_id: Option<u16>,
+    pub frame_src_mac: Option<MacAddr>,
+    pub reply_count: usize,
+    pub verify_status: Option<VerifyStatus>,
+    pub origin: Option<HostOrigin>,
+    pub hostname_source: Option<HostnameSource>,
+    pub hostname_ascii: Option<String>,
+    pub virtualization: Option<String>,
+    pub note: Option<String>
+}
+
+impl TargetDetails {
+
+    /**
+     * An answer sent from another Ethernet address than its ARP sender
+     * hardware address is a telltale of proxy ARP (a router answering on
+     * behalf of a host) or of a middlebox rewriting frames.
+     */
+    pub fn is_proxy_arp_suspect(&self) -> bool {
+
+        self.frame_src_mac.is_some_and(|frame_src_mac| frame_src_mac != self.mac)
+    }
+
+    /**
+     * Host details of a test answer received on 'eth0', without any
+     * enrichment. Tests override the fields they need with the struct update
+     * syntax.
+     */
+    #[cfg(test)]
+    pub fn for_test(ipv4: Ipv4Addr, mac: MacAddr) -> TargetDetails {
+
+        build_target_details(ipv4, mac, "eth0", None)
+    }
+}
+
+/**
+ * Method that produced the hostname of a host: a static entry of a hosts
+ * file, a reverse DNS lookup (PTR request to the system resolver), an mDNS
+ * query on the local network, a NetBIOS name query or a DHCP lease.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostnameSource {
+    HostsFile,
+    Dns,
+    Mdns,
+    Netbios,
+    Dhcp
+}
+
+impl HostnameSource {
+
+    pub fn describe(&self) -> String {
+
+        match self {
+            HostnameSource::HostsFile => "hosts".to_string(),
+            HostnameSource::Dns => "dns".to_string(),
+            HostnameSource::Mdns => "mdns".to_string(),
+            HostnameSource::Netbios => "netbios".to_string(),
+            HostnameSource::Dhcp => "dhcp".to_string()
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<HostnameSource> {
+
+        [HostnameSource::HostsFile, HostnameSource::Dns, HostnameSource::Mdns, HostnameSource::Netbios, HostnameSource::Dhcp].into_iter().find(|source| source.describe() == name)
+    }
+}
+
+/**
+ * Merge the kernel ARP cache entries of an interface with the hosts that
+ * answered the scan. Answering hosts are marked with the scan origin, while
+ * cached entries are only added for IPv4 addresses that did not answer.
+ */
+pub fn merge_cached_neighbors(target_details: Vec<TargetDetails>, cached_neighbors: &[NeighborEntry], interface_name: &str) -> Vec<TargetDetails> {
+
+    let answered_ipv4s: HashSet<Ipv4Addr> = target_details.iter().map(|detail| detail.ipv4).collect();
+    let mut merged_details: Vec<TargetDetails> = target_details.into_iter().map(|mut detail| {
+        detail.origin = Some(HostOrigin::Scan);
+        detail
+    }).collect();
+
+    for neighbor in cached_neighbors.iter().filter(|neighbor| !answered_ipv4s.contains(&neighbor.ipv4)) {
+        merged_details.push(TargetDetails {
+            origin: Some(HostOrigin::Cache),
+            ..build_target_details(neighbor.ipv4, neighbor.mac, interface_name, None)
+        });
+    }
+    merged_details
+}
+
+/**
+ * An IPv4 address that has been answered for by several MAC addresses during
+ * the scan. This may come from ARP spoofing or a misconfigured failover pair.
+ */
+#[derive(Debug, PartialEq)]
+pub struct AddressConflict {
+    pub ipv4: Ipv4Addr,
+    pub vlan_id: Option<u16>,
+    pub macs: Vec<MacAddr>
+}
+
+/**
+ * Find the IPv4 addresses answered by several distinct MAC addresses. Each
+ * VLAN is a distinct network, the same IPv4 seen on two VLANs is therefore
+ * not a conflict. The conflicts are sorted by IPv4 address & VLAN, with
+ * sorted MAC addresses.
+ */
+pub fn find_address_conflicts(target_details: &[TargetDetails]) -> Vec<AddressConflict> {
+
+    let mut macs_by_ipv4: HashMap<(Ipv4Addr, Option<u16>), Vec<MacAddr>> = HashMap::new();
+    for detail in target_details.iter() {
+        let macs = macs_by_ipv4.entry((detail.ipv4, detail.vlan_id)).or_default();
+        if !macs.contains(&detail.mac) {
+            macs.push(detail.mac);
+        }
+    }
+
+    let mut conflicts: Vec<AddressConflict> = macs_by_ipv4.into_iter()
+        .filter(|(_, macs)| macs.len() > 1)
+        .map(|((ipv4, vlan_id), mut macs)| {
+            macs.sort();
+            AddressConflict { ipv4, vlan_id, macs }
+        })
+        .collect();
+    conflicts.sort_by_key(|conflict| (conflict.ipv4, conflict.vlan_id));
+    conflicts
+}
+
+/**
+ * A MAC address answering for several IPv4 addresses, which is common with
+ * virtual machines, virtual IPs or NAT boxes.
+ */
+#[derive(Debug, PartialEq)]
+pub struct SharedMac {
+    pub mac: MacAddr,
+    pub ipv4s: Vec<Ipv4Addr>
+}
+
+/**
+ * Find the MAC addresses answering for several distinct IPv4 addresses. The
+ * results are sorted by MAC address, with sorted IPv4 addresses.
+ */
+pub fn find_shared_macs(target_details: &[TargetDetails]) -> Vec<SharedMac> {
+
+    let mut ipv4s_by_mac: HashMap<MacAddr, Vec<Ipv4Addr>> = HashMap::new();
+    for detail in target_details.iter() {
+        let ipv4s = ipv4s_by_mac.entry(detail.mac).or_default();
+        if !ipv4s.contains(&detail.ipv4) {
+            ipv4s.push(detail.ipv4);
+        }
+    }
+
+    let mut shared_macs: Vec<SharedMac> = ipv4s_by_mac.into_iter()
+        .filter(|(_, ipv4s)| ipv4s.len() > 1)
+        .map(|(mac, mut ipv4s)| {
+            ipv4s.sort();
+            SharedMac { mac, ipv4s }
+        })
+        .collect();
+    shared_macs.sort_by_key(|shared_mac| shared_mac.mac);
+    shared_macs
+}
+
+/**
+ * The hosts answering with a single MAC address (routers, VIP owners, VMs with
+ * several addresses, ...), with the vendor of the MAC and the distinct
+ * hostnames of its IPv4 addresses.
+ */
+#[derive(Debug, PartialEq)]
+pub struct MacGroup {
+    pub mac: MacAddr,
+    pub vendor: Option<String>,
+    pub ipv4s: Vec<Ipv4Addr>,
+    pub hostnames: Vec<String>
+}
+
+/**
+ * Group the hosts by MAC address. Groups are sorted by MAC address, with
+ * sorted IPv4 addresses and hostnames in the order of their IPv4 address.
+ */
+pub fn group_hosts_by_mac(target_details: &[TargetDetails]) -> Vec<MacGroup> {
+
+    let mut sorted_details: Vec<&TargetDetails> = target_details.iter().collect();
+    sorted_details.sort_by_key(|detail| (detail.mac, detail.ipv4));
+
+    let mut mac_groups: Vec<MacGroup> = vec![];
+    for detail in sorted_details {
+        if mac_groups.last().map(|group| group.mac) != Some(detail.mac) {
+            mac_groups.push(MacGroup { mac: detail.mac, vendor: None, ipv4s: vec![], hostnames: vec![] });
+        }
+        if let Some(group) = mac_groups.last_mut() {
+            if group.vendor.is_none() {
+                group.vendor = detail.vendor.clone();
+            }
+            if !group.ipv4s.contains(&detail.ipv4) {
+                group.ipv4s.push(detail.ipv4);
+            }
+            if let Some(hostname) = detail.hostname.as_ref().filter(|hostname| !group.hostnames.contains(hostname)) {
+                group.hostnames.push(hostname.clone());
+            }
+        }
+    }
+    mac_groups
+}
+
+/**
+ * Result of the unicast verification of a kernel neighbor entry. A neighbor
+ * answering with another MAC than the recorded one keeps the kernel MAC, since
+ * this may indicate ARP spoofing.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VerifyStatus {
+    Alive,
+    Stale,
+    Mismatch(MacAddr)
+}
+
+impl VerifyStatus {
+
+    pub fn describe(&self) -> String {
+
+        match self {
+            VerifyStatus::Alive => "alive".to_string(),
+            VerifyStatus::Stale => "stale".to_string(),
+            VerifyStatus::Mismatch(kernel_mac) => format!("mismatch (kernel {})", kernel_mac)
+        }
+    }
+}
+
+/**
+ * Type of a MAC address, based on its first octet: multicast addresses have
+ * the I/G bit set, while locally administered addresses (such as randomized
+ * MACs of phones) have the U/L bit set. Other addresses are universally
+ * administered, their vendor can be found in the IEEE registries.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MacType {
+    Universal,
+    Local,
+    Multicast
+}
+
+impl MacType {
+
+    pub fn classify(mac: &MacAddr) -> MacType {
+
+        match mac.0 {
+            first_octet if first_octet & 0x01 != 0 => MacType::Multicast,
+            first_octet if first_octet & 0x02 != 0 => MacType::Local,
+            _ => MacType::Universal
+        }
+    }
+
+    pub fn describe(&self) -> String {
+
+        match self {
+            MacType::Universal => "universal".to_string(),
+            MacType::Local => "local".to_string(),
+            MacType::Multicast => "multicast".to_string()
+        }
+    }
+}
+
+/**
+ * Origin of a host in the results: either an answer to the scan, or an entry
+ * of the kernel ARP cache that did not answer the scan.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostOrigin {
+    Scan,
+    Cache
+}
+
+impl HostOrigin {
+
+    pub fn describe(&self) -> String {
+
+        match self {
+            HostOrigin::Scan => "scan".to_string(),
+            HostOrigin::Cache => "cache".to_string()
+        }
+    }
+}
+
+/**
+ * A complete entry of the kernel neighbor table, with the MAC address the
+ * kernel associates to the IPv4 address and the interface (device) name.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct NeighborEntry {
+    pub ipv4: Ipv4Addr,
+    pub mac: MacAddr,
+    pub device: String
+}
+
+/**
+ * Parse the content of the kernel neighbor table ('/proc/net/arp' format,
+ * with a header line). Incomplete entries and malformed lines are skipped.
+ */
+pub fn parse_neighbor_table(content: &str) -> Vec<NeighborEntry> {
+
+    content.lines().skip(1).filter_map(|line| {
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 6 {
+            return None;
+        }
+
+        let flags = u32::from_str_radix(fields[2].trim_start_matches("0x"), 16).ok()?;
+        let mac = fields[3].parse::<MacAddr>().ok()?;
+        if flags & NEIGHBOR_FLAG_COMPLETE == 0 || mac == MacAddr::zero() {
+            return None;
+        }
+
+        Some(NeighborEntry {
+            ipv4: fields[0].parse::<Ipv4Addr>().ok()?,
+            mac,
+            device: fields[5].to_string()
+        })
+    }).collect()
+}
+
+/**
+ * Read the kernel neighbor table, which is only available on Linux.
+ */
+pub fn read_neighbor_table(path: &str) -> Result<Vec<NeighborEntry>, String> {
+
+    fs::read_to_string(path).map(|content| parse_neighbor_table(&content)).map_err(|err| {
+        format!("Could not read neighbor table {} - {}", path, err)
+    })
+}
+
+/**
+ * Compare the hosts that answered the unicast verification with the kernel
+ * neighbor entries of an interface. Answers from hosts outside the neighbor
+ * table are dropped, while silent neighbors are added as stale entries. A
+ * neighbor answered by several MAC addresses keeps one entry for each MAC.
+ */
+pub fn apply_verify_statuses(target_details: Vec<TargetDetails>, neighbors: &[NeighborEntry], interface_name: &str) -> Vec<TargetDetails> {
+
+    let mut verified_details: Vec<TargetDetails> = vec![];
+    let mut answered_details: HashMap<Ipv4Addr, Vec<TargetDetails>> = HashMap::new();
+    for detail in target_details {
+        answered_details.entry(detail.ipv4).or_default().push(detail);
+    }
+
+    for neighbor in neighbors.iter() {
+        match answered_details.remove(&neighbor.ipv4) {
+            Some(details) => {
+                for mut detail in details {
+                    detail.verify_status = match detail.mac == neighbor.mac {
+                        true => Some(VerifyStatus::Alive),
+                        false => Some(VerifyStatus::Mismatch(neighbor.mac))
+                    };
+                    verified_details.push(detail);
+                }
+            },
+            None => verified_details.push(TargetDetails {
+                verify_status: Some(VerifyStatus::Stale),
+                ..build_target_details(neighbor.ipv4, neighbor.mac, interface_name, None)
+            })
+        }
+    }
+    verified_details
+}
+
+/**
+ * The IPv4 addresses that already answered an ARP request, shared between the
+ * sending thread and the response thread so that retries skip these hosts.
+ */
+pub type RespondedHosts = Arc<Mutex<HashSet<Ipv4Addr>>>;
+
+/**
+ * Check if a host already answered during the scan, retries should only be
+ * sent to the hosts that did not answer yet.
+ */
+pub fn has_responded(responded_hosts: &RespondedHosts, ipv4: Ipv4Addr) -> bool {
+
+    responded_hosts.lock().map(|hosts| hosts.contains(&ipv4)).unwrap_or(false)
 }
 
 /**
- * Compute a network configuration based on the scan options and available
- * interfaces. This configuration will be used in the scan process to target a
- * specific network on a network interfaces.
+ * Keeps track of the last ARP request sent to each target IPv4 address. This
+ * is shared between the sending thread and the response thread, in order to
+ * compute the response time of each host.
+ */
+pub type RequestTimes = Arc<Mutex<HashMap<Ipv4Addr, Instant>>>;
+
+/**
+ * Shared state between the main thread (sending ARP requests) and the response
+ * thread: the stop flag of the response thread, the request times and the
+ * hosts that already answered. The state is reset before each interface scan,
+ * except the count of hosts found which is kept for the whole scan round.
  */
-pub fn compute_network_configuration<'a>(interfaces: &'a [NetworkInterface], scan_options: &'a Arc<ScanOptions>) -> (&'a NetworkInterface, Vec<&'a IpNetwork>) {
+#[derive(Clone, Default)]
+pub struct ScanState {
+    pub timed_out: Arc<AtomicBool>,
+    pub request_times: RequestTimes,
+    pub responded_hosts: RespondedHosts,
+    pub found_hosts: Arc<AtomicUsize>,
+    pub cancellation: CancellationToken
+}
+
+impl ScanState {
 
-    let interface_name = match &scan_options.interface_name {
-        Some(name) => String::from(name),
-        None => {
+    pub fn reset_round(&self) {
+
+        self.reset();
+        self.found_hosts.store(0, Ordering::Relaxed);
+    }
+
+    /**
+     * Check if the maximum amount of hosts has been found during the current
+     * scan round, in which case both the sender and the response thread stop.
+     */
+    pub fn has_reached_host_limit(&self, max_hosts: Option<usize>) -> bool {
+
+        max_hosts.is_some_and(|max_hosts| self.found_hosts.load(Ordering::Relaxed) >= max_hosts)
+    }
+
+    pub fn reset(&self) {
+
+        self.timed_out.store(false, Ordering::Relaxed);
+        if let Ok(mut times) = self.request_times.lock() {
+            times.clear();
+        }
+        if let Ok(mut hosts) = self.responded_hosts.lock() {
+            hosts.clear();
+        }
+    }
+}
+
+/**
+ * Cancel a running scan from another thread (or from a signal handler). The
+ * sender stops between two requests and the response thread stops on its
+ * next iteration, the scan then returns the partial results collected so far.
+ * Clones share the same cancellation state, and a cancelled token can not be
+ * reset. Child tokens are cancelled with their parent, but cancelling a child
+ * leaves its parent untouched.
+ */
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    parent: Option<Arc<CancellationToken>>
+}
+
+impl CancellationToken {
+
+    pub fn new() -> Self {
+
+        CancellationToken::default()
+    }
+
+    pub fn child_token(&self) -> Self {
+
+        CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            parent: Some(Arc::new(self.clone()))
+        }
+    }
+
+    pub fn cancel(&self) {
+
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+
+        self.cancelled.load(Ordering::Relaxed) || self.parent.as_ref().is_some_and(|parent| parent.is_cancelled())
+    }
+}
+
+/**
+ * Identify a single scan pass: the receiving interface, the ARP sender
+ * addresses used in outgoing packets and the scanned VLAN (if any). New hosts
+ * are sent to the host sender, given to the host callback and sent as scan
+ * events as soon as they answer, when streaming. Without targets, every
+ * answer is recorded (passive scans for example).
+ */
+pub struct ScanPass {
+    pub interface_name: String,
+    pub own_mac: Option<MacAddr>,
+    pub source_ipv4: Ipv4Addr,
+    pub vlan_id: Option<u16>,
+    pub host_sender: Option<Sender<TargetDetails>>,
+    pub host_callback: Option<HostCallback>,
+    pub event_sender: Option<Sender<ScanEvent>>,
+    pub frame_filter: Option<FrameFilter>,
+    pub resolvers: Option<ResolverChain>,
+    pub targets: Option<ScanTargets>
+}
+
+impl ScanPass {
+
+    /**
+     * Check if new hosts are reported while the scan runs, in which case
+     * they are enriched (vendor, hostname, ...) as soon as they answer.
+     */
+    pub fn reports_new_hosts(&self) -> bool {
+
+        self.host_sender.is_some() || self.host_callback.is_some() || self.event_sender.is_some()
+    }
+}
+
+/**
+ * The IPv4 addresses requested by a scan pass: the scanned networks without
+ * the excluded networks, or the verified neighbors only. Answers for other
+ * addresses are unsolicited.
+ */
+#[derive(Clone, Default)]
+pub struct ScanTargets {
+    pub networks: Vec<IpNetwork>,
+    pub excluded_networks: Vec<Ipv4Network>,
+    pub ipv4s: Option<HashSet<Ipv4Addr>>
+}
+
+impl ScanTargets {
+
+    pub fn contains(&self, ipv4: Ipv4Addr) -> bool {
+
+        match &self.ipv4s {
+            Some(ipv4s) => ipv4s.contains(&ipv4),
+            None => self.networks.iter().any(|network| network.contains(IpAddr::V4(ipv4))) && !is_excluded_target(ipv4, &self.excluded_networks)
+        }
+    }
+}
+
+/**
+ * Events of a scan round, sent to the channel given to the scanner builder
+ * (see 'ArpScannerBuilder::events'). Events are sent in this order for each
+ * round: 'Started', then 'PacketSent' for each request ('RetryRound' before
+ * the requests of each retry), and 'Finished' once the round is done.
+ * 'HostFound' events are sent by the response thread as soon as a new host
+ * answers, between the first request and 'Finished'. Other events are sent by
+ * the thread running the scan round. Events are sent without blocking the
+ * scan, they are dropped if the receiver has been closed.
+ */
+#[derive(Clone)]
+pub enum ScanEvent {
+    Started { targets: u128 },
+    PacketSent { ip: Ipv4Addr },
+    HostFound(TargetDetails),
+    RetryRound(u8),
+    Finished(ResponseSummary)
+}
+
+/**
+ * A frame received during a scan pass, given to the frame filter with its
+ * Ethernet layer and its ARP layer (if the frame holds an ARP packet for the
+ * scanned VLAN).
+ */
+pub struct ReceivedFrame<'a> {
+    pub ethernet: EthernetPacket<'a>,
+    pub arp: Option<ArpPacket<'a>>,
+    pub interface_name: &'a str,
+    pub vlan_id: Option<u16>
+}
+
+impl ReceivedFrame<'_> {
+
+    /**
+     * Build the details of a host seen in this frame, for frames accepted by
+     * a frame filter. The vendor and hostname are added by the scan.
+     */
+    pub fn target_details(&self, ipv4: Ipv4Addr, mac: MacAddr) -> TargetDetails {
+
+        TargetDetails {
+            frame_src_mac: Some(self.ethernet.get_source()),
+            ..build_target_details(ipv4, mac, self.interface_name, self.vlan_id)
+        }
+    }
+}
+
+/**
+ * Decision of a frame filter: record a host from the frame, or ignore it.
+ */
+pub enum FrameVerdict {
+    Accept(TargetDetails),
+    Ignore
+}
+
+/**
+ * Filter of received frames, see 'ArpScannerBuilder::frame_filter'.
+ */
+pub type FrameFilter = Arc<Mutex<dyn FnMut(&ReceivedFrame, Option<TargetDetails>) -> FrameVerdict + Send>>;
+
+/**
+ * Callback invoked with each new host, see 'ArpScannerBuilder::on_host_found'.
+ */
+pub type HostCallback = Arc<Mutex<dyn FnMut(&TargetDetails) + Send>>;
+
+/**
+ * Compute the network configurations based on the scan options and available
+ * interfaces. These configurations will be used in the scan process to target
+ * specific networks on each selected network interface. All selected
+ * interfaces are verified before the scan starts.
+ */
+pub fn compute_network_configuration<'a>(interfaces: &'a [NetworkInterface], scan_options: &'a ScanOptions) -> Result<Vec<(&'a NetworkInterface, Vec<&'a IpNetwork>)>, ScanError> {
+
+    let interface_names = match scan_options.interface_names.is_empty() {
+        false => scan_options.interface_names.clone(),
+        true => {
 
             let name = utils::select_default_interface(interfaces).map(|interface| interface.name);
 
             match name {
-                Some(name) => name,
-                None => {
-                    eprintln!("Could not find a default network interface");
-                    eprintln!("Use 'arp scan -l' to list available interfaces");
-                    process::exit(1);
-                }
+                Some(name) => vec![name],
+                None => return Err(ScanError::Interface("Could not find a default network interface\nUse 'arp scan -l' to list available interfaces".to_string()))
             }
         }
     };
 
-    let selected_interface: &NetworkInterface = interfaces.iter()
-        .find(|interface| { interface.name == interface_name && interface.is_up() && !interface.is_loopback() })
-        .unwrap_or_else(|| {
-            eprintln!("Could not find interface with name {}", interface_name);
-            eprintln!("Make sure the interface is up, not loopback and has a valid IPv4");
-            process::exit(1);
-        });
+    interface_names.iter().map(|interface_name| {
 
-    let ip_networks: Vec<&IpNetwork> = match &scan_options.network_range {
-        Some(network_range) => network_range.iter().collect(),
-        None => selected_interface.ips.iter()
-            .filter(|ip_network| ip_network.is_ipv4())
-            .collect()
-    };
+        let selected_interface: &NetworkInterface = interfaces.iter()
+            .find(|interface| { &interface.name == interface_name && interface.is_up() && !interface.is_loopback() })
+            .ok_or_else(|| ScanError::InterfaceNotFound(interface_name.clone()))?;
+
+        let ip_networks: Vec<&IpNetwork> = match &scan_options.network_range {
+            Some(network_range) => network_range.iter().collect(),
+            None => selected_interface.ips.iter()
+                .filter(|ip_network| ip_network.is_ipv4())
+                .collect()
+        };
+
+        Ok((selected_interface, ip_networks))
+
+    }).collect()
+}
+
+/**
+ * Compute the network configuration of all eligible interfaces (up, not
+ * loopback, with a MAC and an IPv4 address). Each interface will target its
+ * directly attached IPv4 networks.
+ */
+pub fn compute_all_interfaces_configuration(interfaces: &[NetworkInterface]) -> Result<Vec<(&NetworkInterface, Vec<&IpNetwork>)>, ScanError> {
+
+    let configurations: Vec<(&NetworkInterface, Vec<&IpNetwork>)> = interfaces.iter()
+        .filter(|interface| utils::is_eligible_interface(interface))
+        .map(|interface| (interface, interface.ips.iter().filter(|ip_network| ip_network.is_ipv4()).collect()))
+        .collect();
 
-    (selected_interface, ip_networks)
+    match configurations.is_empty() {
+        true => Err(ScanError::Interface("Could not find any eligible network interface\nUse 'arp scan -l' to list available interfaces".to_string())),
+        false => Ok(configurations)
+    }
+}
+
+/**
+ * Sending & receiving ends of an Ethernet datalink channel.
+ */
+pub type EthernetChannel = (Box<dyn DataLinkSender>, Box<dyn DataLinkReceiver>);
+
+/**
+ * Open an Ethernet datalink channel on a network interface. Permission errors
+ * are reported on their own, since raw sockets require root permissions (or
+ * the CAP_NET_RAW capability).
+ */
+pub fn open_ethernet_channel(interface: &NetworkInterface, config: pnet_datalink::Config) -> Result<EthernetChannel, ScanError> {
+
+    match pnet_datalink::channel(interface, config) {
+        Ok(pnet_datalink::Channel::Ethernet(tx, rx)) => Ok((tx, rx)),
+        Ok(_) => Err(ScanError::Interface(format!("Expected an Ethernet datalink channel on {}", interface.name))),
+        Err(error) if error.kind() == ErrorKind::PermissionDenied => Err(ScanError::PermissionDenied),
+        Err(error) => Err(ScanError::Channel(error))
+    }
 }
 
 /**
@@ -117,7 +860,9 @@ pub fn compute_scan_estimation(host_count: u128, options: &Arc<ScanOptions>) ->
         true => ETHERNET_VLAN_PACKET_SIZE.try_into().expect("Internal number conversion failed for VLAN packet size"),
         false => ETHERNET_STD_PACKET_SIZE.try_into().expect("Internal number conversion failed for Ethernet packet size")
     };
-    let retry_count: u128 = options.retry_count.try_into().unwrap_or_else(|err| {
+    // Retries are only sent to unanswered hosts, the estimation is therefore
+    // based on the worst case (no host answering).
+    let attempt_count: u128 = (options.retry_count + 1).try_into().unwrap_or_else(|err| {
         eprintln!("[warn] Could not cast retry count, defaults to 1 - {}", err);
         1
     });
@@ -129,6 +874,9 @@ pub fn compute_scan_estimation(host_count: u128, options: &Arc<ScanOptions>) ->
 
     let request_size: u128 = host_count * packet_size;
 
+    // Without any host to scan (such as with a fully excluded range), the
+    // divisions below fall back to a null interval or bandwidth.
+
     // Either the user provides an interval (expressed in milliseconds), either
     // he provides a bandwidth (in bits per second) or either we are using the
     // default interval. The goal of the code below is to compute the interval
@@ -138,23 +886,31 @@ pub fn compute_scan_estimation(host_count: u128, options: &Arc<ScanOptions>) ->
         ScanTiming::Bandwidth(bandwidth) => {
 
             let bandwidth_lg: u128 = bandwidth.into();
-            let request_phase_ms: u128 = (request_size * 1000) as u128 / bandwidth_lg;
-            let interval_ms: u128 = (request_phase_ms/retry_count/host_count) - avg_arp_request_ms;
+            let request_phase_ms: u128 = (request_size * 1000) / bandwidth_lg;
+            let interval_ms: u128 = (request_phase_ms / attempt_count).checked_div(host_count).unwrap_or(0).saturating_sub(avg_arp_request_ms);
             
             (interval_ms.try_into().unwrap(), bandwidth_lg, request_phase_ms)
 
         },
+        ScanTiming::Rate(rate) => {
+
+            let rate_lg: u128 = rate.max(1).into();
+            let request_phase_ms: u128 = (host_count * attempt_count * 1000) / rate_lg;
+            let bandwidth = packet_size * rate_lg;
+
+            ((1000 / rate_lg).try_into().unwrap_or(0), bandwidth, request_phase_ms)
+        },
         ScanTiming::Interval(interval) => {
 
             let interval_ms_lg: u128 = interval.into();
-            let request_phase_ms: u128 = (host_count * (avg_arp_request_ms + interval_ms_lg)) * retry_count;
-            let bandwidth = (request_size * 1000) / request_phase_ms;
+            let request_phase_ms: u128 = (host_count * (avg_arp_request_ms + interval_ms_lg)) * attempt_count;
+            let bandwidth = (request_size * 1000).checked_div(request_phase_ms).unwrap_or(0);
 
             (interval, bandwidth, request_phase_ms)
         }
     };
     
-    let duration_ms = request_phase_ms + timeout + avg_resolve_ms;
+    let duration_ms = request_phase_ms + (timeout * attempt_count) + avg_resolve_ms;
 
     ScanEstimation {
         interval_ms,
@@ -168,64 +924,186 @@ pub fn compute_scan_estimation(host_count: u128, options: &Arc<ScanOptions>) ->
  * Send a single ARP request - using a datalink-layer sender, a given network
  * interface and a target IPv4 address. The ARP request will be broadcasted to
  * the whole local network with the first valid IPv4 address on the interface.
+ * Only frame building errors are returned, a request that could not be handed
+ * to the interface (such as on a congested NIC) is reported as not sent.
+ */
+pub fn send_arp_request(link: &dyn DataLink, interface: &NetworkInterface, source_ip: Ipv4Addr, target_ip: Ipv4Addr, vlan_id: Option<u16>, options: Arc<ScanOptions>) -> Result<bool, ScanError> {
+
+    let ethernet_buffer = build_arp_frame(interface, source_ip, target_ip, None, vlan_id, &options)?;
+    Ok(link.send(&ethernet_buffer).is_ok())
+}
+
+/**
+ * Send a unicast ARP request directly to the MAC address recorded for a
+ * neighbor, instead of broadcasting it to the whole local network.
+ */
+pub fn send_unicast_arp_request(link: &dyn DataLink, interface: &NetworkInterface, source_ip: Ipv4Addr, target_ip: Ipv4Addr, target_mac: MacAddr, vlan_id: Option<u16>, options: Arc<ScanOptions>) -> Result<bool, ScanError> {
+
+    let ethernet_buffer = build_arp_frame(interface, source_ip, target_ip, Some(target_mac), vlan_id, &options)?;
+    Ok(link.send(&ethernet_buffer).is_ok())
+}
+
+/**
+ * Send a gratuitous ARP frame, announcing the given IPv4 address for the
+ * interface MAC (or the forced source MAC). Both sender and target protocol
+ * addresses are set to the announced IPv4, so that neighbors update their
+ * ARP cache. Sending errors are returned to count the frames that went out.
  */
-pub fn send_arp_request(tx: &mut Box<dyn DataLinkSender>, interface: &NetworkInterface, source_ip: Ipv4Addr, target_ip: Ipv4Addr, options: Arc<ScanOptions>) {
+pub fn send_gratuitous_arp(link: &dyn DataLink, interface: &NetworkInterface, announced_ipv4: Ipv4Addr, options: &ScanOptions) -> Result<(), ScanError> {
 
-    let mut ethernet_buffer = match options.has_vlan() {
+    let ethernet_buffer = build_arp_frame(interface, announced_ipv4, announced_ipv4, None, options.vlan_ids.first().copied(), options)?;
+    link.send(&ethernet_buffer).map_err(|err| ScanError::Packet(format!("Could not send gratuitous ARP frame ({})", err)))
+}
+
+/**
+ * Send an ARP probe (see RFC 5227) for a candidate IPv4 address. The sender
+ * protocol address is unspecified (0.0.0.0), so that the probe does not
+ * pollute the ARP cache of other hosts.
+ */
+pub fn send_arp_probe(link: &dyn DataLink, interface: &NetworkInterface, candidate_ipv4: Ipv4Addr, options: &ScanOptions) -> Result<(), ScanError> {
+
+    let ethernet_buffer = build_arp_frame(interface, Ipv4Addr::UNSPECIFIED, candidate_ipv4, None, options.vlan_ids.first().copied(), options)?;
+    link.send(&ethernet_buffer).map_err(|err| ScanError::Packet(format!("Could not send ARP probe ({})", err)))
+}
+
+/**
+ * Find if an ARP packet conflicts with a probed IPv4 address, and return the
+ * MAC address of the conflicting host. Any ARP packet sent from the candidate
+ * address is a conflict (reply or request), as well as probes for the same
+ * address sent by another host (RFC 5227, section 2.1.1).
+ */
+pub fn find_probe_conflict(arp: &ArpPacket, candidate_ipv4: Ipv4Addr, own_mac: Option<MacAddr>) -> Option<MacAddr> {
+
+    let sender_ipv4 = arp.get_sender_proto_addr();
+    let sender_mac = arp.get_sender_hw_addr();
+
+    if Some(sender_mac) == own_mac {
+        return None;
+    }
+
+    let is_sent_from_candidate = sender_ipv4 == candidate_ipv4;
+    let is_conflicting_probe = sender_ipv4.is_unspecified() && arp.get_operation() == ArpOperations::Request && arp.get_target_proto_addr() == candidate_ipv4;
+
+    match is_sent_from_candidate || is_conflicting_probe {
+        true => Some(sender_mac),
+        false => None
+    }
+}
+
+/**
+ * Receive ARP packets until a conflict is found for the probed IPv4 address
+ * or until the 'timed_out' flag is set. The MAC address of the first
+ * conflicting host is returned.
+ */
+pub fn receive_probe_conflict(link: &dyn DataLink, candidate_ipv4: Ipv4Addr, own_mac: Option<MacAddr>, timed_out: Arc<AtomicBool>) -> Result<Option<MacAddr>, String> {
+
+    while !timed_out.load(Ordering::Relaxed) {
+
+        let arp_buffer = match link.recv(Duration::from_millis(DATALINK_RCV_TIMEOUT)) {
+            Ok(Some(buffer)) => buffer,
+            Ok(None) => continue,
+            Err(error) => return Err(format!("Failed to receive ARP responses ({})", error))
+        };
+
+        let is_arp_type = EthernetPacket::new(&arp_buffer).map(|packet| packet.get_ethertype() == EtherTypes::Arp).unwrap_or(false);
+        if !is_arp_type {
+            continue;
+        }
+
+        let conflict = ArpPacket::new(&arp_buffer[MutableEthernetPacket::minimum_packet_size()..]).and_then(|arp| {
+            find_probe_conflict(&arp, candidate_ipv4, own_mac)
+        });
+        if conflict.is_some() {
+            return Ok(conflict);
+        }
+    }
+
+    Ok(None)
+}
+
+/**
+ * Check if an IPv4 address is assigned to a network interface. Gratuitous ARP
+ * frames should only announce addresses owned by the selected interface.
+ */
+pub fn is_interface_ipv4(interface: &NetworkInterface, ipv4: Ipv4Addr) -> bool {
+
+    interface.ips.iter().any(|ip_network| ip_network.ip() == IpAddr::V4(ipv4))
+}
+
+/**
+ * Build the ARP reply of a host, sent back to the MAC address of the
+ * requester. The frame goes through the same builder as scan requests, with
+ * the default ARP fields.
+ */
+pub fn build_arp_reply(interface: &NetworkInterface, sender_mac: MacAddr, sender_ipv4: Ipv4Addr, target_mac: MacAddr, target_ipv4: Ipv4Addr, vlan_id: Option<u16>) -> Result<Vec<u8>, ScanError> {
+
+    let reply_options = ScanOptions {
+        source_mac: Some(sender_mac),
+        arp_operation: Some(ArpOperations::Reply),
+        ..ScanOptions::default()
+    };
+    build_arp_frame(interface, sender_ipv4, target_ipv4, Some(target_mac), vlan_id, &reply_options)
+}
+
+/**
+ * Build an Ethernet frame holding an ARP packet, with the optional VLAN tag.
+ * All ARP fields can be customized with the scan options (hardware type,
+ * operation, source & destination MAC addresses, ...). A unicast target MAC
+ * takes the priority over the destination MAC option.
+ */
+fn build_arp_frame(interface: &NetworkInterface, source_ip: Ipv4Addr, target_ip: Ipv4Addr, unicast_mac: Option<MacAddr>, vlan_id: Option<u16>, options: &ScanOptions) -> Result<Vec<u8>, ScanError> {
+
+    let mut ethernet_buffer = match vlan_id.is_some() {
         true => vec![0u8; ETHERNET_VLAN_PACKET_SIZE],
         false => vec![0u8; ETHERNET_STD_PACKET_SIZE]
     };
-    let mut ethernet_packet = MutableEthernetPacket::new(&mut ethernet_buffer).unwrap_or_else(|| {
-        eprintln!("Could not build Ethernet packet");
-        process::exit(1);
-    });
+    let mut ethernet_packet = MutableEthernetPacket::new(&mut ethernet_buffer).ok_or_else(|| {
+        ScanError::Packet("Could not build Ethernet packet".to_string())
+    })?;
 
-    let target_mac = match options.destination_mac {
+    let target_mac = match unicast_mac.or(options.destination_mac) {
         Some(forced_mac) => forced_mac,
         None => MacAddr::broadcast()
     };
     let source_mac = match options.source_mac {
         Some(forced_source_mac) => forced_source_mac,
-        None => interface.mac.unwrap_or_else(|| {
-            eprintln!("Interface should have a MAC address");
-            process::exit(1);
-        })
+        None => interface.mac.ok_or_else(|| {
+            ScanError::Interface(format!("Interface {} should have a MAC address", interface.name))
+        })?
     };
 
     ethernet_packet.set_destination(target_mac);
     ethernet_packet.set_source(source_mac);
 
-    let selected_ethertype = match options.vlan_id {
+    let selected_ethertype = match vlan_id {
         Some(_) => EtherTypes::Vlan,
         None => EtherTypes::Arp
     };
     ethernet_packet.set_ethertype(selected_ethertype);
 
     let mut arp_buffer = [0u8; ARP_PACKET_SIZE];
-    let mut arp_packet = MutableArpPacket::new(&mut arp_buffer).unwrap_or_else(|| {
-        eprintln!("Could not build ARP packet");
-        process::exit(1);
-    });
+    let mut arp_packet = MutableArpPacket::new(&mut arp_buffer).ok_or_else(|| {
+        ScanError::Packet("Could not build ARP packet".to_string())
+    })?;
 
     arp_packet.set_hardware_type(options.hw_type.unwrap_or(ArpHardwareTypes::Ethernet));
     arp_packet.set_protocol_type(options.proto_type.unwrap_or(EtherTypes::Ipv4));
     arp_packet.set_hw_addr_len(options.hw_addr.unwrap_or(6));
     arp_packet.set_proto_addr_len(options.proto_addr.unwrap_or(4));
     arp_packet.set_operation(options.arp_operation.unwrap_or(ArpOperations::Request));
-    arp_packet.set_sender_hw_addr(source_mac);
+    arp_packet.set_sender_hw_addr(options.arp_sender_mac.unwrap_or(source_mac));
     arp_packet.set_sender_proto_addr(source_ip);
     arp_packet.set_target_hw_addr(target_mac);
     arp_packet.set_target_proto_addr(target_ip);
 
-    if let Some(vlan_id) = options.vlan_id {
+    if let Some(vlan_id) = vlan_id {
 
         let mut vlan_buffer = [0u8; VLAN_PACKET_SIZE];
-        let mut vlan_packet = MutableVlanPacket::new(&mut vlan_buffer).unwrap_or_else(|| {
-            eprintln!("Could not build VLAN packet");
-            process::exit(1);
-        });
+        let mut vlan_packet = MutableVlanPacket::new(&mut vlan_buffer).ok_or_else(|| {
+            ScanError::Packet("Could not build VLAN packet".to_string())
+        })?;
         vlan_packet.set_vlan_identifier(vlan_id);
-        vlan_packet.set_priority_code_point(ClassOfService::new(VLAN_QOS_DEFAULT));
+        vlan_packet.set_priority_code_point(ClassOfService::new(options.vlan_priority));
         vlan_packet.set_drop_eligible_indicator(0);
         vlan_packet.set_ethertype(EtherTypes::Arp);
 
@@ -237,70 +1115,197 @@ pub fn send_arp_request(tx: &mut Box<dyn DataLinkSender>, interface: &NetworkInt
         ethernet_packet.set_payload(arp_packet.packet_mut());
     }
 
-    tx.send_to(ethernet_packet.to_immutable().packet(), Some(interface.clone()));
+    Ok(ethernet_buffer)
 }
 
 /**
- * A network iterator for iterating over multiple network ranges in with a
- * low-memory approach. This iterator was crafted to allow iteration over huge
- * network ranges (192.168.0.0/16) without consuming excessive memory.
+ * The requests planned for a scanned network on an interface (and VLAN), as
+ * printed by the dry-run mode. Targets are given in the sending order, the
+ * example frame is the request sent to the first target.
  */
-pub struct NetworkIterator {
-    current_iterator: Option<ipnetwork::IpNetworkIterator>,
-    networks: Vec<IpNetwork>,
-    is_random: bool,
-    random_pool: Vec<IpAddr>
+pub struct ScanPlan {
+    pub interface_name: String,
+    pub network: IpNetwork,
+    pub vlan_id: Option<u16>,
+    pub target_count: u128,
+    pub first_target: Option<Ipv4Addr>,
+    pub last_target: Option<Ipv4Addr>,
+    pub source_ipv4: Ipv4Addr,
+    pub source_mac: Option<MacAddr>,
+    pub destination_mac: MacAddr,
+    pub example_frame: Option<Vec<u8>>
 }
 
-impl NetworkIterator {
+/**
+ * Plan the requests of a scan without opening any datalink channel: targets
+ * are expanded (without the excluded networks) and an example frame is built
+ * for each network, so that invalid packets are reported before scanning.
+ */
+pub fn plan_scan(network_configurations: &[(&NetworkInterface, Vec<&IpNetwork>)], options: &ScanOptions) -> Result<Vec<ScanPlan>, ScanError> {
 
-    pub fn new(networks_ref: &[&IpNetwork], is_random: bool) -> NetworkIterator {
+    let vlan_passes: Vec<Option<u16>> = match options.vlan_ids.is_empty() {
+        true => vec![None],
+        false => options.vlan_ids.iter().map(|vlan_id| Some(*vlan_id)).collect()
+    };
 
-        // The IpNetwork struct implements the Clone trait, which means that a simple
-        // dereference will clone the struct in the new vector
-        let mut networks: Vec<IpNetwork> = networks_ref.iter().map(|network| *(*network)).collect();
+    let mut scan_plans: Vec<ScanPlan> = vec![];
+    for (interface, ip_networks) in network_configurations {
+
+        let source_ipv4 = find_source_ip(interface, options.source_ipv4)?;
+        for ip_network in ip_networks {
+
+            let target_count = utils::compute_network_size(&[ip_network], &options.excluded_networks).map_err(|err| ScanError::InvalidTarget(err.to_string()))?;
+            let mut targets = NetworkIterator::new(&[ip_network], options.random_seed).filter_map(|ip_address| match ip_address {
+                IpAddr::V4(ipv4_address) if !is_excluded_target(ipv4_address, &options.excluded_networks) => Some(ipv4_address),
+                _ => None
+            });
+            let first_target = targets.next();
+            let last_target = targets.last().or(first_target);
 
-        if is_random {
-            let mut rng = rand::thread_rng();
-            networks.shuffle(&mut rng);
+            for vlan_id in &vlan_passes {
+                let example_frame = match first_target {
+                    Some(first_target) => Some(build_arp_frame(interface, source_ipv4, first_target, None, *vlan_id, options)?),
+                    None => None
+                };
+                scan_plans.push(ScanPlan {
+                    interface_name: interface.name.clone(),
+                    network: **ip_network,
+                    vlan_id: *vlan_id,
+                    target_count,
+                    first_target,
+                    last_target,
+                    source_ipv4,
+                    source_mac: options.source_mac.or(interface.mac),
+                    destination_mac: options.destination_mac.unwrap_or_else(MacAddr::broadcast),
+                    example_frame
+                });
+            }
         }
+    }
+    Ok(scan_plans)
+}
 
-        NetworkIterator {
-            current_iterator: None,
+/**
+ * A lazy random permutation over the target index range, based on a linear
+ * congruential generator with a full period over the next power of two (see
+ * the Hull-Dobell theorem). Indexes out of the target range are skipped, so
+ * that each target is visited exactly once without materializing the list.
+ */
+struct TargetPermutation {
+    networks: Vec<IpNetwork>,
+    total: u128,
+    modulus: u128,
+    multiplier: u128,
+    increment: u128,
+    current: u128,
+    remaining: u128
+}
+
+impl TargetPermutation {
+
+    fn new(networks: Vec<IpNetwork>, seed: u64) -> TargetPermutation {
+
+        let total: u128 = networks.iter().map(|network| match network {
+            IpNetwork::V4(network) => utils::ipv4_network_size(network),
+            IpNetwork::V6(_) => 0
+        }).sum();
+        let modulus = total.next_power_of_two();
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        TargetPermutation {
             networks,
-            is_random,
-            random_pool: vec![]
+            total,
+            modulus,
+            multiplier: (u128::from(rng.gen::<u64>()) * 4 + 1) % modulus,
+            increment: (u128::from(rng.gen::<u64>()) * 2 + 1) % modulus,
+            current: u128::from(rng.gen::<u64>()) % modulus,
+            remaining: total
         }
     }
 
-    /**
-     * The functions below are not public and only used by the Iterator trait
-     * to help keep the next() code clean.
-     */
+    fn next_index(&mut self) -> Option<u128> {
 
-    fn has_no_items_left(&self) -> bool {
-        self.current_iterator.is_none() && self.networks.is_empty() && self.random_pool.is_empty()
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            self.current = (self.multiplier * self.current + self.increment) % self.modulus;
+            if self.current < self.total {
+                self.remaining -= 1;
+                return Some(self.current);
+            }
+        }
     }
 
-    fn fill_random_pool(&mut self) {
+    fn address_at(&self, mut index: u128) -> Option<IpAddr> {
 
-        for _ in 0..1000 {
-
-            let next_ip = self.current_iterator.as_mut().unwrap().next();
-            if next_ip.is_none() {
-                break;
+        for network in self.networks.iter() {
+            if let IpNetwork::V4(network) = network {
+                let size = utils::ipv4_network_size(network);
+                if index < size {
+                    return Some(IpAddr::V4(Ipv4Addr::from(u32::from(network.network()) + index as u32)));
+                }
+                index -= size;
             }
+        }
+        None
+    }
+}
+
+/**
+ * A network iterator for iterating over multiple network ranges in with a
+ * low-memory approach. This iterator was crafted to allow iteration over huge
+ * network ranges (192.168.0.0/16) without consuming excessive memory. If a
+ * random seed is given, targets are iterated in a reproducible random order.
+ */
+pub struct NetworkIterator {
+    current_iterator: Option<Box<dyn Iterator<Item = IpAddr> + Send>>,
+    networks: Vec<IpNetwork>,
+    permutation: Option<TargetPermutation>
+}
+
+impl NetworkIterator {
+
+    pub fn new(networks_ref: &[&IpNetwork], random_seed: Option<u64>) -> NetworkIterator {
 
-            self.random_pool.push(next_ip.unwrap());
+        // The IpNetwork struct implements the Clone trait, which means that a simple
+        // dereference will clone the struct in the new vector
+        let networks: Vec<IpNetwork> = networks_ref.iter().map(|network| *(*network)).collect();
+
+        match random_seed {
+            Some(seed) => NetworkIterator {
+                current_iterator: None,
+                networks: vec![],
+                permutation: Some(TargetPermutation::new(networks, seed))
+            },
+            None => NetworkIterator {
+                current_iterator: None,
+                networks,
+                permutation: None
+            }
         }
+    }
+
+    /*
+     * The functions below are not public and only used by the Iterator trait
+     * to help keep the next() code clean.
+     */
 
-        let mut rng = rand::thread_rng();
-        self.random_pool.shuffle(&mut rng);
+    fn has_no_items_left(&self) -> bool {
+        self.current_iterator.is_none() && self.networks.is_empty()
     }
 
+    // IPv4 networks are iterated as address ranges, as the iterator of
+    // ipnetwork overflows on the whole IPv4 space (0.0.0.0/0).
     fn select_new_iterator(&mut self) {
 
-        self.current_iterator = Some(self.networks.remove(0).iter());
+        self.current_iterator = Some(match self.networks.remove(0) {
+            IpNetwork::V4(network) => {
+                let addresses = u32::from(network.network())..=u32::from(network.broadcast());
+                Box::new(addresses.map(|address| IpAddr::V4(Ipv4Addr::from(address))))
+            },
+            network => Box::new(network.iter())
+        });
     }
 
     fn pop_next_iterator_address(&mut self) -> Option<IpAddr> {
@@ -316,6 +1321,10 @@ impl Iterator for NetworkIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
 
+        if let Some(permutation) = self.permutation.as_mut() {
+            return permutation.next_index().and_then(|index| permutation.address_at(index));
+        }
+
         if self.has_no_items_left() {
             return None;
         }
@@ -324,14 +1333,7 @@ impl Iterator for NetworkIterator {
             self.select_new_iterator();
         }
 
-        if self.is_random && self.random_pool.is_empty() {
-            self.fill_random_pool();
-        }
-
-        let next_ip = match self.is_random {
-            true => self.random_pool.pop(),
-            false => self.pop_next_iterator_address()
-        };
+        let next_ip = self.pop_next_iterator_address();
 
         if next_ip.is_none() && !self.networks.is_empty() {
             self.select_new_iterator();
@@ -342,37 +1344,96 @@ impl Iterator for NetworkIterator {
     }
 }
 
+/**
+ * A token bucket used to pace ARP requests at a given rate (packets per
+ * second). Tokens are refilled based on the elapsed time, so that the time
+ * spent building & sending packets is taken into account. A small burst is
+ * allowed to catch up with late wake-ups.
+ */
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant
+}
+
+impl RateLimiter {
+
+    /**
+     * Build a rate limiter for the given rate, a zero rate (only rejected by
+     * the CLI) is paced as one packet per second.
+     */
+    pub fn new(rate: u64) -> RateLimiter {
+
+        let rate = rate.max(1) as f64;
+        RateLimiter {
+            rate,
+            capacity: (rate / 100_f64).max(1_f64),
+            tokens: 1_f64,
+            last_refill: Instant::now()
+        }
+    }
+
+    fn refill(&mut self) {
+
+        let now = Instant::now();
+        let elapsed_seconds = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed_seconds * self.rate).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /**
+     * Block the current thread until a token is available, then consume it.
+     */
+    pub fn wait_for_token(&mut self) {
+
+        self.refill();
+        while self.tokens < 1_f64 {
+            let missing_seconds = (1_f64 - self.tokens) / self.rate;
+            thread::sleep(Duration::from_secs_f64(missing_seconds));
+            self.refill();
+        }
+        self.tokens -= 1_f64;
+    }
+}
+
+/**
+ * Check if a target address is part of the excluded networks, excluded targets
+ * are skipped before any ARP request is built.
+ */
+pub fn is_excluded_target(ipv4: Ipv4Addr, excluded_networks: &[Ipv4Network]) -> bool {
+
+    excluded_networks.iter().any(|network| network.contains(ipv4))
+}
+
 /**
  * Find the most adequate IPv4 address on a given network interface for sending
  * ARP requests. If the 'forced_source_ipv4' parameter is set, it will take
  * the priority over the network interface address.
  */
-pub fn find_source_ip(network_interface: &NetworkInterface, forced_source_ipv4: Option<Ipv4Addr>) -> Ipv4Addr {
+pub fn find_source_ip(network_interface: &NetworkInterface, forced_source_ipv4: Option<Ipv4Addr>) -> Result<Ipv4Addr, ScanError> {
 
     if let Some(forced_ipv4) = forced_source_ipv4 {
-        return forced_ipv4;
+        return Ok(forced_ipv4);
     }
 
     let potential_network = network_interface.ips.iter().find(|network| network.is_ipv4());
     match potential_network.map(|network| network.ip()) {
-        Some(IpAddr::V4(ipv4_addr)) => ipv4_addr,
-        _ => {
-            eprintln!("Expected IPv4 address on network interface");
-            process::exit(1);
-        }
+        Some(IpAddr::V4(ipv4_addr)) => Ok(ipv4_addr),
+        _ => Err(ScanError::Interface(format!("Expected IPv4 address on network interface {}", network_interface.name)))
     }
 }
 
 /**
- * Wait at least N seconds and receive ARP network responses. The main
- * downside of this function is the blocking nature of the datalink receiver:
- * when the N seconds are elapsed, the receiver loop will therefore only stop
- * on the next received frame. Therefore, the receiver should have been
- * configured to stop at certain intervals (500ms for example).
+ * Wait at least N seconds and receive ARP network responses. The datalink
+ * reads are blocking: once the N seconds are elapsed, the receiver loop will
+ * therefore only stop after the next received frame or read timeout (every
+ * 'DATALINK_RCV_TIMEOUT' milliseconds).
  */
-pub fn receive_arp_responses(rx: &mut Box<dyn DataLinkReceiver>, options: Arc<ScanOptions>, timed_out: Arc<AtomicBool>, vendor_list: &mut Vendor) -> (ResponseSummary, Vec<TargetDetails>) {
+pub fn receive_arp_responses(link: &dyn DataLink, options: Arc<ScanOptions>, scan_pass: ScanPass, scan_state: ScanState, capture: Option<Sender<CapturedFrame>>, vendor_list: &mut VendorDatabase) -> Result<(ResponseSummary, Vec<TargetDetails>), ScanError> {
 
-    let mut discover_map: HashMap<Ipv4Addr, TargetDetails> = HashMap::new();
+    let mut discover_map: HashMap<(Ipv4Addr, MacAddr), TargetDetails> = HashMap::new();
+    let mut unsolicited_map: HashMap<(Ipv4Addr, MacAddr), TargetDetails> = HashMap::new();
     let start_recording = Instant::now();
 
     let mut packet_count = 0;
@@ -380,89 +1441,262 @@ pub fn receive_arp_responses(rx: &mut Box<dyn DataLinkReceiver>, options: Arc<Sc
 
     loop {
 
-        if timed_out.load(Ordering::Relaxed) {
+        if scan_state.timed_out.load(Ordering::Relaxed) || scan_state.cancellation.is_cancelled() || scan_state.has_reached_host_limit(options.max_hosts) {
             break;
         }
 
-        let arp_buffer = match rx.next() {
-            Ok(buffer) => buffer,
-            Err(error) => {
-                match error.kind() {
-                    // The 'next' call will only block the thread for a given
-                    // amount of microseconds. The goal is to avoid long blocks
-                    // due to the lack of packets received.
-                    TimedOut => continue,
-                    _ => {
-                        eprintln!("Failed to receive ARP requests ({})", error);
-                        process::exit(1);
-                    }
-                };
-            }
+        // The 'recv' call will only block the thread for a given amount of
+        // milliseconds. The goal is to avoid long blocks due to the lack of
+        // packets received. A link without any frame left (such as a replayed
+        // capture) ends the loop.
+        let frame = match link.recv(Duration::from_millis(DATALINK_RCV_TIMEOUT)) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => continue,
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(ScanError::Packet(format!("Failed to receive ARP requests ({})", error)))
         };
+        let arp_buffer = frame.as_slice();
         packet_count += 1;
+
+        // Captured frames are only copied and sent to the capture thread, the
+        // pcap file is written outside of the receive loop.
+        if let Some(capture_sender) = &capture {
+            let _ = capture_sender.send(CapturedFrame::new(arp_buffer));
+        }
         
-        let ethernet_packet = match EthernetPacket::new(arp_buffer) {
-            Some(packet) => packet,
-            None => continue
+        let arp_payload = extract_arp_payload(arp_buffer, scan_pass.vlan_id);
+        if arp_payload.is_some() {
+            arp_count += 1;
+        }
+        let arp_packet = arp_payload.and_then(ArpPacket::new);
+        let frame_src_mac = EthernetPacket::new(arp_buffer).map(|ethernet| ethernet.get_source());
+        let candidate = arp_packet.as_ref().and_then(|arp| build_arp_candidate(arp, frame_src_mac, &options, &scan_pass, &scan_state, &discover_map));
+
+        // The frame filter sees every frame after the built-in filtering, with
+        // the host that would be recorded (if any). Without filter, ARP
+        // packets kept by the built-in filtering are recorded.
+        let verdict = match (&scan_pass.frame_filter, EthernetPacket::new(arp_buffer)) {
+            (Some(frame_filter), Some(ethernet)) => {
+                let received_frame = ReceivedFrame {
+                    ethernet,
+                    arp: arp_packet,
+                    interface_name: &scan_pass.interface_name,
+                    vlan_id: scan_pass.vlan_id
+                };
+                let mut frame_filter = frame_filter.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                frame_filter(&received_frame, candidate)
+            },
+            (Some(_), None) => FrameVerdict::Ignore,
+            (None, _) => candidate.map_or(FrameVerdict::Ignore, FrameVerdict::Accept)
+        };
+        let mut target_detail = match verdict {
+            FrameVerdict::Accept(target_detail) => target_detail,
+            FrameVerdict::Ignore => continue
         };
 
-        let is_arp_type = matches!(ethernet_packet.get_ethertype(), EtherTypes::Arp);
-        if !is_arp_type {
+        // Hosts answering for an IPv4 that was not requested are kept apart,
+        // they are neither reported while scanning nor counted as found.
+        if scan_pass.targets.as_ref().is_some_and(|targets| !targets.contains(target_detail.ipv4)) {
+            unsolicited_map.entry((target_detail.ipv4, target_detail.mac))
+                .and_modify(|detail| detail.reply_count += 1)
+                .or_insert(TargetDetails { reply_count: 1, ..target_detail });
             continue;
         }
 
-        let arp_packet = ArpPacket::new(&arp_buffer[MutableEthernetPacket::minimum_packet_size()..]);
-        arp_count += 1;
-
-        // If we found an ARP packet, extract the details and add the essential
-        // fields in the discover map. Please note that results are grouped by
-        // IPv4 address - which means that a MAC change will appear as two
-        // separete records in the result table.
-        if let Some(arp) = arp_packet {
+        if let Ok(mut hosts) = scan_state.responded_hosts.lock() {
+            hosts.insert(target_detail.ipv4);
+        }
 
-            let sender_ipv4 = arp.get_sender_proto_addr();
-            let sender_mac = arp.get_sender_hw_addr();
-    
-            discover_map.insert(sender_ipv4, TargetDetails {
-                ipv4: sender_ipv4,
-                mac: sender_mac,
-                hostname: None,
-                vendor: None
-            });
+        // Only the first answer of a host is recorded (later answers would
+        // carry the same details), later answers are only counted. When
+        // streaming, each new host is enriched and reported as soon as it has
+        // been confirmed, instead of waiting for the scan to end.
+        let entry = match discover_map.entry((target_detail.ipv4, target_detail.mac)) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().reply_count += 1;
+                continue;
+            },
+            Entry::Vacant(entry) => entry
+        };
+        target_detail.reply_count = 1;
+        if scan_pass.reports_new_hosts() {
+            enrich_target_details(&mut target_detail, vendor_list, &options.annotations);
+            resolve::resolve_hostnames_in_chain(slice::from_mut(&mut target_detail), &options, scan_pass.resolvers.as_deref())?;
+        }
+        if let Some(host_callback) = &scan_pass.host_callback {
+            let mut callback = host_callback.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            callback(&target_detail);
+        }
+        if let Some(host_sender) = &scan_pass.host_sender {
+            let _ = host_sender.send(target_detail.clone());
+        }
+        if let Some(event_sender) = &scan_pass.event_sender {
+            let _ = event_sender.send(ScanEvent::HostFound(target_detail.clone()));
         }
+        entry.insert(target_detail);
+        scan_state.found_hosts.fetch_add(1, Ordering::Relaxed);
     }
 
-    // For each target found, enhance each item with additional results
-    // results such as the hostname & MAC vendor.
-    let target_details = discover_map.into_iter().map(|(_, mut target_detail)| {
+    // For each target found, enhance each item with the MAC vendor (hostnames
+    // are resolved by the caller). Targets are sorted by IPv4 and MAC, so that
+    // conflicting records keep a stable order.
+    let mut target_details: Vec<TargetDetails> = discover_map.into_values().map(|mut target_detail| {
 
-        if options.resolve_hostname {
-            target_detail.hostname = find_hostname(target_detail.ipv4);
+        if !scan_pass.reports_new_hosts() {
+            enrich_target_details(&mut target_detail, vendor_list, &options.annotations);
         }
 
-        if vendor_list.has_vendor_db() {
-            target_detail.vendor = vendor_list.search_by_mac(&target_detail.mac);
-        }
+        target_detail
+
+    }).collect();
+    target_details.sort_by_key(|detail| (detail.ipv4, detail.mac));
+
+    let mut unsolicited_hosts: Vec<TargetDetails> = unsolicited_map.into_values().map(|mut target_detail| {
+        enrich_target_details(&mut target_detail, vendor_list, &options.annotations);
+        target_detail
+    }).collect();
+    unsolicited_hosts.sort_by_key(|detail| (detail.ipv4, detail.mac));
+
+    // The response summary can be used to display analytics related to the
+    // performed ARP scans (packet counts, timings, ...)
+    let response_summary = ResponseSummary {
+        packet_count,
+        arp_count,
+        duration_ms: start_recording.elapsed().as_millis(),
+        unsolicited_hosts,
+        ..ResponseSummary::default()
+    };
+    Ok((response_summary, target_details))
+}
+
+/**
+ * Build the host recorded for an ARP packet by the built-in filtering, which
+ * skips our own packets (and ARP probes in passive mode). If we found an ARP
+ * packet, results are grouped by IPv4 & MAC address pairs - which means that
+ * an IPv4 answered by two MAC addresses will appear as two conflicting
+ * records.
+ */
+fn build_arp_candidate(arp: &ArpPacket, frame_src_mac: Option<MacAddr>, options: &ScanOptions, scan_pass: &ScanPass, scan_state: &ScanState, discover_map: &HashMap<(Ipv4Addr, MacAddr), TargetDetails>) -> Option<TargetDetails> {
+
+    let sender_ipv4 = arp.get_sender_proto_addr();
+    let sender_mac = arp.get_sender_hw_addr();
+
+    // Passive scans also see ARP probes, which are sent with an unspecified
+    // sender address and do not identify a host.
+    if options.passive && sender_ipv4.is_unspecified() {
+        return None;
+    }
+
+    // Our own packets may be looped back on hubs or by some drivers, which
+    // matters when sending ARP replies as they would otherwise be recorded as
+    // results.
+    if is_own_arp_packet(arp, scan_pass.own_mac, scan_pass.source_ipv4) {
+        return None;
+    }
+
+    // The response time is computed from the last request sent to the host,
+    // but the first answer is kept if the host replies again.
+    let previous_response_time = discover_map.get(&(sender_ipv4, sender_mac)).and_then(|detail| detail.response_time_ms);
+    let response_time_ms = previous_response_time.or_else(|| {
+        scan_state.request_times.lock().ok().and_then(|times| times.get(&sender_ipv4).and_then(|sent_at| u64::try_from(sent_at.elapsed().as_millis()).ok()))
+    });
+
+    Some(TargetDetails {
+        response_time_ms,
+        frame_src_mac,
+        ..build_target_details(sender_ipv4, sender_mac, &scan_pass.interface_name, scan_pass.vlan_id)
+    })
+}
+
+/**
+ * Build the details of a host seen on a scanned interface, before any
+ * enrichment (vendor, hostname, ...).
+ */
+fn build_target_details(ipv4: Ipv4Addr, mac: MacAddr, interface_name: &str, vlan_id: Option<u16>) -> TargetDetails {
+
+    TargetDetails {
+        ipv4,
+        mac,
+        hostname: None,
+        vendor: None,
+        response_time_ms: None,
+        interface: interface_name.to_string(),
+        vlan_id,
+        frame_src_mac: None,
+        reply_count: 0,
+        verify_status: None,
+        origin: None,
+        hostname_source: None,
+        hostname_ascii: None,
+        virtualization: None,
+        note: None
+    }
+}
+
+/**
+ * Check if an ARP packet has been sent by the scanner itself, using both the
+ * ARP sender hardware address and the source IPv4 of outgoing packets. A
+ * host answering with our IPv4 from another MAC address is still recorded.
+ */
+fn is_own_arp_packet(arp: &ArpPacket, own_mac: Option<MacAddr>, source_ipv4: Ipv4Addr) -> bool {
+
+    own_mac == Some(arp.get_sender_hw_addr()) && arp.get_sender_proto_addr() == source_ipv4
+}
+
+/**
+ * Read the operation, the sender (MAC & IPv4) and the target IPv4 of an ARP
+ * frame, with the same VLAN filtering as the receive loop. Used to match the
+ * requests & replies of a replayed capture.
+ */
+pub fn read_arp_exchange(ethernet_buffer: &[u8], vlan_id: Option<u16>) -> Option<(ArpOperation, MacAddr, Ipv4Addr, Ipv4Addr)> {
+
+    let arp_packet = ArpPacket::new(extract_arp_payload(ethernet_buffer, vlan_id)?)?;
+    Some((arp_packet.get_operation(), arp_packet.get_sender_hw_addr(), arp_packet.get_sender_proto_addr(), arp_packet.get_target_proto_addr()))
+}
 
-        target_detail
+/**
+ * Extract the ARP payload of a received Ethernet frame. For VLAN scans, only
+ * frames with an 802.1Q tag holding the scanned VLAN identifier are kept,
+ * otherwise only untagged ARP frames are kept.
+ */
+fn extract_arp_payload(ethernet_buffer: &[u8], vlan_id: Option<u16>) -> Option<&[u8]> {
+
+    let ethernet_packet = EthernetPacket::new(ethernet_buffer)?;
+    let header_size = MutableEthernetPacket::minimum_packet_size();
+
+    match (ethernet_packet.get_ethertype(), vlan_id) {
+        (EtherTypes::Arp, None) => Some(&ethernet_buffer[header_size..]),
+        (EtherTypes::Vlan, Some(scanned_vlan_id)) => {
+            let vlan_packet = VlanPacket::new(&ethernet_buffer[header_size..])?;
+            let is_scanned_vlan = vlan_packet.get_vlan_identifier() == scanned_vlan_id && vlan_packet.get_ethertype() == EtherTypes::Arp;
+            match is_scanned_vlan {
+                true => Some(&ethernet_buffer[header_size + VlanPacket::minimum_packet_size()..]),
+                false => None
+            }
+        },
+        _ => None
+    }
+}
 
-    }).collect();
+/**
+ * Enhance a target detail with the MAC vendor, the virtualization hint and the
+ * user annotation. Hostnames are resolved separately, once all responses have
+ * been received.
+ */
+pub fn enrich_target_details(target_detail: &mut TargetDetails, vendor_list: &mut VendorDatabase, annotations: &HashMap<MacAddr, String>) {
 
-    // The response summary can be used to display analytics related to the
-    // performed ARP scans (packet counts, timings, ...)
-    let response_summary = ResponseSummary {
-        packet_count,
-        arp_count,
-        duration_ms: start_recording.elapsed().as_millis()
-    };
-    (response_summary, target_details)
+    if vendor_list.has_vendor_db() {
+        target_detail.vendor = vendor_list.lookup(&target_detail.mac).map(|vendor| vendor.to_string());
+    }
+    target_detail.virtualization = vendor_list.search_virtualization(&target_detail.mac);
+    target_detail.note = annotations.get(&target_detail.mac).cloned();
 }
 
 /**
  * Find the local hostname linked to an IPv4 address. This will perform a
  * reverse DNS request in the local network to find the IPv4 hostname.
  */
-fn find_hostname(ipv4: Ipv4Addr) -> Option<String> {
+pub fn find_hostname(ipv4: Ipv4Addr) -> Option<String> {
 
     let ip: IpAddr = ipv4.into();
     match lookup_addr(&ip) {
@@ -486,8 +1720,22 @@ mod tests {
     use super::*;
 
     use ipnetwork::Ipv4Network;
+    use pnet::packet::Packet;
     use std::env;
 
+    #[test]
+    fn should_estimate_scans_without_hosts() {
+
+        for scan_timing in [ScanTiming::Interval(10), ScanTiming::Bandwidth(100_000), ScanTiming::Rate(100), ScanTiming::Rate(0)] {
+            let options = Arc::new(ScanOptions { scan_timing, ..ScanOptions::default() });
+
+            let estimation = compute_scan_estimation(0, &options);
+
+            assert_eq!(estimation.request_size, 0);
+            assert!(estimation.duration_ms > 0);
+        }
+    }
+
     #[test]
     fn should_resolve_public_ip() {
 
@@ -518,10 +1766,21 @@ mod tests {
         assert_eq!(find_hostname(ipv4), None);
     }
 
+    #[test]
+    fn should_classify_mac_types() {
+
+        assert_eq!(MacType::classify(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)), MacType::Universal);
+        assert_eq!(MacType::classify(&MacAddr::new(0xda, 0xa1, 0x19, 0x00, 0x00, 0x01)), MacType::Local);
+        assert_eq!(MacType::classify(&MacAddr::new(0x02, 0x00, 0x00, 0x00, 0x00, 0x01)), MacType::Local);
+        assert_eq!(MacType::classify(&MacAddr::new(0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb)), MacType::Multicast);
+        assert_eq!(MacType::classify(&MacAddr::new(0x33, 0x33, 0x00, 0x00, 0x00, 0x01)), MacType::Multicast);
+        assert_eq!(MacType::classify(&MacAddr::broadcast()), MacType::Multicast);
+    }
+
     #[test]
     fn should_iterate_over_empty_networks() {
 
-        let mut iterator = NetworkIterator::new(&vec![], false);
+        let mut iterator = NetworkIterator::new(&[], None);
 
         assert_eq!(iterator.next(), None);
     }
@@ -536,7 +1795,7 @@ mod tests {
             &network_a
         ];
 
-        let mut iterator = NetworkIterator::new(&target_network, false);
+        let mut iterator = NetworkIterator::new(&target_network, None);
 
         assert_eq!(iterator.next(), Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))));
         assert_eq!(iterator.next(), None);
@@ -552,7 +1811,7 @@ mod tests {
             &network_a
         ];
 
-        let mut iterator = NetworkIterator::new(&target_network, false);
+        let mut iterator = NetworkIterator::new(&target_network, None);
 
         assert_eq!(iterator.next(), Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 0))));
         assert_eq!(iterator.next(), Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))));
@@ -573,7 +1832,7 @@ mod tests {
             &network_b
         ];
 
-        let mut iterator = NetworkIterator::new(&target_network, false);
+        let mut iterator = NetworkIterator::new(&target_network, None);
 
         assert_eq!(iterator.next(), Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))));
         assert_eq!(iterator.next(), Some(IpAddr::V4(Ipv4Addr::new(10, 10, 20, 20))));
@@ -594,11 +1853,762 @@ mod tests {
             &network_b
         ];
 
-        let mut iterator = NetworkIterator::new(&target_network, true);
+        let mut iterator = NetworkIterator::new(&target_network, Some(42));
 
-        assert_eq!(iterator.next().is_some(), true);
-        assert_eq!(iterator.next().is_some(), true);
+        assert!(iterator.next().is_some());
+        assert!(iterator.next().is_some());
         assert_eq!(iterator.next(), None);
     }
 
+    #[test]
+    fn should_iterate_over_random_permutation() {
+
+        let network_a = IpNetwork::V4(
+            Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 0), 24).unwrap()
+        );
+        let network_b = IpNetwork::V4(
+            Ipv4Network::new(Ipv4Addr::new(10, 10, 20, 0), 30).unwrap()
+        );
+        let target_network: Vec<&IpNetwork> = vec![
+            &network_a,
+            &network_b
+        ];
+
+        let sequential: Vec<IpAddr> = NetworkIterator::new(&target_network, None).collect();
+        let random: Vec<IpAddr> = NetworkIterator::new(&target_network, Some(1337)).collect();
+
+        assert_eq!(random.len(), 260);
+        assert_ne!(random, sequential);
+
+        let mut sorted_random = random.clone();
+        sorted_random.sort();
+        let mut sorted_sequential = sequential.clone();
+        sorted_sequential.sort();
+        assert_eq!(sorted_random, sorted_sequential);
+    }
+
+    #[test]
+    fn should_iterate_over_whole_ipv4_space() {
+
+        let any_network: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        let target_network: Vec<&IpNetwork> = vec![&any_network];
+
+        let sequential: Vec<IpAddr> = NetworkIterator::new(&target_network, None).take(2).collect();
+        assert_eq!(sequential, [IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), IpAddr::V4(Ipv4Addr::new(0, 0, 0, 1))]);
+
+        let mut permutation = TargetPermutation::new(vec![any_network], 42);
+        assert_eq!(permutation.total, 1u128 << 32);
+        let random: HashSet<IpAddr> = (0..1000).filter_map(|_| permutation.next_index().and_then(|index| permutation.address_at(index))).collect();
+        assert_eq!(random.len(), 1000);
+        assert_eq!(permutation.address_at(u32::MAX.into()), Some(IpAddr::V4(Ipv4Addr::BROADCAST)));
+        assert_eq!(permutation.address_at(1u128 << 32), None);
+    }
+
+    #[test]
+    fn should_reproduce_random_order_with_seed() {
+
+        let network_a = IpNetwork::V4(
+            Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 0), 24).unwrap()
+        );
+        let target_network: Vec<&IpNetwork> = vec![
+            &network_a
+        ];
+
+        let first_run: Vec<IpAddr> = NetworkIterator::new(&target_network, Some(7)).collect();
+        let second_run: Vec<IpAddr> = NetworkIterator::new(&target_network, Some(7)).collect();
+        let other_run: Vec<IpAddr> = NetworkIterator::new(&target_network, Some(8)).collect();
+
+        assert_eq!(first_run, second_run);
+        assert_ne!(first_run, other_run);
+    }
+
+    #[test]
+    fn should_cancel_child_tokens_with_parent() {
+
+        let parent = CancellationToken::new();
+        let (first_child, second_child) = (parent.child_token(), parent.child_token());
+
+        first_child.cancel();
+        assert!(!parent.is_cancelled() && !second_child.is_cancelled());
+
+        parent.cancel();
+        assert!(second_child.is_cancelled() && second_child.child_token().is_cancelled());
+    }
+
+    #[test]
+    fn should_pace_requests_with_rate_limiter() {
+
+        let mut rate_limiter = RateLimiter::new(1000);
+        let started_at = Instant::now();
+        for _ in 0..201 {
+            rate_limiter.wait_for_token();
+        }
+        let elapsed_ms = started_at.elapsed().as_millis();
+
+        // Only the lower bound is tight, loaded machines wake up late.
+        assert!((190..=2000).contains(&elapsed_ms), "200 tokens took {}ms", elapsed_ms);
+    }
+
+    #[test]
+    fn should_pace_zero_rate_as_one_packet_per_second() {
+
+        let mut rate_limiter = RateLimiter::new(0);
+        rate_limiter.wait_for_token();
+        let started_at = Instant::now();
+        rate_limiter.wait_for_token();
+
+        assert!(started_at.elapsed() >= Duration::from_millis(900));
+    }
+
+    #[test]
+    fn should_compute_achieved_send_rate() {
+
+        let mut summary = ResponseSummary {
+            packet_count: 0,
+            arp_count: 0,
+            duration_ms: 0,
+            responses_per_attempt: vec![],
+            requests_sent: 1,
+            send_errors: 0,
+            send_duration_ms: 0,
+            truncated: false,
+            host_limit_reached: false,
+            cancelled: false,
+            unsolicited_hosts: vec![]
+        };
+        assert_eq!(summary.achieved_send_rate(), None);
+
+        summary.requests_sent = 501;
+        summary.send_duration_ms = 500;
+        assert_eq!(summary.achieved_send_rate(), Some(1000_f64));
+    }
+
+    fn build_arp_packet(operation: ArpOperation, sender_mac: MacAddr, sender_ipv4: Ipv4Addr, target_ipv4: Ipv4Addr) -> Vec<u8> {
+
+        let mut arp_buffer = vec![0u8; ARP_PACKET_SIZE];
+        let mut arp_packet = MutableArpPacket::new(&mut arp_buffer).unwrap();
+        arp_packet.set_operation(operation);
+        arp_packet.set_sender_hw_addr(sender_mac);
+        arp_packet.set_sender_proto_addr(sender_ipv4);
+        arp_packet.set_target_proto_addr(target_ipv4);
+        arp_buffer
+    }
+
+    #[test]
+    fn should_detect_probe_conflicts() {
+
+        let candidate = Ipv4Addr::new(192, 168, 1, 50);
+        let own_mac = MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72);
+        let other_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+
+        let reply = build_arp_packet(ArpOperations::Reply, other_mac, candidate, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(find_probe_conflict(&ArpPacket::new(&reply).unwrap(), candidate, Some(own_mac)), Some(other_mac));
+
+        let other_probe = build_arp_packet(ArpOperations::Request, other_mac, Ipv4Addr::UNSPECIFIED, candidate);
+        assert_eq!(find_probe_conflict(&ArpPacket::new(&other_probe).unwrap(), candidate, Some(own_mac)), Some(other_mac));
+
+        let own_probe = build_arp_packet(ArpOperations::Request, own_mac, Ipv4Addr::UNSPECIFIED, candidate);
+        assert_eq!(find_probe_conflict(&ArpPacket::new(&own_probe).unwrap(), candidate, Some(own_mac)), None);
+
+        let unrelated = build_arp_packet(ArpOperations::Request, other_mac, Ipv4Addr::new(192, 168, 1, 1), candidate);
+        assert_eq!(find_probe_conflict(&ArpPacket::new(&unrelated).unwrap(), candidate, Some(own_mac)), None);
+    }
+
+    #[test]
+    fn should_find_interface_ipv4() {
+
+        let interface = NetworkInterface {
+            name: "eth0".to_string(),
+            description: String::new(),
+            index: 2,
+            mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            ips: vec!["192.168.1.21/24".parse().unwrap()],
+            flags: 0
+        };
+
+        assert!(is_interface_ipv4(&interface, Ipv4Addr::new(192, 168, 1, 21)));
+        assert!(!is_interface_ipv4(&interface, Ipv4Addr::new(192, 168, 1, 250)));
+    }
+
+    fn build_options(arguments: &[&str]) -> Arc<ScanOptions> {
+
+        ScanOptions::new(&crate::args::build_args().get_matches_from([&["arp-scan"], arguments].concat())).unwrap()
+    }
+
+    fn build_scan_pass() -> ScanPass {
+
+        ScanPass {
+            interface_name: "eth0".to_string(),
+            own_mac: None,
+            source_ipv4: Ipv4Addr::new(192, 168, 1, 21),
+            vlan_id: None,
+            host_sender: None,
+            host_callback: None,
+            event_sender: None,
+            frame_filter: None,
+            resolvers: None,
+            targets: None
+        }
+    }
+
+    #[test]
+    fn should_report_interface_errors() {
+
+        let interface = NetworkInterface {
+            name: "eth0".to_string(),
+            description: String::new(),
+            index: 2,
+            mac: None,
+            ips: vec!["fe80::1/64".parse().unwrap()],
+            flags: 0
+        };
+        let options = build_options(&["-i", "eth9"]);
+
+        let configuration = compute_network_configuration(std::slice::from_ref(&interface), &options);
+        assert!(matches!(configuration, Err(ScanError::InterfaceNotFound(name)) if name == "eth9"));
+        assert!(matches!(find_source_ip(&interface, None), Err(ScanError::Interface(_))));
+        assert!(matches!(build_arp_frame(&interface, Ipv4Addr::new(192, 168, 1, 21), Ipv4Addr::new(192, 168, 1, 1), None, None, &options), Err(ScanError::Interface(_))));
+    }
+
+    #[test]
+    fn should_plan_scan_without_channel() {
+
+        let mut interface = NetworkInterface {
+            name: "eth0".to_string(),
+            description: String::new(),
+            index: 2,
+            mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            ips: vec!["192.168.1.21/24".parse().unwrap()],
+            flags: 0
+        };
+        let options = build_options(&["-n", "192.168.1.0/30", "--exclude", "192.168.1.0/32", "-Q", "10"]);
+        let network: IpNetwork = "192.168.1.0/30".parse().unwrap();
+
+        let scan_plans = plan_scan(&[(&interface, vec![&network])], &options).unwrap();
+        assert_eq!(scan_plans.len(), 1);
+        assert_eq!(scan_plans[0].target_count, 3);
+        assert_eq!((scan_plans[0].first_target, scan_plans[0].last_target), (Some(Ipv4Addr::new(192, 168, 1, 1)), Some(Ipv4Addr::new(192, 168, 1, 3))));
+        assert_eq!(scan_plans[0].vlan_id, Some(10));
+        assert_eq!(scan_plans[0].destination_mac, MacAddr::broadcast());
+        let example_frame = scan_plans[0].example_frame.as_ref().unwrap();
+        assert_eq!(example_frame.len(), ETHERNET_VLAN_PACKET_SIZE);
+        assert_eq!(extract_arp_payload(example_frame, Some(10)).and_then(ArpPacket::new).map(|arp| arp.get_target_proto_addr()), Some(Ipv4Addr::new(192, 168, 1, 1)));
+
+        // Frames which could not be built are reported before any channel is
+        // opened.
+        interface.mac = None;
+        assert!(matches!(plan_scan(&[(&interface, vec![&network])], &options), Err(ScanError::Interface(_))));
+    }
+
+    #[test]
+    fn should_merge_interface_summaries() {
+
+        let mut summary = ResponseSummary {
+            packet_count: 10,
+            arp_count: 4,
+            duration_ms: 2000,
+            responses_per_attempt: vec![3],
+            ..ResponseSummary::default()
+        };
+        summary.merge(ResponseSummary {
+            packet_count: 5,
+            arp_count: 2,
+            duration_ms: 1500,
+            responses_per_attempt: vec![1, 2],
+            truncated: true,
+            ..ResponseSummary::default()
+        });
+
+        assert_eq!(summary.packet_count, 15);
+        assert_eq!(summary.arp_count, 6);
+        assert_eq!(summary.duration_ms, 3500);
+        assert_eq!(summary.responses_per_attempt, vec![4, 2]);
+        assert!(summary.truncated);
+    }
+
+    #[test]
+    fn should_parse_neighbor_table() {
+
+        let content = "IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         00:22:72:d7:b5:23     *        eth0
+192.168.1.30     0x1         0x0         00:00:00:00:00:00     *        eth0
+10.0.0.4         0x1         0x6         40:55:82:c3:e5:5b     *        wlan0
+";
+
+        assert_eq!(parse_neighbor_table(content), vec![
+            NeighborEntry {
+                ipv4: Ipv4Addr::new(192, 168, 1, 1),
+                mac: MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23),
+                device: "eth0".to_string()
+            },
+            NeighborEntry {
+                ipv4: Ipv4Addr::new(10, 0, 0, 4),
+                mac: MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b),
+                device: "wlan0".to_string()
+            }
+        ]);
+    }
+
+    #[test]
+    fn should_apply_verify_statuses() {
+
+        let kernel_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let spoofed_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+        let neighbors = vec![
+            NeighborEntry { ipv4: Ipv4Addr::new(192, 168, 1, 1), mac: kernel_mac, device: "eth0".to_string() },
+            NeighborEntry { ipv4: Ipv4Addr::new(192, 168, 1, 2), mac: kernel_mac, device: "eth0".to_string() },
+            NeighborEntry { ipv4: Ipv4Addr::new(192, 168, 1, 3), mac: kernel_mac, device: "eth0".to_string() }
+        ];
+        let answered = |ipv4: Ipv4Addr, mac: MacAddr| TargetDetails {
+            response_time_ms: Some(3),
+            ..TargetDetails::for_test(ipv4, mac)
+        };
+        let target_details = vec![
+            answered(Ipv4Addr::new(192, 168, 1, 1), kernel_mac),
+            answered(Ipv4Addr::new(192, 168, 1, 2), spoofed_mac),
+            answered(Ipv4Addr::new(192, 168, 1, 99), spoofed_mac)
+        ];
+
+        let verified = apply_verify_statuses(target_details, &neighbors, "eth0");
+        let statuses: Vec<Option<VerifyStatus>> = verified.iter().map(|detail| detail.verify_status).collect();
+
+        assert_eq!(statuses, vec![Some(VerifyStatus::Alive), Some(VerifyStatus::Mismatch(kernel_mac)), Some(VerifyStatus::Stale)]);
+        assert_eq!(verified[1].mac, spoofed_mac);
+        assert_eq!(verified[2].mac, kernel_mac);
+    }
+
+    #[test]
+    fn should_find_address_conflicts() {
+
+        let detail = TargetDetails::for_test;
+        let first_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let second_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+        let target_details = vec![
+            detail(Ipv4Addr::new(192, 168, 1, 20), second_mac),
+            detail(Ipv4Addr::new(192, 168, 1, 1), first_mac),
+            detail(Ipv4Addr::new(192, 168, 1, 20), first_mac)
+        ];
+
+        assert_eq!(find_address_conflicts(&target_details), vec![AddressConflict {
+            ipv4: Ipv4Addr::new(192, 168, 1, 20),
+            vlan_id: None,
+            macs: vec![first_mac, second_mac]
+        }]);
+    }
+
+    #[test]
+    fn should_find_mac_with_three_ipv4s() {
+
+        let detail = TargetDetails::for_test;
+        let nat_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let other_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+        let target_details = vec![
+            detail(Ipv4Addr::new(192, 168, 1, 30), nat_mac),
+            detail(Ipv4Addr::new(192, 168, 1, 2), nat_mac),
+            detail(Ipv4Addr::new(192, 168, 1, 10), other_mac),
+            detail(Ipv4Addr::new(192, 168, 1, 4), nat_mac)
+        ];
+
+        assert_eq!(find_shared_macs(&target_details), vec![SharedMac {
+            mac: nat_mac,
+            ipv4s: vec![Ipv4Addr::new(192, 168, 1, 2), Ipv4Addr::new(192, 168, 1, 4), Ipv4Addr::new(192, 168, 1, 30)]
+        }]);
+    }
+
+    #[test]
+    fn should_group_hosts_by_mac() {
+
+        let detail = |ipv4: Ipv4Addr, mac: MacAddr, hostname: Option<&str>| TargetDetails {
+            hostname: hostname.map(|hostname| hostname.to_string()),
+            vendor: Some("Vendor & Co.".to_string()),
+            reply_count: 1,
+            ..TargetDetails::for_test(ipv4, mac)
+        };
+        let router_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let other_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+        let target_details = vec![
+            detail(Ipv4Addr::new(192, 168, 1, 30), other_mac, None),
+            detail(Ipv4Addr::new(192, 168, 1, 254), router_mac, Some("gateway.home")),
+            detail(Ipv4Addr::new(192, 168, 1, 1), router_mac, Some("router.home")),
+            detail(Ipv4Addr::new(192, 168, 1, 2), router_mac, Some("router.home"))
+        ];
+
+        assert_eq!(group_hosts_by_mac(&target_details), vec![
+            MacGroup {
+                mac: router_mac,
+                vendor: Some("Vendor & Co.".to_string()),
+                ipv4s: vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2), Ipv4Addr::new(192, 168, 1, 254)],
+                hostnames: vec!["router.home".to_string(), "gateway.home".to_string()]
+            },
+            MacGroup {
+                mac: other_mac,
+                vendor: Some("Vendor & Co.".to_string()),
+                ipv4s: vec![Ipv4Addr::new(192, 168, 1, 30)],
+                hostnames: vec![]
+            }
+        ]);
+    }
+
+    fn build_ethernet_frame(vlan_id: Option<u16>, arp_buffer: &[u8]) -> Vec<u8> {
+
+        let header_size = MutableEthernetPacket::minimum_packet_size();
+        let mut frame = vec![0u8; header_size];
+        match vlan_id {
+            Some(vlan_id) => {
+                frame[12..14].copy_from_slice(&EtherTypes::Vlan.0.to_be_bytes());
+                frame.extend_from_slice(&vlan_id.to_be_bytes());
+                frame.extend_from_slice(&EtherTypes::Arp.0.to_be_bytes());
+            },
+            None => frame[12..14].copy_from_slice(&EtherTypes::Arp.0.to_be_bytes())
+        }
+        frame.extend_from_slice(arp_buffer);
+        frame
+    }
+
+    #[test]
+    fn should_filter_frames_by_vlan_tag() {
+
+        let arp_buffer = build_arp_packet(ArpOperations::Reply, MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23), Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 21));
+        let untagged_frame = build_ethernet_frame(None, &arp_buffer);
+        let tagged_frame = build_ethernet_frame(Some(20), &arp_buffer);
+
+        assert_eq!(extract_arp_payload(&untagged_frame, None), Some(arp_buffer.as_slice()));
+        assert_eq!(extract_arp_payload(&tagged_frame, Some(20)), Some(arp_buffer.as_slice()));
+        assert_eq!(extract_arp_payload(&tagged_frame, Some(30)), None);
+        assert_eq!(extract_arp_payload(&tagged_frame, None), None);
+        assert_eq!(extract_arp_payload(&untagged_frame, Some(20)), None);
+    }
+
+    // A datalink layer replaying frames, the scan is stopped once all frames
+    // have been read.
+    struct ReplayLink {
+        frames: Mutex<Vec<Vec<u8>>>,
+        timed_out: Arc<AtomicBool>
+    }
+
+    impl ReplayLink {
+
+        fn new(frames: Vec<Vec<u8>>, scan_state: &ScanState) -> Self {
+
+            ReplayLink {
+                frames: Mutex::new(frames),
+                timed_out: Arc::clone(&scan_state.timed_out)
+            }
+        }
+    }
+
+    impl DataLink for ReplayLink {
+
+        fn send(&self, _: &[u8]) -> std::io::Result<()> {
+
+            Ok(())
+        }
+
+        fn recv(&self, _: Duration) -> std::io::Result<Option<Vec<u8>>> {
+
+            let mut frames = self.frames.lock().unwrap();
+            match frames.is_empty() {
+                true => {
+                    self.timed_out.store(true, Ordering::Relaxed);
+                    Ok(None)
+                },
+                false => Ok(Some(frames.remove(0)))
+            }
+        }
+    }
+
+    #[test]
+    fn should_stream_each_new_host_once() {
+
+        let options = build_options(&["--numeric"]);
+        let scan_state = ScanState::default();
+        let router_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let printer_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+        let router_reply = build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, router_mac, Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 21)));
+        let printer_reply = build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, printer_mac, Ipv4Addr::new(192, 168, 1, 30), Ipv4Addr::new(192, 168, 1, 21)));
+
+        let link = ReplayLink::new(vec![router_reply.clone(), router_reply, vec![0u8; 4], printer_reply], &scan_state);
+        let (host_sender, host_receiver) = std::sync::mpsc::channel();
+        let scan_pass = ScanPass {
+            host_sender: Some(host_sender),
+            ..build_scan_pass()
+        };
+        let mut vendor_list = VendorDatabase::load_from_path("./data/ieee-oui.csv").unwrap();
+
+        let (summary, target_details) = receive_arp_responses(&link, options, scan_pass, scan_state.clone(), None, &mut vendor_list).unwrap();
+        let streamed_hosts: Vec<TargetDetails> = host_receiver.iter().collect();
+
+        assert_eq!(summary.packet_count, 4);
+        assert_eq!(streamed_hosts.iter().map(|host| host.ipv4).collect::<Vec<Ipv4Addr>>(), [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 30)]);
+        assert_eq!(streamed_hosts[0].vendor.as_deref(), Some("American Micro-Fuel Device Corp."));
+        assert_eq!(target_details.len(), 2);
+        assert_eq!(target_details[1].vendor.as_deref(), Some("Nokia"));
+        assert_eq!(scan_state.found_hosts.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn should_call_host_callback_once_per_host() {
+
+        let options = build_options(&["--numeric"]);
+        let scan_state = ScanState::default();
+        let router_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let router_reply = build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, router_mac, Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 21)));
+        let moved_reply = build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, router_mac, Ipv4Addr::new(192, 168, 1, 2), Ipv4Addr::new(192, 168, 1, 21)));
+
+        let link = ReplayLink::new(vec![router_reply.clone(), moved_reply, router_reply.clone(), router_reply], &scan_state);
+        let found_hosts = Arc::new(Mutex::new(Vec::<(Ipv4Addr, Option<String>)>::new()));
+        let cloned_found_hosts = Arc::clone(&found_hosts);
+        let host_callback: HostCallback = Arc::new(Mutex::new(move |host: &TargetDetails| {
+            cloned_found_hosts.lock().unwrap().push((host.ipv4, host.vendor.clone()));
+        }));
+        let scan_pass = ScanPass {
+            host_callback: Some(host_callback),
+            ..build_scan_pass()
+        };
+        let mut vendor_list = VendorDatabase::load_from_path("./data/ieee-oui.csv").unwrap();
+
+        let (_, target_details) = receive_arp_responses(&link, options, scan_pass, scan_state, None, &mut vendor_list).unwrap();
+
+        let vendor = Some("American Micro-Fuel Device Corp.".to_string());
+        assert_eq!(*found_hosts.lock().unwrap(), [(Ipv4Addr::new(192, 168, 1, 1), vendor.clone()), (Ipv4Addr::new(192, 168, 1, 2), vendor)]);
+        assert_eq!(target_details.len(), 2);
+    }
+
+    #[test]
+    fn should_send_host_found_events() {
+
+        let options = build_options(&["--numeric"]);
+        let scan_state = ScanState::default();
+        let router_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let router_reply = build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, router_mac, Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 21)));
+
+        let link = ReplayLink::new(vec![router_reply.clone(), router_reply], &scan_state);
+        let (event_sender, event_receiver) = std::sync::mpsc::channel();
+        let scan_pass = ScanPass {
+            event_sender: Some(event_sender),
+            ..build_scan_pass()
+        };
+        let mut vendor_list = VendorDatabase::load_from_path("./data/ieee-oui.csv").unwrap();
+
+        receive_arp_responses(&link, options, scan_pass, scan_state, None, &mut vendor_list).unwrap();
+        let events: Vec<ScanEvent> = event_receiver.iter().collect();
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            ScanEvent::HostFound(host) => {
+                assert_eq!(host.ipv4, Ipv4Addr::new(192, 168, 1, 1));
+                assert_eq!(host.vendor.as_deref(), Some("American Micro-Fuel Device Corp."));
+            },
+            _ => panic!("Expected a HostFound event")
+        }
+    }
+
+    fn replay_with_filter(frames: Vec<Vec<u8>>, frame_filter: Option<FrameFilter>) -> (ResponseSummary, Vec<TargetDetails>) {
+
+        let options = build_options(&["--numeric"]);
+        let scan_state = ScanState::default();
+        let link = ReplayLink::new(frames, &scan_state);
+        let scan_pass = ScanPass {
+            own_mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            frame_filter,
+            ..build_scan_pass()
+        };
+        let mut vendor_list = VendorDatabase::disabled();
+
+        receive_arp_responses(&link, options, scan_pass, scan_state, None, &mut vendor_list).unwrap()
+    }
+
+    fn build_recorded_frames() -> Vec<Vec<u8>> {
+
+        let own_mac = MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72);
+        let router_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let printer_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+
+        let mut ipv4_frame = vec![0u8; MutableEthernetPacket::minimum_packet_size() + 20];
+        ipv4_frame[6..12].copy_from_slice(&[0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5c]);
+        ipv4_frame[12..14].copy_from_slice(&EtherTypes::Ipv4.0.to_be_bytes());
+        ipv4_frame[14] = 0x45;
+        ipv4_frame[26..30].copy_from_slice(&[192, 168, 1, 40]);
+
+        vec![
+            build_ethernet_frame(None, &build_arp_packet(ArpOperations::Request, own_mac, Ipv4Addr::new(192, 168, 1, 21), Ipv4Addr::new(192, 168, 1, 1))),
+            build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, router_mac, Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 21))),
+            vec![0u8; 4],
+            ipv4_frame,
+            build_ethernet_frame(Some(10), &build_arp_packet(ArpOperations::Reply, printer_mac, Ipv4Addr::new(192, 168, 1, 31), Ipv4Addr::new(192, 168, 1, 21))),
+            build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, printer_mac, Ipv4Addr::new(192, 168, 1, 30), Ipv4Addr::new(192, 168, 1, 21)))
+        ]
+    }
+
+    #[test]
+    fn should_keep_default_filtering_with_pass_through_filter() {
+
+        let call_count = Arc::new(AtomicUsize::new(0));
+        let cloned_call_count = Arc::clone(&call_count);
+        let pass_through: FrameFilter = Arc::new(Mutex::new(move |_: &ReceivedFrame, candidate: Option<TargetDetails>| {
+            cloned_call_count.fetch_add(1, Ordering::Relaxed);
+            candidate.map_or(FrameVerdict::Ignore, FrameVerdict::Accept)
+        }));
+
+        let (default_summary, default_details) = replay_with_filter(build_recorded_frames(), None);
+        let (filtered_summary, filtered_details) = replay_with_filter(build_recorded_frames(), Some(pass_through));
+
+        let hosts = |details: &[TargetDetails]| details.iter().map(|detail| (detail.ipv4, detail.mac, detail.interface.clone(), detail.vlan_id)).collect::<Vec<_>>();
+        assert_eq!(hosts(&default_details), [
+            (Ipv4Addr::new(192, 168, 1, 1), MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23), "eth0".to_string(), None),
+            (Ipv4Addr::new(192, 168, 1, 30), MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b), "eth0".to_string(), None)
+        ]);
+        assert_eq!(hosts(&filtered_details), hosts(&default_details));
+        assert_eq!((filtered_summary.packet_count, filtered_summary.arp_count), (6, 3));
+        assert_eq!((default_summary.packet_count, default_summary.arp_count), (6, 3));
+        assert_eq!(call_count.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn should_record_hosts_accepted_by_frame_filter() {
+
+        // Hosts sending IPv4 traffic are recorded as well, while the router
+        // is ignored.
+        let frame_filter: FrameFilter = Arc::new(Mutex::new(|frame: &ReceivedFrame, candidate: Option<TargetDetails>| {
+            match (candidate, frame.ethernet.get_ethertype()) {
+                (Some(host), _) if host.ipv4 == Ipv4Addr::new(192, 168, 1, 1) => FrameVerdict::Ignore,
+                (Some(host), _) => FrameVerdict::Accept(host),
+                (None, EtherTypes::Ipv4) => {
+                    let payload = frame.ethernet.payload();
+                    let source_ipv4 = Ipv4Addr::new(payload[12], payload[13], payload[14], payload[15]);
+                    FrameVerdict::Accept(frame.target_details(source_ipv4, frame.ethernet.get_source()))
+                },
+                (None, _) => FrameVerdict::Ignore
+            }
+        }));
+
+        let (_, target_details) = replay_with_filter(build_recorded_frames(), Some(frame_filter));
+
+        let hosts: Vec<(Ipv4Addr, MacAddr)> = target_details.iter().map(|detail| (detail.ipv4, detail.mac)).collect();
+        assert_eq!(hosts, [
+            (Ipv4Addr::new(192, 168, 1, 30), MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b)),
+            (Ipv4Addr::new(192, 168, 1, 40), MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5c))
+        ]);
+    }
+
+    // A datalink layer failing on each read.
+    struct FailingLink;
+
+    impl DataLink for FailingLink {
+
+        fn send(&self, _: &[u8]) -> std::io::Result<()> {
+
+            Ok(())
+        }
+
+        fn recv(&self, _: Duration) -> std::io::Result<Option<Vec<u8>>> {
+
+            Err(std::io::Error::from(ErrorKind::NotConnected))
+        }
+    }
+
+    #[test]
+    fn should_return_receive_errors() {
+
+        let options = build_options(&["--numeric"]);
+        let scan_pass = build_scan_pass();
+
+        let result = receive_arp_responses(&FailingLink, options, scan_pass, ScanState::default(), None, &mut VendorDatabase::disabled());
+        assert!(matches!(result, Err(ScanError::Packet(_))));
+    }
+
+    #[test]
+    fn should_stop_receiving_once_cancelled() {
+
+        let options = build_options(&["--numeric"]);
+        let scan_state = ScanState::default();
+        let router_reply = build_ethernet_frame(None, &build_arp_packet(ArpOperations::Reply, MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23), Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 21)));
+
+        let link = ReplayLink::new(vec![router_reply], &scan_state);
+        let scan_pass = build_scan_pass();
+        scan_state.cancellation.cancel();
+
+        let (summary, target_details) = receive_arp_responses(&link, options, scan_pass, scan_state.clone(), None, &mut VendorDatabase::disabled()).unwrap();
+
+        assert_eq!(summary.packet_count, 0);
+        assert!(target_details.is_empty());
+        assert!(!scan_state.timed_out.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn should_exclude_own_arp_packets() {
+
+        let own_mac = MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72);
+        let source_ipv4 = Ipv4Addr::new(192, 168, 1, 21);
+
+        let own_reply = build_arp_packet(ArpOperations::Reply, own_mac, source_ipv4, Ipv4Addr::new(192, 168, 1, 1));
+        assert!(is_own_arp_packet(&ArpPacket::new(&own_reply).unwrap(), Some(own_mac), source_ipv4));
+
+        let own_request = build_arp_packet(ArpOperations::Request, own_mac, source_ipv4, Ipv4Addr::new(192, 168, 1, 1));
+        assert!(is_own_arp_packet(&ArpPacket::new(&own_request).unwrap(), Some(own_mac), source_ipv4));
+    }
+
+    #[test]
+    fn should_keep_foreign_arp_packets() {
+
+        let own_mac = MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72);
+        let other_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let source_ipv4 = Ipv4Addr::new(192, 168, 1, 21);
+
+        let host_reply = build_arp_packet(ArpOperations::Reply, other_mac, Ipv4Addr::new(192, 168, 1, 1), source_ipv4);
+        assert!(!is_own_arp_packet(&ArpPacket::new(&host_reply).unwrap(), Some(own_mac), source_ipv4));
+
+        // Another host using our IPv4 is an address conflict, not a loop.
+        let conflicting_reply = build_arp_packet(ArpOperations::Reply, other_mac, source_ipv4, Ipv4Addr::new(192, 168, 1, 1));
+        assert!(!is_own_arp_packet(&ArpPacket::new(&conflicting_reply).unwrap(), Some(own_mac), source_ipv4));
+
+        // Our MAC announcing another IPv4 (e.g. a secondary address).
+        let secondary_reply = build_arp_packet(ArpOperations::Reply, own_mac, Ipv4Addr::new(192, 168, 1, 22), Ipv4Addr::new(192, 168, 1, 1));
+        assert!(!is_own_arp_packet(&ArpPacket::new(&secondary_reply).unwrap(), Some(own_mac), source_ipv4));
+
+        assert!(!is_own_arp_packet(&ArpPacket::new(&host_reply).unwrap(), None, source_ipv4));
+    }
+
+    #[test]
+    fn should_reach_host_limit() {
+
+        let scan_state = ScanState::default();
+        assert!(!scan_state.has_reached_host_limit(Some(2)));
+
+        scan_state.found_hosts.fetch_add(2, Ordering::Relaxed);
+        assert!(scan_state.has_reached_host_limit(Some(2)));
+        assert!(!scan_state.has_reached_host_limit(Some(3)));
+        assert!(!scan_state.has_reached_host_limit(None));
+
+        // Interface scans share the count of a round, only a new round resets it.
+        scan_state.reset();
+        assert!(scan_state.has_reached_host_limit(Some(2)));
+        scan_state.reset_round();
+        assert!(!scan_state.has_reached_host_limit(Some(1)));
+    }
+
+    #[test]
+    fn should_merge_cached_neighbors() {
+
+        let answered_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let target_details = vec![TargetDetails {
+            response_time_ms: Some(12),
+            ..TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, 1), answered_mac)
+        }];
+        let neighbors = vec![
+            NeighborEntry { ipv4: Ipv4Addr::new(192, 168, 1, 1), mac: answered_mac, device: "eth0".to_string() },
+            NeighborEntry { ipv4: Ipv4Addr::new(192, 168, 1, 30), mac: MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b), device: "eth0".to_string() }
+        ];
+
+        let merged = merge_cached_neighbors(target_details, &neighbors, "eth0");
+        let origins: Vec<(Ipv4Addr, Option<HostOrigin>)> = merged.iter().map(|detail| (detail.ipv4, detail.origin)).collect();
+
+        assert_eq!(origins, vec![
+            (Ipv4Addr::new(192, 168, 1, 1), Some(HostOrigin::Scan)),
+            (Ipv4Addr::new(192, 168, 1, 30), Some(HostOrigin::Cache))
+        ]);
+        assert_eq!(merged[1].interface, "eth0");
+        assert_eq!(merged[1].response_time_ms, None);
+    }
+
 }
@@ -0,0 +1,834 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use pnet_datalink::NetworkInterface;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ScanError;
+use crate::utils;
+
+// Names of the built-in scan profiles, which can not be used for saved
+// profiles (the '--profile' option selects both).
+pub const BUILTIN_PROFILE_NAMES: [&str; 8] = ["default", "d", "fast", "f", "stealth", "s", "chaos", "c"];
+
+// Options describing the program invocation rather than a scan, they are
+// never saved in a profile (nor read from the config file).
+const UNSAVED_OPTIONS: [&str; 8] = ["save_profile", "config", "no_config", "list_profiles", "list", "update_vendors", "help", "version"];
+
+/**
+ * The value of an option in a saved profile: a flag, a single value or the
+ * values of a repeated option (such as '--interface').
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ProfileValue {
+    Flag(bool),
+    Value(String),
+    Values(Vec<String>)
+}
+
+/**
+ * A saved set of CLI options, keyed by their long name (such as 'timeout' or
+ * 'interface'). Profiles are stored as TOML files, and merged with the
+ * options given on the command line (which take precedence).
+ *
+ * ```toml
+ * interface = ["eth0"]
+ * network = "192.168.1.0/24"
+ * timeout = "500ms"
+ * numeric = true
+ * ```
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct ScanProfile {
+    pub options: BTreeMap<String, ProfileValue>
+}
+
+impl ScanProfile {
+
+    /**
+     * Build a profile from the options explicitly given on the command line,
+     * default values are not saved.
+     */
+    pub fn from_matches(command: &Command, matches: &ArgMatches) -> ScanProfile {
+
+        let mut options: BTreeMap<String, ProfileValue> = BTreeMap::new();
+        for arg in command.get_arguments() {
+
+            let (id, long_name) = match arg.get_long() {
+                Some(long_name) if !UNSAVED_OPTIONS.contains(&arg.get_id()) => (arg.get_id(), long_name),
+                _ => continue
+            };
+            if matches.value_source(id) != Some(clap::ValueSource::CommandLine) {
+                continue;
+            }
+
+            let values: Vec<String> = matches.get_raw(id).map(|raw_values| {
+                raw_values.map(|value| value.to_string_lossy().into_owned()).collect()
+            }).unwrap_or_default();
+
+            let value = match (arg.is_takes_value_set(), arg.get_action()) {
+                (false, _) => ProfileValue::Flag(true),
+                (true, ArgAction::Append) => ProfileValue::Values(values),
+                (true, _) => ProfileValue::Value(values.into_iter().next().unwrap_or_default())
+            };
+            options.insert(long_name.to_string(), value);
+        }
+        ScanProfile { options }
+    }
+
+    pub fn parse(content: &str) -> Result<ScanProfile, String> {
+
+        toml::from_str::<ScanProfile>(content).map_err(|err| format!("Invalid profile ({})", err))
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+
+        toml::to_string(self).map_err(|err| format!("Could not serialize profile ({})", err))
+    }
+
+    /**
+     * Merge the options of another profile over this one, the options of the
+     * other profile take precedence. Options of this profile conflicting with
+     * an option set by the other one are dropped as well (such as 'interface'
+     * overridden by 'all-interfaces'), as declared on the CLI arguments.
+     */
+    pub fn merge(mut self, overrides: ScanProfile, command: &Command) -> ScanProfile {
+
+        let overriding_args: Vec<&Arg> = overrides.options.iter()
+            .filter(|(_, value)| **value != ProfileValue::Flag(false))
+            .filter_map(|(name, _)| find_arg(command, name))
+            .collect();
+        self.options.retain(|name, _| match find_arg(command, name) {
+            Some(arg) => !overriding_args.iter().any(|overriding_arg| args_conflict(command, arg, overriding_arg)),
+            None => true
+        });
+        self.options.extend(overrides.options);
+        self
+    }
+
+    /**
+     * Convert the profile to CLI arguments, so that the merged options go
+     * through the usual argument parsing. Unknown option names are rejected.
+     */
+    pub fn to_arguments(&self, command: &Command) -> Result<Vec<String>, String> {
+
+        let mut arguments: Vec<String> = vec![];
+        for (name, value) in &self.options {
+
+            let is_known = command.get_arguments().any(|arg| arg.get_long() == Some(name.as_str()) && !UNSAVED_OPTIONS.contains(&arg.get_id()));
+            if !is_known {
+                return Err(format!("Unknown option '{}' in profile", name));
+            }
+            match value {
+                ProfileValue::Flag(true) => arguments.push(format!("--{}", name)),
+                ProfileValue::Flag(false) => {},
+                ProfileValue::Value(value) => arguments.push(format!("--{}={}", name, value)),
+                ProfileValue::Values(values) => arguments.extend(values.iter().map(|value| format!("--{}={}", name, value)))
+            }
+        }
+        Ok(arguments)
+    }
+
+    /**
+     * One-line summary of the options set by the profile, such as
+     * 'interface=eth0, numeric, timeout=500ms'.
+     */
+    pub fn summary(&self) -> String {
+
+        if self.options.is_empty() {
+            return "no options".to_string();
+        }
+        self.options.iter().map(|(name, value)| match value {
+            ProfileValue::Flag(true) => name.clone(),
+            ProfileValue::Flag(false) => format!("{}=false", name),
+            ProfileValue::Value(value) => format!("{}={}", name, value),
+            ProfileValue::Values(values) => format!("{}={}", name, values.join(","))
+        }).collect::<Vec<String>>().join(", ")
+    }
+
+    /**
+     * Interface names of the profile which do not exist on this host, they
+     * are checked when the profile is loaded (before opening any channel).
+     */
+    pub fn find_unknown_interfaces(&self, interfaces: &[NetworkInterface]) -> Vec<String> {
+
+        let interface_names: Vec<String> = match self.options.get("interface") {
+            Some(ProfileValue::Values(names)) => names.clone(),
+            Some(ProfileValue::Value(name)) => vec![name.clone()],
+            _ => vec![]
+        };
+        interface_names.into_iter().filter(|name| !interfaces.iter().any(|interface| &interface.name == name)).collect()
+    }
+}
+
+/**
+ * The options of a config file: the defaults (top-level keys) and the named
+ * profiles of the '[profile.NAME]' sections, which override the defaults when
+ * selected with '--profile NAME'.
+ *
+ * ```toml
+ * timeout = "2s"
+ * output = "json"
+ *
+ * [profile.lab]
+ * interface = "eth1"
+ * retry = 3
+ * ```
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScanConfig {
+    pub defaults: ScanProfile,
+    pub profiles: BTreeMap<String, ScanProfile>
+}
+
+impl ScanConfig {
+
+    /**
+     * Parse a config file, whose keys are the long option names. Unlike saved
+     * profiles (written by the CLI), config files are written by hand: TOML
+     * numbers are accepted for option values and a single string for
+     * repeated options. Unknown keys are returned apart to be reported, while
+     * a value of the wrong type fails with the key name and expected type.
+     */
+    pub fn parse(content: &str, command: &Command) -> Result<(ScanConfig, Vec<String>), String> {
+
+        let mut table = toml::from_str::<toml::Table>(content).map_err(|err| format!("Invalid TOML ({})", err))?;
+
+        let mut unknown_keys: Vec<String> = vec![];
+        let mut profiles: BTreeMap<String, ScanProfile> = BTreeMap::new();
+        // The 'profile' key either holds the named profiles, or selects a
+        // profile like the '--profile' option.
+        match table.remove("profile") {
+            Some(toml::Value::Table(profile_tables)) => for (name, value) in profile_tables {
+                validate_profile_name(&name)?;
+                let profile_table = match value {
+                    toml::Value::Table(profile_table) => profile_table,
+                    _ => return Err(format!("Invalid value for 'profile.{}' (expected a table)", name))
+                };
+                let prefix = format!("profile.{}.", name);
+                profiles.insert(name, parse_config_options(profile_table, command, &prefix, &mut unknown_keys)?);
+            },
+            Some(value) => {
+                table.insert("profile".to_string(), value);
+            },
+            None => {}
+        }
+
+        let defaults = parse_config_options(table, command, "", &mut unknown_keys)?;
+        Ok((ScanConfig { defaults, profiles }, unknown_keys))
+    }
+}
+
+/**
+ * Options of a config file table (the defaults or a named profile), the
+ * unknown keys are collected with the given prefix.
+ */
+fn parse_config_options(table: toml::Table, command: &Command, prefix: &str, unknown_keys: &mut Vec<String>) -> Result<ScanProfile, String> {
+
+    let mut options: BTreeMap<String, ProfileValue> = BTreeMap::new();
+    for (key, value) in table {
+
+        let arg = command.get_arguments().find(|arg| arg.get_long() == Some(key.as_str()) && !UNSAVED_OPTIONS.contains(&arg.get_id()));
+        let arg = match arg {
+            Some(arg) => arg,
+            None => {
+                unknown_keys.push(format!("{}{}", prefix, key));
+                continue;
+            }
+        };
+
+        let profile_value = match (arg.is_takes_value_set(), arg.get_action(), value) {
+            (false, _, toml::Value::Boolean(enabled)) => ProfileValue::Flag(enabled),
+            (false, _, _) => return Err(format!("Invalid value for '{}{}' (expected a boolean)", prefix, key)),
+            (true, ArgAction::Append, toml::Value::Array(values)) => {
+                let values: Option<Vec<String>> = values.into_iter().map(config_value_text).collect();
+                match values {
+                    Some(values) => ProfileValue::Values(values),
+                    None => return Err(format!("Invalid value for '{}{}' (expected an array of strings)", prefix, key))
+                }
+            },
+            (true, ArgAction::Append, value) => match config_value_text(value) {
+                Some(value) => ProfileValue::Values(vec![value]),
+                None => return Err(format!("Invalid value for '{}{}' (expected a string or an array of strings)", prefix, key))
+            },
+            (true, _, value) => match config_value_text(value) {
+                Some(value) => ProfileValue::Value(value),
+                None => return Err(format!("Invalid value for '{}{}' (expected a string)", prefix, key))
+            }
+        };
+        options.insert(key, profile_value);
+    }
+    Ok(ScanProfile { options })
+}
+
+fn find_arg<'a, 'help>(command: &'a Command<'help>, long_name: &str) -> Option<&'a Arg<'help>> {
+
+    command.get_arguments().find(|arg| arg.get_long() == Some(long_name))
+}
+
+/**
+ * Whether two arguments can not be given together, clap conflicts are only
+ * declared on one of both arguments (and exclusive arguments conflict with
+ * any other argument).
+ */
+fn args_conflict(command: &Command, first: &Arg, second: &Arg) -> bool {
+
+    if first.get_id() == second.get_id() {
+        return false;
+    }
+    first.is_exclusive_set() || second.is_exclusive_set()
+        || command.get_arg_conflicts_with(first).iter().any(|arg| arg.get_id() == second.get_id())
+        || command.get_arg_conflicts_with(second).iter().any(|arg| arg.get_id() == first.get_id())
+}
+
+/**
+ * Text of a config file value given to an option, TOML numbers are accepted
+ * as well (such as 'retry = 3').
+ */
+fn config_value_text(value: toml::Value) -> Option<String> {
+
+    match value {
+        toml::Value::String(text) => Some(text),
+        toml::Value::Integer(number) => Some(number.to_string()),
+        toml::Value::Float(number) => Some(number.to_string()),
+        _ => None
+    }
+}
+
+/**
+ * Merge the option layers of a scan, from the lowest to the highest
+ * precedence: the config file defaults, the selected profile (a config file
+ * section or a saved profile) and the command line. When a profile is
+ * selected, the 'profile' option of the defaults and of the command line
+ * selected it and is not kept (the profile may still select a built-in scan
+ * profile).
+ */
+pub fn merge_option_layers(command: &Command, defaults: ScanProfile, selected: Option<ScanProfile>, command_line: ScanProfile) -> ScanProfile {
+
+    match selected {
+        Some(selected_profile) => {
+            let (mut defaults, mut command_line) = (defaults, command_line);
+            defaults.options.remove("profile");
+            command_line.options.remove("profile");
+            defaults.merge(selected_profile, command).merge(command_line, command)
+        },
+        None => defaults.merge(command_line, command)
+    }
+}
+
+/**
+ * Apply the config file and the profile selected with '--profile', either a
+ * '[profile.NAME]' section of the config file or a saved profile (built-in
+ * scan profiles are left untouched). The options of the command line take
+ * precedence over the profile, which takes precedence over the config file
+ * defaults, and the merged options are parsed again. Unknown profiles and
+ * interfaces of the profile are rejected before any interface is selected.
+ * The profile merged with the command line is returned as well, so that it
+ * can be saved under another name (config file defaults are not saved).
+ */
+pub fn apply_saved_options(command: &Command, matches: ArgMatches, interfaces: &[NetworkInterface]) -> Result<(ArgMatches, ScanProfile), ScanError> {
+
+    let command_line_profile = ScanProfile::from_matches(command, &matches);
+    let config = load_selected_config(command, &matches).map_err(ScanError::Profile)?;
+
+    let selected_profile = config.defaults.clone().merge(command_line_profile.clone(), command);
+    let profile_name = match selected_profile.options.get("profile") {
+        Some(ProfileValue::Value(name)) if !BUILTIN_PROFILE_NAMES.contains(&name.as_str()) => Some(name.clone()),
+        _ => None
+    };
+    let named_profile = match &profile_name {
+        Some(name) => Some(load_checked_profile(name, &config, interfaces)?),
+        None if config.defaults.options.is_empty() => return Ok((matches, command_line_profile)),
+        None => None
+    };
+
+    let profile_to_save = match &named_profile {
+        Some(named_profile) => merge_option_layers(command, ScanProfile::default(), Some(named_profile.clone()), command_line_profile.clone()),
+        None => command_line_profile.clone()
+    };
+    let merged_profile = merge_option_layers(command, config.defaults, named_profile, command_line_profile);
+
+    let source_name = match &profile_name {
+        Some(name) => format!("profile '{}'", name),
+        None => "config file".to_string()
+    };
+    let arguments = merged_profile.to_arguments(command).map_err(|err| ScanError::Profile(format!("{} {}", err, source_name)))?;
+    let merged_matches = command.clone().try_get_matches_from([vec!["arp-scan".to_string()], arguments].concat()).map_err(|err| {
+        ScanError::Profile(format!("The {} can not be applied ({})", source_name, err.to_string().trim()))
+    })?;
+    Ok((merged_matches, profile_to_save))
+}
+
+/**
+ * Load a named profile (a config file section first, then a saved profile),
+ * which may only select a built-in scan profile and should only use
+ * interfaces of this host.
+ */
+fn load_checked_profile(profile_name: &str, config: &ScanConfig, interfaces: &[NetworkInterface]) -> Result<ScanProfile, ScanError> {
+
+    let named_profile = match config.profiles.get(profile_name) {
+        Some(config_profile) => config_profile.clone(),
+        None => load_profile(profile_name).map_err(ScanError::Profile)?
+    };
+    if let Some(ProfileValue::Value(nested_name)) = named_profile.options.get("profile") {
+        if !BUILTIN_PROFILE_NAMES.contains(&nested_name.as_str()) {
+            return Err(ScanError::Profile(format!("Profile '{}' should only select a built-in scan profile, not '{}'", profile_name, nested_name)));
+        }
+    }
+    let unknown_interfaces = named_profile.find_unknown_interfaces(interfaces);
+    if !unknown_interfaces.is_empty() {
+        return Err(ScanError::Profile(format!("Profile '{}' uses unknown interface {}\nUse 'arp scan -l' to list available interfaces", profile_name, unknown_interfaces.join(", "))));
+    }
+    Ok(named_profile)
+}
+
+/**
+ * Load the config file selected on the command line: none with '--no-config',
+ * the file given with '--config' or the default one.
+ */
+pub fn load_selected_config(command: &Command, matches: &ArgMatches) -> Result<ScanConfig, String> {
+
+    match matches.contains_id("no_config") {
+        true => Ok(ScanConfig::default()),
+        false => load_config(command, matches.get_one::<String>("config").map(PathBuf::from))
+    }
+}
+
+/**
+ * Load the config file given with '--config', or the default one (which may
+ * not exist). Unknown keys are reported as warnings and ignored.
+ */
+pub fn load_config(command: &Command, file_path: Option<PathBuf>) -> Result<ScanConfig, String> {
+
+    let is_default_path = file_path.is_none();
+    let file_path = match file_path.or_else(config_path) {
+        Some(file_path) => file_path,
+        None => return Ok(ScanConfig::default())
+    };
+    let content = match fs::read_to_string(&file_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound && is_default_path => return Ok(ScanConfig::default()),
+        Err(err) => return Err(format!("Could not read config file {} ({})", file_path.display(), err))
+    };
+
+    let (config, unknown_keys) = ScanConfig::parse(&content, command).map_err(|err| format!("{} in config file {}", err, file_path.display()))?;
+    for unknown_key in unknown_keys {
+        eprintln!("[warn] Unknown option '{}' in config file {}, ignored", unknown_key, file_path.display());
+    }
+    Ok(config)
+}
+
+/**
+ * Names of the available profiles with a one-line summary of their options:
+ * the sections of the config file, then the saved profiles (a saved profile
+ * shadowed by a config file section is not listed).
+ */
+pub fn list_profiles(config: &ScanConfig) -> Vec<(String, String)> {
+
+    let mut profiles: Vec<(String, String)> = config.profiles.iter().map(|(name, profile)| {
+        (name.clone(), format!("{} (config file)", profile.summary()))
+    }).collect();
+
+    let saved_names: Vec<String> = profiles_directory().and_then(|directory| fs::read_dir(directory).ok())
+        .map(|entries| entries.filter_map(|entry| entry.ok()).filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            file_name.strip_suffix(".toml").map(|name| name.to_string())
+        }).collect())
+        .unwrap_or_default();
+
+    let mut saved_profiles: Vec<(String, String)> = saved_names.into_iter()
+        .filter(|name| !config.profiles.contains_key(name))
+        .map(|name| {
+            let summary = match load_profile(&name) {
+                Ok(profile) => format!("{} (saved)", profile.summary()),
+                Err(err) => format!("unreadable ({})", err)
+            };
+            (name, summary)
+        }).collect();
+    saved_profiles.sort();
+    profiles.extend(saved_profiles);
+    profiles
+}
+
+/**
+ * Saved profile names are used as file names, and should not shadow the
+ * built-in scan profiles.
+ */
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+
+    if BUILTIN_PROFILE_NAMES.contains(&name) {
+        return Err(format!("Profile name '{}' is reserved for a built-in scan profile", name));
+    }
+    let is_valid = !name.is_empty() && name.chars().all(|character| character.is_ascii_alphanumeric() || character == '-' || character == '_');
+    match is_valid {
+        true => Ok(()),
+        false => Err(format!("Invalid profile name '{}' (expected letters, digits, '-' or '_')", name))
+    }
+}
+
+/**
+ * Load a saved profile by name. A missing profile is reported with the path
+ * where it was expected.
+ */
+pub fn load_profile(name: &str) -> Result<ScanProfile, String> {
+
+    validate_profile_name(name)?;
+    let file_path = profile_path(name).ok_or_else(|| "Could not find the configuration directory for profiles".to_string())?;
+    let content = fs::read_to_string(&file_path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => format!("Unknown profile '{}' (no [profile.{}] section in the config file, nor file at {})", name, name, file_path.display()),
+        _ => format!("Could not read profile {} ({})", file_path.display(), err)
+    })?;
+    ScanProfile::parse(&content).map_err(|err| format!("{} in {}", err, file_path.display()))
+}
+
+pub fn save_profile(name: &str, profile: &ScanProfile) -> Result<PathBuf, String> {
+
+    validate_profile_name(name)?;
+    let file_path = profile_path(name).ok_or_else(|| "Could not find the configuration directory for profiles".to_string())?;
+    if let Some(directory) = file_path.parent() {
+        fs::create_dir_all(directory).map_err(|err| format!("Could not create directory {} ({})", directory.display(), err))?;
+    }
+    let content = profile.to_toml()?;
+    let path_text = file_path.to_string_lossy().into_owned();
+    utils::write_output(Some(&path_text), content.as_bytes())?;
+    Ok(file_path)
+}
+
+/**
+ * Path of a saved profile, in the per-user configuration directory.
+ */
+pub fn profile_path(name: &str) -> Option<PathBuf> {
+
+    profiles_directory().map(|directory| directory.join(format!("{}.toml", name)))
+}
+
+fn profiles_directory() -> Option<PathBuf> {
+
+    config_directory().map(|directory| directory.join("profiles"))
+}
+
+/**
+ * Path of the default config file, in the per-user configuration directory.
+ */
+pub fn config_path() -> Option<PathBuf> {
+
+    config_directory().map(|directory| directory.join("config.toml"))
+}
+
+/**
+ * Per-user configuration directory of arp-scan (the XDG base directory is
+ * used on Linux).
+ */
+fn config_directory() -> Option<PathBuf> {
+
+    if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(|path| PathBuf::from(path).join("arp-scan"))
+    }
+    else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|path| PathBuf::from(path).join("Library/Application Support/arp-scan"))
+    }
+    else {
+        env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|path| path.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|path| PathBuf::from(path).join(".config")))
+            .map(|path| path.join("arp-scan"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::args::{build_args, ScanOptions};
+
+    fn build_profile(arguments: &[&str]) -> ScanProfile {
+
+        let command = build_args();
+        let matches = command.clone().get_matches_from([&["arp-scan"], arguments].concat());
+        ScanProfile::from_matches(&command, &matches)
+    }
+
+    fn build_interface(name: &str) -> NetworkInterface {
+
+        NetworkInterface {
+            name: name.to_string(),
+            description: String::new(),
+            index: 2,
+            mac: None,
+            ips: vec![],
+            flags: 0
+        }
+    }
+
+    #[test]
+    fn should_keep_explicit_options_only() {
+
+        let profile = build_profile(&["-i", "eth0", "-i", "eth1", "-t", "500ms", "--numeric", "--save-profile", "lab"]);
+
+        assert_eq!(profile.options.len(), 3);
+        assert_eq!(profile.options.get("interface"), Some(&ProfileValue::Values(vec!["eth0".to_string(), "eth1".to_string()])));
+        assert_eq!(profile.options.get("timeout"), Some(&ProfileValue::Value("500ms".to_string())));
+        assert_eq!(profile.options.get("numeric"), Some(&ProfileValue::Flag(true)));
+    }
+
+    #[test]
+    fn should_round_trip_profiles() {
+
+        let profile = build_profile(&["-i", "eth0", "-n", "10.0.0.0/22", "-I", "5ms", "--resolve-order", "mdns,dns", "--verbose"]);
+
+        let content = profile.to_toml().unwrap();
+        assert_eq!(ScanProfile::parse(&content), Ok(profile.clone()));
+
+        let command = build_args();
+        let arguments = profile.to_arguments(&command).unwrap();
+        let reparsed = ScanProfile::from_matches(&command, &command.clone().get_matches_from([vec!["arp-scan".to_string()], arguments].concat()));
+        assert_eq!(reparsed, profile);
+    }
+
+    #[test]
+    fn should_parse_handwritten_profiles() {
+
+        let profile = ScanProfile::parse("interface = [\"eth0\"]\ntimeout = \"800ms\"\nnumeric = true\nverbose = false\n").unwrap();
+        let arguments = profile.to_arguments(&build_args()).unwrap();
+
+        assert_eq!(arguments, ["--interface=eth0", "--numeric", "--timeout=800ms"]);
+        assert!(ScanProfile::parse("timeout = 800").is_err());
+    }
+
+    #[test]
+    fn should_prefer_command_line_options() {
+
+        let saved = build_profile(&["-i", "eth0", "-t", "5s", "-p", "fast"]);
+        let merged = saved.merge(build_profile(&["-t", "1s"]), &build_args());
+
+        let command = build_args();
+        let arguments = merged.to_arguments(&command).unwrap();
+        let options = ScanOptions::new(&command.get_matches_from([vec!["arp-scan".to_string()], arguments].concat())).unwrap();
+        assert_eq!(options.timeout_ms, 1000);
+        assert_eq!(options.interface_names, ["eth0"]);
+        assert!(matches!(options.scan_timing, crate::args::ScanTiming::Interval(0)));
+    }
+
+    #[test]
+    fn should_merge_option_layers_by_precedence() {
+
+        // Each case gives the config file, the saved profile and the command
+        // line, with the expected timeout, interfaces and numeric mode.
+        type PrecedenceCase = (&'static str, Option<&'static [&'static str]>, &'static [&'static str], u64, &'static [&'static str], bool);
+        let cases: [PrecedenceCase; 6] = [
+            ("timeout = \"5s\"", None, &[], 5000, &[], false),
+            ("timeout = \"5s\"", None, &["-t", "1s"], 1000, &[], false),
+            ("timeout = \"5s\"\ninterface = \"eth0\"", Some(&["-t", "3s"]), &[], 3000, &["eth0"], false),
+            ("timeout = \"5s\"", Some(&["-t", "3s", "-i", "eth1"]), &["-t", "1s"], 1000, &["eth1"], false),
+            ("interface = [\"eth0\", \"eth1\"]\nnumeric = true", None, &["-i", "eth2"], 2000, &["eth2"], true),
+            ("numeric = false", Some(&["--numeric"]), &[], 2000, &[], true)
+        ];
+
+        let command = build_args();
+        for (config, saved, command_line, timeout_ms, interface_names, numeric) in cases {
+
+            let config_profile = ScanConfig::parse(config, &command).unwrap().0.defaults;
+            let merged = merge_option_layers(&command, config_profile, saved.map(build_profile), build_profile(command_line));
+
+            let arguments = merged.to_arguments(&command).unwrap();
+            let options = ScanOptions::new(&command.clone().get_matches_from([vec!["arp-scan".to_string()], arguments].concat())).unwrap();
+            assert_eq!(options.timeout_ms, timeout_ms, "{}", config);
+            assert_eq!(options.interface_names, interface_names, "{}", config);
+            assert_eq!(!options.resolve_hostname, numeric, "{}", config);
+        }
+    }
+
+    #[test]
+    fn should_apply_config_profiles_over_defaults() {
+
+        // Each case gives the command line (with the config file), with the
+        // expected timeout, retry count and interfaces: the defaults are
+        // overridden by the selected profile, itself overridden by the CLI.
+        let config_file = write_config(concat!(
+            "timeout = \"5s\"\nretry = 2\ninterface = \"eth0\"\n",
+            "[profile.lab]\ntimeout = \"3s\"\ninterface = [\"eth1\"]\n",
+            "[profile.home]\nretry = 4\n"
+        ));
+        let config_path = config_file.to_string_lossy().into_owned();
+
+        let cases: [(&[&str], u64, usize, &[&str]); 6] = [
+            (&[], 5000, 2, &["eth0"]),
+            (&["-p", "lab"], 3000, 2, &["eth1"]),
+            (&["-p", "home"], 5000, 4, &["eth0"]),
+            (&["-p", "lab", "-t", "1s"], 1000, 2, &["eth1"]),
+            (&["-p", "home", "-r", "1", "-i", "eth2"], 5000, 1, &["eth2"]),
+            (&["-p", "fast", "-t", "1s"], 1000, 2, &["eth0"])
+        ];
+
+        let command = build_args();
+        let interfaces: Vec<NetworkInterface> = ["eth0", "eth1", "eth2"].iter().map(|name| build_interface(name)).collect();
+        for (arguments, timeout_ms, retry_count, interface_names) in cases {
+
+            let matches = command.clone().get_matches_from([&["arp-scan", "--config", &config_path], arguments].concat());
+            let (merged_matches, _) = apply_saved_options(&command, matches, &interfaces).unwrap();
+
+            let options = ScanOptions::new(&merged_matches).unwrap();
+            assert_eq!(options.timeout_ms, timeout_ms, "{:?}", arguments);
+            assert_eq!(options.retry_count, retry_count, "{:?}", arguments);
+            assert_eq!(options.interface_names, interface_names, "{:?}", arguments);
+        }
+
+        let matches = command.clone().get_matches_from(["arp-scan", "--no-config", "-t", "1s"]);
+        let (merged_matches, _) = apply_saved_options(&command, matches, &interfaces).unwrap();
+        assert_eq!(ScanOptions::new(&merged_matches).unwrap().retry_count, 0);
+
+        let matches = command.clone().get_matches_from(["arp-scan", "--config", &config_path, "-p", "office-lab-2"]);
+        let result = apply_saved_options(&command, matches, &[]);
+        assert!(result.is_err_and(|err| err.to_string().starts_with("Unknown profile 'office-lab-2'")));
+
+        fs::remove_file(&config_file).unwrap();
+    }
+
+    fn write_config(content: &str) -> PathBuf {
+
+        let thread_name = std::thread::current().name().unwrap_or_default().replace("::", "-");
+        let config_file = env::temp_dir().join(format!("arp-scan-config-{}-{}.toml", std::process::id(), thread_name));
+        fs::write(&config_file, content).unwrap();
+        config_file
+    }
+
+    #[test]
+    fn should_drop_config_options_conflicting_with_the_command_line() {
+
+        // Each case gives the config file and the command line, with the
+        // option of the config file overridden by a conflicting CLI option.
+        let cases: [(&str, &[&str], &str, &str); 3] = [
+            ("interface = \"lo\"", &["--all-interfaces"], "all_interfaces", "interface"),
+            ("numeric = true", &["--resolve-mdns"], "resolve_mdns", "numeric"),
+            ("interval = \"10ms\"", &["--bandwidth", "1000"], "bandwidth", "interval")
+        ];
+
+        let command = build_args();
+        let config_file = write_config(&cases.iter().map(|(config, ..)| *config).collect::<Vec<&str>>().join("\n"));
+        let config_path = config_file.to_string_lossy().into_owned();
+        for (config, command_line, kept_id, dropped_id) in cases {
+
+            let matches = command.clone().get_matches_from([&["arp-scan", "--config", &config_path], command_line].concat());
+            let (merged_matches, _) = apply_saved_options(&command, matches, &[build_interface("lo")]).unwrap();
+
+            assert!(merged_matches.contains_id(kept_id), "{}", config);
+            assert!(!merged_matches.contains_id(dropped_id), "{}", config);
+        }
+        fs::remove_file(&config_file).unwrap();
+    }
+
+    #[test]
+    fn should_drop_conflicting_options_at_each_layer() {
+
+        // Each case gives the command line (with the config file), with the
+        // options kept and dropped: the profile overrides conflicting
+        // defaults, and the command line overrides a conflicting profile.
+        let cases: [(&[&str], &[&str], &[&str]); 4] = [
+            (&["-p", "mdns"], &["resolve_mdns", "interface"], &["numeric"]),
+            (&["-p", "lab"], &["interface", "bandwidth"], &["interval"]),
+            (&["-p", "lab", "--all-interfaces"], &["all_interfaces", "bandwidth"], &["interface", "interval"]),
+            (&["-p", "mdns", "--numeric"], &["numeric", "interface"], &["resolve_mdns"])
+        ];
+
+        let command = build_args();
+        let config_file = write_config(concat!(
+            "numeric = true\ninterval = \"10ms\"\ninterface = \"eth0\"\n",
+            "[profile.mdns]\nresolve-mdns = true\n",
+            "[profile.lab]\ninterface = \"eth1\"\nbandwidth = 1000\n"
+        ));
+        let config_path = config_file.to_string_lossy().into_owned();
+        let interfaces = [build_interface("eth0"), build_interface("eth1")];
+        for (command_line, kept_ids, dropped_ids) in cases {
+
+            let matches = command.clone().get_matches_from([&["arp-scan", "--config", &config_path], command_line].concat());
+            let (merged_matches, _) = apply_saved_options(&command, matches, &interfaces).unwrap();
+
+            for kept_id in kept_ids {
+                assert!(merged_matches.contains_id(kept_id), "{:?} {}", command_line, kept_id);
+            }
+            for dropped_id in dropped_ids {
+                assert!(!merged_matches.contains_id(dropped_id), "{:?} {}", command_line, dropped_id);
+            }
+        }
+        fs::remove_file(&config_file).unwrap();
+    }
+
+    #[test]
+    fn should_only_keep_built_in_profiles_of_saved_layers() {
+
+        let command = build_args();
+        let config_profile = ScanConfig::parse("profile = \"office\"", &command).unwrap().0.defaults;
+
+        let merged = merge_option_layers(&command, config_profile.clone(), Some(build_profile(&["-p", "fast"])), build_profile(&[]));
+        assert_eq!(merged.options.get("profile"), Some(&ProfileValue::Value("fast".to_string())));
+
+        let merged = merge_option_layers(&command, config_profile, None, build_profile(&["-p", "stealth"]));
+        assert_eq!(merged.options.get("profile"), Some(&ProfileValue::Value("stealth".to_string())));
+    }
+
+    #[test]
+    fn should_parse_config_files() {
+
+        let command = build_args();
+        let content = "retry = 3\ninterface = \"eth0\"\nnumeric = true\ncolour = true\nsave-profile = \"lab\"\n\n[profile.lab]\ntimeout = \"1s\"\ncolour = 2\n";
+        let (config, unknown_keys) = ScanConfig::parse(content, &command).unwrap();
+
+        assert_eq!(unknown_keys, ["profile.lab.colour", "colour", "save-profile"]);
+        assert_eq!(config.defaults.to_arguments(&command).unwrap(), ["--interface=eth0", "--numeric", "--retry=3"]);
+        assert_eq!(config.profiles.keys().collect::<Vec<&String>>(), ["lab"]);
+        assert_eq!(config.profiles["lab"].summary(), "timeout=1s");
+        assert_eq!(config.defaults.summary(), "interface=eth0, numeric, retry=3");
+    }
+
+    #[test]
+    fn should_reject_config_values_of_the_wrong_type() {
+
+        let command = build_args();
+
+        assert_eq!(ScanConfig::parse("numeric = \"yes\"", &command).map(|(config, _)| config), Err("Invalid value for 'numeric' (expected a boolean)".to_string()));
+        assert_eq!(ScanConfig::parse("timeout = true", &command).map(|(config, _)| config), Err("Invalid value for 'timeout' (expected a string)".to_string()));
+        assert_eq!(ScanConfig::parse("interface = [1, false]", &command).map(|(config, _)| config), Err("Invalid value for 'interface' (expected an array of strings)".to_string()));
+        assert!(ScanConfig::parse("timeout = ", &command).is_err_and(|err| err.starts_with("Invalid TOML")));
+        assert_eq!(ScanConfig::parse("[profile.lab]\nretry = false", &command).map(|(config, _)| config), Err("Invalid value for 'profile.lab.retry' (expected a string)".to_string()));
+        assert!(ScanConfig::parse("[profile.fast]\nretry = 3", &command).is_err_and(|err| err.contains("reserved")));
+    }
+
+    #[test]
+    fn should_report_missing_config_files() {
+
+        let missing_path = env::temp_dir().join("arp-scan-missing-config.toml");
+
+        assert!(load_config(&build_args(), Some(missing_path)).is_err_and(|err| err.starts_with("Could not read config file")));
+    }
+
+    #[test]
+    fn should_reject_unknown_options() {
+
+        let profile = ScanProfile::parse("interfaces = [\"eth0\"]").unwrap();
+
+        assert_eq!(profile.to_arguments(&build_args()), Err("Unknown option 'interfaces' in profile".to_string()));
+    }
+
+    #[test]
+    fn should_find_unknown_interfaces() {
+
+        let profile = build_profile(&["-i", "eth0", "-i", "eth7"]);
+
+        assert_eq!(profile.find_unknown_interfaces(&[build_interface("eth0")]), ["eth7"]);
+    }
+
+    #[test]
+    fn should_validate_profile_names() {
+
+        assert!(validate_profile_name("home-lab_2").is_ok());
+        assert!(validate_profile_name("fast").is_err());
+        assert!(validate_profile_name("../office").is_err());
+        assert!(validate_profile_name("").is_err());
+    }
+
+}
@@ -0,0 +1,265 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// Marks an unset timestamp (the round or the response window has not started
+// yet, or is not finished yet).
+const UNSET_MS: u64 = u64::MAX;
+
+/**
+ * A snapshot of the progress of a scan round: the targets sent on the first
+ * attempt (out of the network size), the hosts found so far, the elapsed time
+ * and an estimation of the remaining time. Retries only target unanswered
+ * hosts, they are not counted as sent targets.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScanProgress {
+    pub sent_count: u64,
+    pub target_count: u64,
+    pub host_count: usize,
+    pub elapsed: Duration,
+    pub estimated_remaining: Duration,
+    pub finished: bool
+}
+
+impl ScanProgress {
+
+    /**
+     * Share of the targets already sent, between 0 and 100. Scans without
+     * any target (such as passive scans) are always complete.
+     */
+    pub fn percent(&self) -> f64 {
+
+        match self.target_count {
+            0 => 100.0,
+            target_count => (self.sent_count.min(target_count) as f64 * 100.0) / target_count as f64
+        }
+    }
+}
+
+/**
+ * Progress counters shared by a scan and its observers, updated with atomics
+ * so that snapshots are cheap to poll (from a progress bar for example). The
+ * counters are reset at the start of each scan round, clones share the same
+ * counters.
+ *
+ * The remaining time is estimated from the pacing of the requests (interval
+ * or rate) for the unsent targets, and from the response windows left: the
+ * scan waits for the timeout after each attempt on each interface, including
+ * after the last packet.
+ */
+#[derive(Clone, Debug)]
+pub struct ProgressTracker {
+    state: Arc<ProgressState>
+}
+
+#[derive(Debug)]
+struct ProgressState {
+    origin: Instant,
+    started_ms: AtomicU64,
+    finished_ms: AtomicU64,
+    target_count: AtomicU64,
+    sent_count: AtomicU64,
+    found_hosts: Arc<AtomicUsize>,
+    request_interval_us: AtomicU64,
+    timeout_ms: AtomicU64,
+    window_count: AtomicU64,
+    completed_windows: AtomicU64,
+    window_started_ms: AtomicU64
+}
+
+impl Default for ProgressTracker {
+
+    fn default() -> Self {
+
+        ProgressTracker::new()
+    }
+}
+
+impl ProgressTracker {
+
+    pub fn new() -> Self {
+
+        ProgressTracker {
+            state: Arc::new(ProgressState {
+                origin: Instant::now(),
+                started_ms: AtomicU64::new(UNSET_MS),
+                finished_ms: AtomicU64::new(UNSET_MS),
+                target_count: AtomicU64::new(0),
+                sent_count: AtomicU64::new(0),
+                found_hosts: Arc::new(AtomicUsize::new(0)),
+                request_interval_us: AtomicU64::new(0),
+                timeout_ms: AtomicU64::new(0),
+                window_count: AtomicU64::new(0),
+                completed_windows: AtomicU64::new(0),
+                window_started_ms: AtomicU64::new(UNSET_MS)
+            })
+        }
+    }
+
+    /**
+     * Counter of the hosts found during the current round, which is shared
+     * with the scan state (and incremented by the response thread).
+     */
+    pub fn found_hosts(&self) -> Arc<AtomicUsize> {
+
+        Arc::clone(&self.state.found_hosts)
+    }
+
+    /**
+     * Start a new scan round, with the pacing of the requests (in
+     * microseconds) and the amount of response windows (one for each
+     * attempt on each interface and VLAN) lasting the scan timeout.
+     */
+    pub fn start_round(&self, target_count: u128, request_interval_us: u64, timeout_ms: u64, window_count: u64) {
+
+        let state = &self.state;
+        state.target_count.store(target_count.try_into().unwrap_or(u64::MAX), Ordering::Relaxed);
+        state.sent_count.store(0, Ordering::Relaxed);
+        state.request_interval_us.store(request_interval_us, Ordering::Relaxed);
+        state.timeout_ms.store(timeout_ms, Ordering::Relaxed);
+        state.window_count.store(window_count, Ordering::Relaxed);
+        state.completed_windows.store(0, Ordering::Relaxed);
+        state.window_started_ms.store(UNSET_MS, Ordering::Relaxed);
+        state.finished_ms.store(UNSET_MS, Ordering::Relaxed);
+        state.started_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self) {
+
+        self.state.sent_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn start_window(&self) {
+
+        self.state.window_started_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    pub fn complete_window(&self) {
+
+        self.state.window_started_ms.store(UNSET_MS, Ordering::Relaxed);
+        self.state.completed_windows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /**
+     * End the current round, including when it has been stopped early (the
+     * remaining time is then zero).
+     */
+    pub fn finish_round(&self) {
+
+        self.state.window_started_ms.store(UNSET_MS, Ordering::Relaxed);
+        self.state.finished_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ScanProgress {
+
+        let state = &self.state;
+        let now_ms = self.now_ms();
+        let started_ms = state.started_ms.load(Ordering::Relaxed);
+        let finished_ms = state.finished_ms.load(Ordering::Relaxed);
+        let target_count = state.target_count.load(Ordering::Relaxed);
+        let sent_count = state.sent_count.load(Ordering::Relaxed);
+        let finished = finished_ms != UNSET_MS;
+
+        let elapsed_ms = match (started_ms, finished) {
+            (UNSET_MS, _) => 0,
+            (_, true) => finished_ms.saturating_sub(started_ms),
+            (_, false) => now_ms.saturating_sub(started_ms)
+        };
+
+        let estimated_remaining_ms = match finished {
+            true => 0,
+            false => {
+                let unsent_count = target_count.saturating_sub(sent_count);
+                let sending_ms = unsent_count.saturating_mul(state.request_interval_us.load(Ordering::Relaxed)) / 1000;
+                let remaining_windows = state.window_count.load(Ordering::Relaxed).saturating_sub(state.completed_windows.load(Ordering::Relaxed));
+                let waiting_ms = remaining_windows.saturating_mul(state.timeout_ms.load(Ordering::Relaxed));
+                let window_elapsed_ms = match state.window_started_ms.load(Ordering::Relaxed) {
+                    UNSET_MS => 0,
+                    window_started_ms => now_ms.saturating_sub(window_started_ms)
+                };
+                sending_ms.saturating_add(waiting_ms).saturating_sub(window_elapsed_ms)
+            }
+        };
+
+        ScanProgress {
+            sent_count,
+            target_count,
+            host_count: state.found_hosts.load(Ordering::Relaxed),
+            elapsed: Duration::from_millis(elapsed_ms),
+            estimated_remaining: Duration::from_millis(estimated_remaining_ms),
+            finished
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+
+        self.state.origin.elapsed().as_millis().try_into().unwrap_or(u64::MAX - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn should_estimate_pacing_and_response_windows() {
+
+        let progress = ProgressTracker::new();
+        progress.start_round(4, 10_000, 2000, 2);
+        progress.record_sent();
+        progress.record_sent();
+
+        let snapshot = progress.snapshot();
+        assert_eq!(snapshot.sent_count, 2);
+        assert_eq!(snapshot.percent(), 50.0);
+        assert_eq!(snapshot.estimated_remaining, Duration::from_millis(2 * 10 + 2 * 2000));
+        assert!(!snapshot.finished);
+    }
+
+    #[test]
+    fn should_reach_all_sent_targets() {
+
+        let progress = ProgressTracker::new();
+        progress.start_round(254, 0, 50, 1);
+
+        let sender_progress = progress.clone();
+        let found_hosts = progress.found_hosts();
+        thread::spawn(move || {
+            for target_index in 0..254 {
+                sender_progress.record_sent();
+                if target_index % 100 == 0 {
+                    found_hosts.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            sender_progress.start_window();
+        }).join().unwrap();
+
+        // Once all targets are sent, only the trailing response window is
+        // left before the end of the round.
+        let snapshot = progress.snapshot();
+        assert_eq!(snapshot.percent(), 100.0);
+        assert_eq!(snapshot.host_count, 3);
+        assert!(snapshot.estimated_remaining <= Duration::from_millis(50));
+
+        progress.complete_window();
+        progress.finish_round();
+        let snapshot = progress.snapshot();
+        assert!(snapshot.finished);
+        assert_eq!(snapshot.estimated_remaining, Duration::ZERO);
+        assert_eq!(snapshot.sent_count, snapshot.target_count);
+    }
+
+    #[test]
+    fn should_complete_scans_without_targets() {
+
+        let progress = ProgressTracker::new();
+        progress.start_round(0, 10_000, 1000, 1);
+
+        assert_eq!(progress.snapshot().percent(), 100.0);
+        assert_eq!(progress.snapshot().estimated_remaining, Duration::from_millis(1000));
+    }
+
+}
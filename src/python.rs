@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use ipnetwork::{IpNetwork, Ipv4Network};
+use pyo3::exceptions::{PyOSError, PyPermissionError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::error::ScanError;
+use crate::network::{ResponseSummary, TargetDetails};
+use crate::scanner::ArpScanner;
+
+/**
+ * Scan arguments given by Python callers, mirroring the main scan options.
+ * Missing values keep the defaults of the CLI.
+ */
+#[derive(Default)]
+struct ScanArguments {
+    interface: Option<String>,
+    networks: Vec<String>,
+    excluded_networks: Vec<String>,
+    timeout_ms: Option<u64>,
+    interval_ms: Option<u64>,
+    retry_count: Option<usize>,
+    resolve: bool,
+    include_unsolicited: bool
+}
+
+impl ScanArguments {
+
+    /**
+     * Check the arguments and build the scanner, before the GIL is released.
+     * Invalid arguments are reported as usage errors, as with the CLI.
+     */
+    fn build_scanner(&self) -> Result<ArpScanner, ScanError> {
+
+        let mut builder = ArpScanner::builder()
+            .resolve_hostnames(self.resolve)
+            .include_unsolicited(self.include_unsolicited);
+
+        if let Some(interface_name) = &self.interface {
+            if interface_name.is_empty() {
+                return Err(ScanError::InvalidTarget("The interface name should not be empty".to_string()));
+            }
+            builder = builder.interface(interface_name);
+        }
+        for network in self.networks.iter() {
+            let ip_network: IpNetwork = network.parse().map_err(|err| ScanError::InvalidTarget(format!("Invalid network {} ({})", network, err)))?;
+            if !ip_network.is_ipv4() {
+                return Err(ScanError::InvalidTarget(format!("Only IPv4 networks can be scanned (got {})", network)));
+            }
+            builder = builder.network(ip_network);
+        }
+        for network in self.excluded_networks.iter() {
+            let excluded_network: Ipv4Network = network.parse().map_err(|err| ScanError::InvalidTarget(format!("Invalid excluded network {} ({})", network, err)))?;
+            builder = builder.exclude(excluded_network);
+        }
+        match self.timeout_ms {
+            Some(0) => return Err(ScanError::InvalidTarget("The timeout should be strictly positive, no response could be received".to_string())),
+            Some(timeout_ms) => builder = builder.timeout(Duration::from_millis(timeout_ms)),
+            None => {}
+        }
+        if let Some(interval_ms) = self.interval_ms {
+            builder = builder.interval(Duration::from_millis(interval_ms));
+        }
+        if let Some(retry_count) = self.retry_count {
+            builder = builder.retries(retry_count);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+// The doc comment of the function is its Python docstring.
+/// Run a single ARP scan round, and return the hosts found (as a list of
+/// dicts) with the scan summary (as a dict). Without interface, the default
+/// interface is scanned, and without networks the IPv4 networks of the
+/// interface are targeted. The GIL is released while the scan runs.
+#[pyfunction]
+#[pyo3(signature = (interface=None, networks=None, excluded_networks=None, timeout_ms=None, interval_ms=None, retry_count=None, resolve=true, include_unsolicited=false))]
+#[allow(clippy::too_many_arguments)]
+fn scan<'py>(
+    py: Python<'py>,
+    interface: Option<String>,
+    networks: Option<Vec<String>>,
+    excluded_networks: Option<Vec<String>>,
+    timeout_ms: Option<u64>,
+    interval_ms: Option<u64>,
+    retry_count: Option<usize>,
+    resolve: bool,
+    include_unsolicited: bool
+) -> PyResult<(Bound<'py, PyList>, Bound<'py, PyDict>)> {
+
+    let arguments = ScanArguments {
+        interface,
+        networks: networks.unwrap_or_default(),
+        excluded_networks: excluded_networks.unwrap_or_default(),
+        timeout_ms,
+        interval_ms,
+        retry_count,
+        resolve,
+        include_unsolicited
+    };
+    let scanner = arguments.build_scanner().map_err(into_python_error)?;
+    let scan_result = py.allow_threads(move || scanner.run()).map_err(into_python_error)?;
+
+    let hosts = PyList::empty(py);
+    for host in scan_result.hosts.iter() {
+        hosts.append(build_host(py, host)?)?;
+    }
+    Ok((hosts, build_summary(py, &scan_result.summary)?))
+}
+
+fn build_host<'py>(py: Python<'py>, host: &TargetDetails) -> PyResult<Bound<'py, PyDict>> {
+
+    let host_dict = PyDict::new(py);
+    host_dict.set_item("ipv4", host.ipv4.to_string())?;
+    host_dict.set_item("mac", host.mac.to_string())?;
+    host_dict.set_item("hostname", host.hostname.as_deref())?;
+    host_dict.set_item("vendor", host.vendor.as_deref())?;
+    host_dict.set_item("interface", host.interface.as_str())?;
+    host_dict.set_item("vlan_id", host.vlan_id)?;
+    host_dict.set_item("response_time_ms", host.response_time_ms)?;
+    Ok(host_dict)
+}
+
+fn build_summary<'py>(py: Python<'py>, summary: &ResponseSummary) -> PyResult<Bound<'py, PyDict>> {
+
+    let summary_dict = PyDict::new(py);
+    summary_dict.set_item("packet_count", summary.packet_count)?;
+    summary_dict.set_item("arp_count", summary.arp_count)?;
+    summary_dict.set_item("requests_sent", summary.requests_sent)?;
+    summary_dict.set_item("send_errors", summary.send_errors)?;
+    summary_dict.set_item("duration_ms", summary.duration_ms)?;
+    summary_dict.set_item("truncated", summary.truncated)?;
+    Ok(summary_dict)
+}
+
+/**
+ * Raise the Python exception matching the category of a scan error: scans
+ * without raw socket permissions raise 'PermissionError', invalid arguments
+ * 'ValueError' and interface or network failures 'OSError'.
+ */
+fn into_python_error(error: ScanError) -> PyErr {
+
+    let message = error.to_string();
+    match error {
+        ScanError::PermissionDenied => PyPermissionError::new_err("Scans require root permissions or the CAP_NET_RAW capability"),
+        ScanError::InvalidTarget(_) | ScanError::InvalidOptions(_) | ScanError::Profile(_) => PyValueError::new_err(message),
+        ScanError::InterfaceNotFound(_) | ScanError::Interface(_) | ScanError::Channel(_) | ScanError::Packet(_) => PyOSError::new_err(message),
+        _ => PyRuntimeError::new_err(message)
+    }
+}
+
+/**
+ * The 'arp_scan' Python module, built with the 'python' feature.
+ */
+#[pymodule]
+#[pyo3(name = "arp_scan")]
+fn arp_scan_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+
+    module.add_function(wrap_pyfunction!(scan, module)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn assert_invalid_target(arguments: ScanArguments) {
+
+        match arguments.build_scanner() {
+            Err(ScanError::InvalidTarget(_)) => {},
+            Err(error) => panic!("Unexpected error {}", error),
+            Ok(_) => panic!("Arguments should be rejected")
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_arguments() {
+
+        assert_invalid_target(ScanArguments { networks: vec!["192.168.1.0/33".to_string()], ..ScanArguments::default() });
+        assert_invalid_target(ScanArguments { networks: vec!["fe80::/64".to_string()], ..ScanArguments::default() });
+        assert_invalid_target(ScanArguments { excluded_networks: vec!["192.168.1".to_string()], ..ScanArguments::default() });
+        assert_invalid_target(ScanArguments { interface: Some(String::new()), ..ScanArguments::default() });
+        assert_invalid_target(ScanArguments { timeout_ms: Some(0), ..ScanArguments::default() });
+    }
+
+    #[test]
+    fn should_build_scanner_from_arguments() {
+
+        let arguments = ScanArguments {
+            interface: Some("eth0".to_string()),
+            networks: vec!["192.168.1.0/24".to_string(), "10.0.0.1".to_string()],
+            excluded_networks: vec!["192.168.1.1/32".to_string()],
+            timeout_ms: Some(500),
+            retry_count: Some(2),
+            ..ScanArguments::default()
+        };
+
+        assert!(arguments.build_scanner().is_ok());
+    }
+}
@@ -0,0 +1,742 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pnet_datalink::MacAddr;
+
+use crate::args::{HostnameStyle, ResolverBackend, ScanOptions};
+use crate::cache::{self, HostnameCache};
+use crate::dns;
+use crate::error::ScanError;
+use crate::idn;
+use crate::mdns;
+use crate::netbios;
+use crate::network::{find_hostname, HostnameSource, TargetDetails};
+#[cfg(all(feature = "systemd-resolved", target_os = "linux"))]
+use crate::resolved;
+use crate::resolver::{HostnameResolver, StaticResolver};
+
+#[cfg(not(target_os = "windows"))]
+pub const SYSTEM_HOSTS_FILE_PATH: &str = "/etc/hosts";
+#[cfg(target_os = "windows")]
+pub const SYSTEM_HOSTS_FILE_PATH: &str = "C:\\Windows\\System32\\drivers\\etc\\hosts";
+
+type HostnameLookup = dyn Fn(Ipv4Addr) -> Option<String> + Send + Sync;
+
+// Progress is printed in verbose mode each time this share of the lookups
+// has been settled (in percents).
+const PROGRESS_STEP_PERCENT: usize = 10;
+
+/**
+ * Resolution chain of a scan, each resolver is tried in turn and the source
+ * is recorded as the hostname source of the hosts it resolved.
+ */
+pub type ResolverChain = Vec<(HostnameSource, Arc<dyn HostnameResolver>)>;
+
+/**
+ * Resolve the hostnames of the hosts found by a scan. Each resolution method
+ * (hosts files, reverse DNS, mDNS and NetBIOS) is tried in the configured
+ * order, and only for the hosts that are still unresolved: the first answer
+ * wins and the method is recorded as the hostname source. Hostnames cached by
+ * previous scans are used before the first network method. The resolution
+ * happens once the responses have been received, so that slow lookups never
+ * delay the receive loop.
+ */
+pub fn resolve_hostnames(target_details: &mut [TargetDetails], options: &ScanOptions) -> Result<(), ScanError> {
+
+    if !options.resolve_hostname || target_details.is_empty() {
+        return Ok(());
+    }
+
+    let resolvers = build_resolvers(target_details, options)?;
+    resolve_hostnames_with(target_details, options, &resolvers);
+    Ok(())
+}
+
+/**
+ * Resolve hostnames with a custom resolution chain if given, or with the
+ * chain configured by the scan options otherwise.
+ */
+pub fn resolve_hostnames_in_chain(target_details: &mut [TargetDetails], options: &ScanOptions, resolvers: Option<&[(HostnameSource, Arc<dyn HostnameResolver>)]>) -> Result<(), ScanError> {
+
+    match resolvers {
+        Some(resolvers) if options.resolve_hostname && !target_details.is_empty() => resolve_hostnames_with(target_details, options, resolvers),
+        Some(_) => (),
+        None => resolve_hostnames(target_details, options)?
+    }
+    Ok(())
+}
+
+/**
+ * Build the resolution chain configured by the scan options, with one
+ * resolver for each method of the resolution order. This fails when the hosts
+ * files are part of the resolution order but can not be read.
+ */
+pub fn build_resolvers(target_details: &[TargetDetails], options: &ScanOptions) -> Result<ResolverChain, ScanError> {
+
+    options.resolve_order.iter().map(|method| {
+        let resolver: Arc<dyn HostnameResolver> = match method {
+            HostnameSource::HostsFile => Arc::new(StaticResolver::new(static_hostnames(options)?.clone())),
+            HostnameSource::Dns => build_dns_resolver(target_details, options),
+            HostnameSource::Mdns => Arc::new(MdnsResolver { timeout_ms: options.mdns_timeout_ms }),
+            HostnameSource::Netbios => Arc::new(NetbiosResolver { timeout_ms: options.netbios_timeout_ms }),
+            HostnameSource::Dhcp => Arc::new(StaticResolver::new(find_lease_hostnames(target_details, &options.dhcp_hostnames)))
+        };
+        Ok((*method, resolver))
+    }).collect()
+}
+
+/**
+ * Resolve the hostnames of the hosts found by a scan with the given chain of
+ * resolvers, see the 'resolve_hostnames' function. The cache, the hostname
+ * style and the Unicode conversion are applied as with the default chain.
+ */
+pub fn resolve_hostnames_with(target_details: &mut [TargetDetails], options: &ScanOptions, resolvers: &[(HostnameSource, Arc<dyn HostnameResolver>)]) {
+
+    for detail in target_details.iter_mut() {
+        detail.hostname = None;
+        detail.hostname_source = None;
+        detail.hostname_ascii = None;
+    }
+
+    let now = cache::unix_timestamp();
+    let mut hostname_cache = options.resolve_cache_file.as_deref().map(HostnameCache::load);
+    let mut is_cache_applied = false;
+    let mut is_unresolved: Vec<bool> = vec![true; target_details.len()];
+    for (method, resolver) in resolvers.iter() {
+
+        if !is_local_method(*method) && !is_cache_applied {
+            if let Some(hostname_cache) = hostname_cache.as_ref() {
+                apply_cached_hostnames(target_details, hostname_cache, now, options.resolve_cache_ttl_ms / 1000);
+            }
+            is_cache_applied = true;
+            is_unresolved = target_details.iter().map(|detail| detail.hostname.is_none()).collect();
+        }
+
+        // Each method is timed in verbose mode, which helps tuning the
+        // timeout of each protocol.
+        let started_at = Instant::now();
+        let unresolved_count = target_details.iter().filter(|detail| detail.hostname.is_none()).count();
+        apply_hostnames(target_details, *method, resolver.as_ref());
+        if options.verbose {
+            let found_count = unresolved_count - target_details.iter().filter(|detail| detail.hostname.is_none()).count();
+            eprintln!("Resolved {} of {} hostnames with {} in {}ms", found_count, unresolved_count, method.describe(), started_at.elapsed().as_millis());
+        }
+    }
+
+    // Freshly resolved hostnames are cached for the next scans, hostnames of
+    // the hosts files and DHCP leases are not cached since they are always
+    // available.
+    if let (Some(hostname_cache), Some(cache_file), true) = (hostname_cache.as_mut(), options.resolve_cache_file.as_deref(), is_cache_applied) {
+        let fresh_details = target_details.iter().zip(is_unresolved).filter(|(_, is_unresolved)| *is_unresolved).map(|(detail, _)| detail);
+        for detail in fresh_details {
+            if let (Some(hostname), Some(source)) = (detail.hostname.as_ref(), detail.hostname_source) {
+                if !is_local_method(source) {
+                    hostname_cache.record(&detail.mac, hostname, &source.describe(), now);
+                }
+            }
+        }
+        hostname_cache.save(cache_file).unwrap_or_else(|err| eprintln!("[warn] {}", err));
+    }
+
+    // Internationalized hostnames are displayed in their Unicode form, while
+    // the ASCII form is kept for exports.
+    for detail in target_details.iter_mut() {
+        let hostname = detail.hostname.as_deref().map(|hostname| format_hostname(hostname, options.hostname_style));
+        detail.hostname = hostname.as_deref().map(idn::to_unicode);
+        detail.hostname_ascii = hostname.filter(|hostname| Some(hostname) != detail.hostname.as_ref());
+    }
+}
+
+/**
+ * Format a resolved hostname with the selected style, the trailing dot of
+ * fully qualified names (as returned by PTR lookups) is always removed. The
+ * short style only keeps the name up to the first dot. Empty names and IP
+ * literals are kept untouched.
+ */
+pub fn format_hostname(hostname: &str, style: HostnameStyle) -> String {
+
+    if hostname.is_empty() || hostname.parse::<IpAddr>().is_ok() {
+        return hostname.to_string();
+    }
+
+    let full_name = hostname.strip_suffix('.').unwrap_or(hostname);
+    match style {
+        HostnameStyle::Full => full_name.to_string(),
+        HostnameStyle::Short => full_name.split('.').next().unwrap_or(full_name).to_string()
+    }
+}
+
+/**
+ * Local methods read hostnames from files instead of sending queries, they
+ * do not need to be cached.
+ */
+fn is_local_method(method: HostnameSource) -> bool {
+
+    matches!(method, HostnameSource::HostsFile | HostnameSource::Dhcp)
+}
+
+/**
+ * DHCP lease hostnames are keyed by MAC address, so that hosts keep their
+ * name when their IPv4 address changed since the lease was written.
+ */
+fn find_lease_hostnames(target_details: &[TargetDetails], dhcp_hostnames: &HashMap<MacAddr, String>) -> HashMap<Ipv4Addr, String> {
+
+    target_details.iter().filter_map(|detail| dhcp_hostnames.get(&detail.mac).map(|hostname| (detail.ipv4, hostname.clone()))).collect()
+}
+
+fn apply_cached_hostnames(target_details: &mut [TargetDetails], hostname_cache: &HostnameCache, now: u64, ttl_seconds: u64) {
+
+    for detail in target_details.iter_mut().filter(|detail| detail.hostname.is_none()) {
+        if let Some(entry) = hostname_cache.lookup(&detail.mac, now, ttl_seconds) {
+            detail.hostname = Some(entry.hostname.clone());
+            detail.hostname_source = HostnameSource::parse(&entry.source);
+        }
+    }
+}
+
+fn apply_hostnames(target_details: &mut [TargetDetails], source: HostnameSource, resolver: &dyn HostnameResolver) {
+
+    let unresolved_ipv4s: Vec<Ipv4Addr> = target_details.iter().filter(|detail| detail.hostname.is_none()).map(|detail| detail.ipv4).collect();
+    if unresolved_ipv4s.is_empty() {
+        return;
+    }
+    let hostnames = resolver.resolve_all(&unresolved_ipv4s);
+    for detail in target_details.iter_mut().filter(|detail| detail.hostname.is_none()) {
+        if let Some(hostname) = hostnames.get(&detail.ipv4) {
+            detail.hostname = Some(hostname.clone());
+            detail.hostname_source = Some(source);
+        }
+    }
+}
+
+/**
+ * Parse the content of a hosts file into a map of IPv4 addresses and names.
+ * Comments are ignored, the first name of a line is the canonical name and
+ * the first line wins when an address is listed several times. IPv6 lines
+ * (and lines without a valid IPv4 address) are skipped.
+ */
+pub fn parse_hosts_file(content: &str) -> HashMap<Ipv4Addr, String> {
+
+    let mut hostnames: HashMap<Ipv4Addr, String> = HashMap::new();
+    for line in content.lines() {
+
+        let entry = line.split('#').next().unwrap_or_default();
+        let mut fields = entry.split_whitespace();
+        let ipv4 = match fields.next().and_then(|address| address.parse::<Ipv4Addr>().ok()) {
+            Some(ipv4) => ipv4,
+            None => continue
+        };
+        if let Some(name) = fields.next() {
+            hostnames.entry(ipv4).or_insert_with(|| name.to_string());
+        }
+    }
+    hostnames
+}
+
+/**
+ * Load the static hostnames of the system hosts file, then of a hosts file
+ * given by the user (which takes precedence). A missing system hosts file is
+ * not an error, since some systems do not have one.
+ */
+pub fn load_static_hostnames(hosts_file: Option<&str>) -> Result<HashMap<Ipv4Addr, String>, String> {
+
+    let mut hostnames = match fs::read_to_string(SYSTEM_HOSTS_FILE_PATH) {
+        Ok(content) => parse_hosts_file(&content),
+        Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+        Err(err) => return Err(format!("Could not open hosts file {} ({})", SYSTEM_HOSTS_FILE_PATH, err))
+    };
+    if let Some(file_path) = hosts_file {
+        let content = fs::read_to_string(file_path).map_err(|err| format!("Could not open hosts file {} ({})", file_path, err))?;
+        hostnames.extend(parse_hosts_file(&content));
+    }
+    Ok(hostnames)
+}
+
+/**
+ * Static hostnames of the scan (system hosts file and '--hosts-file'), which
+ * are read the first time they are needed and then kept for the next rounds.
+ */
+pub fn static_hostnames(options: &ScanOptions) -> Result<&HashMap<Ipv4Addr, String>, ScanError> {
+
+    if let Some(hostnames) = options.static_hostnames.get() {
+        return Ok(hostnames);
+    }
+    let hostnames = load_static_hostnames(options.hosts_file.as_deref()).map_err(ScanError::Resolution)?;
+    Ok(options.static_hostnames.get_or_init(|| hostnames))
+}
+
+/**
+ * Find the interface index (network link) of each host, systemd-resolved
+ * scopes its lookups to these links.
+ */
+#[cfg(all(feature = "systemd-resolved", target_os = "linux"))]
+fn find_link_indexes(target_details: &[TargetDetails]) -> HashMap<Ipv4Addr, u32> {
+
+    let interfaces = pnet_datalink::interfaces();
+    target_details.iter().filter_map(|detail| {
+        interfaces.iter().find(|interface| interface.name == detail.interface).map(|interface| (detail.ipv4, interface.index))
+    }).collect()
+}
+
+/**
+ * Check if systemd-resolved can be used for reverse lookups, which requires
+ * a build with the 'systemd-resolved' feature and a running service.
+ */
+#[cfg(all(feature = "systemd-resolved", target_os = "linux"))]
+pub fn is_systemd_resolved_available() -> bool {
+
+    resolved::is_available()
+}
+
+#[cfg(not(all(feature = "systemd-resolved", target_os = "linux")))]
+pub fn is_systemd_resolved_available() -> bool {
+
+    false
+}
+
+/**
+ * Reverse DNS lookups go to the DNS servers given by the user, then to
+ * systemd-resolved or to the system resolver.
+ */
+fn build_dns_resolver(target_details: &[TargetDetails], options: &ScanOptions) -> Arc<dyn HostnameResolver> {
+
+    if !options.dns_servers.is_empty() {
+        return Arc::new(DnsServerResolver {
+            servers: options.dns_servers.clone(),
+            timeout_ms: options.dns_timeout_ms,
+            verbose: options.verbose
+        });
+    }
+    match options.resolver {
+        ResolverBackend::Auto if is_systemd_resolved_available() => build_systemd_resolver(target_details, options),
+        ResolverBackend::Systemd => build_systemd_resolver(target_details, options),
+        ResolverBackend::Auto | ResolverBackend::System => Arc::new(SystemResolver::from_options(options))
+    }
+}
+
+#[cfg(all(feature = "systemd-resolved", target_os = "linux"))]
+fn build_systemd_resolver(target_details: &[TargetDetails], options: &ScanOptions) -> Arc<dyn HostnameResolver> {
+
+    Arc::new(SystemdResolver {
+        links: Arc::new(find_link_indexes(target_details)),
+        system: SystemResolver::from_options(options)
+    })
+}
+
+#[cfg(not(all(feature = "systemd-resolved", target_os = "linux")))]
+fn build_systemd_resolver(_target_details: &[TargetDetails], options: &ScanOptions) -> Arc<dyn HostnameResolver> {
+
+    Arc::new(SystemResolver::from_options(options))
+}
+
+/**
+ * Reverse lookups with the system resolver, run on a bounded pool of worker
+ * threads (see the 'lookup_hostnames' function).
+ */
+pub struct SystemResolver {
+    concurrency: usize,
+    timeout_ms: u64,
+    verbose: bool
+}
+
+impl SystemResolver {
+
+    fn from_options(options: &ScanOptions) -> Self {
+
+        SystemResolver {
+            concurrency: options.resolve_concurrency,
+            timeout_ms: options.dns_timeout_ms,
+            verbose: options.verbose
+        }
+    }
+}
+
+impl HostnameResolver for SystemResolver {
+
+    fn resolve(&self, ipv4: Ipv4Addr) -> Option<String> {
+
+        lookup_with_timeout(ipv4, self.timeout_ms, Arc::new(find_hostname))
+    }
+
+    fn resolve_all(&self, ipv4s: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+
+        lookup_system_hostnames(ipv4s, self.concurrency, self.timeout_ms, self.verbose)
+    }
+}
+
+/**
+ * Reverse lookups with systemd-resolved, scoped to the link of each host. The
+ * system resolver is used when systemd-resolved is not running (or for a
+ * lookup that systemd-resolved could not handle).
+ */
+#[cfg(all(feature = "systemd-resolved", target_os = "linux"))]
+pub struct SystemdResolver {
+    links: Arc<HashMap<Ipv4Addr, u32>>,
+    system: SystemResolver
+}
+
+#[cfg(all(feature = "systemd-resolved", target_os = "linux"))]
+impl HostnameResolver for SystemdResolver {
+
+    fn resolve(&self, ipv4: Ipv4Addr) -> Option<String> {
+
+        self.resolve_all(&[ipv4]).remove(&ipv4)
+    }
+
+    fn resolve_all(&self, ipv4s: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+
+        if !resolved::is_available() {
+            eprintln!("[warn] systemd-resolved is not running, using the system resolver");
+            return self.system.resolve_all(ipv4s);
+        }
+
+        let links = Arc::clone(&self.links);
+        let timeout_ms = self.system.timeout_ms;
+        lookup_hostnames(ipv4s, self.system.concurrency, timeout_ms, self.system.verbose, Arc::new(move |ipv4| {
+            resolved::resolve_address(ipv4, links.get(&ipv4).copied(), timeout_ms).unwrap_or_else(|_| find_hostname(ipv4))
+        }))
+    }
+}
+
+/**
+ * Reverse lookups sent directly to the DNS servers given by the user.
+ */
+pub struct DnsServerResolver {
+    servers: Vec<SocketAddr>,
+    timeout_ms: u64,
+    verbose: bool
+}
+
+impl HostnameResolver for DnsServerResolver {
+
+    fn resolve(&self, ipv4: Ipv4Addr) -> Option<String> {
+
+        self.resolve_all(&[ipv4]).remove(&ipv4)
+    }
+
+    fn resolve_all(&self, ipv4s: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+
+        dns::resolve_hostnames(ipv4s, &self.servers, self.timeout_ms, self.verbose)
+    }
+}
+
+/**
+ * Multicast DNS queries on the local network. A socket error is reported as
+ * a warning and leaves the hosts unresolved.
+ */
+pub struct MdnsResolver {
+    timeout_ms: u64
+}
+
+impl HostnameResolver for MdnsResolver {
+
+    fn resolve(&self, ipv4: Ipv4Addr) -> Option<String> {
+
+        self.resolve_all(&[ipv4]).remove(&ipv4)
+    }
+
+    fn resolve_all(&self, ipv4s: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+
+        mdns::resolve_hostnames(ipv4s, self.timeout_ms).unwrap_or_else(|err| {
+            eprintln!("[warn] {}", err);
+            HashMap::new()
+        })
+    }
+}
+
+/**
+ * NetBIOS name queries, answered by Windows hosts and Samba servers. A socket
+ * error is reported as a warning and leaves the hosts unresolved.
+ */
+pub struct NetbiosResolver {
+    timeout_ms: u64
+}
+
+impl HostnameResolver for NetbiosResolver {
+
+    fn resolve(&self, ipv4: Ipv4Addr) -> Option<String> {
+
+        self.resolve_all(&[ipv4]).remove(&ipv4)
+    }
+
+    fn resolve_all(&self, ipv4s: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+
+        netbios::resolve_names(ipv4s, self.timeout_ms).unwrap_or_else(|err| {
+            eprintln!("[warn] {}", err);
+            HashMap::new()
+        })
+    }
+}
+
+/**
+ * Resolve several IPv4 addresses with the system resolver, see the
+ * 'lookup_hostnames' function.
+ */
+pub fn lookup_system_hostnames(ipv4s: &[Ipv4Addr], concurrency: usize, timeout_ms: u64, verbose: bool) -> HashMap<Ipv4Addr, String> {
+
+    lookup_hostnames(ipv4s, concurrency, timeout_ms, verbose, Arc::new(find_hostname))
+}
+
+/**
+ * Resolve several IPv4 addresses with a lookup function, using a bounded
+ * pool of worker threads. Each lookup is abandoned after the given timeout,
+ * the worst case duration is therefore about (hosts / concurrency) x timeout.
+ * Addresses without hostname are not part of the returned map.
+ */
+fn lookup_hostnames(ipv4s: &[Ipv4Addr], concurrency: usize, timeout_ms: u64, verbose: bool, lookup: Arc<HostnameLookup>) -> HashMap<Ipv4Addr, String> {
+
+    let mut hostnames: HashMap<Ipv4Addr, String> = HashMap::new();
+    if ipv4s.is_empty() {
+        return hostnames;
+    }
+
+    let shared_ipv4s: Arc<Vec<Ipv4Addr>> = Arc::new(ipv4s.to_vec());
+    let next_index = Arc::new(AtomicUsize::new(0));
+    let (result_tx, result_rx) = mpsc::channel::<(Ipv4Addr, Option<String>)>();
+
+    let worker_count = concurrency.clamp(1, ipv4s.len());
+    for _ in 0..worker_count {
+
+        let worker_ipv4s = Arc::clone(&shared_ipv4s);
+        let worker_index = Arc::clone(&next_index);
+        let worker_tx = result_tx.clone();
+        let worker_lookup = Arc::clone(&lookup);
+        thread::spawn(move || {
+            while let Some(ipv4) = worker_ipv4s.get(worker_index.fetch_add(1, Ordering::Relaxed)).copied() {
+                if worker_tx.send((ipv4, lookup_with_timeout(ipv4, timeout_ms, Arc::clone(&worker_lookup)))).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(result_tx);
+
+    // The channel is closed once all workers are done, each settled lookup
+    // is received exactly once.
+    let mut progress_step = 0;
+    for (settled_count, (ipv4, hostname)) in result_rx.iter().enumerate() {
+
+        if let Some(hostname) = hostname {
+            hostnames.insert(ipv4, hostname);
+        }
+
+        let settled_percent = (settled_count + 1) * 100 / ipv4s.len();
+        if verbose && settled_percent / PROGRESS_STEP_PERCENT > progress_step {
+            progress_step = settled_percent / PROGRESS_STEP_PERCENT;
+            eprintln!("Resolved {} of {} hostnames ({} found)", settled_count + 1, ipv4s.len(), hostnames.len());
+        }
+    }
+    hostnames
+}
+
+/**
+ * Run a single reverse lookup on a helper thread. The system resolver can not
+ * be interrupted, a lookup exceeding the timeout is left running in the
+ * background and its result is ignored.
+ */
+fn lookup_with_timeout(ipv4: Ipv4Addr, timeout_ms: u64, lookup: Arc<HostnameLookup>) -> Option<String> {
+
+    let (lookup_tx, lookup_rx) = mpsc::channel();
+    thread::spawn(move || {
+        lookup_tx.send(lookup(ipv4)).unwrap_or(());
+    });
+
+    lookup_rx.recv_timeout(Duration::from_millis(timeout_ms)).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::args::build_args;
+
+    #[test]
+    fn should_parse_hosts_file_fixture() {
+
+        let content = fs::read_to_string("./data/hosts-sample.txt").unwrap();
+        let hostnames = parse_hosts_file(&content);
+
+        assert_eq!(hostnames.len(), 4);
+        assert_eq!(hostnames.get(&Ipv4Addr::new(127, 0, 0, 1)), Some(&"localhost".to_string()));
+        assert_eq!(hostnames.get(&Ipv4Addr::new(192, 168, 1, 1)), Some(&"router.lab".to_string()));
+        assert_eq!(hostnames.get(&Ipv4Addr::new(192, 168, 1, 30)), Some(&"printer.lab".to_string()));
+    }
+
+    #[test]
+    fn should_keep_first_hosts_entry() {
+
+        let content = fs::read_to_string("./data/hosts-sample.txt").unwrap();
+        let hostnames = parse_hosts_file(&content);
+
+        assert_eq!(hostnames.get(&Ipv4Addr::new(192, 168, 1, 20)), Some(&"nas.lab".to_string()));
+    }
+
+    #[test]
+    fn should_skip_commented_and_incomplete_hosts_entries() {
+
+        let content = fs::read_to_string("./data/hosts-sample.txt").unwrap();
+        let hostnames = parse_hosts_file(&content);
+
+        assert_eq!(hostnames.get(&Ipv4Addr::new(192, 168, 1, 40)), None);
+        assert_eq!(hostnames.get(&Ipv4Addr::new(192, 168, 1, 50)), None);
+        assert!(hostnames.values().all(|name| name != "router-v6" && name != "invalid.lab"));
+    }
+
+    #[test]
+    fn should_reject_missing_user_hosts_file() {
+
+        assert!(load_static_hostnames(Some("./data/missing-hosts.txt")).is_err());
+        assert_eq!(load_static_hostnames(Some("./data/hosts-sample.txt")).unwrap().get(&Ipv4Addr::new(192, 168, 1, 1)), Some(&"router.lab".to_string()));
+    }
+
+    #[test]
+    fn should_skip_empty_lookups() {
+
+        assert!(lookup_system_hostnames(&[], 20, 1000, false).is_empty());
+    }
+
+    #[test]
+    fn should_lookup_hostnames_with_workers() {
+
+        let ipv4s = vec![Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(10, 254, 254, 254), Ipv4Addr::new(127, 0, 0, 1)];
+
+        let hostnames = lookup_system_hostnames(&ipv4s, 2, 5000, false);
+        assert_eq!(hostnames.get(&Ipv4Addr::new(127, 0, 0, 1)), Some(&"localhost".to_string()));
+        assert_eq!(hostnames.get(&Ipv4Addr::new(10, 254, 254, 254)), None);
+    }
+
+    struct MockResolver {
+        hostnames: HashMap<Ipv4Addr, String>,
+        queried_ipv4s: Mutex<Vec<Ipv4Addr>>
+    }
+
+    impl MockResolver {
+
+        fn new(hostnames: &[(Ipv4Addr, &str)]) -> Self {
+
+            MockResolver {
+                hostnames: hostnames.iter().map(|(ipv4, hostname)| (*ipv4, hostname.to_string())).collect(),
+                queried_ipv4s: Mutex::new(vec![])
+            }
+        }
+    }
+
+    impl HostnameResolver for MockResolver {
+
+        fn resolve(&self, ipv4: Ipv4Addr) -> Option<String> {
+
+            self.queried_ipv4s.lock().unwrap().push(ipv4);
+            self.hostnames.get(&ipv4).cloned()
+        }
+    }
+
+    fn build_target_detail(ipv4: Ipv4Addr) -> TargetDetails {
+
+        TargetDetails::for_test(ipv4, MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, ipv4.octets()[3]))
+    }
+
+    #[test]
+    fn should_keep_first_resolved_hostname() {
+
+        let mut target_details = vec![build_target_detail(Ipv4Addr::new(192, 168, 1, 1)), build_target_detail(Ipv4Addr::new(192, 168, 1, 20))];
+
+        let dns_resolver = MockResolver::new(&[(Ipv4Addr::new(192, 168, 1, 1), "router.home"), (Ipv4Addr::new(192, 168, 1, 20), "nas.home")]);
+        apply_hostnames(&mut target_details, HostnameSource::Mdns, &MockResolver::new(&[(Ipv4Addr::new(192, 168, 1, 20), "printer.local")]));
+        apply_hostnames(&mut target_details, HostnameSource::Dns, &dns_resolver);
+
+        assert_eq!(*dns_resolver.queried_ipv4s.lock().unwrap(), [Ipv4Addr::new(192, 168, 1, 1)]);
+        assert_eq!(target_details[0].hostname, Some("router.home".to_string()));
+        assert_eq!(target_details[0].hostname_source, Some(HostnameSource::Dns));
+        assert_eq!(target_details[1].hostname, Some("printer.local".to_string()));
+        assert_eq!(target_details[1].hostname_source, Some(HostnameSource::Mdns));
+    }
+
+    #[test]
+    fn should_resolve_hostnames_with_mock_resolver() {
+
+        let matches = build_args().get_matches_from(["arp-scan", "--no-resolve-cache", "--hostname-style", "short"]);
+        let options = ScanOptions::new(&matches).unwrap();
+        let mut target_details = vec![
+            build_target_detail(Ipv4Addr::new(192, 168, 1, 1)),
+            build_target_detail(Ipv4Addr::new(192, 168, 1, 20)),
+            build_target_detail(Ipv4Addr::new(192, 168, 1, 30))
+        ];
+        target_details[2].hostname = Some("stale.home".to_string());
+
+        let dns_resolver = Arc::new(MockResolver::new(&[(Ipv4Addr::new(192, 168, 1, 20), "nas.home."), (Ipv4Addr::new(192, 168, 1, 1), "ignored.home")]));
+        let resolvers: ResolverChain = vec![
+            (HostnameSource::HostsFile, Arc::new(StaticResolver::new(HashMap::from([(Ipv4Addr::new(192, 168, 1, 1), "router.lab".to_string())])))),
+            (HostnameSource::Dns, Arc::clone(&dns_resolver) as Arc<dyn HostnameResolver>)
+        ];
+        resolve_hostnames_with(&mut target_details, &options, &resolvers);
+
+        assert_eq!(*dns_resolver.queried_ipv4s.lock().unwrap(), [Ipv4Addr::new(192, 168, 1, 20), Ipv4Addr::new(192, 168, 1, 30)]);
+        assert_eq!(target_details[0].hostname, Some("router".to_string()));
+        assert_eq!(target_details[0].hostname_source, Some(HostnameSource::HostsFile));
+        assert_eq!(target_details[1].hostname, Some("nas".to_string()));
+        assert_eq!(target_details[1].hostname_source, Some(HostnameSource::Dns));
+        assert_eq!(target_details[2].hostname, None);
+        assert_eq!(target_details[2].hostname_source, None);
+    }
+
+    #[test]
+    fn should_build_configured_resolver_chain() {
+
+        let matches = build_args().get_matches_from(["arp-scan", "--no-resolve-cache", "--resolve-order", "mdns,hosts,netbios"]);
+        let options = ScanOptions::new(&matches).unwrap();
+
+        let sources: Vec<HostnameSource> = build_resolvers(&[], &options).unwrap().iter().map(|(source, _)| *source).collect();
+        assert_eq!(sources, [HostnameSource::Mdns, HostnameSource::HostsFile, HostnameSource::Netbios]);
+    }
+
+    #[test]
+    fn should_load_hosts_files_when_resolving() {
+
+        let options = ScanOptions {
+            hosts_file: Some("./data/missing-hosts.txt".to_string()),
+            ..ScanOptions::default()
+        };
+        assert!(options.static_hostnames.get().is_none());
+        assert!(matches!(build_resolvers(&[], &options), Err(ScanError::Resolution(_))));
+
+        let options = ScanOptions {
+            hosts_file: Some("./data/hosts-sample.txt".to_string()),
+            ..ScanOptions::default()
+        };
+        assert!(build_resolvers(&[], &options).is_ok());
+        assert_eq!(options.static_hostnames.get().and_then(|hostnames| hostnames.get(&Ipv4Addr::new(192, 168, 1, 1))), Some(&"router.lab".to_string()));
+    }
+
+    #[test]
+    fn should_format_hostnames() {
+
+        assert_eq!(format_hostname("printer.office.corp.example.com.", HostnameStyle::Full), "printer.office.corp.example.com");
+        assert_eq!(format_hostname("printer.office.corp.example.com.", HostnameStyle::Short), "printer");
+        assert_eq!(format_hostname("router.home", HostnameStyle::Short), "router");
+    }
+
+    #[test]
+    fn should_format_single_label_hostnames() {
+
+        assert_eq!(format_hostname("nas", HostnameStyle::Full), "nas");
+        assert_eq!(format_hostname("nas", HostnameStyle::Short), "nas");
+        assert_eq!(format_hostname("nas.", HostnameStyle::Short), "nas");
+    }
+
+    #[test]
+    fn should_keep_empty_and_ip_hostnames() {
+
+        assert_eq!(format_hostname("", HostnameStyle::Short), "");
+        assert_eq!(format_hostname("192.168.1.20", HostnameStyle::Short), "192.168.1.20");
+        assert_eq!(format_hostname("fe80::1", HostnameStyle::Full), "fe80::1");
+    }
+
+}
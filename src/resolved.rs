@@ -0,0 +1,117 @@
+use std::io::{Read, Write};
+use std::net::Ipv4Addr;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+// The Varlink interface of systemd-resolved exposes the same methods as its
+// D-Bus API, without requiring a D-Bus client library.
+pub const RESOLVED_SOCKET_PATH: &str = "/run/systemd/resolve/io.systemd.Resolve";
+
+const RESOLVE_ADDRESS_METHOD: &str = "io.systemd.Resolve.ResolveAddress";
+const NO_RECORD_ERRORS: [&str; 2] = ["io.systemd.Resolve.NoSuchResourceRecord", "io.systemd.Resolve.NoNameServers"];
+const AF_INET: u8 = 2;
+
+/**
+ * Check if systemd-resolved is running, by looking for its Varlink socket.
+ */
+pub fn is_available() -> bool {
+
+    Path::new(RESOLVED_SOCKET_PATH).exists()
+}
+
+/**
+ * Resolve the hostname of an IPv4 address with systemd-resolved. The lookup
+ * is scoped to the given network link (interface index), so that the per-link
+ * DNS servers (for instance set by a VPN) and mDNS settings are used. A
+ * missing PTR record is not an error, it gives an empty result.
+ */
+pub fn resolve_address(ipv4: Ipv4Addr, ifindex: Option<u32>, timeout_ms: u64) -> Result<Option<String>, String> {
+
+    let mut stream = UnixStream::connect(RESOLVED_SOCKET_PATH).map_err(|err| format!("Could not connect to systemd-resolved ({})", err))?;
+    stream.set_read_timeout(Some(Duration::from_millis(timeout_ms))).map_err(|err| format!("Could not configure systemd-resolved socket ({})", err))?;
+    stream.set_write_timeout(Some(Duration::from_millis(timeout_ms))).map_err(|err| format!("Could not configure systemd-resolved socket ({})", err))?;
+
+    stream.write_all(&build_request(ipv4, ifindex)).map_err(|err| format!("Could not send systemd-resolved request ({})", err))?;
+
+    // Varlink messages are JSON objects terminated by a NUL byte
+    let mut reply: Vec<u8> = vec![];
+    let mut buffer = [0u8; 4096];
+    while !reply.contains(&0) {
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read_size) => reply.extend_from_slice(&buffer[..read_size]),
+            Err(err) => return Err(format!("Could not read systemd-resolved reply ({})", err))
+        }
+    }
+    parse_reply(&reply)
+}
+
+fn build_request(ipv4: Ipv4Addr, ifindex: Option<u32>) -> Vec<u8> {
+
+    let mut parameters = json!({
+        "family": AF_INET,
+        "address": ipv4.octets()
+    });
+    if let Some(ifindex) = ifindex {
+        parameters["ifindex"] = json!(ifindex);
+    }
+
+    let mut request = json!({ "method": RESOLVE_ADDRESS_METHOD, "parameters": parameters }).to_string().into_bytes();
+    request.push(0);
+    request
+}
+
+fn parse_reply(reply: &[u8]) -> Result<Option<String>, String> {
+
+    let message = reply.split(|byte| *byte == 0).next().unwrap_or_default();
+    let reply: Value = serde_json::from_slice(message).map_err(|err| format!("Malformed systemd-resolved reply ({})", err))?;
+
+    if let Some(error) = reply.get("error").and_then(|error| error.as_str()) {
+        return match NO_RECORD_ERRORS.contains(&error) {
+            true => Ok(None),
+            false => Err(format!("systemd-resolved lookup failed ({})", error))
+        };
+    }
+
+    let name = reply.pointer("/parameters/names/0/name").and_then(|name| name.as_str());
+    Ok(name.map(|name| name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_build_link_scoped_request() {
+
+        let request = build_request(Ipv4Addr::new(192, 168, 1, 20), Some(3));
+
+        assert_eq!(request.last(), Some(&0));
+        let parsed: Value = serde_json::from_slice(&request[..request.len() - 1]).unwrap();
+        assert_eq!(parsed["method"], "io.systemd.Resolve.ResolveAddress");
+        assert_eq!(parsed["parameters"], json!({ "ifindex": 3, "family": 2, "address": [192, 168, 1, 20] }));
+    }
+
+    #[test]
+    fn should_parse_resolved_name() {
+
+        let reply = b"{\"parameters\":{\"names\":[{\"ifindex\":3,\"name\":\"printer.home\"}],\"flags\":1048577}}\0";
+
+        assert_eq!(parse_reply(reply), Ok(Some("printer.home".to_string())));
+    }
+
+    #[test]
+    fn should_parse_missing_record() {
+
+        let reply = b"{\"error\":\"io.systemd.Resolve.NoSuchResourceRecord\",\"parameters\":{}}\0";
+
+        assert_eq!(parse_reply(reply), Ok(None));
+        assert!(parse_reply(b"{\"error\":\"io.systemd.Resolve.TimedOut\",\"parameters\":{}}\0").is_err());
+        assert!(parse_reply(b"{\"parameters\"\0").is_err());
+    }
+
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+/**
+ * A source of hostnames for the hosts found by a scan. Each resolution
+ * method of arp-scan (hosts files, reverse DNS, mDNS, NetBIOS and DHCP
+ * leases) implements this trait, and library users can provide their own
+ * (for instance backed by an inventory database, or a stub in tests).
+ *
+ * Resolvers are shared between threads, a lookup that fails or times out
+ * should simply return `None`.
+ */
+pub trait HostnameResolver: Send + Sync {
+
+    /**
+     * Find the hostname of a single IPv4 address.
+     */
+    fn resolve(&self, ipv4: Ipv4Addr) -> Option<String>;
+
+    /**
+     * Find the hostnames of several IPv4 addresses, addresses without
+     * hostname are not part of the returned map. The default implementation
+     * resolves each address in turn, network resolvers override it to send
+     * their queries in parallel.
+     */
+    fn resolve_all(&self, ipv4s: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+
+        ipv4s.iter().filter_map(|ipv4| self.resolve(*ipv4).map(|hostname| (*ipv4, hostname))).collect()
+    }
+}
+
+/**
+ * A resolver answering from a fixed table of hostnames.
+ *
+ * ```
+ * use std::collections::HashMap;
+ * use std::net::Ipv4Addr;
+ * use arp_scan::resolver::{HostnameResolver, StaticResolver};
+ *
+ * let resolver = StaticResolver::new(HashMap::from([(Ipv4Addr::new(192, 168, 1, 1), "router.lab".to_string())]));
+ * assert_eq!(resolver.resolve(Ipv4Addr::new(192, 168, 1, 1)).as_deref(), Some("router.lab"));
+ * assert_eq!(resolver.resolve(Ipv4Addr::new(192, 168, 1, 2)), None);
+ * ```
+ */
+#[derive(Clone, Debug, Default)]
+pub struct StaticResolver {
+    hostnames: HashMap<Ipv4Addr, String>
+}
+
+impl StaticResolver {
+
+    pub fn new(hostnames: HashMap<Ipv4Addr, String>) -> Self {
+
+        StaticResolver { hostnames }
+    }
+}
+
+impl HostnameResolver for StaticResolver {
+
+    fn resolve(&self, ipv4: Ipv4Addr) -> Option<String> {
+
+        self.hostnames.get(&ipv4).cloned()
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use pnet_datalink::{MacAddr, NetworkInterface};
+use pnet::packet::arp::ArpOperations;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::datalink::DataLink;
+use crate::error::ScanError;
+use crate::network::{self, CancellationToken};
+
+/**
+ * A host simulated by the ARP responder, answering requests for its IPv4
+ * address with its MAC address.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedHost {
+    pub ipv4: Ipv4Addr,
+    pub mac: MacAddr
+}
+
+impl SimulatedHost {
+
+    /**
+     * Parse a simulated host given as 'IPV4=MAC', or as 'IPV4' alone. Hosts
+     * without MAC get a locally administered address ending with their IPv4
+     * bytes (192.168.1.50 is at 02:00:c0:a8:01:32).
+     */
+    pub fn parse(value: &str) -> Result<SimulatedHost, String> {
+
+        let (ipv4_value, mac_value) = match value.split_once('=') {
+            Some((ipv4_value, mac_value)) => (ipv4_value, Some(mac_value)),
+            None => (value, None)
+        };
+        let ipv4 = Ipv4Addr::from_str(ipv4_value.trim()).map_err(|err| {
+            format!("Expected IPv4 address for simulated host {} ({})", value, err)
+        })?;
+        let mac = match mac_value {
+            Some(mac_value) => MacAddr::from_str(mac_value.trim()).map_err(|err| {
+                format!("Expected MAC address for simulated host {} ({})", value, err)
+            })?,
+            None => {
+                let [a, b, c, d] = ipv4.octets();
+                MacAddr::new(0x02, 0x00, a, b, c, d)
+            }
+        };
+
+        Ok(SimulatedHost { ipv4, mac })
+    }
+}
+
+/**
+ * Answer ARP requests for a set of simulated hosts, so that the scanner can
+ * be tested end-to-end (for instance on both ends of a veth pair). Each reply
+ * can be delayed, and a share of the requests can be ignored to simulate
+ * losses. Replies are built with the frame builder of the scanner.
+ */
+pub struct Responder {
+    interface: NetworkInterface,
+    hosts: HashMap<Ipv4Addr, MacAddr>,
+    vlan_id: Option<u16>,
+    delay: Duration,
+    loss_percent: u8,
+    rng: StdRng
+}
+
+impl Responder {
+
+    /**
+     * Build a responder for the given hosts. The random generator deciding
+     * lost requests can be seeded, so that losses are reproducible.
+     */
+    pub fn new(interface: &NetworkInterface, hosts: &[SimulatedHost], vlan_id: Option<u16>, delay: Duration, loss_percent: u8, seed: Option<u64>) -> Self {
+
+        Responder {
+            interface: interface.clone(),
+            hosts: hosts.iter().map(|host| (host.ipv4, host.mac)).collect(),
+            vlan_id,
+            delay,
+            loss_percent: loss_percent.min(100),
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy()
+            }
+        }
+    }
+
+    /**
+     * Build the reply to a received frame, if it is an ARP request for a
+     * simulated host that is not lost.
+     */
+    pub fn answer(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, ScanError> {
+
+        let (requester_mac, requester_ipv4, host_ipv4, host_mac) = match network::read_arp_exchange(frame, self.vlan_id) {
+            Some((ArpOperations::Request, sender_mac, sender_ipv4, target_ipv4)) => match self.hosts.get(&target_ipv4) {
+                Some(host_mac) => (sender_mac, sender_ipv4, target_ipv4, *host_mac),
+                None => return Ok(None)
+            },
+            _ => return Ok(None)
+        };
+
+        if self.rng.gen_range(0..100) < self.loss_percent {
+            return Ok(None);
+        }
+
+        network::build_arp_reply(&self.interface, host_mac, host_ipv4, requester_mac, requester_ipv4, self.vlan_id).map(Some)
+    }
+
+    /**
+     * Answer the requests received on a datalink layer until the token is
+     * cancelled, and return the amount of sent replies. Delayed replies are
+     * queued, so that requests keep being received in the meantime.
+     */
+    pub fn run(&mut self, link: &dyn DataLink, cancellation: &CancellationToken) -> Result<usize, ScanError> {
+
+        let mut pending_replies: VecDeque<(Instant, Vec<u8>)> = VecDeque::new();
+        let mut reply_count: usize = 0;
+
+        while !cancellation.is_cancelled() {
+
+            while pending_replies.front().is_some_and(|(due_at, _)| *due_at <= Instant::now()) {
+                if let Some((_, reply)) = pending_replies.pop_front() {
+                    link.send(&reply).map_err(|err| ScanError::Packet(format!("Could not send ARP reply ({})", err)))?;
+                    reply_count += 1;
+                }
+            }
+
+            let recv_timeout = match pending_replies.front() {
+                Some((due_at, _)) => due_at.saturating_duration_since(Instant::now()),
+                None => Duration::from_millis(network::DATALINK_RCV_TIMEOUT)
+            };
+            let frame = match link.recv(recv_timeout) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => continue,
+                Err(error) => return Err(ScanError::Packet(format!("Failed to receive ARP requests ({})", error)))
+            };
+
+            if let Some(reply) = self.answer(&frame)? {
+                pending_replies.push_back((Instant::now() + self.delay, reply));
+            }
+        }
+
+        Ok(reply_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use ipnetwork::IpNetwork;
+    use crate::datalink::FakeDataLink;
+    use crate::scanner::ArpScanner;
+
+    fn build_interface() -> NetworkInterface {
+
+        NetworkInterface {
+            name: "veth0".to_string(),
+            description: String::new(),
+            index: 2,
+            mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            ips: vec!["10.10.0.1/24".parse().unwrap()],
+            // IFF_UP, the interface is not a loopback
+            flags: 1
+        }
+    }
+
+    #[test]
+    fn should_parse_simulated_hosts() {
+
+        assert_eq!(SimulatedHost::parse("10.10.0.5=00:22:72:d7:b5:23"), Ok(SimulatedHost {
+            ipv4: Ipv4Addr::new(10, 10, 0, 5),
+            mac: MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)
+        }));
+        assert_eq!(SimulatedHost::parse("192.168.1.50").map(|host| host.mac), Ok(MacAddr::new(0x02, 0x00, 0xc0, 0xa8, 0x01, 0x32)));
+        assert!(SimulatedHost::parse("10.10.0.300").is_err());
+        assert!(SimulatedHost::parse("10.10.0.5=00:22").is_err());
+    }
+
+    #[test]
+    fn should_only_answer_requests_for_simulated_hosts() {
+
+        let host = SimulatedHost::parse("10.10.0.5=00:22:72:d7:b5:23").unwrap();
+        let mut responder = Responder::new(&build_interface(), std::slice::from_ref(&host), None, Duration::ZERO, 0, Some(1));
+
+        let requester_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+        let mut request = FakeDataLink::arp_reply(requester_mac, Ipv4Addr::new(10, 10, 0, 1), host.ipv4);
+        pnet::packet::arp::MutableArpPacket::new(&mut request[14..]).unwrap().set_operation(ArpOperations::Request);
+
+        let reply = responder.answer(&request).unwrap().unwrap();
+        assert_eq!(network::read_arp_exchange(&reply, None), Some((ArpOperations::Reply, host.mac, host.ipv4, Ipv4Addr::new(10, 10, 0, 1))));
+        assert_eq!(&reply[0..6], &requester_mac.octets());
+
+        let unknown_request = FakeDataLink::arp_reply(requester_mac, Ipv4Addr::new(10, 10, 0, 1), Ipv4Addr::new(10, 10, 0, 6));
+        assert_eq!(responder.answer(&unknown_request).unwrap(), None);
+        assert_eq!(responder.answer(&FakeDataLink::arp_reply(host.mac, host.ipv4, Ipv4Addr::new(10, 10, 0, 1))).unwrap(), None);
+
+        let mut lossy_responder = Responder::new(&build_interface(), &[host], None, Duration::ZERO, 100, Some(1));
+        assert_eq!(lossy_responder.answer(&request).unwrap(), None);
+    }
+
+    #[test]
+    fn should_be_found_by_the_scanner() {
+
+        let hosts = vec![
+            SimulatedHost::parse("10.10.0.5").unwrap(),
+            SimulatedHost::parse("10.10.0.9=00:22:72:d7:b5:23").unwrap()
+        ];
+        let (scanner_link, responder_link) = FakeDataLink::pair();
+
+        let cancellation = CancellationToken::new();
+        let responder_cancellation = cancellation.clone();
+        let responder_hosts = hosts.clone();
+        let responder = thread::spawn(move || {
+            let mut responder = Responder::new(&build_interface(), &responder_hosts, None, Duration::from_millis(5), 0, Some(1));
+            responder.run(&responder_link, &responder_cancellation)
+        });
+
+        let scanner = ArpScanner::builder()
+            .interface("veth0")
+            .network(IpNetwork::from_str("10.10.0.0/28").unwrap())
+            .timeout(Duration::from_millis(200))
+            .interval(Duration::ZERO)
+            .resolve_hostnames(false)
+            .build();
+        let mut session = scanner.open_with_links(vec![(build_interface(), Arc::new(scanner_link))]).unwrap();
+        let scan_result = session.run_round().unwrap();
+        session.close().unwrap();
+
+        cancellation.cancel();
+        assert_eq!(responder.join().unwrap().unwrap(), 2);
+
+        let found_hosts: Vec<SimulatedHost> = scan_result.hosts.iter().map(|host| SimulatedHost { ipv4: host.ipv4, mac: host.mac }).collect();
+        assert_eq!(found_hosts, hosts);
+    }
+
+}
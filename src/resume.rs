@@ -0,0 +1,294 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::net::Ipv4Addr;
+
+use ipnetwork::IpNetwork;
+use pnet_datalink::MacAddr;
+use serde::{Deserialize, Serialize};
+
+use crate::args::ScanOptions;
+use crate::network::TargetDetails;
+use crate::utils;
+
+// State files written by another version are rejected, the version should be
+// increased on each incompatible change of the checkpoint structure.
+pub const STATE_FILE_VERSION: u32 = 1;
+pub const CHECKPOINT_INTERVAL_MS: u128 = 1000;
+
+/**
+ * A host recorded in a state file. MAC addresses are kept as text since the
+ * datalink MAC type is not serializable.
+ */
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CheckpointHost {
+    pub ipv4: Ipv4Addr,
+    pub mac: String,
+    #[serde(default)]
+    pub frame_src_mac: Option<String>,
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub hostname_ascii: Option<String>,
+    pub vendor: Option<String>,
+    pub response_time_ms: Option<u64>,
+    #[serde(default)]
+    pub reply_count: usize,
+    pub interface: String,
+    pub vlan_id: Option<u16>,
+    #[serde(default)]
+    pub virtualization: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>
+}
+
+/**
+ * The progress of an interrupted scan. Scan passes (one per interface and
+ * VLAN) are done one after another: all passes before 'pass_index' are
+ * complete, and the first 'sent_count' targets of the current pass have been
+ * sent. Hosts that answered the current pass before the last checkpoint but
+ * are not recorded yet are kept in 'pending_ipv4s' and requested again.
+ */
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ScanCheckpoint {
+    pub version: u32,
+    pub target_spec: String,
+    pub pass_index: usize,
+    pub sent_count: usize,
+    pub pending_ipv4s: Vec<Ipv4Addr>,
+    pub hosts: Vec<CheckpointHost>
+}
+
+impl ScanCheckpoint {
+
+    pub fn new(target_spec: String) -> ScanCheckpoint {
+
+        ScanCheckpoint {
+            version: STATE_FILE_VERSION,
+            target_spec,
+            pass_index: 0,
+            sent_count: 0,
+            pending_ipv4s: vec![],
+            hosts: vec![]
+        }
+    }
+
+    /**
+     * Load the checkpoint of a previous scan. A missing state file starts a
+     * new scan, while a state file with another version or recorded for other
+     * targets is rejected.
+     */
+    pub fn load(file_path: &str, target_spec: &str) -> Result<Option<ScanCheckpoint>, String> {
+
+        let content = match fs::read_to_string(file_path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(format!("Could not open state file {} ({})", file_path, err))
+        };
+        ScanCheckpoint::parse(&content, target_spec).map(Some).map_err(|err| {
+            format!("Could not resume from state file {} ({}), remove it to start a new scan", file_path, err)
+        })
+    }
+
+    fn parse(content: &str, target_spec: &str) -> Result<ScanCheckpoint, String> {
+
+        let version = serde_json::from_str::<serde_json::Value>(content).ok()
+            .and_then(|value| value.get("version").and_then(|version| version.as_u64()))
+            .ok_or_else(|| "missing state file version".to_string())?;
+        if version != u64::from(STATE_FILE_VERSION) {
+            return Err(format!("state file version {} is not supported, expected {}", version, STATE_FILE_VERSION));
+        }
+
+        let checkpoint: ScanCheckpoint = serde_json::from_str(content).map_err(|err| format!("malformed state file, {}", err))?;
+        if checkpoint.target_spec != target_spec {
+            return Err("the scan targets are different".to_string());
+        }
+        Ok(checkpoint)
+    }
+
+    /**
+     * Write the checkpoint to the state file. The file is replaced atomically,
+     * so that an interruption while saving keeps the previous checkpoint.
+     */
+    pub fn save(&self, file_path: &str) -> Result<(), String> {
+
+        let content = serde_json::to_string(self).map_err(|err| format!("Could not serialize scan state ({})", err))?;
+        utils::write_output(Some(file_path), content.as_bytes())
+    }
+
+    /**
+     * Check if a target of the current pass has already been sent, based on
+     * its position in the targets of the pass. Pending hosts answered but are
+     * not recorded yet, they are therefore requested again.
+     */
+    pub fn has_sent(&self, target_index: usize, ipv4: Ipv4Addr) -> bool {
+
+        target_index < self.sent_count && !self.pending_ipv4s.contains(&ipv4)
+    }
+
+    /**
+     * Record the hosts found by a scan pass. Hosts that are already recorded
+     * (same IPv4, MAC, interface & VLAN) are replaced by the new answer.
+     */
+    pub fn record_hosts(&mut self, target_details: &[TargetDetails]) {
+
+        for detail in target_details {
+            let host = CheckpointHost {
+                ipv4: detail.ipv4,
+                mac: detail.mac.to_string(),
+                frame_src_mac: detail.frame_src_mac.map(|frame_src_mac| frame_src_mac.to_string()),
+                hostname: detail.hostname.clone(),
+                hostname_ascii: detail.hostname_ascii.clone(),
+                vendor: detail.vendor.clone(),
+                response_time_ms: detail.response_time_ms,
+                reply_count: detail.reply_count,
+                interface: detail.interface.clone(),
+                vlan_id: detail.vlan_id,
+                virtualization: detail.virtualization.clone(),
+                note: detail.note.clone()
+            };
+            self.hosts.retain(|recorded| (recorded.ipv4, &recorded.mac, &recorded.interface, recorded.vlan_id) != (host.ipv4, &host.mac, &host.interface, host.vlan_id));
+            self.hosts.push(host);
+        }
+    }
+
+    /**
+     * Mark the current pass as complete, the next pass starts from its first
+     * target.
+     */
+    pub fn complete_pass(&mut self) {
+
+        self.pass_index += 1;
+        self.sent_count = 0;
+        self.pending_ipv4s.clear();
+    }
+
+    /**
+     * Recorded hosts that answered on the given interface and VLAN, they are
+     * not requested again when the pass is resumed.
+     */
+    pub fn recorded_ipv4s(&self, interface_name: &str, vlan_id: Option<u16>) -> Vec<Ipv4Addr> {
+
+        self.hosts.iter()
+            .filter(|host| host.interface == interface_name && host.vlan_id == vlan_id)
+            .map(|host| host.ipv4)
+            .collect()
+    }
+
+    /**
+     * Build the target details of all recorded hosts, which merges the hosts
+     * of previous runs with the ones found by the current run.
+     */
+    pub fn target_details(&self) -> Vec<TargetDetails> {
+
+        self.hosts.iter().filter_map(|host| {
+            host.mac.parse::<MacAddr>().ok().map(|mac| TargetDetails {
+                ipv4: host.ipv4,
+                mac,
+                hostname: host.hostname.clone(),
+                vendor: host.vendor.clone(),
+                response_time_ms: host.response_time_ms,
+                interface: host.interface.clone(),
+                vlan_id: host.vlan_id,
+                frame_src_mac: host.frame_src_mac.as_deref().and_then(|frame_src_mac| frame_src_mac.parse().ok()),
+                reply_count: host.reply_count,
+                verify_status: None,
+                origin: None,
+                hostname_source: None,
+                hostname_ascii: host.hostname_ascii.clone(),
+                virtualization: host.virtualization.clone(),
+                note: host.note.clone()
+            })
+        }).collect()
+    }
+}
+
+/**
+ * Describe the scan targets of a run (interfaces, networks, exclusions, VLANs
+ * & target order). A state file is only resumed by a scan with the same
+ * targets, since the progress is stored as a position in the targets.
+ */
+pub fn compute_target_spec(interface_networks: &[(&str, Vec<&IpNetwork>)], options: &ScanOptions) -> String {
+
+    let interfaces: Vec<String> = interface_networks.iter().map(|(interface_name, networks)| {
+        let network_texts: Vec<String> = networks.iter().map(|network| network.to_string()).collect();
+        format!("{}={}", interface_name, network_texts.join(","))
+    }).collect();
+    let excluded_networks: Vec<String> = options.excluded_networks.iter().map(|network| network.to_string()).collect();
+    let vlan_ids: Vec<String> = options.vlan_ids.iter().map(|vlan_id| vlan_id.to_string()).collect();
+    let order = match options.random_seed {
+        Some(seed) => format!("seed={}", seed),
+        None => "sequential".to_string()
+    };
+
+    format!("{};exclude={};vlan={};{}", interfaces.join(";"), excluded_networks.join(","), vlan_ids.join(","), order)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn build_target_detail(ipv4: Ipv4Addr, mac: MacAddr) -> TargetDetails {
+
+        TargetDetails {
+            vendor: Some("Vendor".to_string()),
+            response_time_ms: Some(12),
+            ..TargetDetails::for_test(ipv4, mac)
+        }
+    }
+
+    #[test]
+    fn should_reload_saved_checkpoint() {
+
+        let mut checkpoint = ScanCheckpoint::new("eth0=192.168.1.0/24".to_string());
+        checkpoint.sent_count = 42;
+        checkpoint.record_hosts(&[build_target_detail(Ipv4Addr::new(192, 168, 1, 1), MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23))]);
+
+        let content = serde_json::to_string(&checkpoint).unwrap();
+        assert_eq!(ScanCheckpoint::parse(&content, "eth0=192.168.1.0/24"), Ok(checkpoint));
+    }
+
+    #[test]
+    fn should_reject_stale_state_files() {
+
+        let checkpoint = ScanCheckpoint::new("eth0=192.168.1.0/24".to_string());
+        let content = serde_json::to_string(&checkpoint).unwrap();
+
+        assert_eq!(ScanCheckpoint::parse(&content, "eth0=10.0.0.0/24"), Err("the scan targets are different".to_string()));
+        assert_eq!(ScanCheckpoint::parse(&content.replace("\"version\":1", "\"version\":0"), "eth0=192.168.1.0/24"), Err("state file version 0 is not supported, expected 1".to_string()));
+        assert!(ScanCheckpoint::parse("{}", "eth0=192.168.1.0/24").is_err());
+    }
+
+    #[test]
+    fn should_skip_sent_targets_except_pending() {
+
+        let mut checkpoint = ScanCheckpoint::new(String::new());
+        checkpoint.sent_count = 10;
+        checkpoint.pending_ipv4s = vec![Ipv4Addr::new(192, 168, 1, 5)];
+
+        assert!(checkpoint.has_sent(4, Ipv4Addr::new(192, 168, 1, 4)));
+        assert!(!checkpoint.has_sent(5, Ipv4Addr::new(192, 168, 1, 5)));
+        assert!(!checkpoint.has_sent(10, Ipv4Addr::new(192, 168, 1, 10)));
+
+        checkpoint.complete_pass();
+        assert_eq!(checkpoint.pass_index, 1);
+        assert!(!checkpoint.has_sent(4, Ipv4Addr::new(192, 168, 1, 4)));
+    }
+
+    #[test]
+    fn should_merge_recorded_hosts() {
+
+        let mut checkpoint = ScanCheckpoint::new(String::new());
+        checkpoint.record_hosts(&[build_target_detail(Ipv4Addr::new(192, 168, 1, 1), MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23))]);
+        checkpoint.record_hosts(&[
+            build_target_detail(Ipv4Addr::new(192, 168, 1, 1), MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)),
+            build_target_detail(Ipv4Addr::new(192, 168, 1, 2), MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b))
+        ]);
+
+        let target_details = checkpoint.target_details();
+        assert_eq!(target_details.len(), 2);
+        assert_eq!(target_details[0].mac, MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23));
+        assert_eq!(checkpoint.recorded_ipv4s("eth0", None), vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)]);
+        assert!(checkpoint.recorded_ipv4s("eth1", None).is_empty());
+    }
+
+}
@@ -0,0 +1,1624 @@
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use ipnetwork::{IpNetwork, Ipv4Network};
+use pnet_datalink::{MacAddr, NetworkInterface};
+use pnet::packet::arp::ArpOperations;
+
+use crate::args::{ScanOptions, ScanTiming};
+use crate::capture::{self, CaptureThread, CapturedFrame};
+use crate::datalink::{DataLink, PnetDataLink, ReplayDataLink};
+use crate::error::ScanError;
+use crate::network::{self, CancellationToken, FrameFilter, FrameVerdict, HostCallback, HostOrigin, HostnameSource, NeighborEntry, NetworkIterator, ReceivedFrame, ResponseSummary, ScanEvent, ScanMetadata, ScanState, ScanTargets, TargetDetails};
+use crate::progress::{ProgressTracker, ScanProgress};
+use crate::resolve::{self, ResolverChain};
+use crate::resume::{self, ScanCheckpoint};
+use crate::time;
+use crate::utils::{self, SizeError};
+use crate::vendor::{self, VendorDatabase};
+
+/**
+ * The results of a scan round: the response summary, the hosts found on all
+ * scanned interfaces and the scan metadata (used by several exports).
+ */
+pub struct ScanResult {
+    pub summary: ResponseSummary,
+    pub hosts: Vec<TargetDetails>,
+    pub metadata: ScanMetadata
+}
+
+/**
+ * Build an ARP scanner for library use, starting from the default options of
+ * the CLI. Networks default to the IPv4 networks of the scanned interface,
+ * and the default interface is selected if none is given.
+ *
+ * ```no_run
+ * use std::time::Duration;
+ * use arp_scan::ArpScanner;
+ *
+ * let scanner = ArpScanner::builder()
+ *     .interface("eth0")
+ *     .network("192.168.1.0/24".parse().unwrap())
+ *     .timeout(Duration::from_secs(1))
+ *     .resolve_hostnames(false)
+ *     .build();
+ *
+ * let result = scanner.run().unwrap();
+ * println!("{} hosts found", result.hosts.len());
+ * ```
+ */
+#[derive(Default)]
+pub struct ArpScannerBuilder {
+    options: ScanOptions,
+    resolvers: Option<ResolverChain>,
+    host_callback: Option<HostCallback>,
+    event_sender: Option<Sender<ScanEvent>>,
+    frame_filter: Option<FrameFilter>
+}
+
+impl ArpScannerBuilder {
+
+    pub fn interface(mut self, interface_name: &str) -> Self {
+
+        if !self.options.interface_names.iter().any(|name| name == interface_name) {
+            self.options.interface_names.push(interface_name.to_string());
+        }
+        self
+    }
+
+    pub fn network(mut self, ip_network: IpNetwork) -> Self {
+
+        self.options.network_range.get_or_insert_with(Vec::new).push(ip_network);
+        self
+    }
+
+    pub fn exclude(mut self, excluded_network: Ipv4Network) -> Self {
+
+        self.options.excluded_networks.push(excluded_network);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+
+        self.options.timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+
+        self.options.scan_timing = ScanTiming::Interval(interval.as_millis() as u64);
+        self
+    }
+
+    pub fn retries(mut self, retry_count: usize) -> Self {
+
+        self.options.retry_count = retry_count;
+        self
+    }
+
+    pub fn resolve_hostnames(mut self, resolve_hostname: bool) -> Self {
+
+        self.options.resolve_hostname = resolve_hostname;
+        self
+    }
+
+    /**
+     * Record hosts answering for an IPv4 that was not targeted with the other
+     * hosts, instead of listing them apart in the unsolicited hosts of the
+     * response summary.
+     */
+    pub fn include_unsolicited(mut self, include_unsolicited: bool) -> Self {
+
+        self.options.include_unsolicited = include_unsolicited;
+        self
+    }
+
+    /**
+     * Replace the hostname resolution chain (hosts files, DNS, ...) by custom
+     * resolvers, which are tried in the given order.
+     */
+    pub fn hostname_resolvers(mut self, resolvers: ResolverChain) -> Self {
+
+        self.resolvers = Some(resolvers);
+        self
+    }
+
+    /**
+     * Register a callback invoked with each new host as soon as it answers,
+     * already enriched with its vendor and hostname. The callback runs on the
+     * response thread of the scan (never concurrently with itself), and is
+     * called at most once per host (IPv4 & MAC pair) on each interface and
+     * VLAN. A slow callback delays the processing of the next answers.
+     *
+     * ```no_run
+     * use arp_scan::ArpScanner;
+     *
+     * let result = ArpScanner::builder()
+     *     .interface("eth0")
+     *     .on_host_found(|host| println!("{} is at {}", host.ipv4, host.mac))
+     *     .build()
+     *     .run()
+     *     .unwrap();
+     * ```
+     */
+    pub fn on_host_found(mut self, callback: impl FnMut(&TargetDetails) + Send + 'static) -> Self {
+
+        self.host_callback = Some(Arc::new(Mutex::new(callback)));
+        self
+    }
+
+    /**
+     * Send the events of each scan round (start, sent requests, hosts found,
+     * retries and end) to the given channel, for user interfaces following
+     * the scan. See 'ScanEvent' for the order of events and the threads
+     * sending them.
+     *
+     * ```no_run
+     * use std::sync::mpsc;
+     * use std::thread;
+     * use arp_scan::{ArpScanner, ScanEvent};
+     *
+     * let (event_sender, events) = mpsc::channel();
+     * thread::spawn(move || {
+     *     for event in events {
+     *         if let ScanEvent::HostFound(host) = event {
+     *             println!("{} is at {}", host.ipv4, host.mac);
+     *         }
+     *     }
+     * });
+     * let result = ArpScanner::builder().interface("eth0").events(event_sender).build().run().unwrap();
+     * ```
+     */
+    pub fn events(mut self, event_sender: Sender<ScanEvent>) -> Self {
+
+        self.event_sender = Some(event_sender);
+        self
+    }
+
+    /**
+     * Register a filter deciding which received frames are recorded as
+     * hosts. The filter runs after the built-in ARP filtering, and is given
+     * each frame of the scanned interface with the host the built-in
+     * filtering would record (for ARP packets other than our own requests).
+     * The filter may accept this host, record another host from the frame
+     * (such as the sender of an unsolicited ARP request) or ignore the frame.
+     *
+     * The filter runs on the response thread for every received frame
+     * (including non-ARP traffic), so it should return quickly: a slow filter
+     * delays the next frames, which may then be dropped by the kernel.
+     *
+     * ```no_run
+     * use arp_scan::ArpScanner;
+     * use arp_scan::network::FrameVerdict;
+     *
+     * // Only record hosts answering with a locally administered MAC address.
+     * let result = ArpScanner::builder()
+     *     .interface("eth0")
+     *     .frame_filter(|_, candidate| match candidate {
+     *         Some(host) if host.mac.0 & 0x02 == 0x02 => FrameVerdict::Accept(host),
+     *         _ => FrameVerdict::Ignore
+     *     })
+     *     .build()
+     *     .run()
+     *     .unwrap();
+     * ```
+     */
+    pub fn frame_filter(mut self, filter: impl FnMut(&ReceivedFrame, Option<TargetDetails>) -> FrameVerdict + Send + 'static) -> Self {
+
+        self.frame_filter = Some(Arc::new(Mutex::new(filter)));
+        self
+    }
+
+    pub fn build(self) -> ArpScanner {
+
+        ArpScanner {
+            options: Arc::new(self.options),
+            resolvers: self.resolvers,
+            host_callback: self.host_callback,
+            event_sender: self.event_sender,
+            frame_filter: self.frame_filter,
+            cancellation: CancellationToken::new(),
+            progress: ProgressTracker::new()
+        }
+    }
+}
+
+/**
+ * An ARP scanner, running scans on the local network with the given scan
+ * options. Scans require root permissions (or the CAP_NET_RAW capability) to
+ * open datalink channels.
+ */
+pub struct ArpScanner {
+    options: Arc<ScanOptions>,
+    resolvers: Option<ResolverChain>,
+    host_callback: Option<HostCallback>,
+    event_sender: Option<Sender<ScanEvent>>,
+    frame_filter: Option<FrameFilter>,
+    cancellation: CancellationToken,
+    progress: ProgressTracker
+}
+
+impl ArpScanner {
+
+    pub fn builder() -> ArpScannerBuilder {
+
+        ArpScannerBuilder::default()
+    }
+
+    pub fn new(options: Arc<ScanOptions>) -> Self {
+
+        ArpScanner {
+            options,
+            resolvers: None,
+            host_callback: None,
+            event_sender: None,
+            frame_filter: None,
+            cancellation: CancellationToken::new(),
+            progress: ProgressTracker::new()
+        }
+    }
+
+    /**
+     * Register a callback invoked with each new host, for scanners built
+     * from scan options (see 'ArpScannerBuilder::on_host_found').
+     */
+    pub fn on_host_found(mut self, callback: impl FnMut(&TargetDetails) + Send + 'static) -> Self {
+
+        self.host_callback = Some(Arc::new(Mutex::new(callback)));
+        self
+    }
+
+    /**
+     * Send the events of each scan round to the given channel, for scanners
+     * built from scan options (see 'ArpScannerBuilder::events').
+     */
+    pub fn events(mut self, event_sender: Sender<ScanEvent>) -> Self {
+
+        self.event_sender = Some(event_sender);
+        self
+    }
+
+    /**
+     * Register a filter of received frames, for scanners built from scan
+     * options (see 'ArpScannerBuilder::frame_filter').
+     */
+    pub fn frame_filter(mut self, filter: impl FnMut(&ReceivedFrame, Option<TargetDetails>) -> FrameVerdict + Send + 'static) -> Self {
+
+        self.frame_filter = Some(Arc::new(Mutex::new(filter)));
+        self
+    }
+
+    /**
+     * Token cancelling the running scan (as a halt signal does in the CLI),
+     * the scan then returns the partial results with the 'cancelled' flag of
+     * the response summary. Each scan session is stopped by a child of this
+     * token (see 'open'), the maximum scan duration only cancels the session.
+     * Cancelling the scanner token is final: all later runs of the scanner
+     * return at once, without hosts and with the 'cancelled' flag set.
+     */
+    pub fn cancellation_token(&self) -> CancellationToken {
+
+        self.cancellation.clone()
+    }
+
+    /**
+     * Progress of the running scan round, shared with the scanner (see
+     * 'ScanHandle::progress' for streaming scans).
+     */
+    pub fn progress(&self) -> ProgressTracker {
+
+        self.progress.clone()
+    }
+
+    /**
+     * Run a single scan round on all selected interfaces. The scanner can be
+     * run several times, unless its cancellation token has been cancelled.
+     */
+    pub fn run(&self) -> Result<ScanResult, ScanError> {
+
+        let mut session = self.open()?;
+        let scan_result = session.run_round()?;
+        session.close()?;
+        Ok(scan_result)
+    }
+
+    /**
+     * Run a single scan round on a background thread, and receive each new
+     * host as soon as it answers. Duplicated answers are filtered before
+     * being sent, each host (IPv4 & MAC pair) is received once per interface
+     * and VLAN. Hosts are enriched inline before being sent: the vendor, the
+     * note and the hostname are already set, a slow hostname resolution
+     * therefore delays the next hosts. The receiver is closed once the scan is
+     * done, the handle then gives the complete results.
+     *
+     * ```no_run
+     * use arp_scan::ArpScanner;
+     *
+     * let (handle, hosts) = ArpScanner::builder().interface("eth0").build().run_streaming();
+     * for host in hosts {
+     *     println!("{} is at {}", host.ipv4, host.mac);
+     * }
+     * let result = handle.wait().unwrap();
+     * println!("{} ARP packets received", result.summary.arp_count);
+     * ```
+     */
+    pub fn run_streaming(self) -> (ScanHandle, Receiver<TargetDetails>) {
+
+        let (host_sender, host_receiver) = mpsc::channel();
+        let cancellation = self.cancellation_token();
+        let progress = self.progress();
+        let scan_thread = thread::spawn(move || {
+            let mut session = self.open()?;
+            let scan_result = session.run_round_streaming(host_sender)?;
+            session.close()?;
+            Ok(scan_result)
+        });
+
+        (ScanHandle { scan_thread, cancellation, progress }, host_receiver)
+    }
+
+    /**
+     * Replay the frames of a pcap file instead of scanning: no request is
+     * sent, frames are received & filtered as during a live scan (once for
+     * each requested VLAN). The scan duration, the start time and the
+     * response times are computed from the capture timestamps.
+     *
+     * The scanner of the capture is identified by the source IPv4 & MAC
+     * options, so that its own requests are not recorded. If they are not
+     * given, the sender of the first ARP request is considered to be the
+     * scanner (as in captures written with '--capture'), except in passive
+     * mode.
+     */
+    pub fn replay(&self, file_path: &str) -> Result<ScanResult, ScanError> {
+
+        let options = Arc::clone(&self.options);
+        let frames = capture::read_pcap(file_path).map_err(ScanError::Capture)?;
+
+        let first_request = match options.passive {
+            true => None,
+            false => frames.iter().find_map(|frame| match network::read_arp_exchange(&frame.data, None) {
+                Some((ArpOperations::Request, sender_mac, sender_ipv4, _)) => Some((sender_mac, sender_ipv4)),
+                _ => None
+            })
+        };
+        let own_mac = options.arp_sender_mac.or(options.source_mac).or(first_request.map(|(mac, _)| mac));
+        let source_ipv4 = options.source_ipv4.or(first_request.map(|(_, ipv4)| ipv4)).unwrap_or(Ipv4Addr::UNSPECIFIED);
+        let interface_name = options.interface_names.first().cloned().unwrap_or_else(|| "pcap".to_string());
+
+        let first_timestamp = frames.first().map_or(Duration::ZERO, |frame| frame.timestamp);
+        let last_timestamp = frames.last().map_or(Duration::ZERO, |frame| frame.timestamp);
+        let started_at = chrono::DateTime::from_timestamp(first_timestamp.as_secs() as i64, first_timestamp.subsec_nanos()).unwrap_or_default();
+        let scan_metadata = ScanMetadata {
+            started_at: started_at.fixed_offset(),
+            interface_name: interface_name.clone(),
+            interface_mac: own_mac,
+            source_ipv4,
+            target_networks: options.network_range.clone().unwrap_or_default()
+        };
+
+        let scan_state = ScanState {
+            cancellation: self.cancellation.child_token(),
+            found_hosts: self.progress.found_hosts(),
+            ..ScanState::default()
+        };
+        scan_state.reset_round();
+        self.progress.start_round(0, 0, 0, 0);
+
+        let event_sender = self.event_sender.clone();
+        let send_event = |event: ScanEvent| {
+            if let Some(event_sender) = &event_sender {
+                let _ = event_sender.send(event);
+            }
+        };
+        send_event(ScanEvent::Started { targets: 0 });
+
+        let vlan_passes: Vec<Option<u16>> = match options.vlan_ids.is_empty() {
+            true => vec![None],
+            false => options.vlan_ids.iter().map(|vlan_id| Some(*vlan_id)).collect()
+        };
+
+        let mut vendor_list = load_vendor_list(&options);
+        let mut response_summary = ResponseSummary::default();
+        let mut target_details: Vec<TargetDetails> = vec![];
+        for vlan_id in vlan_passes {
+
+            scan_state.reset();
+            let link = ReplayDataLink::new(frames.iter().map(|frame| frame.data.clone()).collect());
+            let scan_pass = network::ScanPass {
+                interface_name: interface_name.clone(),
+                own_mac,
+                source_ipv4,
+                vlan_id,
+                host_sender: None,
+                host_callback: self.host_callback.clone(),
+                event_sender: self.event_sender.clone(),
+                frame_filter: self.frame_filter.clone(),
+                resolvers: self.resolvers.clone(),
+                targets: match options.passive || options.include_unsolicited {
+                    true => None,
+                    false => options.network_range.as_ref().map(|networks| ScanTargets {
+                        networks: networks.clone(),
+                        excluded_networks: options.excluded_networks.clone(),
+                        ipv4s: None
+                    })
+                }
+            };
+            let (pass_summary, mut pass_details) = network::receive_arp_responses(&link, Arc::clone(&options), scan_pass, scan_state.clone(), None, &mut vendor_list)?;
+            apply_replayed_response_times(&mut pass_details, &frames, own_mac, vlan_id);
+            resolve::resolve_hostnames_in_chain(&mut pass_details, &options, self.resolvers.as_deref())?;
+
+            response_summary.merge(pass_summary);
+            response_summary.requests_sent += frames.iter().filter(|frame| match network::read_arp_exchange(&frame.data, vlan_id) {
+                Some((ArpOperations::Request, sender_mac, _, _)) => own_mac == Some(sender_mac),
+                _ => false
+            }).count();
+            target_details.extend(pass_details);
+        }
+
+        self.progress.finish_round();
+        response_summary.duration_ms = last_timestamp.saturating_sub(first_timestamp).as_millis();
+        response_summary.host_limit_reached = scan_state.has_reached_host_limit(options.max_hosts);
+        response_summary.truncated = response_summary.host_limit_reached;
+        response_summary.cancelled = scan_state.cancellation.is_cancelled();
+        send_event(ScanEvent::Finished(response_summary.clone()));
+
+        Ok(ScanResult {
+            summary: response_summary,
+            hosts: target_details,
+            metadata: scan_metadata
+        })
+    }
+
+    /**
+     * Open a scan session: the datalink channels of all selected interfaces,
+     * the vendor database, the capture file and the checkpoint are set up once
+     * and reused by all scan rounds (see the loop mode of the CLI).
+     */
+    pub fn open(&self) -> Result<ScanSession, ScanError> {
+
+        let interfaces = pnet_datalink::interfaces();
+        self.open_with(&interfaces, |interface| {
+            PnetDataLink::open(interface).map(|link| Arc::new(link) as Arc<dyn DataLink>)
+        })
+    }
+
+    /**
+     * Open a scan session on the given datalink layers instead of pnet
+     * channels (such as scripted 'FakeDataLink' instances in tests). The
+     * interfaces are selected among the given ones, as 'open' does with the
+     * interfaces of the system.
+     */
+    pub fn open_with_links(&self, links: Vec<(NetworkInterface, Arc<dyn DataLink>)>) -> Result<ScanSession, ScanError> {
+
+        let interfaces: Vec<NetworkInterface> = links.iter().map(|(interface, _)| interface.clone()).collect();
+        self.open_with(&interfaces, |interface| {
+            links.iter()
+                .find(|(link_interface, _)| link_interface.name == interface.name)
+                .map(|(_, link)| Arc::clone(link))
+                .ok_or_else(|| ScanError::InterfaceNotFound(interface.name.clone()))
+        })
+    }
+
+    fn open_with(&self, interfaces: &[NetworkInterface], open_link: impl Fn(&NetworkInterface) -> Result<Arc<dyn DataLink>, ScanError>) -> Result<ScanSession, ScanError> {
+
+        let options = &self.options;
+        let network_configurations = match options.all_interfaces {
+            true => network::compute_all_interfaces_configuration(interfaces),
+            false => network::compute_network_configuration(interfaces, options)
+        }?;
+
+        // The verify mode targets the complete entries of the kernel neighbor
+        // table, each interface only verifies its own entries.
+        let neighbor_entries: Option<Vec<NeighborEntry>> = match options.verify {
+            true => Some(network::read_neighbor_table(network::NEIGHBOR_TABLE_PATH).map_err(ScanError::NeighborTable)?),
+            false => None
+        };
+
+        // Kernel ARP cache entries are added to the results of each interface.
+        // The cache is optional: a read failure only prints a warning.
+        let cached_entries: Vec<NeighborEntry> = match options.include_arp_cache {
+            true => network::read_neighbor_table(network::NEIGHBOR_TABLE_PATH).unwrap_or_else(|err| {
+                eprintln!("[warn] {}, scanning without the ARP cache", err);
+                vec![]
+            }),
+            false => vec![]
+        };
+
+        // Hosts files are read before any request is sent, so that an
+        // unreadable file does not waste a whole scan.
+        if options.resolve_hostname && self.resolvers.is_none() && options.resolve_order.contains(&HostnameSource::HostsFile) {
+            resolve::static_hostnames(options)?;
+        }
+
+        // A datalink channel is opened on each scanned interface. When scanning
+        // several interfaces, an interface that could not be opened is reported
+        // and skipped, the scan only stops if no channel could be opened (with
+        // the last channel error).
+        let interface_count = network_configurations.len();
+        let mut channels: Vec<InterfaceChannel> = vec![];
+        let mut channel_error: Option<ScanError> = None;
+        for (selected_interface, ip_networks) in network_configurations {
+
+            match open_link(selected_interface) {
+                Ok(link) => channels.push(InterfaceChannel {
+                    interface: selected_interface.clone(),
+                    ip_networks: ip_networks.into_iter().copied().collect(),
+                    neighbors: neighbor_entries.as_ref().map(|entries| {
+                        entries.iter().filter(|entry| entry.device == selected_interface.name).cloned().collect()
+                    }),
+                    cached_neighbors: cached_entries.iter().filter(|entry| entry.device == selected_interface.name).cloned().collect(),
+                    source_ip: network::find_source_ip(selected_interface, options.source_ipv4)?,
+                    link
+                }),
+                Err(error) => {
+                    if interface_count > 1 {
+                        eprintln!("[warn] {}, skipping interface {}", error, selected_interface.name);
+                    }
+                    channel_error = Some(error);
+                }
+            };
+        }
+
+        if channels.is_empty() {
+            return Err(channel_error.unwrap_or_else(|| ScanError::Interface("Could not open a datalink channel on any interface".to_string())));
+        }
+
+        // Each interface is scanned once for each requested VLAN, or once without
+        // 802.1Q tag if no VLAN has been requested.
+        let vlan_passes: Vec<Option<u16>> = match options.vlan_ids.is_empty() {
+            true => vec![None],
+            false => options.vlan_ids.iter().map(|vlan_id| Some(*vlan_id)).collect()
+        };
+        let scan_passes: Vec<(usize, Option<u16>)> = (0..channels.len()).flat_map(|channel_index| {
+            vlan_passes.iter().map(move |vlan_id| (channel_index, *vlan_id))
+        }).collect();
+
+        let vlan_count = vlan_passes.len() as u128;
+        let target_count: u128 = channels.iter().try_fold(0u128, |total_size, channel| {
+            let channel_size = match &channel.neighbors {
+                Some(neighbors) => neighbors.len() as u128,
+                None => utils::compute_network_size(&channel.ip_networks.iter().collect::<Vec<&IpNetwork>>(), &options.excluded_networks)?
+            };
+            utils::add_network_size(total_size, channel_size)
+        }).and_then(|total_size| total_size.checked_mul(vlan_count).ok_or(SizeError::Overflow)).map_err(|err| ScanError::InvalidTarget(err.to_string()))?;
+
+        // Exclusions may cover all targets and the neighbor table may have no
+        // entry for the scanned interfaces, there is then nothing to send (the
+        // passive mode does not send any request anyway).
+        if target_count == 0 && !options.passive {
+            return Err(ScanError::InvalidTarget(match options.verify {
+                true => format!("No neighbor entries to verify in {}", network::NEIGHBOR_TABLE_PATH),
+                false => "No targets left after exclusions".to_string()
+            }));
+        }
+
+        // All frames seen by the response thread can be saved in a pcap file, the
+        // file is written by a dedicated capture thread.
+        let (capture_sender, capture_thread) = match &options.capture_file {
+            Some(capture_file) => {
+                let (sender, capture_thread) = capture::start_capture(capture_file).map_err(ScanError::Capture)?;
+                (Some(sender), Some(capture_thread))
+            },
+            None => (None, None)
+        };
+
+        // With a state file, the scan progress is checkpointed so that an
+        // interrupted scan can be resumed with the same targets. Completed passes
+        // are skipped, and the current pass resumes after the last sent target.
+        let mut resumed_host_count: Option<usize> = None;
+        let checkpoint: Option<ScanCheckpoint> = match &options.state_file {
+            Some(state_file) => {
+                let interface_networks: Vec<(&str, Vec<&IpNetwork>)> = channels.iter().map(|channel| (channel.interface.name.as_str(), channel.ip_networks.iter().collect())).collect();
+                let target_spec = resume::compute_target_spec(&interface_networks, options);
+                match ScanCheckpoint::load(state_file, &target_spec).map_err(ScanError::Checkpoint)? {
+                    Some(loaded_checkpoint) => {
+                        resumed_host_count = Some(loaded_checkpoint.hosts.len());
+                        Some(loaded_checkpoint)
+                    },
+                    None => Some(ScanCheckpoint::new(target_spec))
+                }
+            },
+            None => None
+        };
+
+        // The maximum scan duration is enforced by a watchdog thread, which stops
+        // both the sender (as a halt signal would) and the response thread. The
+        // watchdog belongs to the session, it only cancels the session token and
+        // is stopped when the session is closed (or dropped) before the deadline.
+        let scan_state = ScanState {
+            cancellation: self.cancellation.child_token(),
+            found_hosts: self.progress.found_hosts(),
+            ..ScanState::default()
+        };
+        let truncated = Arc::new(AtomicBool::new(false));
+        let watchdog = options.max_duration_ms.map(|max_duration_ms| {
+            let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+            let cloned_truncated = Arc::clone(&truncated);
+            let cancellation = scan_state.cancellation.clone();
+            let watchdog_thread = thread::spawn(move || {
+                if let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(Duration::from_millis(max_duration_ms)) {
+                    cloned_truncated.store(true, Ordering::Relaxed);
+                    cancellation.cancel();
+                }
+            });
+            (stop_sender, watchdog_thread)
+        });
+
+        Ok(ScanSession {
+            options: Arc::clone(options),
+            resolvers: self.resolvers.clone(),
+            host_callback: self.host_callback.clone(),
+            event_sender: self.event_sender.clone(),
+            frame_filter: self.frame_filter.clone(),
+            progress: self.progress(),
+            truncated,
+            channels,
+            scan_state,
+            vendor_list: load_vendor_list(options),
+            capture_sender,
+            capture_thread,
+            watchdog,
+            checkpoint,
+            resumed_host_count,
+            scan_passes,
+            first_vlan_id: vlan_passes[0],
+            target_count
+        })
+    }
+}
+
+/**
+ * A datalink channel opened on a scanned interface, with the target networks
+ * and the ARP source IPv4 of this interface. The link is shared with the
+ * response thread during each scan. In verify mode, the kernel neighbors of
+ * the interface replace the networks.
+ */
+pub(crate) struct InterfaceChannel {
+    pub(crate) interface: NetworkInterface,
+    pub(crate) ip_networks: Vec<IpNetwork>,
+    pub(crate) neighbors: Option<Vec<NeighborEntry>>,
+    pub(crate) cached_neighbors: Vec<NeighborEntry>,
+    pub(crate) source_ip: Ipv4Addr,
+    pub(crate) link: Arc<dyn DataLink>
+}
+
+impl InterfaceChannel {
+
+    /**
+     * The IPv4 addresses requested on this interface: the verified neighbors
+     * or the scanned networks, without the excluded networks.
+     */
+    pub(crate) fn scan_targets(&self, options: &ScanOptions) -> ScanTargets {
+
+        ScanTargets {
+            networks: self.ip_networks.clone(),
+            excluded_networks: options.excluded_networks.clone(),
+            ipv4s: self.neighbors.as_ref().map(|neighbors| neighbors.iter().map(|entry| entry.ipv4).collect())
+        }
+    }
+}
+
+/**
+ * An open scan session, see 'ArpScanner::open'. The session should be closed
+ * once all rounds are done, so that the capture file is flushed.
+ */
+pub struct ScanSession {
+    pub(crate) options: Arc<ScanOptions>,
+    pub(crate) resolvers: Option<ResolverChain>,
+    pub(crate) host_callback: Option<HostCallback>,
+    pub(crate) event_sender: Option<Sender<ScanEvent>>,
+    pub(crate) frame_filter: Option<FrameFilter>,
+    pub(crate) progress: ProgressTracker,
+    pub(crate) truncated: Arc<AtomicBool>,
+    pub(crate) channels: Vec<InterfaceChannel>,
+    pub(crate) scan_state: ScanState,
+    pub(crate) vendor_list: VendorDatabase,
+    pub(crate) capture_sender: Option<Sender<CapturedFrame>>,
+    capture_thread: Option<CaptureThread>,
+    watchdog: Option<(Sender<()>, JoinHandle<()>)>,
+    pub(crate) checkpoint: Option<ScanCheckpoint>,
+    resumed_host_count: Option<usize>,
+    pub(crate) scan_passes: Vec<(usize, Option<u16>)>,
+    pub(crate) first_vlan_id: Option<u16>,
+    pub(crate) target_count: u128
+}
+
+impl ScanSession {
+
+    /**
+     * Count the ARP requests of a scan round (without retries), for all
+     * interfaces and VLANs.
+     */
+    pub fn target_count(&self) -> u128 {
+
+        self.target_count
+    }
+
+    pub fn interfaces(&self) -> Vec<&NetworkInterface> {
+
+        self.channels.iter().map(|channel| &channel.interface).collect()
+    }
+
+    /**
+     * Count the hosts recorded by a previous run, if the scan has been resumed
+     * from a state file.
+     */
+    pub fn resumed_host_count(&self) -> Option<usize> {
+
+        self.resumed_host_count
+    }
+
+    /**
+     * Check if the scan has been stopped by the maximum scan duration.
+     */
+    pub fn has_reached_max_duration(&self) -> bool {
+
+        self.truncated.load(Ordering::Relaxed)
+    }
+
+    /**
+     * Run a scan round on all interfaces (and VLANs). ARP responses on each
+     * interface are collected in a separate thread, while the current thread
+     * sends a batch of ARP requests for each targeted IPv4 address.
+     */
+    pub fn run_round(&mut self) -> Result<ScanResult, ScanError> {
+
+        self.run_round_with(None)
+    }
+
+    /**
+     * Run a scan round and send each new host to the given channel as soon as
+     * it answers, see 'ArpScanner::run_streaming'. The channel is closed once
+     * the round is done (unless the sender is also held by the caller).
+     */
+    pub fn run_round_streaming(&mut self, host_sender: Sender<TargetDetails>) -> Result<ScanResult, ScanError> {
+
+        self.run_round_with(Some(host_sender))
+    }
+
+    fn run_round_with(&mut self, host_sender: Option<Sender<TargetDetails>>) -> Result<ScanResult, ScanError> {
+
+        let options = Arc::clone(&self.options);
+        let scan_state = self.scan_state.clone();
+        scan_state.reset_round();
+
+        let scanned_interfaces: Vec<&NetworkInterface> = self.channels.iter().map(|channel| &channel.interface).collect();
+        let scanned_networks: Vec<&IpNetwork> = self.channels.iter().flat_map(|channel| channel.ip_networks.iter()).collect();
+        let scan_metadata = ScanMetadata::new(&scanned_interfaces, &scanned_networks, self.channels[0].source_ip);
+
+        let estimations = network::compute_scan_estimation(self.target_count, &options);
+        let interval_ms = estimations.interval_ms;
+
+        // A scan round is stopped early by a halt signal, by the maximum scan
+        // duration or once enough hosts have answered. In all cases, the same
+        // cleanup is done (stopping the response thread & exporting results).
+        let is_halted = || scan_state.cancellation.is_cancelled() || scan_state.has_reached_host_limit(options.max_hosts);
+
+        // The first attempt targets all hosts, while each retry only targets
+        // the hosts that did not answer yet. The timeout is applied after each
+        // attempt, and the amount of hosts answering each attempt is kept for
+        // the scan summary. No request is sent in passive mode, hosts are only
+        // discovered from existing traffic.
+        let attempt_count = match options.passive {
+            true => 0,
+            false => options.retry_count + 1
+        };
+
+        // With a packets-per-second rate, a single token bucket paces all
+        // requests of the round (across retries, networks and interfaces).
+        // Otherwise, a fixed interval is applied after each request.
+        let mut rate_limiter = match options.scan_timing {
+            ScanTiming::Rate(rate) => Some(network::RateLimiter::new(rate)),
+            _ => None
+        };
+        // Progress is reported on the targets of the first attempt, the
+        // response windows follow each attempt of each remaining scan pass.
+        let progress = self.progress.clone();
+        let request_interval_us = match options.scan_timing {
+            ScanTiming::Rate(rate) => 1_000_000 / rate.max(1),
+            _ => interval_ms * 1000
+        };
+        let remaining_pass_count = self.scan_passes.len().saturating_sub(self.checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.pass_index));
+        let window_count = (remaining_pass_count * attempt_count.max(1)) as u64;
+        let progress_target_count = match options.passive {
+            true => 0,
+            false => self.target_count
+        };
+        progress.start_round(progress_target_count, request_interval_us, options.timeout_ms, window_count);
+
+        // Events are dropped once the receiver has been closed, the scan goes
+        // on as usual.
+        let event_sender = self.event_sender.clone();
+        let send_event = |event: ScanEvent| {
+            if let Some(event_sender) = &event_sender {
+                let _ = event_sender.send(event);
+            }
+        };
+        send_event(ScanEvent::Started { targets: progress_target_count });
+
+        let mut requests_sent: usize = 0;
+        let mut send_errors: usize = 0;
+        let mut send_started_at: Option<Instant> = None;
+        let mut send_duration_ms: u128 = 0;
+
+        // Interfaces (and VLANs) are scanned one after another, the results of
+        // each scan pass are merged in a single response summary for the round.
+        let mut response_summary = ResponseSummary::default();
+        let mut target_details: Vec<TargetDetails> = vec![];
+
+        for (pass_index, (channel_index, vlan_id)) in self.scan_passes.clone().into_iter().enumerate() {
+
+            if pass_index > 0 && is_halted() {
+                break;
+            }
+            if self.checkpoint.as_ref().is_some_and(|checkpoint| pass_index < checkpoint.pass_index) {
+                continue;
+            }
+
+            let channel = &mut self.channels[channel_index];
+
+            scan_state.reset();
+            if let (Some(checkpoint), Ok(mut hosts)) = (&self.checkpoint, scan_state.responded_hosts.lock()) {
+                hosts.extend(checkpoint.recorded_ipv4s(&channel.interface.name, vlan_id));
+            }
+
+            let link = Arc::clone(&channel.link);
+            let cloned_options = Arc::clone(&options);
+            let scan_pass = network::ScanPass {
+                interface_name: channel.interface.name.clone(),
+                own_mac: options.arp_sender_mac.or(options.source_mac).or(channel.interface.mac),
+                source_ipv4: channel.source_ip,
+                vlan_id,
+                host_sender: host_sender.clone(),
+                host_callback: self.host_callback.clone(),
+                event_sender: self.event_sender.clone(),
+                frame_filter: self.frame_filter.clone(),
+                resolvers: self.resolvers.clone(),
+                targets: match options.passive || options.include_unsolicited {
+                    true => None,
+                    false => Some(channel.scan_targets(&options))
+                }
+            };
+            let reports_new_hosts = scan_pass.reports_new_hosts();
+            let cloned_scan_state = scan_state.clone();
+            let cloned_capture_sender = self.capture_sender.clone();
+            let mut vendor_list = mem::replace(&mut self.vendor_list, VendorDatabase::disabled());
+            let arp_responses = thread::spawn(move || {
+                let scan_results = network::receive_arp_responses(link.as_ref(), cloned_options, scan_pass, cloned_scan_state, cloned_capture_sender, &mut vendor_list);
+                (scan_results, vendor_list)
+            });
+
+            let mut responses_per_attempt: Vec<usize> = vec![];
+            let mut send_error: Option<ScanError> = None;
+
+            let mut checkpoint_saved_at = Instant::now();
+
+            for attempt_index in 0..attempt_count {
+
+                if is_halted() {
+                    break;
+                }
+                if attempt_index > 0 {
+                    send_event(ScanEvent::RetryRound(attempt_index.try_into().unwrap_or(u8::MAX)));
+                }
+
+                // Neighbor entries are verified with unicast requests sent to
+                // their recorded MAC, other targets get broadcast requests.
+                let ip_networks: Vec<&IpNetwork> = channel.ip_networks.iter().collect();
+                let targets: Box<dyn Iterator<Item = (IpAddr, Option<MacAddr>)>> = match &channel.neighbors {
+                    Some(neighbors) => Box::new(neighbors.iter().map(|entry| (IpAddr::V4(entry.ipv4), Some(entry.mac)))),
+                    None => Box::new(NetworkIterator::new(&ip_networks, options.random_seed).map(|ip_address| (ip_address, None)))
+                };
+
+                for (target_index, (ip_address, unicast_mac)) in targets.enumerate() {
+
+                    if is_halted() {
+                        break;
+                    }
+
+                    // The first attempt of a resumed pass skips the targets
+                    // sent before the interruption, and the position of the
+                    // last sent target is checkpointed periodically.
+                    if let (0, Some(checkpoint), IpAddr::V4(ipv4_address)) = (attempt_index, self.checkpoint.as_mut(), ip_address) {
+                        if checkpoint.has_sent(target_index, ipv4_address) {
+                            progress.record_sent();
+                            continue;
+                        }
+                        checkpoint.sent_count = target_index;
+                        if checkpoint_saved_at.elapsed().as_millis() >= resume::CHECKPOINT_INTERVAL_MS {
+                            checkpoint.pending_ipv4s = scan_state.responded_hosts.lock().map(|hosts| hosts.iter().copied().collect()).unwrap_or_default();
+                            save_checkpoint(checkpoint, &options);
+                            checkpoint_saved_at = Instant::now();
+                        }
+                    }
+
+                    if let IpAddr::V4(ipv4_address) = ip_address {
+                        if network::is_excluded_target(ipv4_address, &options.excluded_networks) {
+                            continue;
+                        }
+                        if network::has_responded(&scan_state.responded_hosts, ipv4_address) {
+                            if attempt_index == 0 {
+                                progress.record_sent();
+                            }
+                            continue;
+                        }
+                        if let Some(rate_limiter) = rate_limiter.as_mut() {
+                            rate_limiter.wait_for_token();
+                        }
+                        // The request time is recorded before sending, a
+                        // fast answer could otherwise be received first.
+                        if let Ok(mut times) = scan_state.request_times.lock() {
+                            times.insert(ipv4_address, Instant::now());
+                        }
+                        let send_result = match unicast_mac {
+                            Some(target_mac) => network::send_unicast_arp_request(channel.link.as_ref(), &channel.interface, channel.source_ip, ipv4_address, target_mac, vlan_id, Arc::clone(&options)),
+                            None => network::send_arp_request(channel.link.as_ref(), &channel.interface, channel.source_ip, ipv4_address, vlan_id, Arc::clone(&options))
+                        };
+                        // A request that could not be handed to the interface
+                        // is counted as a send error, the scan goes on (the
+                        // host may still be found by a retry).
+                        match send_result {
+                            Ok(true) => {
+                                requests_sent += 1;
+                                send_event(ScanEvent::PacketSent { ip: ipv4_address });
+                            },
+                            Ok(false) => send_errors += 1,
+                            Err(error) => {
+                                send_error = Some(error);
+                                break;
+                            }
+                        }
+                        if attempt_index == 0 {
+                            progress.record_sent();
+                        }
+                        let send_started_at = *send_started_at.get_or_insert_with(Instant::now);
+                        send_duration_ms = send_started_at.elapsed().as_millis();
+                        if rate_limiter.is_none() {
+                            wait_until_halted(is_halted, interval_ms);
+                        }
+                    }
+                }
+
+                // A request that could not be built stops the scan pass, the
+                // response thread is still stopped before returning the error.
+                if send_error.is_some() {
+                    break;
+                }
+
+                // Once the ARP packets are sent, the main thread will sleep for
+                // T seconds (where T is the timeout option) before the next
+                // attempt.
+                progress.start_window();
+                wait_until_halted(is_halted, options.timeout_ms);
+                progress.complete_window();
+
+                let responded_count = scan_state.responded_hosts.lock().map(|hosts| hosts.len()).unwrap_or(0);
+                let previous_count: usize = responses_per_attempt.iter().sum();
+                responses_per_attempt.push(responded_count.saturating_sub(previous_count));
+            }
+            if attempt_count == 0 {
+                progress.start_window();
+                wait_until_halted(is_halted, options.timeout_ms);
+                progress.complete_window();
+            }
+
+            // After the last attempt, the response thread will receive a stop
+            // request through the 'timed_out' flag.
+            scan_state.timed_out.store(true, Ordering::Relaxed);
+
+            let (scan_results, returned_vendor_list) = arp_responses.join().map_err(|error| {
+                ScanError::Thread(format!("Failed to close receive thread ({:?})", error))
+            })?;
+            self.vendor_list = returned_vendor_list;
+            if let Some(error) = send_error {
+                progress.finish_round();
+                return Err(error);
+            }
+            let (mut interface_summary, interface_details) = scan_results?;
+            interface_summary.responses_per_attempt = responses_per_attempt;
+            response_summary.merge(interface_summary);
+
+            // Cached entries are not tagged, they are only merged once for
+            // each interface (with the first VLAN scan).
+            let vendor_list = &mut self.vendor_list;
+            let mut interface_details: Vec<TargetDetails> = match options.include_arp_cache && vlan_id == self.first_vlan_id {
+                true => network::merge_cached_neighbors(interface_details, &channel.cached_neighbors, &channel.interface.name).into_iter().map(|mut detail| {
+                    if detail.origin == Some(HostOrigin::Cache) {
+                        network::enrich_target_details(&mut detail, vendor_list, &options.annotations);
+                    }
+                    detail
+                }).collect(),
+                false => interface_details
+            };
+
+            // Hostnames are resolved once the receive thread is done, streamed
+            // hosts have already been resolved one by one.
+            if !reports_new_hosts {
+                resolve::resolve_hostnames_in_chain(&mut interface_details, &options, self.resolvers.as_deref())?;
+            }
+
+            if let Some(checkpoint) = self.checkpoint.as_mut() {
+                checkpoint.record_hosts(&interface_details);
+                checkpoint.pending_ipv4s.clear();
+                if !is_halted() {
+                    checkpoint.complete_pass();
+                }
+                save_checkpoint(checkpoint, &options);
+            }
+
+            match &channel.neighbors {
+                Some(neighbors) => target_details.extend(network::apply_verify_statuses(interface_details, neighbors, &channel.interface.name)),
+                None => target_details.extend(interface_details)
+            }
+        }
+
+        // Resumed scans report the hosts of previous runs as well. Once all
+        // passes are complete, the state file is removed so that the next
+        // scan starts from scratch.
+        if let Some(checkpoint) = self.checkpoint.as_ref() {
+            target_details = checkpoint.target_details();
+            if checkpoint.pass_index >= self.scan_passes.len() {
+                remove_state_file(&options);
+            }
+        }
+
+        progress.finish_round();
+        response_summary.requests_sent = requests_sent;
+        response_summary.send_errors = send_errors;
+        response_summary.send_duration_ms = send_duration_ms;
+        response_summary.host_limit_reached = scan_state.has_reached_host_limit(options.max_hosts);
+        response_summary.truncated = self.truncated.load(Ordering::Relaxed) || response_summary.host_limit_reached;
+        response_summary.cancelled = scan_state.cancellation.is_cancelled() && !self.truncated.load(Ordering::Relaxed);
+        send_event(ScanEvent::Finished(response_summary.clone()));
+
+        Ok(ScanResult {
+            summary: response_summary,
+            hosts: target_details,
+            metadata: scan_metadata
+        })
+    }
+
+    /**
+     * Close the scan session. The capture sender is dropped, the capture
+     * thread will therefore flush the pcap file and stop.
+     */
+    pub fn close(mut self) -> Result<(), ScanError> {
+
+        if let Some((stop_sender, watchdog_thread)) = self.watchdog.take() {
+            drop(stop_sender);
+            watchdog_thread.join().map_err(|error| ScanError::Thread(format!("Failed to stop scan watchdog ({:?})", error)))?;
+        }
+        drop(self.capture_sender.take());
+        match self.capture_thread.take() {
+            Some(capture_thread) => capture_thread.join().map_err(|error| {
+                ScanError::Thread(format!("Failed to close capture thread ({:?})", error))
+            })?.map_err(ScanError::Capture),
+            None => Ok(())
+        }
+    }
+}
+
+/**
+ * Handle of a scan running on a background thread, see
+ * 'ArpScanner::run_streaming'.
+ */
+pub struct ScanHandle {
+    scan_thread: JoinHandle<Result<ScanResult, ScanError>>,
+    cancellation: CancellationToken,
+    progress: ProgressTracker
+}
+
+impl ScanHandle {
+
+    /**
+     * Cancel the scan, 'wait' then returns the partial results promptly.
+     */
+    pub fn cancel(&self) {
+
+        self.cancellation.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+
+        self.scan_thread.is_finished()
+    }
+
+    /**
+     * Snapshot of the scan progress (targets sent, hosts found and remaining
+     * time estimation), cheap enough to be polled frequently.
+     */
+    pub fn progress(&self) -> ScanProgress {
+
+        self.progress.snapshot()
+    }
+
+    /**
+     * Wait for the end of the scan, and get the scan results (with all the
+     * hosts that have been streamed).
+     */
+    pub fn wait(self) -> Result<ScanResult, ScanError> {
+
+        self.scan_thread.join().map_err(|error| ScanError::Thread(format!("Failed to close scan thread ({:?})", error)))?
+    }
+}
+
+/**
+ * Load the MAC vendor database, with the same code path as the library
+ * ('VendorDatabase::load_default'). The default database is optional, while
+ * an unusable custom vendor file is reported; in both cases the vendor
+ * snapshot embedded in the binary is used instead.
+ */
+pub fn load_vendor_list(scan_options: &ScanOptions) -> VendorDatabase {
+
+    let oui_file = scan_options.oui_file.as_ref().map(PathBuf::from).unwrap_or_else(|| vendor::default_vendor_file_path(false));
+    let (vendor_list, file_error) = VendorDatabase::load_default_from(&oui_file);
+    match file_error {
+        None => if scan_options.verbose {
+            let age = vendor::vendor_file_age(&oui_file).map(|age| time::format_milliseconds(age.as_millis()));
+            eprintln!("Loaded {} vendor prefixes from {} (updated {} ago)", vendor_list.prefix_count(), oui_file.display(), age.as_deref().unwrap_or("?"));
+        },
+        Some(err) => {
+            if scan_options.custom_oui_file {
+                eprintln!("[warn] {}, using the embedded vendor database", err);
+            }
+            if scan_options.verbose {
+                eprintln!("Using the embedded vendor database ({} prefixes)", vendor_list.prefix_count());
+            }
+        }
+    }
+    vendor_list
+}
+
+/**
+ * Set the response times of replayed hosts, from the last request sent by the
+ * scanner to the host before its first reply in the capture. Hosts that have
+ * not been targeted by the scanner keep an unknown response time.
+ */
+fn apply_replayed_response_times(target_details: &mut [TargetDetails], frames: &[CapturedFrame], own_mac: Option<MacAddr>, vlan_id: Option<u16>) {
+
+    let mut request_times: HashMap<Ipv4Addr, Duration> = HashMap::new();
+    let mut response_times: HashMap<(Ipv4Addr, MacAddr), u64> = HashMap::new();
+
+    for frame in frames {
+        match network::read_arp_exchange(&frame.data, vlan_id) {
+            Some((ArpOperations::Request, sender_mac, _, target_ipv4)) if Some(sender_mac) == own_mac => {
+                request_times.insert(target_ipv4, frame.timestamp);
+            },
+            Some((_, sender_mac, sender_ipv4, _)) => {
+                if let Some(requested_at) = request_times.get(&sender_ipv4) {
+                    let response_time_ms = frame.timestamp.saturating_sub(*requested_at).as_millis();
+                    response_times.entry((sender_ipv4, sender_mac)).or_insert(response_time_ms.try_into().unwrap_or(u64::MAX));
+                }
+            },
+            None => {}
+        }
+    }
+
+    for target_detail in target_details.iter_mut() {
+        target_detail.response_time_ms = response_times.get(&(target_detail.ipv4, target_detail.mac)).copied();
+    }
+}
+
+/**
+ * Save the scan progress to the state file. A failed checkpoint does not stop
+ * the scan, but the scan may not be resumed from this point.
+ */
+fn save_checkpoint(checkpoint: &ScanCheckpoint, scan_options: &ScanOptions) {
+
+    if let Some(state_file) = &scan_options.state_file {
+        if let Err(err) = checkpoint.save(state_file) {
+            eprintln!("[warn] {}", err);
+        }
+    }
+}
+
+fn remove_state_file(scan_options: &ScanOptions) {
+
+    if let Some(state_file) = &scan_options.state_file {
+        if let Err(err) = fs::remove_file(state_file) {
+            eprintln!("[warn] Could not remove state file {} ({})", state_file, err);
+        }
+    }
+}
+
+/**
+ * Sleep for the given duration (by steps of 100ms), unless a halt signal is
+ * received (or the scan is stopped early) while waiting.
+ */
+pub fn wait_until_halted(is_halted: impl Fn() -> bool, duration_ms: u64) {
+
+    let mut sleep_ms_mount: u64 = 0;
+    while !is_halted() && sleep_ms_mount < duration_ms {
+
+        let sleep_ms = (duration_ms - sleep_ms_mount).min(100);
+        thread::sleep(Duration::from_millis(sleep_ms));
+        sleep_ms_mount += sleep_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::datalink::FakeDataLink;
+
+    const ROUTER_MAC: MacAddr = MacAddr(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+    const PRINTER_MAC: MacAddr = MacAddr(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+
+    fn build_interface() -> NetworkInterface {
+
+        NetworkInterface {
+            name: "eth0".to_string(),
+            description: String::new(),
+            index: 2,
+            mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            ips: vec!["192.168.1.21/24".parse().unwrap()],
+            // IFF_UP, the interface is not a loopback
+            flags: 1
+        }
+    }
+
+    fn scan_with_link<L: DataLink + Clone + 'static>(link: &L, target_ipv4s: &[Ipv4Addr], retry_count: usize) -> ScanResult {
+
+        scan_with_builder(ArpScanner::builder(), link, target_ipv4s, retry_count)
+    }
+
+    fn scan_with_builder<L: DataLink + Clone + 'static>(builder: ArpScannerBuilder, link: &L, target_ipv4s: &[Ipv4Addr], retry_count: usize) -> ScanResult {
+
+        let scanner = target_ipv4s.iter().fold(builder.interface("eth0"), |builder, ipv4| {
+            builder.network(IpNetwork::from(IpAddr::V4(*ipv4)))
+        });
+        let scanner = scanner
+            .timeout(Duration::from_millis(100))
+            .interval(Duration::ZERO)
+            .retries(retry_count)
+            .resolve_hostnames(false)
+            .build();
+
+        let mut session = scanner.open_with_links(vec![(build_interface(), Arc::new(link.clone()))]).unwrap();
+        let scan_result = session.run_round().unwrap();
+        session.close().unwrap();
+        scan_result
+    }
+
+    fn replay_options() -> Arc<ScanOptions> {
+
+        Arc::new(ScanOptions {
+            oui_file: Some("./data/ieee-oui.csv".to_string()),
+            resolve_hostname: false,
+            ..ScanOptions::default()
+        })
+    }
+
+    fn reply_from(mac: MacAddr, ipv4: Ipv4Addr) -> Vec<u8> {
+
+        FakeDataLink::arp_reply(mac, ipv4, Ipv4Addr::new(192, 168, 1, 21))
+    }
+
+    #[test]
+    fn should_time_out_without_answers() {
+
+        let link = FakeDataLink::new();
+        let targets = [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)];
+
+        let scan_result = scan_with_link(&link, &targets, 1);
+
+        assert!(scan_result.hosts.is_empty());
+        assert_eq!(link.sent_requests(), [targets[0], targets[1], targets[0], targets[1]]);
+        assert_eq!(scan_result.summary.requests_sent, 4);
+        assert_eq!(scan_result.summary.responses_per_attempt, [0, 0]);
+        assert_eq!(scan_result.summary.packet_count, 0);
+    }
+
+    #[test]
+    fn should_keep_max_duration_to_each_session() {
+
+        let link = FakeDataLink::new();
+        let router_ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        link.reply_to(router_ipv4, vec![reply_from(ROUTER_MAC, router_ipv4)]);
+        link.reply_to(router_ipv4, vec![reply_from(ROUTER_MAC, router_ipv4)]);
+        let scanner = ArpScanner::new(Arc::new(ScanOptions {
+            interface_names: vec!["eth0".to_string()],
+            network_range: Some(vec![IpNetwork::from(IpAddr::V4(router_ipv4))]),
+            timeout_ms: 100,
+            scan_timing: ScanTiming::Interval(0),
+            resolve_hostname: false,
+            max_duration_ms: Some(1500),
+            ..ScanOptions::default()
+        }));
+
+        // The first session is closed before its deadline, the next session
+        // should not be stopped once this deadline has passed.
+        let first_opened_at = Instant::now();
+        for session_index in 0..2 {
+            let mut session = scanner.open_with_links(vec![(build_interface(), Arc::new(link.clone()))]).unwrap();
+            let scan_result = session.run_round().unwrap();
+            assert!(!session.has_reached_max_duration());
+            session.close().unwrap();
+
+            assert_eq!(scan_result.hosts.len(), 1);
+            assert!(!scan_result.summary.cancelled && !scan_result.summary.truncated);
+            if session_index == 0 {
+                thread::sleep(Duration::from_millis(1600).saturating_sub(first_opened_at.elapsed()));
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_reuse_cancelled_scanner() {
+
+        let link = FakeDataLink::new();
+        let router_ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        let scanner = ArpScanner::builder()
+            .interface("eth0")
+            .network(IpNetwork::from(IpAddr::V4(router_ipv4)))
+            .timeout(Duration::from_millis(100))
+            .resolve_hostnames(false)
+            .build();
+        scanner.cancellation_token().cancel();
+
+        for _ in 0..2 {
+            let mut session = scanner.open_with_links(vec![(build_interface(), Arc::new(link.clone()))]).unwrap();
+            let scan_result = session.run_round().unwrap();
+            session.close().unwrap();
+
+            assert!(scan_result.hosts.is_empty());
+            assert!(scan_result.summary.cancelled);
+        }
+        assert!(link.sent_requests().is_empty());
+    }
+
+    #[test]
+    fn should_only_cancel_session_at_max_duration() {
+
+        let link = FakeDataLink::new();
+        let scanner = ArpScanner::new(Arc::new(ScanOptions {
+            interface_names: vec!["eth0".to_string()],
+            network_range: Some(vec!["192.168.1.0/24".parse().unwrap()]),
+            timeout_ms: 100,
+            scan_timing: ScanTiming::Interval(10),
+            resolve_hostname: false,
+            max_duration_ms: Some(50),
+            ..ScanOptions::default()
+        }));
+
+        let mut session = scanner.open_with_links(vec![(build_interface(), Arc::new(link.clone()))]).unwrap();
+        let scan_result = session.run_round().unwrap();
+        session.close().unwrap();
+
+        assert!(scan_result.summary.truncated && !scan_result.summary.cancelled);
+        assert!(!scanner.cancellation_token().is_cancelled());
+    }
+
+    #[test]
+    fn should_refuse_fully_excluded_targets() {
+
+        let link = FakeDataLink::new();
+        let scanner = ArpScanner::builder()
+            .interface("eth0")
+            .network("192.168.1.5/32".parse().unwrap())
+            .exclude("192.168.1.5/32".parse().unwrap())
+            .resolve_hostnames(false)
+            .build();
+
+        let scan_error = scanner.open_with_links(vec![(build_interface(), Arc::new(link.clone()))]).err().unwrap();
+
+        assert!(matches!(scan_error, ScanError::InvalidTarget(message) if message == "No targets left after exclusions"));
+        assert!(link.sent_requests().is_empty());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn should_refuse_verification_without_neighbor_entries() {
+
+        // No kernel neighbor entry exists for this interface name
+        let interface = NetworkInterface { name: "arpscan-test0".to_string(), ..build_interface() };
+        let scanner = ArpScanner::new(Arc::new(ScanOptions {
+            interface_names: vec!["arpscan-test0".to_string()],
+            verify: true,
+            resolve_hostname: false,
+            ..ScanOptions::default()
+        }));
+
+        let scan_error = scanner.open_with_links(vec![(interface, Arc::new(FakeDataLink::new()))]).err().unwrap();
+
+        assert!(matches!(scan_error, ScanError::InvalidTarget(message) if message == "No neighbor entries to verify in /proc/net/arp"));
+    }
+
+    // A datalink layer refusing every frame handed to it.
+    #[derive(Clone)]
+    struct UnsendableLink;
+
+    impl DataLink for UnsendableLink {
+
+        fn send(&self, _: &[u8]) -> std::io::Result<()> {
+
+            Err(std::io::Error::from(std::io::ErrorKind::WouldBlock))
+        }
+
+        fn recv(&self, _: Duration) -> std::io::Result<Option<Vec<u8>>> {
+
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn should_count_send_errors_with_retries() {
+
+        let targets = [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)];
+
+        let scan_result = scan_with_link(&UnsendableLink, &targets, 1);
+
+        assert!(scan_result.hosts.is_empty());
+        assert_eq!(scan_result.summary.requests_sent, 0);
+        assert_eq!(scan_result.summary.send_errors, 4);
+    }
+
+    #[test]
+    fn should_only_retry_unanswered_hosts() {
+
+        let link = FakeDataLink::new();
+        let (router_ipv4, printer_ipv4) = (Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 30));
+        link.reply_to(router_ipv4, vec![reply_from(ROUTER_MAC, router_ipv4)]);
+        link.reply_to(printer_ipv4, vec![]);
+        link.reply_to(printer_ipv4, vec![reply_from(PRINTER_MAC, printer_ipv4)]);
+
+        let scan_result = scan_with_link(&link, &[router_ipv4, printer_ipv4], 2);
+
+        let hosts: Vec<(Ipv4Addr, MacAddr)> = scan_result.hosts.iter().map(|host| (host.ipv4, host.mac)).collect();
+        assert_eq!(hosts, [(router_ipv4, ROUTER_MAC), (printer_ipv4, PRINTER_MAC)]);
+        assert_eq!(link.sent_requests(), [router_ipv4, printer_ipv4, printer_ipv4]);
+        assert_eq!(scan_result.summary.responses_per_attempt, [1, 1, 0]);
+    }
+
+    #[test]
+    fn should_reach_all_sent_targets_in_progress() {
+
+        let link = FakeDataLink::new();
+        let router_ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        link.reply_to(router_ipv4, vec![reply_from(ROUTER_MAC, router_ipv4)]);
+        let scanner = ArpScanner::builder()
+            .interface("eth0")
+            .network("192.168.1.0/30".parse().unwrap())
+            .exclude("192.168.1.2/32".parse().unwrap())
+            .timeout(Duration::from_millis(100))
+            .interval(Duration::ZERO)
+            .retries(1)
+            .resolve_hostnames(false)
+            .build();
+
+        let mut session = scanner.open_with_links(vec![(build_interface(), Arc::new(link.clone()))]).unwrap();
+        let scan_result = session.run_round().unwrap();
+        let target_count = session.target_count();
+        session.close().unwrap();
+
+        // Retries only target the unanswered hosts, the excluded host is
+        // never sent.
+        let progress = scanner.progress().snapshot();
+        assert_eq!(scan_result.hosts.len(), 1);
+        assert!(!link.sent_requests().contains(&Ipv4Addr::new(192, 168, 1, 2)));
+        assert_eq!(link.sent_requests().len() as u128, target_count * 2 - 1);
+        assert_eq!(u128::from(progress.target_count), target_count);
+        assert_eq!(progress.sent_count, progress.target_count);
+        assert_eq!(progress.percent(), 100.0);
+        assert_eq!(progress.host_count, 1);
+        assert!(progress.finished);
+    }
+
+    #[test]
+    fn should_record_duplicate_replies_once() {
+
+        let link = FakeDataLink::new();
+        let router_ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        let router_reply = reply_from(ROUTER_MAC, router_ipv4);
+        link.reply_to(router_ipv4, vec![router_reply.clone(), router_reply.clone(), router_reply]);
+
+        let scan_result = scan_with_link(&link, &[router_ipv4], 0);
+
+        assert_eq!(scan_result.hosts.len(), 1);
+        assert_eq!(scan_result.hosts[0].mac, ROUTER_MAC);
+        assert_eq!(scan_result.hosts[0].reply_count, 3);
+        assert!(scan_result.hosts[0].response_time_ms.is_some());
+        assert_eq!((scan_result.summary.packet_count, scan_result.summary.arp_count), (3, 3));
+    }
+
+    #[test]
+    fn should_record_the_ethernet_source_of_replies() {
+
+        let link = FakeDataLink::new();
+        let (router_ipv4, printer_ipv4) = (Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 30));
+        // The router answers for the printer from its own Ethernet address,
+        // as a proxy ARP would.
+        let mut proxied_reply = reply_from(PRINTER_MAC, printer_ipv4);
+        proxied_reply[6..12].copy_from_slice(&ROUTER_MAC.octets());
+        link.reply_to(router_ipv4, vec![reply_from(ROUTER_MAC, router_ipv4)]);
+        link.reply_to(printer_ipv4, vec![proxied_reply]);
+
+        let scan_result = scan_with_link(&link, &[router_ipv4, printer_ipv4], 0);
+
+        let hosts: Vec<(Ipv4Addr, MacAddr, Option<MacAddr>, bool)> = scan_result.hosts.iter().map(|host| (host.ipv4, host.mac, host.frame_src_mac, host.is_proxy_arp_suspect())).collect();
+        assert_eq!(hosts, [(router_ipv4, ROUTER_MAC, Some(ROUTER_MAC), false), (printer_ipv4, PRINTER_MAC, Some(ROUTER_MAC), true)]);
+    }
+
+    #[test]
+    fn should_skip_malformed_frames() {
+
+        let link = FakeDataLink::new();
+        let router_ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        let router_reply = reply_from(ROUTER_MAC, router_ipv4);
+        link.push_frame(vec![0xff; 4]);
+        link.push_frame(vec![]);
+        link.push_frame(router_reply[..20].to_vec());
+        link.reply_to(router_ipv4, vec![vec![0u8; 60], router_reply]);
+
+        let scan_result = scan_with_link(&link, &[router_ipv4], 0);
+
+        let hosts: Vec<Ipv4Addr> = scan_result.hosts.iter().map(|host| host.ipv4).collect();
+        assert_eq!(hosts, [router_ipv4]);
+        assert_eq!(scan_result.summary.packet_count, 5);
+    }
+
+    #[test]
+    fn should_keep_unsolicited_replies_apart() {
+
+        // A host answering for an IPv4 that was never requested is listed
+        // apart, and is not reported while scanning.
+        let link = FakeDataLink::new();
+        let (router_ipv4, unknown_ipv4) = (Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 99));
+        link.reply_to(router_ipv4, vec![reply_from(ROUTER_MAC, router_ipv4), reply_from(PRINTER_MAC, unknown_ipv4), reply_from(PRINTER_MAC, unknown_ipv4)]);
+        let (event_sender, events) = mpsc::channel();
+
+        let scan_result = scan_with_builder(ArpScanner::builder().events(event_sender), &link, &[router_ipv4], 1);
+
+        let hosts: Vec<Ipv4Addr> = scan_result.hosts.iter().map(|host| host.ipv4).collect();
+        let unsolicited_hosts: Vec<(Ipv4Addr, MacAddr, usize)> = scan_result.summary.unsolicited_hosts.iter().map(|host| (host.ipv4, host.mac, host.reply_count)).collect();
+        assert_eq!(hosts, [router_ipv4]);
+        assert_eq!(unsolicited_hosts, [(unknown_ipv4, PRINTER_MAC, 2)]);
+        assert_eq!(events.try_iter().filter(|event| matches!(event, ScanEvent::HostFound(_))).count(), 1);
+        assert_eq!(link.sent_requests(), [router_ipv4]);
+    }
+
+    #[test]
+    fn should_merge_unsolicited_replies_when_included() {
+
+        let link = FakeDataLink::new();
+        let (router_ipv4, unknown_ipv4) = (Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 99));
+        link.reply_to(router_ipv4, vec![reply_from(ROUTER_MAC, router_ipv4), reply_from(PRINTER_MAC, unknown_ipv4)]);
+
+        let scan_result = scan_with_builder(ArpScanner::builder().include_unsolicited(true), &link, &[router_ipv4], 1);
+
+        let hosts: Vec<Ipv4Addr> = scan_result.hosts.iter().map(|host| host.ipv4).collect();
+        assert_eq!(hosts, [router_ipv4, unknown_ipv4]);
+        assert_eq!(scan_result.hosts[1].response_time_ms, None);
+        assert!(scan_result.summary.unsolicited_hosts.is_empty());
+        assert_eq!(link.sent_requests(), [router_ipv4]);
+    }
+
+    #[test]
+    fn should_replay_captures_as_live_scans() {
+
+        let scan_result = ArpScanner::new(replay_options()).replay("./data/replay-scan.pcap").unwrap();
+        let json = utils::export_to_json(scan_result.summary, scan_result.hosts, &scan_result.metadata, true, None, false).unwrap();
+
+        let expected_json = fs::read_to_string("./data/replay-scan.json").unwrap().replace("{version}", env!("CARGO_PKG_VERSION"));
+        assert_eq!(json, expected_json.trim_end());
+    }
+
+    #[test]
+    fn should_refuse_replays_of_other_link_types() {
+
+        let scan_error = ArpScanner::new(replay_options()).replay("./data/replay-cooked.pcap").err().unwrap();
+
+        assert_eq!(scan_error.to_string(), "Could not replay capture file ./data/replay-cooked.pcap (unsupported link type 113, only Ethernet captures (link type 1) can be replayed)");
+        assert_eq!(scan_error.exit_code(), crate::error::EXIT_IO);
+    }
+
+}
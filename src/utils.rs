@@ -1,17 +1,87 @@
+use std::collections::HashMap;
 use std::env;
+use std::error::Error;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
 use std::process;
 use std::sync::Arc;
 
-use pnet_datalink::NetworkInterface;
-use ipnetwork::{IpNetwork, NetworkSize};
-use serde::Serialize;
+use pnet_datalink::{MacAddr, NetworkInterface};
+use ipnetwork::{IpNetwork, Ipv4Network};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection};
+use ansi_term::Color::{Green, Red};
+#[cfg(windows)]
 use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+#[cfg(windows)]
 use windows::Win32::Security::{GetTokenInformation, TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation};
-use ansi_term::Color::{Green, Red};
+#[cfg(windows)]
 use windows::Win32::Foundation::HANDLE;
 
-use crate::network::{ResponseSummary, TargetDetails};
-use crate::args::ScanOptions;
+use crate::network::{find_address_conflicts, find_shared_macs, group_hosts_by_mac, AddressConflict, MacType, ResponseSummary, ScanMetadata, ScanPlan, TargetDetails, VerifyStatus};
+use crate::args::{AnsibleGrouping, OutputColumn, ScanOptions};
+use crate::progress::ScanProgress;
+use crate::time;
+
+/**
+ * Version of the shape of the JSON, YAML and MessagePack exports. It should be
+ * increased whenever a field is added, removed or changed, along with the
+ * JSON Schema of the exports.
+ */
+pub const EXPORT_SCHEMA_VERSION: u32 = 6;
+
+/**
+ * JSON Schema document of the exports for the current schema version, as
+ * printed by '--schema'.
+ */
+pub const EXPORT_SCHEMA: &str = include_str!("../assets/export-schema.json");
+
+const UNKNOWN_VENDOR: &str = "unknown";
+const OTHER_VENDORS: &str = "others";
+
+/**
+ * An export failure (serialization, CSV writer, ...), the message is ready to
+ * be displayed to users.
+ */
+#[derive(Debug, PartialEq)]
+pub struct ExportError(pub String);
+
+impl fmt::Display for ExportError {
+
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+
+        write!(formatter, "{}", self.0)
+    }
+}
+
+impl Error for ExportError {}
+
+/**
+ * Target networks that cannot be sized, only IPv4 networks can be scanned
+ * with ARP requests and the total size must fit in a 128-bit counter.
+ */
+#[derive(Debug, PartialEq)]
+pub enum SizeError {
+    Ipv6Network(IpNetwork),
+    Overflow
+}
+
+impl fmt::Display for SizeError {
+
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+
+        match self {
+            SizeError::Ipv6Network(ip_network) => write!(formatter, "IPv6 networks are not supported by the ARP protocol ({})", ip_network),
+            SizeError::Overflow => write!(formatter, "The target networks are too large to be counted")
+        }
+    }
+}
+
+impl Error for SizeError {}
 
 /**
  * Based on the current UNIX environment, find if the process is run as root
@@ -57,7 +127,7 @@ pub fn show_interfaces(interfaces: &[NetworkInterface]) {
             Some(mac_address) => format!("{}", mac_address),
             None => "No MAC address".to_string()
         };
-        let first_ip = match interface.ips.get(0) {
+        let first_ip = match interface.ips.first() {
             Some(ip_address) => format!("{}", ip_address),
             None => "".to_string()
         };
@@ -84,250 +154,3314 @@ pub fn show_interfaces(interfaces: &[NetworkInterface]) {
  */
 pub fn select_default_interface(interfaces: &[NetworkInterface]) -> Option<NetworkInterface> {
 
-    let default_interface = interfaces.iter().find(|interface| {
+    let default_interface = interfaces.iter().find(|interface| is_eligible_interface(interface));
 
-        if interface.mac.is_none() {
-            return false;
-        }
+    default_interface.cloned()
+}
 
-        if interface.ips.is_empty() || !interface.is_up() || interface.is_loopback() {
-            return false;
-        }
+/**
+ * Check if a network interface can be used for ARP scans: the interface should
+ * be up, not loopback and have both a MAC address and an IPv4 address.
+ */
+pub fn is_eligible_interface(interface: &NetworkInterface) -> bool {
 
-        let potential_ipv4 = interface.ips.iter().find(|ip| ip.is_ipv4());
-        if potential_ipv4.is_none() {
-            return false;
-        }
+    if interface.mac.is_none() {
+        return false;
+    }
 
-        true
-    });
+    if interface.ips.is_empty() || !interface.is_up() || interface.is_loopback() {
+        return false;
+    }
 
-    default_interface.cloned()
+    let potential_ipv4 = interface.ips.iter().find(|ip| ip.is_ipv4());
+    if potential_ipv4.is_none() {
+        return false;
+    }
+
+    true
 }
 
 /**
  * Display scan settings before launching an ARP scan. This includes network
  * details (IP range, interface, ...) and timing informations.
  */
-pub fn display_prescan_details(ip_networks: &[&IpNetwork], selected_interface: &NetworkInterface, scan_options: Arc<ScanOptions>) {
-    
-    let mut network_list = ip_networks.iter().take(5).map(|network| network.to_string()).collect::<Vec<String>>().join(", ");
-    if ip_networks.len() > 5 {
-        let more_text = format!(" ({} more)", ip_networks.len()-5);
-        network_list.push_str(&more_text);
-    }
+pub fn display_prescan_details(network_configurations: &[(&NetworkInterface, Vec<&IpNetwork>)], scan_options: Arc<ScanOptions>) {
 
     println!();
-    println!("Selected interface {} with IP {}", selected_interface.name, network_list);
+    for (selected_interface, ip_networks) in network_configurations.iter() {
+
+        let mut network_list = ip_networks.iter().take(5).map(|network| network.to_string()).collect::<Vec<String>>().join(", ");
+        if ip_networks.len() > 5 {
+            let more_text = format!(" ({} more)", ip_networks.len()-5);
+            network_list.push_str(&more_text);
+        }
+        println!("Selected interface {} with IP {}", selected_interface.name, network_list);
+    }
     if let Some(forced_source_ipv4) = scan_options.source_ipv4 {
         println!("The ARP source IPv4 will be forced to {}", forced_source_ipv4);
     }
+    if let Some(forced_source_mac) = scan_options.source_mac {
+        println!("The ARP source MAC will be forced to {}", forced_source_mac);
+    }
+    if let Some(forced_sender_mac) = scan_options.arp_sender_mac {
+        println!("The ARP sender hardware address will be forced to {}", forced_sender_mac);
+    }
     if let Some(forced_destination_mac) = scan_options.destination_mac {
         println!("The ARP destination MAC will be forced to {}", forced_destination_mac);
     }
+    for target_hostname in scan_options.target_hostnames.iter() {
+        let addresses: Vec<String> = target_hostname.addresses.iter().map(|address| address.to_string()).collect();
+        println!("Target {} resolved to {}", target_hostname.name, addresses.join(", "));
+    }
+    if let Some(random_seed) = scan_options.random_seed {
+        println!("Targets will be scanned in random order (seed {})", random_seed);
+    }
 }
 
 /**
- * Computes multiple IPv4 networks total size, IPv6 network are not being
- * supported by this function. 
+ * Computes multiple IPv4 networks total size, an IPv6 network or a total
+ * size overflow is returned as an error for the caller to handle.
  */
-pub fn compute_network_size(ip_networks: &[&IpNetwork]) -> u128 {
+pub fn compute_network_size(ip_networks: &[&IpNetwork], excluded_networks: &[Ipv4Network]) -> Result<u128, SizeError> {
 
-    ip_networks.iter().fold(0u128, |total_size, ip_network| {
+    let excluded_ranges = merge_ipv4_ranges(excluded_networks);
 
-        let network_size: u128 = match ip_network.size() {
-            NetworkSize::V4(ipv4_network_size) => ipv4_network_size.into(),
-            NetworkSize::V6(_) => {
-                eprintln!("IPv6 networks are not supported by the ARP protocol");
-                process::exit(1);
-            }
+    ip_networks.iter().try_fold(0u128, |total_size, ip_network| {
+
+        let network_size: u128 = match ip_network {
+            IpNetwork::V4(network) => ipv4_network_size(network),
+            IpNetwork::V6(_) => return Err(SizeError::Ipv6Network(**ip_network))
+        };
+
+        // Excluded ranges are merged beforehand, so that overlapping exclusions
+        // are only subtracted once from each target network.
+        let excluded_size: u128 = match ip_network {
+            IpNetwork::V4(network) => {
+                let (start, end) = (u32::from(network.network()), u32::from(network.broadcast()));
+                excluded_ranges.iter().map(|(excluded_start, excluded_end)| {
+                    let (overlap_start, overlap_end) = (start.max(*excluded_start), end.min(*excluded_end));
+                    match overlap_start <= overlap_end {
+                        true => u128::from(overlap_end - overlap_start) + 1,
+                        false => 0
+                    }
+                }).sum()
+            },
+            IpNetwork::V6(_) => 0
         };
-        total_size + network_size
+        add_network_size(total_size, network_size - excluded_size)
     })
 }
 
 /**
- * Display the scan results on stdout with a table. The 'final_result' vector
- * contains all items that will be displayed.
+ * Size of an IPv4 network, derived from its prefix: a 0.0.0.0/0 network holds
+ * 2^32 addresses, which would overflow the u32 size computed by ipnetwork.
  */
-pub fn display_scan_results(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, options: &ScanOptions) {
+pub fn ipv4_network_size(network: &Ipv4Network) -> u128 {
+    1u128 << (32 - u32::from(network.prefix()))
+}
 
-    target_details.sort_by_key(|item| item.ipv4);
+/**
+ * Adds a network size to a running total, pathological target lists (such as
+ * countless 0.0.0.0/0 networks) are rejected instead of overflowing.
+ */
+pub fn add_network_size(total_size: u128, network_size: u128) -> Result<u128, SizeError> {
+    total_size.checked_add(network_size).ok_or(SizeError::Overflow)
+}
 
-    let mut hostname_len = 15;
-    let mut vendor_len = 15;
-    for detail in target_details.iter() {
+/**
+ * Merge IPv4 networks into sorted & non-overlapping address ranges (with both
+ * bounds included).
+ */
+fn merge_ipv4_ranges(networks: &[Ipv4Network]) -> Vec<(u32, u32)> {
 
-        if let Some(hostname) = &detail.hostname {
-            if hostname.len() > hostname_len {
-                hostname_len = hostname.len();
-            }
-        }
+    let mut ranges: Vec<(u32, u32)> = networks.iter().map(|network| {
+        (u32::from(network.network()), u32::from(network.broadcast()))
+    }).collect();
+    ranges.sort_unstable();
 
-        if let Some(vendor) = &detail.vendor {
-            if vendor.len() > vendor_len {
-                vendor_len = vendor.len();
-            }
+    let mut merged_ranges: Vec<(u32, u32)> = vec![];
+    for (start, end) in ranges {
+        match merged_ranges.last_mut() {
+            Some((_, last_end)) if start <= last_end.saturating_add(1) => *last_end = (*last_end).max(end),
+            _ => merged_ranges.push((start, end))
         }
     }
+    merged_ranges
+}
+
+/**
+ * Compute the column widths for result tables, based on the longest cell of
+ * each column (including the header). Every column has a minimum width.
+ */
+fn compute_column_widths(columns: &[OutputColumn], rows: &[Vec<String>]) -> Vec<usize> {
+
+    columns.iter().enumerate().map(|(index, column)| {
+        rows.iter().fold(column_minimum_width(column), |width, row| width.max(display_width(&row[index])))
+    }).collect()
+}
+
+/**
+ * Compute the width of a text in terminal columns: East Asian wide characters
+ * (and emojis) take two columns, while combining marks and zero-width
+ * characters take none.
+ */
+pub fn display_width(text: &str) -> usize {
+
+    text.chars().map(|character| match character as u32 {
+        0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F => 0,
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xA000..=0xA4CF
+            | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x2FFFD | 0x30000..=0x3FFFD => 2,
+        _ => 1
+    }).sum()
+}
+
+fn pad_cell(text: &str, width: usize) -> String {
 
-    if !target_details.is_empty() {
-        println!();
-        println!("| IPv4            | MAC               | {: <h_max$} | {: <v_max$} |", "Hostname", "Vendor", h_max=hostname_len, v_max=vendor_len);
-        println!("|-----------------|-------------------|-{:-<h_max$}-|-{:-<v_max$}-|", "", "", h_max=hostname_len, v_max=vendor_len);
+    format!(" {}{} ", text, " ".repeat(width.saturating_sub(display_width(text))))
+}
+
+fn column_title(column: &OutputColumn) -> &'static str {
+
+    match column {
+        OutputColumn::Ipv4 => "IPv4",
+        OutputColumn::Mac => "MAC",
+        OutputColumn::FrameSrcMac => "Frame MAC",
+        OutputColumn::Hostname => "Hostname",
+        OutputColumn::Vendor => "Vendor",
+        OutputColumn::ResponseTime => "Latency",
+        OutputColumn::ReplyCount => "Replies",
+        OutputColumn::Interface => "Interface",
+        OutputColumn::Status => "Status",
+        OutputColumn::Vlan => "VLAN",
+        OutputColumn::Origin => "Origin",
+        OutputColumn::MacType => "MAC type",
+        OutputColumn::Virtualization => "Virtualization",
+        OutputColumn::Note => "Note"
     }
+}
 
-    for detail in target_details.iter() {
+fn column_minimum_width(column: &OutputColumn) -> usize {
 
-        let hostname: &str = match &detail.hostname {
-            Some(hostname) => hostname,
-            None if !options.resolve_hostname => "(disabled)",
-            None => ""
-        };
-        let vendor: &str = match &detail.vendor {
-            Some(vendor) => vendor,
-            None => ""
-        };
-        println!("| {: <15} | {: <18} | {: <h_max$} | {: <v_max$} |", detail.ipv4, detail.mac, hostname, vendor, h_max=hostname_len, v_max=vendor_len);
+    match column {
+        OutputColumn::Ipv4 => 15,
+        OutputColumn::Mac => 17,
+        OutputColumn::FrameSrcMac => 17,
+        OutputColumn::Hostname => 15,
+        OutputColumn::Vendor => 15,
+        OutputColumn::ResponseTime => 8,
+        OutputColumn::ReplyCount => 7,
+        OutputColumn::Interface => 9,
+        OutputColumn::Status => 6,
+        OutputColumn::Vlan => 4,
+        OutputColumn::Origin => 6,
+        OutputColumn::MacType => 9,
+        OutputColumn::Virtualization => 14,
+        OutputColumn::Note => 4
     }
+}
 
-    println!();
-    print!("ARP scan finished, ");
+/**
+ * Format rows as a pipe-delimited table with a header and a separator line,
+ * this layout is shared by the plain-text and the Markdown outputs. Rows
+ * flagged in 'highlighted_rows' are painted in red (after padding, so that
+ * color codes do not change column widths).
+ */
+fn format_table(columns: &[OutputColumn], rows: &[Vec<String>], highlighted_rows: &[bool]) -> String {
+
+    let widths = compute_column_widths(columns, rows);
+    let format_line = |cells: Vec<String>| format!("|{}|\n", cells.join("|"));
+
+    let mut table = String::new();
+    table.push_str(&format_line(columns.iter().zip(widths.iter()).map(|(column, width)| {
+        pad_cell(column_title(column), *width)
+    }).collect()));
+    table.push_str(&format_line(widths.iter().map(|width| "-".repeat(width + 2)).collect()));
+    for (row_index, row) in rows.iter().enumerate() {
+        let line = format_line(row.iter().zip(widths.iter()).map(|(cell, width)| pad_cell(cell, *width)).collect());
+        match highlighted_rows.get(row_index).copied().unwrap_or(false) {
+            true => table.push_str(&format!("{}\n", Red.paint(line.trim_end_matches('\n')))),
+            false => table.push_str(&line)
+        }
+    }
+    table
+}
+
+/**
+ * Format the scan results as a plain-text table, followed by the scan summary.
+ * The 'colored' flag should be disabled when the text is not printed on a
+ * terminal (when writing to a file for example).
+ */
+pub fn format_scan_results(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, options: &ScanOptions, colored: bool) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    // MAC mismatches of verified neighbors and IPv4 addresses answered by
+    // several MACs may indicate ARP spoofing, these rows are highlighted when
+    // printed on a terminal. Conflicting rows are also marked in the text.
+    let conflicts = find_address_conflicts(&target_details);
+    let is_conflicting = |detail: &TargetDetails| conflicts.iter().any(|conflict| conflict.ipv4 == detail.ipv4 && conflict.vlan_id == detail.vlan_id);
+
+    let rows: Vec<Vec<String>> = target_details.iter().map(|detail| {
+        options.columns.iter().map(|column| match column {
+            OutputColumn::Ipv4 if is_conflicting(detail) => format!("{} CONFLICT", detail.ipv4),
+            OutputColumn::Ipv4 => detail.ipv4.to_string(),
+            // Answers sent from another Ethernet address than the ARP sender
+            // address are flagged, as they may come from a proxy ARP.
+            OutputColumn::Mac if detail.is_proxy_arp_suspect() => format!("{} proxy?", detail.mac),
+            OutputColumn::Mac => detail.mac.to_string(),
+            OutputColumn::FrameSrcMac => detail.frame_src_mac.map(|frame_src_mac| frame_src_mac.to_string()).unwrap_or_default(),
+            OutputColumn::Hostname => match &detail.hostname {
+                Some(hostname) => hostname.clone(),
+                None if !options.resolve_hostname => "(disabled)".to_string(),
+                None => String::new()
+            },
+            // Locally administered MACs (such as randomized MACs) and multicast
+            // MACs are not registered by the IEEE, the reason is made explicit.
+            OutputColumn::Vendor => match (&detail.vendor, MacType::classify(&detail.mac)) {
+                (Some(vendor), _) => vendor.clone(),
+                (None, MacType::Local) => "(locally administered)".to_string(),
+                (None, MacType::Multicast) => "(multicast)".to_string(),
+                (None, MacType::Universal) => String::new()
+            },
+            OutputColumn::ResponseTime => detail.response_time_ms.map(|response_time_ms| format!("{}ms", response_time_ms)).unwrap_or_default(),
+            OutputColumn::ReplyCount => detail.reply_count.to_string(),
+            OutputColumn::Interface => detail.interface.clone(),
+            OutputColumn::Status => detail.verify_status.map(|status| status.describe()).unwrap_or_default(),
+            OutputColumn::Vlan => detail.vlan_id.map(|vlan_id| vlan_id.to_string()).unwrap_or_default(),
+            OutputColumn::Origin => detail.origin.map(|origin| origin.describe()).unwrap_or_default(),
+            OutputColumn::MacType => MacType::classify(&detail.mac).describe(),
+            OutputColumn::Virtualization => detail.virtualization.clone().unwrap_or_default(),
+            OutputColumn::Note => detail.note.clone().unwrap_or_default()
+        }).collect()
+    }).collect();
+
+    let highlighted_rows: Vec<bool> = target_details.iter().map(|detail| {
+        colored && (matches!(detail.verify_status, Some(VerifyStatus::Mismatch(_))) || is_conflicting(detail))
+    }).collect();
+
+    let mut text = String::new();
+
+    if options.group_by_mac {
+        text.push_str(&format_mac_groups(&target_details));
+    }
+    else if !rows.is_empty() {
+        text.push('\n');
+        text.push_str(&format_table(&options.columns, &rows, &highlighted_rows));
+    }
+
+    text.push_str(&format_duplicates(&target_details, &conflicts, colored));
+    text.push_str(&format_unsolicited_hosts(&response_summary.unsolicited_hosts));
+    if let Some(top_count) = options.top_vendors {
+        text.push_str(&format_vendor_summary(&target_details, top_count));
+    }
+
+    text.push('\n');
+    text.push_str("ARP scan finished, ");
     let target_count = target_details.len();
     match target_count {
-        0 => print!("{}", Red.paint("no hosts found")),
-        1 => print!("1 host found"),
-        _ => print!("{} hosts found", target_count)
+        0 if colored => text.push_str(&Red.paint("no hosts found").to_string()),
+        0 => text.push_str("no hosts found"),
+        1 => text.push_str("1 host found"),
+        _ => text.push_str(&format!("{} hosts found", target_count))
     }
     let seconds_duration = (response_summary.duration_ms as f32) / (1000_f32);
-    println!(" in {:.3} seconds", seconds_duration);
+    text.push_str(&format!(" in {:.3} seconds\n", seconds_duration));
 
     match response_summary.packet_count {
-        0 => print!("No packets received, "),
-        1 => print!("1 packet received, "),
-        _ => print!("{} packets received, ", response_summary.packet_count)
+        0 => text.push_str("No packets received, "),
+        1 => text.push_str("1 packet received, "),
+        _ => text.push_str(&format!("{} packets received, ", response_summary.packet_count))
     };
     match response_summary.arp_count {
-        0 => println!("no ARP packets filtered"),
-        1 => println!("1 ARP packet filtered"),
-        _ => println!("{} ARP packets filtered", response_summary.arp_count)
+        0 => text.push_str("no ARP packets filtered"),
+        1 => text.push_str("1 ARP packet filtered"),
+        _ => text.push_str(&format!("{} ARP packets filtered", response_summary.arp_count))
     };
-    println!();
-}
+    if !options.passive {
+        match response_summary.requests_sent {
+            1 => text.push_str(", 1 request sent"),
+            requests_sent => text.push_str(&format!(", {} requests sent", requests_sent))
+        };
+        match response_summary.send_errors {
+            0 => {},
+            1 if colored => text.push_str(&format!(", {}", Red.paint("1 send error"))),
+            1 => text.push_str(", 1 send error"),
+            send_errors if colored => text.push_str(&format!(", {}", Red.paint(format!("{} send errors", send_errors)))),
+            send_errors => text.push_str(&format!(", {} send errors", send_errors))
+        };
+    }
+    // Duplicate replies are answers received after the first answer of a
+    // host, such as retransmissions or frames seen twice on a mirrored port.
+    let duplicate_replies: usize = target_details.iter().map(|detail| detail.reply_count.saturating_sub(1)).sum();
+    match duplicate_replies {
+        0 => {},
+        1 => text.push_str(", 1 duplicate reply"),
+        _ => text.push_str(&format!(", {} duplicate replies", duplicate_replies))
+    };
+    text.push('\n');
+    if options.passive {
+        text.push_str("Passive mode, 0 packets sent\n");
+    }
+    if response_summary.truncated {
+        let truncated_text = match (response_summary.host_limit_reached, options.max_hosts) {
+            (true, Some(max_hosts)) => format!("Scan truncated, the maximum of {} hosts has been found\n", max_hosts),
+            _ => "Scan truncated, the maximum scan duration has been reached\n".to_string()
+        };
+        match colored {
+            true => text.push_str(&Red.paint(truncated_text).to_string()),
+            false => text.push_str(&truncated_text)
+        }
+    }
+    if response_summary.cancelled {
+        let cancelled_text = "Scan cancelled, partial results are displayed\n";
+        match colored {
+            true => text.push_str(&Red.paint(cancelled_text).to_string()),
+            false => text.push_str(cancelled_text)
+        }
+    }
+    if !conflicts.is_empty() {
+        let conflict_title = format!("{} conflicting IPv4 addresses (answered by several MAC addresses)", conflicts.len());
+        match colored {
+            true => text.push_str(&format!("{}\n", Red.paint(conflict_title))),
+            false => text.push_str(&format!("{}\n", conflict_title))
+        }
+    }
+    if options.verify {
+        let count_status = |expected: fn(&VerifyStatus) -> bool| target_details.iter().filter(|detail| detail.verify_status.as_ref().map(expected).unwrap_or(false)).count();
+        let alive_count = count_status(|status| matches!(status, VerifyStatus::Alive));
+        let stale_count = count_status(|status| matches!(status, VerifyStatus::Stale));
+        let mismatch_count = count_status(|status| matches!(status, VerifyStatus::Mismatch(_)));
+        text.push_str(&format!("Neighbor verification: {} alive, {} stale, {} mismatch\n", alive_count, stale_count, mismatch_count));
+    }
+    if let Some(send_rate) = response_summary.achieved_send_rate() {
+        text.push_str(&format!("ARP requests sent at {:.1} packets/s\n", send_rate));
+    }
+    if response_summary.responses_per_attempt.len() > 1 {
+        let attempts: Vec<String> = response_summary.responses_per_attempt.iter().enumerate().map(|(index, count)| {
+            format!("{} on attempt {}", count, index + 1)
+        }).collect();
+        text.push_str(&format!("Hosts answering: {}\n", attempts.join(", ")));
+    }
+    text.push('\n');
 
-#[derive(Serialize)]
-struct SerializableResultItem {
-    ipv4: String,
-    mac: String,
-    hostname: String,
-    vendor: String
+    text
 }
 
-#[derive(Serialize)]
-struct SerializableGlobalResult {
-    packet_count: usize,
-    arp_count: usize,
-    duration_ms: u128,
-    results: Vec<SerializableResultItem>
+/**
+ * Report which woken MAC addresses appeared in the scan results, with the IPv4
+ * addresses they answered for.
+ */
+pub fn format_wake_report(wake_macs: &[MacAddr], target_details: &[TargetDetails]) -> String {
+
+    let mut report = String::new();
+    let mut appeared_count: usize = 0;
+    for wake_mac in wake_macs.iter() {
+
+        let mut answered_ipv4s: Vec<Ipv4Addr> = target_details.iter().filter(|detail| detail.mac == *wake_mac).map(|detail| detail.ipv4).collect();
+        answered_ipv4s.sort();
+
+        match answered_ipv4s.is_empty() {
+            true => report.push_str(&format!("Woken host {} did not appear in the results\n", wake_mac)),
+            false => {
+                appeared_count += 1;
+                let addresses: Vec<String> = answered_ipv4s.iter().map(|ipv4| ipv4.to_string()).collect();
+                report.push_str(&format!("Woken host {} appeared as {}\n", wake_mac, addresses.join(", ")));
+            }
+        }
+    }
+    report.push_str(&format!("{} of {} woken hosts appeared in the results\n", appeared_count, wake_macs.len()));
+    report
 }
 
 /**
- * Transforms an ARP scan result (including KPI and target details) to a structure
- * that can be serialized for export (JSON, YAML, CSV, ...)
+ * Format the requests planned in dry-run mode: for each network, the targets
+ * and the addresses of the requests, followed by the bytes of an example
+ * frame (16 bytes per line).
  */
-fn get_serializable_result(response_summary: ResponseSummary, target_details: Vec<TargetDetails>) -> SerializableGlobalResult {
+pub fn format_scan_plans(scan_plans: &[ScanPlan]) -> String {
 
-    let exportable_results: Vec<SerializableResultItem> = target_details.into_iter()
-        .map(|detail| {
+    let mut output = String::from("Dry run, no packet will be sent\n");
+    for scan_plan in scan_plans {
 
-            let hostname = match &detail.hostname {
-                Some(hostname) => hostname.clone(),
-                None => String::from("")
-            };
+        let vlan = scan_plan.vlan_id.map(|vlan_id| format!(" (VLAN {})", vlan_id)).unwrap_or_default();
+        output.push_str(&format!("\n{} {}{}: {} targets", scan_plan.interface_name, scan_plan.network, vlan, scan_plan.target_count));
+        if let (Some(first_target), Some(last_target)) = (scan_plan.first_target, scan_plan.last_target) {
+            output.push_str(&format!(", from {} to {}", first_target, last_target));
+        }
+        output.push('\n');
 
-            let vendor = match &detail.vendor {
-                Some(vendor) => vendor.clone(),
-                None => String::from("")
-            };
+        let source_mac = scan_plan.source_mac.map(|mac| mac.to_string()).unwrap_or_else(|| "?".to_string());
+        output.push_str(&format!("  Source {} ({}), destination {}\n", scan_plan.source_ipv4, source_mac, scan_plan.destination_mac));
 
-            SerializableResultItem {
-                ipv4: format!("{}", detail.ipv4),
-                mac: format!("{}", detail.mac),
-                hostname,
-                vendor
+        if let Some(example_frame) = &scan_plan.example_frame {
+            output.push_str(&format!("  Example frame ({} bytes)\n", example_frame.len()));
+            for (line_index, line) in example_frame.chunks(16).enumerate() {
+                let bytes: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+                output.push_str(&format!("  {:04x}  {}\n", line_index * 16, bytes.join(" ")));
             }
-        })
-        .collect();
+        }
+    }
+    output
+}
 
-    SerializableGlobalResult {
-        packet_count: response_summary.packet_count,
-        arp_count: response_summary.arp_count,
-        duration_ms: response_summary.duration_ms,
-        results: exportable_results
+// Width of the progress bar, in characters (without the counters).
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/**
+ * Format a single-line progress bar, with the targets sent, the hosts found
+ * and the time elapsed & remaining. The line is redrawn in place on stderr.
+ */
+pub fn format_progress_bar(progress: &ScanProgress) -> String {
+
+    let percent = progress.percent();
+    let filled_width = ((percent / 100.0) * PROGRESS_BAR_WIDTH as f64).round() as usize;
+    format!(
+        "[{}{}] {:>3.0}% {}/{} targets, {} hosts found, {} elapsed, ~{} remaining",
+        "#".repeat(filled_width),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled_width),
+        percent,
+        progress.sent_count,
+        progress.target_count,
+        progress.host_count,
+        time::format_milliseconds(progress.elapsed.as_millis()),
+        time::format_milliseconds(progress.estimated_remaining.as_millis())
+    )
+}
+
+/**
+ * Format the 'Duplicates' section printed under the result table: MAC
+ * addresses answering for several IPv4 addresses (VMs, VIPs, NAT boxes, ...)
+ * and IPv4 addresses claimed by several MACs. Only the hostnames already
+ * resolved are displayed, no additional lookup is performed.
+ */
+fn format_duplicates(target_details: &[TargetDetails], conflicts: &[AddressConflict], colored: bool) -> String {
+
+    let shared_macs = find_shared_macs(target_details);
+    if shared_macs.is_empty() && conflicts.is_empty() {
+        return String::new();
+    }
+
+    let mut text = String::from("\nDuplicates\n");
+    for shared_mac in shared_macs.iter() {
+        let addresses: Vec<String> = shared_mac.ipv4s.iter().map(|ipv4| {
+            let hostname = target_details.iter().find(|detail| detail.ipv4 == *ipv4 && detail.mac == shared_mac.mac).and_then(|detail| detail.hostname.as_ref());
+            match hostname {
+                Some(hostname) => format!("{} ({})", ipv4, hostname),
+                None => ipv4.to_string()
+            }
+        }).collect();
+        text.push_str(&format!("  MAC {} answers for {} IPv4 addresses: {}\n", shared_mac.mac, shared_mac.ipv4s.len(), addresses.join(", ")));
+    }
+    for conflict in conflicts.iter() {
+        let macs: Vec<String> = conflict.macs.iter().map(|mac| mac.to_string()).collect();
+        let conflict_line = format!("  CONFLICT {} is claimed by {} MAC addresses: {}", conflict.ipv4, conflict.macs.len(), macs.join(", "));
+        match colored {
+            true => text.push_str(&format!("{}\n", Red.paint(conflict_line))),
+            false => text.push_str(&format!("{}\n", conflict_line))
+        }
     }
+    text
 }
 
 /**
- * Export the scan results as a JSON string with response details (timings, ...)
- * and ARP results from the local network.
+ * Format the hosts as one block per MAC address (sorted by MAC), listing its
+ * IPv4 addresses with their hostname. This replaces the result table with
+ * '--group-by-mac'.
  */
-pub fn export_to_json(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>) -> String {
+fn format_mac_groups(target_details: &[TargetDetails]) -> String {
 
-    target_details.sort_by_key(|item| item.ipv4);
+    let mut text = String::new();
+    for mac_group in group_hosts_by_mac(target_details) {
+        let addresses = match mac_group.ipv4s.len() {
+            1 => "1 IPv4 address".to_string(),
+            address_count => format!("{} IPv4 addresses", address_count)
+        };
+        match &mac_group.vendor {
+            Some(vendor) => text.push_str(&format!("\n{} ({}), {}\n", mac_group.mac, vendor, addresses)),
+            None => text.push_str(&format!("\n{}, {}\n", mac_group.mac, addresses))
+        }
+        for ipv4 in mac_group.ipv4s.iter() {
+            let hostname = target_details.iter().find(|detail| detail.ipv4 == *ipv4 && detail.mac == mac_group.mac).and_then(|detail| detail.hostname.as_ref());
+            match hostname {
+                Some(hostname) => text.push_str(&format!("  {} ({})\n", ipv4, hostname)),
+                None => text.push_str(&format!("  {}\n", ipv4))
+            }
+        }
+    }
+    text
+}
 
-    let global_result = get_serializable_result(response_summary, target_details);
+/**
+ * Format the 'Unsolicited replies' section printed under the result table,
+ * with the hosts answering for an IPv4 address that was not targeted (such as
+ * misconfigured proxies).
+ */
+fn format_unsolicited_hosts(unsolicited_hosts: &[TargetDetails]) -> String {
 
-    serde_json::to_string(&global_result).unwrap_or_else(|err| {
-        eprintln!("Could not export JSON results ({})", err);
-        process::exit(1);
-    })
+    if unsolicited_hosts.is_empty() {
+        return String::new();
+    }
+
+    let mut text = String::from("\nUnsolicited replies\n");
+    for host in unsolicited_hosts.iter() {
+        let replies = match host.reply_count {
+            1 => "1 reply".to_string(),
+            reply_count => format!("{} replies", reply_count)
+        };
+        match &host.vendor {
+            Some(vendor) => text.push_str(&format!("  {} answered from {} ({}), {}\n", host.ipv4, host.mac, vendor, replies)),
+            None => text.push_str(&format!("  {} answered from {}, {}\n", host.ipv4, host.mac, replies))
+        }
+    }
+    text
 }
 
 /**
- * Export the scan results as a YAML string with response details (timings, ...)
- * and ARP results from the local network.
+ * Count the hosts of each vendor, sorted by descending count (then by vendor
+ * name). Hosts without vendor are aggregated in a single 'unknown' bucket.
  */
-pub fn export_to_yaml(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>) -> String {
+pub fn compute_vendor_summary(target_details: &[TargetDetails]) -> Vec<(String, usize)> {
 
-    target_details.sort_by_key(|item| item.ipv4);
+    let mut vendor_counts: HashMap<String, usize> = HashMap::new();
+    for detail in target_details {
+        let vendor = detail.vendor.clone().filter(|vendor| !vendor.is_empty()).unwrap_or_else(|| UNKNOWN_VENDOR.to_string());
+        *vendor_counts.entry(vendor).or_insert(0) += 1;
+    }
 
-    let global_result = get_serializable_result(response_summary, target_details);
+    let mut vendor_summary: Vec<(String, usize)> = vendor_counts.into_iter().collect();
+    vendor_summary.sort_by(|(first_vendor, first_count), (second_vendor, second_count)| {
+        second_count.cmp(first_count).then_with(|| first_vendor.cmp(second_vendor))
+    });
+    vendor_summary
+}
 
-    serde_yaml::to_string(&global_result).unwrap_or_else(|err| {
-        eprintln!("Could not export YAML results ({})", err);
-        process::exit(1);
-    })
+/**
+ * Keep the first vendors of a summary, the hosts of all other vendors are
+ * aggregated in an 'others' bucket.
+ */
+fn limit_vendor_summary(mut vendor_summary: Vec<(String, usize)>, top_count: usize) -> Vec<(String, usize)> {
+
+    if vendor_summary.len() > top_count {
+        let other_count: usize = vendor_summary.drain(top_count..).map(|(_, count)| count).sum();
+        vendor_summary.push((OTHER_VENDORS.to_string(), other_count));
+    }
+    vendor_summary
 }
 
 /**
- * Export the scan results as a CSV string with response details (timings, ...)
- * and ARP results from the local network.
+ * Format the 'Top vendors' line printed under the result table, such as
+ * 'Top vendors: Espressif: 14, Raspberry Pi Trading: 6, unknown: 3'.
  */
-pub fn export_to_csv(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>) -> String {
+fn format_vendor_summary(target_details: &[TargetDetails], top_count: usize) -> String {
 
-    target_details.sort_by_key(|item| item.ipv4);
+    let vendor_summary = limit_vendor_summary(compute_vendor_summary(target_details), top_count);
+    if vendor_summary.is_empty() {
+        return String::new();
+    }
 
-    let global_result = get_serializable_result(response_summary, target_details);
+    let counts: Vec<String> = vendor_summary.iter().map(|(vendor, count)| format!("{}: {}", vendor, count)).collect();
+    format!("\nTop vendors: {}\n", counts.join(", "))
+}
 
-    let mut wtr = csv::Writer::from_writer(vec![]);
+fn csv_column_header(column: &OutputColumn) -> &'static str {
 
-    for result in global_result.results {
-        wtr.serialize(result).unwrap_or_else(|err| {
-            eprintln!("Could not serialize result to CSV ({})", err);
-            process::exit(1);
-        });
+    match column {
+        OutputColumn::Ipv4 => "ipv4",
+        OutputColumn::Mac => "mac",
+        OutputColumn::FrameSrcMac => "frame_src_mac",
+        OutputColumn::Hostname => "hostname",
+        OutputColumn::Vendor => "vendor",
+        OutputColumn::ResponseTime => "response_time_ms",
+        OutputColumn::ReplyCount => "reply_count",
+        OutputColumn::Interface => "interface",
+        OutputColumn::Status => "status",
+        OutputColumn::Vlan => "vlan",
+        OutputColumn::Origin => "origin",
+        OutputColumn::MacType => "mac_type",
+        OutputColumn::Virtualization => "virtualization",
+        OutputColumn::Note => "note"
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableResultItem {
+    ipv4: String,
+    mac: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    frame_src_mac: Option<String>,
+    hostname: String,
+    vendor: String,
+    response_time_ms: Option<u64>,
+    #[serde(default)]
+    reply_count: usize,
+    #[serde(default)]
+    interface: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    vlan_id: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    origin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    hostname_source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    hostname_ascii: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    mac_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    virtualization: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    note: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableConflict {
+    ipv4: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    vlan_id: Option<u16>,
+    macs: Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableVendorCount {
+    vendor: String,
+    count: usize
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableMacGroup {
+    mac: String,
+    vendor: String,
+    ipv4s: Vec<String>,
+    hostnames: Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableSharedMac {
+    mac: String,
+    ipv4s: Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableMetadata {
+    started_at: String,
+    interface_name: String,
+    interface_mac: String,
+    source_ipv4: String,
+    target_networks: Vec<String>,
+    version: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableGlobalResult {
+    #[serde(default)]
+    schema_version: u32,
+    packet_count: usize,
+    arp_count: usize,
+    #[serde(default)]
+    requests_sent: usize,
+    #[serde(default)]
+    send_errors: usize,
+    duration_ms: u128,
+    results: Vec<SerializableResultItem>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    metadata: Option<SerializableMetadata>,
+    #[serde(skip_serializing_if = "is_false", default)]
+    truncated: bool,
+    #[serde(skip_serializing_if = "is_false", default)]
+    cancelled: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    conflicts: Vec<SerializableConflict>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    shared_macs: Vec<SerializableSharedMac>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    vendor_summary: Vec<SerializableVendorCount>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    unsolicited_hosts: Vec<SerializableResultItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    hosts_by_mac: Vec<SerializableMacGroup>
+}
+
+// Truncated and cancelled scans are flagged in the exports, while the flags
+// are omitted for complete scans to keep the exported format unchanged.
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "arp_scan")]
+struct SerializableXmlResult {
+    packet_count: usize,
+    arp_count: usize,
+    #[serde(default)]
+    requests_sent: usize,
+    #[serde(default)]
+    send_errors: usize,
+    duration_ms: u128,
+    #[serde(skip_serializing_if = "is_false", default)]
+    truncated: bool,
+    #[serde(skip_serializing_if = "is_false", default)]
+    cancelled: bool,
+    metadata: SerializableMetadata,
+    #[serde(rename = "host", default)]
+    hosts: Vec<SerializableXmlHost>
+}
+
+// XML has no null value, all host fields are exported as text so that missing
+// values are kept as empty elements.
+#[derive(Serialize, Deserialize)]
+struct SerializableXmlHost {
+    ipv4: String,
+    mac: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    frame_src_mac: Option<String>,
+    hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    hostname_ascii: Option<String>,
+    vendor: String,
+    response_time_ms: String,
+    #[serde(default)]
+    reply_count: usize,
+    #[serde(default)]
+    interface: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    vlan_id: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    origin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    mac_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    virtualization: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    note: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableSummary {
+    packet_count: usize,
+    arp_count: usize,
+    #[serde(default)]
+    requests_sent: usize,
+    #[serde(default)]
+    send_errors: usize,
+    duration_ms: u128,
+    #[serde(skip_serializing_if = "is_false", default)]
+    truncated: bool,
+    #[serde(skip_serializing_if = "is_false", default)]
+    cancelled: bool
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializableTomlResult {
+    summary: SerializableSummary,
+    metadata: SerializableMetadata,
+    hosts: Vec<SerializableResultItem>
+}
+
+/**
+ * Transforms a single target detail to a structure that can be serialized for
+ * export. Missing hostnames and vendors are exported as empty strings.
+ */
+fn get_serializable_item(detail: &TargetDetails) -> SerializableResultItem {
+
+    let hostname = match &detail.hostname {
+        Some(hostname) => hostname.clone(),
+        None => String::from("")
+    };
+
+    let vendor = match &detail.vendor {
+        Some(vendor) => vendor.clone(),
+        None => String::from("")
+    };
+
+    SerializableResultItem {
+        ipv4: format!("{}", detail.ipv4),
+        mac: format!("{}", detail.mac),
+        frame_src_mac: detail.frame_src_mac.map(|frame_src_mac| frame_src_mac.to_string()),
+        hostname,
+        vendor,
+        response_time_ms: detail.response_time_ms,
+        reply_count: detail.reply_count,
+        interface: detail.interface.clone(),
+        vlan_id: detail.vlan_id,
+        status: detail.verify_status.map(|status| status.describe()),
+        origin: detail.origin.map(|origin| origin.describe()),
+        hostname_source: detail.hostname_source.map(|source| source.describe()),
+        hostname_ascii: detail.hostname_ascii.clone(),
+        mac_type: get_exported_mac_type(&detail.mac),
+        virtualization: detail.virtualization.clone(),
+        note: detail.note.clone()
+    }
+}
+
+/**
+ * Only MACs that are not universally administered get an explicit MAC type in
+ * exports, which tells why these hosts have no vendor.
+ */
+fn get_exported_mac_type(mac: &MacAddr) -> Option<String> {
+
+    match MacType::classify(mac) {
+        MacType::Universal => None,
+        mac_type => Some(mac_type.describe())
+    }
+}
+
+/**
+ * Transforms the scan metadata (start date, interface, ...) to a structure that
+ * can be serialized for export. The CLI version is added to the metadata.
+ */
+fn get_serializable_metadata(metadata: &ScanMetadata) -> SerializableMetadata {
+
+    let interface_mac = match metadata.interface_mac {
+        Some(mac) => format!("{}", mac),
+        None => String::from("")
+    };
+
+    SerializableMetadata {
+        started_at: metadata.started_at.to_rfc3339(),
+        interface_name: metadata.interface_name.clone(),
+        interface_mac,
+        source_ipv4: format!("{}", metadata.source_ipv4),
+        target_networks: metadata.target_networks.iter().map(|network| network.to_string()).collect(),
+        version: env!("CARGO_PKG_VERSION").to_string()
+    }
+}
+
+/**
+ * Transforms an ARP scan result (including KPI and target details) to a structure
+ * that can be serialized for export (JSON, YAML, CSV, ...). The scan metadata
+ * is only included for exports that carry scan-level details.
+ */
+fn get_serializable_result(response_summary: ResponseSummary, target_details: Vec<TargetDetails>, metadata: Option<&ScanMetadata>) -> SerializableGlobalResult {
+
+    let conflicts: Vec<SerializableConflict> = find_address_conflicts(&target_details).into_iter().map(|conflict| {
+        SerializableConflict {
+            ipv4: conflict.ipv4.to_string(),
+            vlan_id: conflict.vlan_id,
+            macs: conflict.macs.iter().map(|mac| mac.to_string()).collect()
+        }
+    }).collect();
+
+    let shared_macs: Vec<SerializableSharedMac> = find_shared_macs(&target_details).into_iter().map(|shared_mac| {
+        SerializableSharedMac {
+            mac: shared_mac.mac.to_string(),
+            ipv4s: shared_mac.ipv4s.iter().map(|ipv4| ipv4.to_string()).collect()
+        }
+    }).collect();
+
+    let exportable_results: Vec<SerializableResultItem> = target_details.iter()
+        .map(get_serializable_item)
+        .collect();
+    let unsolicited_hosts: Vec<SerializableResultItem> = response_summary.unsolicited_hosts.iter()
+        .map(get_serializable_item)
+        .collect();
+
+    SerializableGlobalResult {
+        schema_version: EXPORT_SCHEMA_VERSION,
+        packet_count: response_summary.packet_count,
+        arp_count: response_summary.arp_count,
+        requests_sent: response_summary.requests_sent,
+        send_errors: response_summary.send_errors,
+        duration_ms: response_summary.duration_ms,
+        results: exportable_results,
+        metadata: metadata.map(get_serializable_metadata),
+        truncated: response_summary.truncated,
+        cancelled: response_summary.cancelled,
+        conflicts,
+        shared_macs,
+        vendor_summary: vec![],
+        unsolicited_hosts,
+        hosts_by_mac: vec![]
+    }
+}
+
+/**
+ * The vendor breakdown is only exported when requested with '--top-vendors'.
+ */
+fn get_serializable_vendor_summary(target_details: &[TargetDetails], top_vendors: Option<usize>) -> Vec<SerializableVendorCount> {
+
+    match top_vendors {
+        Some(top_count) => limit_vendor_summary(compute_vendor_summary(target_details), top_count).into_iter().map(|(vendor, count)| {
+            SerializableVendorCount { vendor, count }
+        }).collect(),
+        None => vec![]
+    }
+}
+
+/**
+ * The hosts grouped by MAC address are only exported when requested with
+ * '--group-by-mac'.
+ */
+fn get_serializable_mac_groups(target_details: &[TargetDetails], group_by_mac: bool) -> Vec<SerializableMacGroup> {
+
+    match group_by_mac {
+        true => group_hosts_by_mac(target_details).into_iter().map(|mac_group| {
+            SerializableMacGroup {
+                mac: mac_group.mac.to_string(),
+                vendor: mac_group.vendor.unwrap_or_default(),
+                ipv4s: mac_group.ipv4s.iter().map(|ipv4| ipv4.to_string()).collect(),
+                hostnames: mac_group.hostnames
+            }
+        }).collect(),
+        false => vec![]
+    }
+}
+
+/**
+ * Export the scan results as a JSON string with response details (timings, ...)
+ * and ARP results from the local network. The JSON string is compact, unless
+ * the pretty-printed output has been requested.
+ */
+pub fn export_to_json(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata, pretty: bool, top_vendors: Option<usize>, group_by_mac: bool) -> Result<String, ExportError> {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let vendor_summary = get_serializable_vendor_summary(&target_details, top_vendors);
+    let hosts_by_mac = get_serializable_mac_groups(&target_details, group_by_mac);
+    let mut global_result = get_serializable_result(response_summary, target_details, Some(metadata));
+    global_result.vendor_summary = vendor_summary;
+    global_result.hosts_by_mac = hosts_by_mac;
+
+    let json_result = match pretty {
+        true => serde_json::to_string_pretty(&global_result),
+        false => serde_json::to_string(&global_result)
+    };
+
+    json_result.map_err(|err| ExportError(format!("Could not export JSON results ({})", err)))
+}
+
+/**
+ * Export the scan results as a YAML string with response details (timings, ...)
+ * and ARP results from the local network.
+ */
+pub fn export_to_yaml(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata, top_vendors: Option<usize>, group_by_mac: bool) -> Result<String, ExportError> {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let vendor_summary = get_serializable_vendor_summary(&target_details, top_vendors);
+    let hosts_by_mac = get_serializable_mac_groups(&target_details, group_by_mac);
+    let mut global_result = get_serializable_result(response_summary, target_details, Some(metadata));
+    global_result.vendor_summary = vendor_summary;
+    global_result.hosts_by_mac = hosts_by_mac;
+
+    serde_yaml::to_string(&global_result).map_err(|err| ExportError(format!("Could not export YAML results ({})", err)))
+}
+
+/**
+ * Export the scan results as a CSV string with ARP results from the local
+ * network. The header row is written first (if requested), even when no hosts
+ * have been found during the scan.
+ */
+pub fn export_to_csv(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, with_headers: bool, columns: &[OutputColumn], delimiter: u8, quote_all: bool) -> Result<String, ExportError> {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let quote_style = match quote_all {
+        true => csv::QuoteStyle::Always,
+        false => csv::QuoteStyle::Necessary
+    };
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .quote_style(quote_style)
+        .from_writer(vec![]);
+
+    // A 'conflict' column is only appended when IPv4 addresses have been
+    // answered by several MAC addresses, the CSV layout is unchanged otherwise.
+    let conflicting_ipv4s: Vec<(&String, Option<u16>)> = global_result.conflicts.iter().map(|conflict| (&conflict.ipv4, conflict.vlan_id)).collect();
+    let with_conflicts = !conflicting_ipv4s.is_empty();
+
+    if with_headers {
+        let mut headers: Vec<&str> = columns.iter().map(csv_column_header).collect();
+        if with_conflicts {
+            headers.push("conflict");
+        }
+        wtr.write_record(headers).map_err(|err| ExportError(format!("Could not write CSV headers ({})", err)))?;
+    }
+
+    for result in global_result.results {
+        let record = columns.iter().map(|column| match column {
+            OutputColumn::Ipv4 => result.ipv4.clone(),
+            OutputColumn::Mac => result.mac.clone(),
+            OutputColumn::FrameSrcMac => result.frame_src_mac.clone().unwrap_or_default(),
+            OutputColumn::Hostname => result.hostname.clone(),
+            OutputColumn::Vendor => result.vendor.clone(),
+            OutputColumn::ResponseTime => result.response_time_ms.map(|value| value.to_string()).unwrap_or_default(),
+            OutputColumn::ReplyCount => result.reply_count.to_string(),
+            OutputColumn::Interface => result.interface.clone(),
+            OutputColumn::Status => result.status.clone().unwrap_or_default(),
+            OutputColumn::Vlan => result.vlan_id.map(|vlan_id| vlan_id.to_string()).unwrap_or_default(),
+            OutputColumn::Origin => result.origin.clone().unwrap_or_default(),
+            OutputColumn::MacType => result.mac_type.clone().unwrap_or_else(|| MacType::Universal.describe()),
+            OutputColumn::Virtualization => result.virtualization.clone().unwrap_or_default(),
+            OutputColumn::Note => result.note.clone().unwrap_or_default()
+        });
+        let conflict_field = match with_conflicts {
+            true => Some(conflicting_ipv4s.contains(&(&result.ipv4, result.vlan_id)).to_string()),
+            false => None
+        };
+        wtr.write_record(record.chain(conflict_field)).map_err(|err| ExportError(format!("Could not serialize result to CSV ({})", err)))?;
+    }
+    wtr.flush().map_err(|err| ExportError(format!("Could not flush CSV writer buffer ({})", err)))?;
+
+    let convert_writer = wtr.into_inner().map_err(|err| ExportError(format!("Could not convert final CSV result ({})", err)))?;
+    String::from_utf8(convert_writer).map_err(|err| ExportError(format!("Could not convert final CSV result to text ({})", err)))
+}
+
+/**
+ * Export the response details (timings, packet counts, ...) and the scan
+ * metadata as a CSV string with a header row. This is used to keep the scan
+ * summary with CSV exports, target networks are separated by spaces.
+ */
+pub fn export_summary_to_csv(response_summary: &ResponseSummary, metadata: &ScanMetadata) -> Result<String, ExportError> {
+
+    let serializable_metadata = get_serializable_metadata(metadata);
+
+    let mut wtr = csv::Writer::from_writer(vec![]);
+    wtr.write_record(["packet_count", "arp_count", "requests_sent", "send_errors", "duration_ms", "started_at", "interface_name", "interface_mac", "source_ipv4", "target_networks", "version"]).map_err(|err| ExportError(format!("Could not write CSV summary headers ({})", err)))?;
+    wtr.write_record([
+        response_summary.packet_count.to_string(),
+        response_summary.arp_count.to_string(),
+        response_summary.requests_sent.to_string(),
+        response_summary.send_errors.to_string(),
+        response_summary.duration_ms.to_string(),
+        serializable_metadata.started_at,
+        serializable_metadata.interface_name,
+        serializable_metadata.interface_mac,
+        serializable_metadata.source_ipv4,
+        serializable_metadata.target_networks.join(" "),
+        serializable_metadata.version
+    ]).map_err(|err| ExportError(format!("Could not serialize summary to CSV ({})", err)))?;
+
+    let convert_writer = wtr.into_inner().map_err(|err| ExportError(format!("Could not convert final CSV summary ({})", err)))?;
+    String::from_utf8(convert_writer).map_err(|err| ExportError(format!("Could not convert final CSV summary to text ({})", err)))
+}
+
+/**
+ * Export the scan results as a XML string with response details (timings, ...)
+ * and ARP results from the local network. Each result is exported as a 'host'
+ * element, where empty fields are kept as empty elements.
+ */
+pub fn export_to_xml(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata) -> Result<String, ExportError> {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, Some(metadata));
+
+    let xml_result = SerializableXmlResult {
+        packet_count: global_result.packet_count,
+        arp_count: global_result.arp_count,
+        requests_sent: global_result.requests_sent,
+        send_errors: global_result.send_errors,
+        duration_ms: global_result.duration_ms,
+        truncated: global_result.truncated,
+        cancelled: global_result.cancelled,
+        metadata: get_serializable_metadata(metadata),
+        hosts: global_result.results.into_iter().map(|result| {
+            SerializableXmlHost {
+                ipv4: result.ipv4,
+                mac: result.mac,
+                frame_src_mac: result.frame_src_mac,
+                hostname: result.hostname,
+                hostname_ascii: result.hostname_ascii,
+                vendor: result.vendor,
+                response_time_ms: result.response_time_ms.map(|value| value.to_string()).unwrap_or_default(),
+                reply_count: result.reply_count,
+                interface: result.interface,
+                vlan_id: result.vlan_id,
+                origin: result.origin,
+                mac_type: result.mac_type,
+                virtualization: result.virtualization,
+                note: result.note
+            }
+        }).collect()
+    };
+
+    quick_xml::se::to_string(&xml_result).map_err(|err| ExportError(format!("Could not export XML results ({})", err)))
+}
+
+/**
+ * Export a single target detail as a one-line JSON string. This is used by the
+ * NDJSON output, where each host is printed as soon as it is discovered.
+ */
+pub fn export_host_to_ndjson(target_detail: &TargetDetails) -> Result<String, ExportError> {
+
+    let result_item = get_serializable_item(target_detail);
+
+    serde_json::to_string(&result_item).map_err(|err| ExportError(format!("Could not export NDJSON result ({})", err)))
+}
+
+/**
+ * Export the scan results as NDJSON, with one JSON line per host followed by
+ * a JSON summary line. This is used when hosts could not be streamed.
+ */
+pub fn export_to_ndjson(response_summary: ResponseSummary, target_details: Vec<TargetDetails>) -> Result<String, ExportError> {
+
+    let mut lines = String::new();
+    for target_detail in target_details.iter() {
+        lines.push_str(&export_host_to_ndjson(target_detail)?);
+        lines.push('\n');
+    }
+    lines.push_str(&export_summary_to_ndjson(response_summary)?);
+    lines.push('\n');
+
+    Ok(lines)
+}
+
+/**
+ * Export the response details (timings, ...) as a one-line JSON string. This
+ * will be the last line of a NDJSON output, after all discovered hosts.
+ */
+pub fn export_summary_to_ndjson(response_summary: ResponseSummary) -> Result<String, ExportError> {
+
+    let summary = SerializableSummary {
+        packet_count: response_summary.packet_count,
+        arp_count: response_summary.arp_count,
+        requests_sent: response_summary.requests_sent,
+        send_errors: response_summary.send_errors,
+        duration_ms: response_summary.duration_ms,
+        truncated: response_summary.truncated,
+        cancelled: response_summary.cancelled
+    };
+
+    serde_json::to_string(&summary).map_err(|err| ExportError(format!("Could not export NDJSON summary ({})", err)))
+}
+
+const HTML_REPORT_STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #ccc; padding: 6px 10px; text-align: left; }
+th { background: #f0f0f0; cursor: pointer; user-select: none; }
+tr:nth-child(even) { background: #fafafa; }";
+
+const HTML_REPORT_SCRIPT: &str = "document.querySelectorAll('th').forEach(function (header, index) {
+  header.addEventListener('click', function () {
+    var body = header.closest('table').querySelector('tbody');
+    var ascending = header.dataset.order !== 'asc';
+    header.dataset.order = ascending ? 'asc' : 'desc';
+    var key = function (row) {
+      var text = row.children[index].textContent;
+      return index === 0 ? text.split('.').map(function (part) { return part.padStart(3, '0'); }).join('.') : text.toLowerCase();
+    };
+    Array.from(body.rows)
+      .sort(function (a, b) { return ascending ? key(a).localeCompare(key(b)) : key(b).localeCompare(key(a)); })
+      .forEach(function (row) { body.appendChild(row); });
+  });
+});";
+
+/**
+ * Escape a text for a safe insertion in HTML content or attributes. Hostnames
+ * and vendors come from the network and should never be trusted.
+ */
+fn escape_html(text: &str) -> String {
+
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character)
+        }
+    }
+    escaped
+}
+
+/**
+ * Export the scan results as a standalone HTML report with scan details (date,
+ * interface, duration) and a sortable table of ARP results. The style and the
+ * scripts are embedded so that the report can be opened offline.
+ */
+pub fn export_to_html(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+    let scan_date = metadata.started_at.format("%Y-%m-%d %H:%M:%S");
+    let seconds_duration = (global_result.duration_ms as f32) / (1000_f32);
+
+    let mut rows = String::new();
+    for result in global_result.results.iter() {
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&result.ipv4), escape_html(&result.mac), escape_html(&result.hostname), escape_html(&result.vendor)
+        ));
+    }
+
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>ARP scan report</title>
+<style>
+{style}
+</style>
+</head>
+<body>
+<h1>ARP scan report</h1>
+<ul>
+<li>Scan date: {date}</li>
+<li>Interface: {interface}</li>
+<li>Duration: {duration:.3} seconds</li>
+<li>Hosts found: {host_count}</li>
+<li>Packets received: {packet_count} ({arp_count} ARP packets)</li>
+<li>Requests sent: {requests_sent} ({send_errors} send errors)</li>
+</ul>
+<table>
+<thead><tr><th>IPv4</th><th>MAC</th><th>Hostname</th><th>Vendor</th></tr></thead>
+<tbody>
+{rows}</tbody>
+</table>
+<script>
+{script}
+</script>
+</body>
+</html>
+",
+        style = HTML_REPORT_STYLE,
+        date = scan_date,
+        interface = escape_html(&metadata.interface_name),
+        duration = seconds_duration,
+        host_count = global_result.results.len(),
+        packet_count = global_result.packet_count,
+        requests_sent = global_result.requests_sent,
+        send_errors = global_result.send_errors,
+        arp_count = global_result.arp_count,
+        rows = rows,
+        script = HTML_REPORT_SCRIPT
+    )
+}
+
+/**
+ * Export the scan results as a Markdown table (GitHub-flavored) followed by a
+ * short summary line. Pipe characters are escaped to keep the table valid.
+ */
+pub fn export_to_markdown(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let rows: Vec<Vec<String>> = global_result.results.iter().map(|result| {
+        vec![
+            result.ipv4.clone(),
+            result.mac.clone(),
+            result.hostname.replace('|', "\\|"),
+            result.vendor.replace('|', "\\|")
+        ]
+    }).collect();
+
+    let mut markdown = String::new();
+    if !rows.is_empty() {
+        markdown.push_str(&format_table(&[OutputColumn::Ipv4, OutputColumn::Mac, OutputColumn::Hostname, OutputColumn::Vendor], &rows, &[]));
+        markdown.push('\n');
+    }
+
+    let target_count = rows.len();
+    let seconds_duration = (global_result.duration_ms as f32) / (1000_f32);
+    match target_count {
+        0 => markdown.push_str("No hosts found"),
+        1 => markdown.push_str("1 host found"),
+        _ => markdown.push_str(&format!("{} hosts found", target_count))
+    }
+    markdown.push_str(&format!(" in {:.3} seconds\n", seconds_duration));
+
+    markdown
+}
+
+/**
+ * Escape a Prometheus label value, as defined by the text exposition format
+ * (backslashes, double-quotes and line feeds must be escaped).
+ */
+fn escape_prometheus_label(value: &str) -> String {
+
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/**
+ * Export the scan results with the Prometheus text exposition format. This
+ * output can be consumed by the node_exporter textfile collector, with global
+ * scan metrics and an 'arp_scan_host_up' series for each discovered host.
+ */
+pub fn export_to_prometheus(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let global_metrics: [(&str, &str, u128); 6] = [
+        ("arp_scan_hosts_found", "Number of hosts found by the ARP scan", global_result.results.len() as u128),
+        ("arp_scan_duration_ms", "Duration of the ARP scan in milliseconds", global_result.duration_ms),
+        ("arp_scan_packets_received", "Number of packets received during the ARP scan", global_result.packet_count as u128),
+        ("arp_scan_arp_packets", "Number of ARP packets received during the ARP scan", global_result.arp_count as u128),
+        ("arp_scan_requests_sent", "Number of ARP requests sent during the ARP scan (with retries)", global_result.requests_sent as u128),
+        ("arp_scan_send_errors", "Number of ARP requests that could not be sent", global_result.send_errors as u128)
+    ];
+
+    let mut metrics = String::new();
+    for (name, help, value) in global_metrics.iter() {
+        metrics.push_str(&format!("# HELP {} {}\n", name, help));
+        metrics.push_str(&format!("# TYPE {} gauge\n", name));
+        metrics.push_str(&format!("{} {}\n", name, value));
+    }
+
+    metrics.push_str("# HELP arp_scan_host_up Host answering to ARP requests\n");
+    metrics.push_str("# TYPE arp_scan_host_up gauge\n");
+    for result in global_result.results.iter() {
+        metrics.push_str(&format!(
+            "arp_scan_host_up{{ipv4=\"{}\",mac=\"{}\",hostname=\"{}\",vendor=\"{}\"}} 1\n",
+            escape_prometheus_label(&result.ipv4),
+            escape_prometheus_label(&result.mac),
+            escape_prometheus_label(&result.hostname),
+            escape_prometheus_label(&result.vendor)
+        ));
+    }
+
+    metrics
+}
+
+// Columns of the 'scans' table added after its creation, with their SQL
+// definition.
+#[cfg(feature = "sqlite")]
+const SQLITE_ADDED_SCAN_COLUMNS: [(&str, &str); 2] = [
+    ("requests_sent", "INTEGER NOT NULL DEFAULT 0"),
+    ("send_errors", "INTEGER NOT NULL DEFAULT 0")
+];
+
+/**
+ * Append the scan results to a SQLite database, which is created if needed. A
+ * new row is added in the 'scans' table for each run, while all discovered
+ * hosts are stored in the 'hosts' table (linked to the scan row).
+ */
+#[cfg(feature = "sqlite")]
+pub fn export_to_sqlite(database_path: &str, metadata: &ScanMetadata, response_summary: &ResponseSummary, target_details: &[TargetDetails]) -> Result<(), ExportError> {
+
+    let mut connection = Connection::open(database_path).map_err(|err| {
+        ExportError(format!("Could not open SQLite database {} ({})", database_path, err))
+    })?;
+
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS scans (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at TEXT NOT NULL,
+            interface TEXT NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            packet_count INTEGER NOT NULL,
+            arp_count INTEGER NOT NULL,
+            requests_sent INTEGER NOT NULL DEFAULT 0,
+            send_errors INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS hosts (
+            scan_id INTEGER NOT NULL REFERENCES scans(id),
+            ipv4 TEXT NOT NULL,
+            mac TEXT NOT NULL,
+            hostname TEXT NOT NULL,
+            vendor TEXT NOT NULL
+        );"
+    ).map_err(|err| ExportError(format!("Could not create SQLite tables in {} ({})", database_path, err)))?;
+
+    // Databases created by previous versions are migrated with the columns
+    // added since then (existing scans keep the default values).
+    for (column_name, column_definition) in SQLITE_ADDED_SCAN_COLUMNS {
+        let has_column = connection.prepare("SELECT 1 FROM pragma_table_info('scans') WHERE name = ?1")
+            .and_then(|mut statement| statement.exists(params![column_name]))
+            .map_err(|err| ExportError(format!("Could not read SQLite table columns in {} ({})", database_path, err)))?;
+        if !has_column {
+            connection.execute_batch(&format!("ALTER TABLE scans ADD COLUMN {} {};", column_name, column_definition))
+                .map_err(|err| ExportError(format!("Could not migrate SQLite tables in {} ({})", database_path, err)))?;
+        }
+    }
+
+    let transaction = connection.transaction().map_err(|err| {
+        ExportError(format!("Could not start SQLite transaction ({})", err))
+    })?;
+
+    let duration_ms = i64::try_from(response_summary.duration_ms).unwrap_or(i64::MAX);
+    let packet_count = i64::try_from(response_summary.packet_count).unwrap_or(i64::MAX);
+    let arp_count = i64::try_from(response_summary.arp_count).unwrap_or(i64::MAX);
+    let requests_sent = i64::try_from(response_summary.requests_sent).unwrap_or(i64::MAX);
+    let send_errors = i64::try_from(response_summary.send_errors).unwrap_or(i64::MAX);
+    transaction.execute(
+        "INSERT INTO scans (started_at, interface, duration_ms, packet_count, arp_count, requests_sent, send_errors) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![metadata.started_at.to_rfc3339(), metadata.interface_name, duration_ms, packet_count, arp_count, requests_sent, send_errors]
+    ).map_err(|err| ExportError(format!("Could not insert scan in SQLite database ({})", err)))?;
+    let scan_id = transaction.last_insert_rowid();
+
+    for detail in target_details.iter() {
+        let item = get_serializable_item(detail);
+        transaction.execute(
+            "INSERT INTO hosts (scan_id, ipv4, mac, hostname, vendor) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![scan_id, item.ipv4, item.mac, item.hostname, item.vendor]
+        ).map_err(|err| ExportError(format!("Could not insert host in SQLite database ({})", err)))?;
+    }
+
+    transaction.commit().map_err(|err| {
+        ExportError(format!("Could not commit SQLite transaction ({})", err))
+    })
+}
+
+/**
+ * Export the scan results as a minimal nmap-compatible XML document (similar to
+ * the 'nmap -oX' output). Only the host addresses, hostnames and run stats are
+ * exported, which is enough for common nmap XML parsers.
+ */
+pub fn export_to_nmap_xml(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let start_timestamp = metadata.started_at.timestamp();
+    let duration_ms = i64::try_from(global_result.duration_ms).unwrap_or(i64::MAX);
+    let end_timestamp = start_timestamp.saturating_add(duration_ms / 1000);
+    let format_timestamp = |timestamp: i64| {
+        chrono::DateTime::from_timestamp(timestamp, 0)
+            .map(|date| date.format("%a %b %e %H:%M:%S %Y").to_string())
+            .unwrap_or_default()
+    };
+    let seconds_duration = (global_result.duration_ms as f32) / (1000_f32);
+    let host_count = global_result.results.len();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<!DOCTYPE nmaprun>\n");
+    xml.push_str(&format!(
+        "<nmaprun scanner=\"arp-scan\" args=\"arp-scan\" start=\"{}\" startstr=\"{}\" version=\"{}\" xmloutputversion=\"1.05\">\n",
+        start_timestamp, format_timestamp(start_timestamp), env!("CARGO_PKG_VERSION")
+    ));
+
+    for result in global_result.results.iter() {
+        xml.push_str(&format!("<host starttime=\"{}\" endtime=\"{}\">", start_timestamp, end_timestamp));
+        xml.push_str("<status state=\"up\" reason=\"arp-response\" reason_ttl=\"0\"/>\n");
+        xml.push_str(&format!("<address addr=\"{}\" addrtype=\"ipv4\"/>\n", escape_html(&result.ipv4)));
+        match result.vendor.is_empty() {
+            true => xml.push_str(&format!("<address addr=\"{}\" addrtype=\"mac\"/>\n", escape_html(&result.mac.to_uppercase()))),
+            false => xml.push_str(&format!("<address addr=\"{}\" addrtype=\"mac\" vendor=\"{}\"/>\n", escape_html(&result.mac.to_uppercase()), escape_html(&result.vendor)))
+        }
+        match result.hostname.is_empty() {
+            true => xml.push_str("<hostnames>\n</hostnames>\n"),
+            false => xml.push_str(&format!("<hostnames>\n<hostname name=\"{}\" type=\"PTR\"/>\n</hostnames>\n", escape_html(&result.hostname)))
+        }
+        xml.push_str("</host>\n");
+    }
+
+    let host_text = match host_count {
+        1 => "1 host".to_string(),
+        _ => format!("{} hosts", host_count)
+    };
+    xml.push_str("<runstats>");
+    xml.push_str(&format!(
+        "<finished time=\"{}\" timestr=\"{}\" summary=\"ARP scan done at {}; {} up scanned in {:.2} seconds\" elapsed=\"{:.2}\" exit=\"success\"/>",
+        end_timestamp, format_timestamp(end_timestamp), format_timestamp(end_timestamp), host_text, seconds_duration, seconds_duration
+    ));
+    xml.push_str(&format!("<hosts up=\"{}\" down=\"0\" total=\"{}\"/>\n", host_count, host_count));
+    xml.push_str("</runstats>\n");
+    xml.push_str("</nmaprun>\n");
+
+    xml
+}
+
+/**
+ * Export the scan results as MessagePack bytes with response details (timings,
+ * ...) and ARP results from the local network. The structure is the same as
+ * the JSON export, with a more compact binary representation.
+ */
+pub fn export_to_msgpack(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata) -> Result<Vec<u8>, ExportError> {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, Some(metadata));
+
+    rmp_serde::to_vec_named(&global_result).map_err(|err| ExportError(format!("Could not export MessagePack results ({})", err)))
+}
+
+/**
+ * Export the scan results as a TOML string, with a 'summary' table for the
+ * response details (timings, ...) and a 'hosts' array of tables for the ARP
+ * results from the local network.
+ */
+pub fn export_to_toml(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata) -> Result<String, ExportError> {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, Some(metadata));
+
+    let toml_result = SerializableTomlResult {
+        summary: SerializableSummary {
+            packet_count: global_result.packet_count,
+            arp_count: global_result.arp_count,
+            requests_sent: global_result.requests_sent,
+            send_errors: global_result.send_errors,
+            duration_ms: global_result.duration_ms,
+            truncated: global_result.truncated,
+            cancelled: global_result.cancelled
+        },
+        metadata: get_serializable_metadata(metadata),
+        hosts: global_result.results
+    };
+
+    toml::to_string(&toml_result).map_err(|err| ExportError(format!("Could not export TOML results ({})", err)))
+}
+
+/**
+ * Write an output to the given file path, or to stdout if no path is given
+ * (or if the path is '-'). The file is first written to a temporary file and
+ * then renamed, which avoids leaving truncated files on failures.
+ */
+pub fn write_output(file_path: Option<&str>, content: &[u8]) -> Result<(), String> {
+
+    let file_path = match file_path {
+        Some(path) if path != "-" => path,
+        _ => {
+            let mut stdout = io::stdout();
+            return stdout.write_all(content).and_then(|_| stdout.flush()).map_err(|err| {
+                format!("Could not write output to stdout ({})", err)
+            });
+        }
+    };
+
+    let final_path = Path::new(file_path);
+    let file_name = final_path.file_name().ok_or(format!("Expected a file name in output path {}", file_path))?;
+    let temporary_path = final_path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), process::id()));
+
+    fs::write(&temporary_path, content).map_err(|err| {
+        format!("Could not write output file {} ({})", file_path, err)
+    })?;
+
+    fs::rename(&temporary_path, final_path).map_err(|err| {
+        let _ = fs::remove_file(&temporary_path);
+        format!("Could not write output file {} ({})", file_path, err)
+    })
+}
+
+/**
+ * Export the scan results with a greppable format (similar to 'nmap -oG'), with
+ * one line per host and a final summary line. Missing hostnames and vendors
+ * are kept as empty parentheses, so that fields always have the same position.
+ */
+pub fn export_to_grep(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let mut lines = String::new();
+    for result in global_result.results.iter() {
+        lines.push_str(&format!("Host: {} ({}) MAC: {} ({})\n", result.ipv4, result.hostname, result.mac, result.vendor));
+    }
+    lines.push_str(&format!(
+        "# Summary: {} hosts found, {} packets received, {} ARP packets filtered, {} requests sent, {} send errors, {} ms\n",
+        global_result.results.len(), global_result.packet_count, global_result.arp_count, global_result.requests_sent, global_result.send_errors, global_result.duration_ms
+    ));
+
+    lines
+}
+
+/**
+ * Export the scan results as a JUnit XML report, with a test case for each
+ * discovered host. If an allowlist is given (IPv4 or MAC addresses), hosts
+ * outside of the allowlist are failures, as well as allowed hosts that were not
+ * found during the scan.
+ */
+pub fn export_to_junit(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, allowlist: Option<&[String]>) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+    let seconds_duration = (global_result.duration_ms as f32) / (1000_f32);
+
+    let is_allowed = |result: &SerializableResultItem| {
+        allowlist.map(|entries| entries.iter().any(|entry| *entry == result.ipv4 || *entry == result.mac)).unwrap_or(true)
+    };
+
+    let mut test_count = 0;
+    let mut failure_count = 0;
+    let mut test_cases = String::new();
+
+    for result in global_result.results.iter() {
+
+        let name = match result.hostname.is_empty() {
+            true => format!("{} ({})", result.ipv4, result.mac),
+            false => format!("{} {} ({})", result.ipv4, result.hostname, result.mac)
+        };
+
+        test_count += 1;
+        match is_allowed(result) {
+            true => test_cases.push_str(&format!("<testcase classname=\"arp-scan.hosts\" name=\"{}\" time=\"0\"/>\n", escape_html(&name))),
+            false => {
+                failure_count += 1;
+                test_cases.push_str(&format!(
+                    "<testcase classname=\"arp-scan.hosts\" name=\"{}\" time=\"0\"><failure type=\"unexpected\" message=\"Host is not in the allowlist\">Vendor: {}</failure></testcase>\n",
+                    escape_html(&name), escape_html(&result.vendor)
+                ));
+            }
+        }
+    }
+
+    for entry in allowlist.unwrap_or(&[]) {
+
+        let is_found = global_result.results.iter().any(|result| *entry == result.ipv4 || *entry == result.mac);
+        if !is_found {
+            test_count += 1;
+            failure_count += 1;
+            test_cases.push_str(&format!(
+                "<testcase classname=\"arp-scan.expected\" name=\"{}\" time=\"0\"><failure type=\"missing\" message=\"Expected host was not found\"/></testcase>\n",
+                escape_html(entry)
+            ));
+        }
+    }
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<testsuites name=\"arp-scan\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n", test_count, failure_count, seconds_duration));
+    xml.push_str(&format!("<testsuite name=\"arp-scan\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">\n", test_count, failure_count, seconds_duration));
+    xml.push_str(&test_cases);
+    xml.push_str("</testsuite>\n");
+    xml.push_str("</testsuites>\n");
+
+    xml
+}
+
+/**
+ * Build a DOT node identifier from any text (MAC addresses, networks, ...), all
+ * characters that are not allowed in DOT identifiers are replaced.
+ */
+fn sanitize_dot_identifier(prefix: &str, text: &str) -> String {
+
+    let sanitized: String = text.chars().map(|character| match character.is_ascii_alphanumeric() {
+        true => character,
+        false => '_'
+    }).collect();
+    format!("{}_{}", prefix, sanitized)
+}
+
+fn escape_dot_label(text: &str) -> String {
+
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " ")
+}
+
+/**
+ * Export the scan results as a Graphviz DOT graph, with the scanning host linked
+ * to each discovered host. If multiple networks were scanned, hosts are grouped
+ * in subgraphs based on their /24 network.
+ */
+pub fn export_to_dot(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let scanner_id = "scanner";
+    let scanner_label = format!("{}\\n{}", escape_dot_label(&metadata.interface_name), metadata.source_ipv4);
+
+    let mut dot = String::new();
+    dot.push_str("graph arp_scan {\n");
+    dot.push_str(&format!("  {} [label=\"{}\", shape=box];\n", scanner_id, scanner_label));
+
+    let build_node = |result: &SerializableResultItem| {
+        let mut label_parts = vec![escape_dot_label(&result.ipv4), escape_dot_label(&result.mac)];
+        if !result.hostname.is_empty() {
+            label_parts.push(escape_dot_label(&result.hostname));
+        }
+        if !result.vendor.is_empty() {
+            label_parts.push(escape_dot_label(&result.vendor));
+        }
+        format!("{} [label=\"{}\"];\n", sanitize_dot_identifier("mac", &result.mac), label_parts.join("\\n"))
+    };
+
+    match metadata.target_networks.len() > 1 {
+        true => {
+            let mut networks: Vec<(String, Vec<&SerializableResultItem>)> = vec![];
+            for result in global_result.results.iter() {
+                let network = match result.ipv4.parse::<Ipv4Addr>() {
+                    Ok(ipv4) => {
+                        let octets = ipv4.octets();
+                        format!("{}.{}.{}.0/24", octets[0], octets[1], octets[2])
+                    },
+                    Err(_) => String::from("unknown")
+                };
+                match networks.iter_mut().find(|(name, _)| *name == network) {
+                    Some((_, results)) => results.push(result),
+                    None => networks.push((network, vec![result]))
+                }
+            }
+            for (network, results) in networks.iter() {
+                dot.push_str(&format!("  subgraph {} {{\n", sanitize_dot_identifier("cluster", network)));
+                dot.push_str(&format!("    label=\"{}\";\n", escape_dot_label(network)));
+                for result in results.iter() {
+                    dot.push_str(&format!("    {}", build_node(result)));
+                }
+                dot.push_str("  }\n");
+            }
+        },
+        false => {
+            for result in global_result.results.iter() {
+                dot.push_str(&format!("  {}", build_node(result)));
+            }
+        }
+    }
+
+    for result in global_result.results.iter() {
+        dot.push_str(&format!("  {} -- {};\n", scanner_id, sanitize_dot_identifier("mac", &result.mac)));
+    }
+    dot.push_str("}\n");
+
+    dot
+}
+
+/**
+ * Ansible group names may only contain letters, digits and underscores, and
+ * should not start with a digit (hence the prefix).
+ */
+fn sanitize_ansible_group(prefix: &str, text: &str) -> String {
+
+    let sanitized: String = text.to_lowercase().chars().map(|character| match character.is_ascii_alphanumeric() {
+        true => character,
+        false => '_'
+    }).collect();
+    let sanitized = sanitized.split('_').filter(|part| !part.is_empty()).collect::<Vec<&str>>().join("_");
+    match sanitized.is_empty() {
+        true => format!("{}_unknown", prefix),
+        false => format!("{}_{}", prefix, sanitized)
+    }
+}
+
+/**
+ * Export the scan results as an Ansible YAML inventory. Each host is keyed by
+ * its hostname (or IPv4 if not resolved), and hosts sharing the same name are
+ * disambiguated with the last octet of their IPv4. Hosts can be grouped by
+ * vendor or by scanned subnet.
+ */
+pub fn export_to_ansible(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata, group_by: Option<&AnsibleGrouping>) -> Result<String, ExportError> {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let names: Vec<String> = global_result.results.iter().map(|result| {
+        match result.hostname.is_empty() {
+            true => result.ipv4.clone(),
+            false => result.hostname.clone()
+        }
+    }).collect();
+
+    let mut groups: Vec<(String, serde_yaml::Mapping)> = vec![];
+    for (index, result) in global_result.results.iter().enumerate() {
+
+        let is_duplicate = names.iter().filter(|name| **name == names[index]).count() > 1;
+        let name = match (is_duplicate, result.ipv4.parse::<Ipv4Addr>()) {
+            (true, Ok(ipv4)) => format!("{}-{}", names[index], ipv4.octets()[3]),
+            _ => names[index].clone()
+        };
+
+        let mut host_vars = serde_yaml::Mapping::new();
+        host_vars.insert("ansible_host".into(), result.ipv4.clone().into());
+        host_vars.insert("mac".into(), result.mac.clone().into());
+        if !result.vendor.is_empty() {
+            host_vars.insert("vendor".into(), result.vendor.clone().into());
+        }
+        if let Some(note) = &result.note {
+            host_vars.insert("note".into(), note.clone().into());
+        }
+
+        let group = match group_by {
+            Some(AnsibleGrouping::Vendor) => sanitize_ansible_group("vendor", &result.vendor),
+            Some(AnsibleGrouping::Subnet) => {
+                let network = result.ipv4.parse::<Ipv4Addr>().ok().and_then(|ipv4| {
+                    metadata.target_networks.iter().find(|network| network.contains(ipv4.into()))
+                });
+                match network {
+                    Some(network) => sanitize_ansible_group("subnet", &network.to_string()),
+                    None => sanitize_ansible_group("subnet", "")
+                }
+            },
+            None => String::from("all")
+        };
+        match groups.iter_mut().find(|(group_name, _)| *group_name == group) {
+            Some((_, hosts)) => { hosts.insert(name.into(), host_vars.into()); },
+            None => {
+                let mut hosts = serde_yaml::Mapping::new();
+                hosts.insert(name.into(), host_vars.into());
+                groups.push((group, hosts));
+            }
+        }
+    }
+
+    let build_group = |hosts: serde_yaml::Mapping| {
+        let mut group = serde_yaml::Mapping::new();
+        group.insert("hosts".into(), hosts.into());
+        serde_yaml::Value::from(group)
+    };
+
+    let all_group = match group_by {
+        Some(_) => {
+            let mut children = serde_yaml::Mapping::new();
+            for (group_name, hosts) in groups {
+                children.insert(group_name.into(), build_group(hosts));
+            }
+            let mut all_group = serde_yaml::Mapping::new();
+            all_group.insert("children".into(), children.into());
+            serde_yaml::Value::from(all_group)
+        },
+        None => build_group(groups.pop().map(|(_, hosts)| hosts).unwrap_or_default())
+    };
+
+    let mut inventory = serde_yaml::Mapping::new();
+    inventory.insert("all".into(), all_group);
+
+    let mut yaml = serde_yaml::to_string(&inventory).map_err(|err| ExportError(format!("Could not export Ansible inventory ({})", err)))?;
+    yaml.push('\n');
+    Ok(yaml)
+}
+
+/**
+ * Sanitize a hostname to valid DNS label characters (letters, digits and
+ * hyphens), labels are lowercased and empty labels are removed.
+ */
+fn sanitize_hostname(hostname: &str) -> String {
+
+    hostname.split('.').map(|label| {
+        let sanitized: String = label.to_lowercase().chars().map(|character| match character.is_ascii_alphanumeric() {
+            true => character,
+            false => '-'
+        }).collect();
+        sanitized.trim_matches('-').to_string()
+    }).filter(|label| !label.is_empty()).collect::<Vec<String>>().join(".")
+}
+
+/**
+ * Export the scan results as '/etc/hosts' lines ('ipv4<TAB>hostname'), hosts
+ * without a resolved hostname are skipped. Internationalized hostnames are
+ * written in their ASCII form, as resolvers expect.
+ */
+pub fn export_to_hosts(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let mut lines = String::new();
+    for result in global_result.results.iter() {
+        let hostname = sanitize_hostname(result.hostname_ascii.as_deref().unwrap_or(&result.hostname));
+        if !hostname.is_empty() {
+            lines.push_str(&format!("{}\t{}\n", result.ipv4, hostname));
+        }
+    }
+    lines
+}
+
+/**
+ * Export the scan results as dnsmasq static leases ('dhcp-host=MAC,IP,NAME').
+ * Hosts without a resolved hostname are written without name, unless they
+ * should be skipped with the 'only_resolved' flag. Internationalized
+ * hostnames are written in their ASCII form.
+ */
+pub fn export_to_dnsmasq(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, only_resolved: bool) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let mut lines = String::new();
+    for result in global_result.results.iter() {
+        let hostname = sanitize_hostname(result.hostname_ascii.as_deref().unwrap_or(&result.hostname));
+        match (hostname.is_empty(), only_resolved) {
+            (true, true) => {},
+            (true, false) => lines.push_str(&format!("dhcp-host={},{}\n", result.mac, result.ipv4)),
+            (false, _) => lines.push_str(&format!("dhcp-host={},{},{}\n", result.mac, result.ipv4, hostname))
+        }
+    }
+    lines
+}
+
+/**
+ * Escape an InfluxDB line protocol tag value (commas, equal signs and spaces
+ * must be escaped with a backslash).
+ */
+fn escape_influx_tag(value: &str) -> String {
+
+    value.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+/**
+ * Export the scan results with the InfluxDB line protocol: one 'arp_host' point
+ * per host and a final 'arp_scan' point with the summary. All points use the
+ * scan start date as timestamp (in nanoseconds). Empty tags are omitted, as
+ * they are not allowed by the protocol.
+ */
+pub fn export_to_influx(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, metadata: &ScanMetadata) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let timestamp = metadata.started_at.timestamp_nanos_opt().unwrap_or(0);
+
+    let mut lines = String::new();
+    for result in global_result.results.iter() {
+        let tags = [("hostname", &result.hostname), ("ipv4", &result.ipv4), ("mac", &result.mac), ("vendor", &result.vendor)];
+        let tag_set: String = tags.iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| format!(",{}={}", key, escape_influx_tag(value)))
+            .collect();
+        lines.push_str(&format!("arp_host{} up=1i {}\n", tag_set, timestamp));
+    }
+    lines.push_str(&format!(
+        "arp_scan hosts_found={}i,duration_ms={}i,packets_received={}i,requests_sent={}i,send_errors={}i {}\n",
+        global_result.results.len(), global_result.duration_ms, global_result.packet_count, global_result.requests_sent, global_result.send_errors, timestamp
+    ));
+
+    lines
+}
+
+pub const HOST_TEMPLATE_FIELDS: [&str; 7] = ["ipv4", "mac", "hostname", "vendor", "response_time_ms", "interface", "note"];
+pub const SUMMARY_TEMPLATE_FIELDS: [&str; 6] = ["duration_ms", "host_count", "packet_count", "arp_count", "requests_sent", "send_errors"];
+
+/**
+ * An output template is parsed into text parts and field placeholders, so that
+ * all placeholders can be validated before the scan starts.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplatePart {
+    Text(String),
+    Field(String)
+}
+
+/**
+ * Parse a user-defined output template such as '{ipv4}\t{mac}'. The '\t', '\n'
+ * and '\\' escape sequences are supported, while literal braces are written
+ * '{{' and '}}'. Placeholders must be part of the allowed fields.
+ */
+pub fn parse_template(template: &str, allowed_fields: &[&str]) -> Result<Vec<TemplatePart>, String> {
+
+    let mut parts: Vec<TemplatePart> = vec![];
+    let mut text = String::new();
+    let mut characters = template.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some('\\') => text.push('\\'),
+                Some(other) => return Err(format!("Unknown escape sequence \\{} in template", other)),
+                None => return Err("Unfinished escape sequence in template".to_string())
+            },
+            '{' if characters.peek() == Some(&'{') => {
+                characters.next();
+                text.push('{');
+            },
+            '}' if characters.peek() == Some(&'}') => {
+                characters.next();
+                text.push('}');
+            },
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match characters.next() {
+                        Some('}') => break,
+                        Some(character) => field.push(character),
+                        None => return Err(format!("Unclosed placeholder {{{} in template", field))
+                    }
+                }
+                if !allowed_fields.contains(&field.as_str()) {
+                    return Err(format!("Unknown placeholder {{{}}} in template (expected {})", field, allowed_fields.join(", ")));
+                }
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(text.clone()));
+                    text.clear();
+                }
+                parts.push(TemplatePart::Field(field));
+            },
+            '}' => return Err("Unexpected '}' in template, use '}}' for a literal brace".to_string()),
+            _ => text.push(character)
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Ok(parts)
+}
+
+fn render_template(parts: &[TemplatePart], field_value: impl Fn(&str) -> String) -> String {
+
+    parts.iter().map(|part| match part {
+        TemplatePart::Text(text) => text.clone(),
+        TemplatePart::Field(field) => field_value(field)
+    }).collect()
+}
+
+/**
+ * Export the scan results with user-defined templates, the host template is
+ * rendered once per host and the optional summary template is rendered last.
+ */
+pub fn export_to_template(response_summary: ResponseSummary, mut target_details: Vec<TargetDetails>, host_template: &[TemplatePart], summary_template: Option<&[TemplatePart]>) -> String {
+
+    target_details.sort_by_key(|item| item.ipv4);
+
+    let global_result = get_serializable_result(response_summary, target_details, None);
+
+    let mut output = String::new();
+    for result in global_result.results.iter() {
+        output.push_str(&render_template(host_template, |field| match field {
+            "ipv4" => result.ipv4.clone(),
+            "mac" => result.mac.clone(),
+            "hostname" => result.hostname.clone(),
+            "vendor" => result.vendor.clone(),
+            "response_time_ms" => result.response_time_ms.map(|value| value.to_string()).unwrap_or_default(),
+            "interface" => result.interface.clone(),
+            "note" => result.note.clone().unwrap_or_default(),
+            _ => String::new()
+        }));
+        output.push('\n');
+    }
+
+    if let Some(summary_template) = summary_template {
+        output.push_str(&render_template(summary_template, |field| match field {
+            "duration_ms" => global_result.duration_ms.to_string(),
+            "host_count" => global_result.results.len().to_string(),
+            "packet_count" => global_result.packet_count.to_string(),
+            "arp_count" => global_result.arp_count.to_string(),
+            "requests_sent" => global_result.requests_sent.to_string(),
+            "send_errors" => global_result.send_errors.to_string(),
+            _ => String::new()
+        }));
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::time::Duration;
+    use crate::network::{HostOrigin, HostnameSource};
+
+    const ALL_COLUMNS: [OutputColumn; 5] = [OutputColumn::Ipv4, OutputColumn::Mac, OutputColumn::Hostname, OutputColumn::Vendor, OutputColumn::ResponseTime];
+
+    fn build_summary() -> ResponseSummary {
+        ResponseSummary {
+            packet_count: 12,
+            arp_count: 2,
+            duration_ms: 2500,
+            responses_per_attempt: vec![],
+            requests_sent: 254,
+            send_errors: 2,
+            send_duration_ms: 0,
+            truncated: false,
+            host_limit_reached: false,
+            cancelled: false,
+            unsolicited_hosts: vec![]
+        }
+    }
+
+    fn build_metadata() -> ScanMetadata {
+        ScanMetadata {
+            started_at: chrono::DateTime::parse_from_rfc3339("2022-09-17T10:00:00+00:00").unwrap(),
+            interface_name: "eth0".to_string(),
+            interface_mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            source_ipv4: Ipv4Addr::new(192, 168, 1, 21),
+            target_networks: vec!["192.168.1.0/24".parse().unwrap()]
+        }
+    }
+
+    fn build_target_details() -> Vec<TargetDetails> {
+        vec![
+            TargetDetails {
+                reply_count: 1,
+                ..TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, 20), MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b))
+            },
+            TargetDetails {
+                hostname: Some("router.home".to_string()),
+                vendor: Some("Vendor & Co.".to_string()),
+                response_time_ms: Some(14),
+                reply_count: 1,
+                ..TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, 1), MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23))
+            }
+        ]
+    }
+
+    #[test]
+    fn should_export_xml_round_trip() {
+
+        let xml = export_to_xml(build_summary(), build_target_details(), &build_metadata()).unwrap();
+        let parsed: SerializableXmlResult = quick_xml::de::from_str(&xml).unwrap();
+
+        assert_eq!(parsed.packet_count, 12);
+        assert_eq!(parsed.arp_count, 2);
+        assert_eq!(parsed.duration_ms, 2500);
+        assert_eq!(parsed.hosts.len(), 2);
+        assert_eq!(parsed.hosts[0].ipv4, "192.168.1.1");
+        assert_eq!(parsed.hosts[0].mac, "00:22:72:d7:b5:23");
+        assert_eq!(parsed.hosts[0].hostname, "router.home");
+        assert_eq!(parsed.hosts[0].vendor, "Vendor & Co.");
+        assert_eq!(parsed.hosts[0].response_time_ms, "14");
+        assert_eq!(parsed.hosts[1].ipv4, "192.168.1.20");
+        assert_eq!(parsed.hosts[1].response_time_ms, "");
+    }
+
+    #[test]
+    fn should_export_xml_empty_elements() {
+
+        let xml = export_to_xml(build_summary(), build_target_details(), &build_metadata()).unwrap();
+
+        assert!(xml.contains("<hostname/>"));
+        assert!(xml.contains("<vendor/>"));
+        assert!(xml.contains("Vendor &amp; Co."));
+    }
+
+    #[test]
+    fn should_export_xml_without_hosts() {
+
+        let xml = export_to_xml(build_summary(), vec![], &build_metadata()).unwrap();
+        let parsed: SerializableXmlResult = quick_xml::de::from_str(&xml).unwrap();
+
+        assert!(xml.starts_with("<arp_scan>"));
+        assert!(parsed.hosts.is_empty());
+    }
+
+    #[test]
+    fn should_export_host_to_ndjson() {
+
+        let target_details = build_target_details();
+        let line = export_host_to_ndjson(&target_details[0]).unwrap();
+
+        assert!(!line.contains('\n'));
+        assert_eq!(line, "{\"ipv4\":\"192.168.1.20\",\"mac\":\"40:55:82:c3:e5:5b\",\"hostname\":\"\",\"vendor\":\"\",\"response_time_ms\":null,\"reply_count\":1,\"interface\":\"eth0\"}");
+    }
+
+    #[test]
+    fn should_export_mac_type_of_local_macs() {
+
+        let mut target_details = build_target_details();
+        target_details[0].mac = MacAddr::new(0xda, 0xa1, 0x19, 0x2b, 0x3c, 0x4d);
+
+        let line = export_host_to_ndjson(&target_details[0]).unwrap();
+        let xml = export_to_xml(build_summary(), target_details, &build_metadata()).unwrap();
+
+        assert!(line.ends_with(",\"mac_type\":\"local\"}"));
+        assert!(xml.contains("<mac_type>local</mac_type>"));
+        assert!(!export_host_to_ndjson(&build_target_details()[1]).unwrap().contains("mac_type"));
+    }
+
+    #[test]
+    fn should_compute_display_width() {
+
+        assert_eq!(display_width("router.home"), 11);
+        assert_eq!(display_width("münchen"), 7);
+        assert_eq!(display_width("ドメイン"), 8);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+    }
+
+    #[test]
+    fn should_align_wide_characters_in_tables() {
+
+        let rows = vec![vec!["ドメイン".to_string()], vec!["nas".to_string()]];
+        let table = format_table(&[OutputColumn::Hostname], &rows, &[]);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines[2], "| ドメイン        |");
+        assert_eq!(lines[3], "| nas             |");
+        assert_eq!(display_width(lines[2]), display_width(lines[3]));
+    }
+
+    #[test]
+    fn should_export_ascii_hostname() {
+
+        let mut target_details = build_target_details();
+        target_details[0].hostname = Some("münchen.example".to_string());
+        target_details[0].hostname_ascii = Some("xn--mnchen-3ya.example".to_string());
+
+        let line = export_host_to_ndjson(&target_details[0]).unwrap();
+
+        assert!(line.contains("\"hostname\":\"münchen.example\""));
+        assert!(line.contains("\"hostname_ascii\":\"xn--mnchen-3ya.example\""));
+        assert!(!export_host_to_ndjson(&build_target_details()[1]).unwrap().contains("hostname_ascii"));
+    }
+
+    #[test]
+    fn should_export_annotation_notes() {
+
+        let mut target_details = build_target_details();
+        target_details[0].note = Some("Alice's laptop".to_string());
+        let mut other_details = build_target_details();
+        other_details[0].note = Some("Alice's laptop".to_string());
+
+        let yaml = export_to_yaml(build_summary(), target_details, &build_metadata(), None, false).unwrap();
+        let csv = export_to_csv(build_summary(), other_details, true, &[OutputColumn::Ipv4, OutputColumn::Note], b',', false).unwrap();
+
+        assert!(yaml.contains("note: \"Alice's laptop\"") || yaml.contains("note: Alice's laptop"));
+        assert_eq!(yaml.matches("note:").count(), 1);
+        assert!(csv.starts_with("ipv4,note\n"));
+        assert!(csv.contains(",Alice's laptop\n"));
+    }
+
+    #[test]
+    fn should_export_virtualization_hint() {
+
+        let mut target_details = build_target_details();
+        target_details[0].virtualization = Some("VirtualBox".to_string());
+
+        let json = export_to_json(build_summary(), target_details, &build_metadata(), false, None, false).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed["results"][1]["virtualization"].as_str(), Some("VirtualBox"));
+        assert!(parsed["results"][0].get("virtualization").is_none());
+    }
+
+    #[test]
+    fn should_count_hosts_per_vendor() {
+
+        let mut target_details = build_target_details();
+        let mut other_details = build_target_details();
+        other_details[0].vendor = Some(String::new());
+        target_details.extend(other_details);
+
+        assert_eq!(compute_vendor_summary(&target_details), vec![
+            ("Vendor & Co.".to_string(), 2),
+            ("unknown".to_string(), 2)
+        ]);
+        assert!(compute_vendor_summary(&[]).is_empty());
+    }
+
+    #[test]
+    fn should_aggregate_vendors_beyond_top_count() {
+
+        let vendor_summary = vec![("Espressif".to_string(), 14), ("Raspberry Pi Trading".to_string(), 6), ("unknown".to_string(), 3), ("Nokia".to_string(), 1)];
+
+        assert_eq!(limit_vendor_summary(vendor_summary.clone(), 2), vec![
+            ("Espressif".to_string(), 14),
+            ("Raspberry Pi Trading".to_string(), 6),
+            ("others".to_string(), 4)
+        ]);
+        assert_eq!(limit_vendor_summary(vendor_summary.clone(), 4), vendor_summary);
+    }
+
+    #[test]
+    fn should_format_vendor_summary() {
+
+        let text = format_vendor_summary(&build_target_details(), 5);
+
+        assert_eq!(text, "\nTop vendors: Vendor & Co.: 1, unknown: 1\n");
+        assert_eq!(format_vendor_summary(&[], 5), "");
+    }
+
+    #[test]
+    fn should_export_vendor_summary_when_requested() {
+
+        let json = export_to_json(build_summary(), build_target_details(), &build_metadata(), false, Some(1), false).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed["vendor_summary"][0]["vendor"].as_str(), Some("Vendor & Co."));
+        assert_eq!(parsed["vendor_summary"][1]["vendor"].as_str(), Some("others"));
+        assert!(!export_to_yaml(build_summary(), build_target_details(), &build_metadata(), None, false).unwrap().contains("vendor_summary"));
+    }
+
+    #[test]
+    fn should_export_summary_to_ndjson() {
+
+        let line = export_summary_to_ndjson(build_summary()).unwrap();
+
+        assert_eq!(line, "{\"packet_count\":12,\"arp_count\":2,\"requests_sent\":254,\"send_errors\":2,\"duration_ms\":2500}");
+    }
+
+    #[test]
+    fn should_escape_html_report() {
+
+        let mut target_details = build_target_details();
+        target_details[0].hostname = Some("<script>alert('x')</script>".to_string());
+
+        let html = export_to_html(build_summary(), target_details, &build_metadata());
+
+        assert!(!html.contains("<script>alert"));
+        assert!(html.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;"));
+        assert!(html.contains("<td>Vendor &amp; Co.</td>"));
+    }
+
+    #[test]
+    fn should_export_html_without_hosts() {
+
+        let html = export_to_html(build_summary(), vec![], &build_metadata());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<li>Hosts found: 0</li>"));
+        assert!(html.contains("<tbody>\n</tbody>"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn should_export_markdown_table() {
+
+        let mut target_details = build_target_details();
+        target_details[0].vendor = Some("Pipe | Corp".to_string());
+
+        let markdown = export_to_markdown(build_summary(), target_details);
+        let lines: Vec<&str> = markdown.lines().collect();
+
+        assert_eq!(lines[0], "| IPv4            | MAC               | Hostname        | Vendor          |");
+        assert_eq!(lines[1], "|-----------------|-------------------|-----------------|-----------------|");
+        assert_eq!(lines[2], "| 192.168.1.1     | 00:22:72:d7:b5:23 | router.home     | Vendor & Co.    |");
+        assert_eq!(lines[3], "| 192.168.1.20    | 40:55:82:c3:e5:5b |                 | Pipe \\| Corp    |");
+        assert_eq!(lines[5], "2 hosts found in 2.500 seconds");
+    }
+
+    #[test]
+    fn should_export_markdown_without_hosts() {
+
+        let markdown = export_to_markdown(build_summary(), vec![]);
+
+        assert_eq!(markdown, "No hosts found in 2.500 seconds\n");
+    }
+
+    /**
+     * Minimal validator for the Prometheus text exposition format, only
+     * covering the syntax used by the export (gauges with optional labels).
+     */
+    fn validate_prometheus_exposition(text: &str) -> Result<(), String> {
+
+        let is_valid_name = |name: &str| {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+        };
+
+        let mut typed_metrics: Vec<String> = vec![];
+        for line in text.lines() {
+
+            if let Some(comment) = line.strip_prefix("# ") {
+                let parts: Vec<&str> = comment.splitn(3, ' ').collect();
+                match parts.as_slice() {
+                    ["HELP", name, _] if is_valid_name(name) => {},
+                    ["TYPE", name, "gauge"] if is_valid_name(name) => typed_metrics.push(name.to_string()),
+                    _ => return Err(format!("Invalid comment line: {}", line))
+                }
+                continue;
+            }
+
+            let (series, value) = line.rsplit_once(' ').ok_or(format!("Missing value: {}", line))?;
+            value.parse::<f64>().map_err(|_| format!("Invalid value: {}", line))?;
+
+            let name = match series.split_once('{') {
+                Some((name, labels)) => {
+                    let mut labels = labels.strip_suffix('}').ok_or(format!("Unclosed labels: {}", line))?;
+                    while !labels.is_empty() {
+                        let (label_name, rest) = labels.split_once("=\"").ok_or(format!("Invalid label: {}", line))?;
+                        if !is_valid_name(label_name) {
+                            return Err(format!("Invalid label name: {}", line));
+                        }
+                        let mut chars = rest.char_indices();
+                        let mut end = None;
+                        while let Some((index, character)) = chars.next() {
+                            match character {
+                                '\\' => match chars.next() {
+                                    Some((_, '\\')) | Some((_, '"')) | Some((_, 'n')) => {},
+                                    _ => return Err(format!("Invalid escape: {}", line))
+                                },
+                                '"' => { end = Some(index); break; },
+                                '\n' => return Err(format!("Raw line feed: {}", line)),
+                                _ => {}
+                            }
+                        }
+                        let end = end.ok_or(format!("Unterminated label value: {}", line))?;
+                        labels = rest[end + 1..].strip_prefix(',').unwrap_or(&rest[end + 1..]);
+                    }
+                    name
+                },
+                None => series
+            };
+
+            if !typed_metrics.iter().any(|typed| typed == name) {
+                return Err(format!("Metric without TYPE: {}", line));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_export_valid_prometheus_metrics() {
+
+        let mut target_details = build_target_details();
+        target_details[0].hostname = Some("evil\"host\\name\nx".to_string());
+
+        let metrics = export_to_prometheus(build_summary(), target_details);
+
+        assert_eq!(validate_prometheus_exposition(&metrics), Ok(()));
+        assert!(metrics.contains("arp_scan_hosts_found 2\n"));
+        assert!(metrics.contains("arp_scan_duration_ms 2500\n"));
+        assert!(metrics.contains("hostname=\"evil\\\"host\\\\name\\nx\""));
+    }
+
+    #[test]
+    fn should_export_prometheus_without_hosts() {
+
+        let metrics = export_to_prometheus(build_summary(), vec![]);
+
+        assert_eq!(validate_prometheus_exposition(&metrics), Ok(()));
+        assert!(metrics.contains("arp_scan_hosts_found 0\n"));
+        assert!(!metrics.contains("arp_scan_host_up{"));
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn should_append_scans_to_sqlite() {
+
+        let database_path = env::temp_dir().join(format!("arp-scan-test-{}.db", std::process::id()));
+        let database_path = database_path.to_str().unwrap();
+
+        let target_details = build_target_details();
+        export_to_sqlite(database_path, &build_metadata(), &build_summary(), &target_details).unwrap();
+        export_to_sqlite(database_path, &build_metadata(), &build_summary(), &target_details[..1]).unwrap();
+
+        let connection = Connection::open(database_path).unwrap();
+        let scan_count: i64 = connection.query_row("SELECT COUNT(*) FROM scans", [], |row| row.get(0)).unwrap();
+        let host_count: i64 = connection.query_row("SELECT COUNT(*) FROM hosts WHERE scan_id = 2", [], |row| row.get(0)).unwrap();
+        let hostname: String = connection.query_row("SELECT hostname FROM hosts WHERE ipv4 = '192.168.1.1'", [], |row| row.get(0)).unwrap();
+        drop(connection);
+        std::fs::remove_file(database_path).unwrap();
+
+        assert_eq!(scan_count, 2);
+        assert_eq!(host_count, 1);
+        assert_eq!(hostname, "router.home");
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn should_fail_unwritable_sqlite_path() {
+
+        let result = export_to_sqlite("/nonexistent-directory/scans.db", &build_metadata(), &build_summary(), &[]);
+
+        assert!(result.unwrap_err().0.starts_with("Could not open SQLite database /nonexistent-directory/scans.db"));
+    }
+
+    #[test]
+    fn should_export_nmap_xml_sample() {
+
+        let xml = export_to_nmap_xml(build_summary(), build_target_details(), &build_metadata());
+        let sample = std::fs::read_to_string("./data/nmap-sample.xml").unwrap();
+
+        assert_eq!(xml, sample.replace("{version}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn should_export_nmap_xml_without_hosts() {
+
+        let xml = export_to_nmap_xml(build_summary(), vec![], &build_metadata());
+
+        assert!(!xml.contains("<host "));
+        assert!(xml.contains("<hosts up=\"0\" down=\"0\" total=\"0\"/>"));
+        assert!(xml.ends_with("</nmaprun>\n"));
+    }
+
+    #[test]
+    fn should_export_msgpack_round_trip() {
+
+        let bytes = export_to_msgpack(build_summary(), build_target_details(), &build_metadata()).unwrap();
+        let parsed: SerializableGlobalResult = rmp_serde::from_slice(&bytes).unwrap();
+
+        assert_eq!(parsed.packet_count, 12);
+        assert_eq!(parsed.arp_count, 2);
+        assert_eq!(parsed.duration_ms, 2500);
+        assert_eq!(parsed.results.len(), 2);
+        assert_eq!(parsed.results[0].ipv4, "192.168.1.1");
+        assert_eq!(parsed.results[0].vendor, "Vendor & Co.");
+        assert_eq!(parsed.results[1].hostname, "");
+    }
+
+    #[test]
+    fn should_export_toml_round_trip() {
+
+        let exported = export_to_toml(build_summary(), build_target_details(), &build_metadata()).unwrap();
+        let parsed: SerializableTomlResult = toml::from_str(&exported).unwrap();
+
+        assert!(exported.contains("[summary]"));
+        assert!(exported.contains("[[hosts]]"));
+        assert_eq!(parsed.summary.packet_count, 12);
+        assert_eq!(parsed.summary.arp_count, 2);
+        assert_eq!(parsed.summary.duration_ms, 2500);
+        assert_eq!(parsed.hosts.len(), 2);
+        assert_eq!(parsed.hosts[0].hostname, "router.home");
+        assert_eq!(parsed.hosts[1].hostname, "");
+        assert_eq!(parsed.hosts[1].vendor, "");
+    }
+
+    #[test]
+    fn should_export_pretty_json() {
+
+        let compact = export_to_json(build_summary(), build_target_details(), &build_metadata(), false, None, false).unwrap();
+        let pretty = export_to_json(build_summary(), build_target_details(), &build_metadata(), true, None, false).unwrap();
+
+        assert!(!compact.contains('\n'));
+        assert!(pretty.contains('\n'));
+
+        let compact_value: serde_json::Value = serde_json::from_str(&compact).unwrap();
+        let pretty_value: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(compact_value, pretty_value);
+    }
+
+    #[test]
+    fn should_export_csv_with_headers() {
+
+        let csv = export_to_csv(build_summary(), build_target_details(), true, &ALL_COLUMNS, b',', false).unwrap();
+
+        assert_eq!(csv, "ipv4,mac,hostname,vendor,response_time_ms\n192.168.1.1,00:22:72:d7:b5:23,router.home,Vendor & Co.,14\n192.168.1.20,40:55:82:c3:e5:5b,,,\n");
+    }
+
+    #[test]
+    fn should_export_csv_selected_columns() {
+
+        let csv = export_to_csv(build_summary(), build_target_details(), true, &[OutputColumn::Mac, OutputColumn::Ipv4], b',', false).unwrap();
+
+        assert_eq!(csv, "mac,ipv4\n00:22:72:d7:b5:23,192.168.1.1\n40:55:82:c3:e5:5b,192.168.1.20\n");
+    }
+
+    #[test]
+    fn should_export_csv_with_custom_delimiter() {
+
+        let mut target_details = build_target_details();
+        target_details[1].vendor = Some("Vendor; Co.".to_string());
+
+        let csv = export_to_csv(build_summary(), target_details, false, &[OutputColumn::Ipv4, OutputColumn::Vendor], b';', false).unwrap();
+
+        assert_eq!(csv, "192.168.1.1;\"Vendor; Co.\"\n192.168.1.20;\n");
+    }
+
+    #[test]
+    fn should_export_csv_quoting_all_fields() {
+
+        let csv = export_to_csv(build_summary(), build_target_details(), true, &[OutputColumn::Ipv4, OutputColumn::Vendor], b';', true).unwrap();
+
+        assert_eq!(csv, "\"ipv4\";\"vendor\"\n\"192.168.1.1\";\"Vendor & Co.\"\n\"192.168.1.20\";\"\"\n");
+    }
+
+    #[test]
+    fn should_format_table_with_column_widths() {
+
+        let rows = vec![
+            vec!["192.168.1.1".to_string(), "A very long vendor name, Inc.".to_string()],
+            vec!["192.168.1.20".to_string(), "".to_string()]
+        ];
+
+        let table = format_table(&[OutputColumn::Ipv4, OutputColumn::Vendor], &rows, &[]);
+
+        assert_eq!(table, "\
+| IPv4            | Vendor                        |
+|-----------------|-------------------------------|
+| 192.168.1.1     | A very long vendor name, Inc. |
+| 192.168.1.20    |                               |
+");
+    }
+
+    #[test]
+    fn should_export_csv_without_headers() {
+
+        let csv = export_to_csv(build_summary(), build_target_details(), false, &ALL_COLUMNS, b',', false).unwrap();
+
+        assert!(csv.starts_with("192.168.1.1,"));
+    }
+
+    #[test]
+    fn should_export_csv_headers_without_hosts() {
+
+        let csv = export_to_csv(build_summary(), vec![], true, &ALL_COLUMNS, b',', false).unwrap();
+
+        assert_eq!(csv, "ipv4,mac,hostname,vendor,response_time_ms\n");
+    }
+
+    #[test]
+    fn should_export_csv_summary() {
+
+        let csv = export_summary_to_csv(&build_summary(), &build_metadata()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "packet_count,arp_count,requests_sent,send_errors,duration_ms,started_at,interface_name,interface_mac,source_ipv4,target_networks,version");
+        assert_eq!(lines[1], format!("12,2,254,2,2500,2022-09-17T10:00:00+00:00,eth0,d2:71:d8:29:a8:72,192.168.1.21,192.168.1.0/24,{}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn should_export_ndjson_without_streaming() {
+
+        let ndjson = export_to_ndjson(build_summary(), build_target_details()).unwrap();
+        let lines: Vec<&str> = ndjson.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("{\"ipv4\":\"192.168.1.20\""));
+        assert_eq!(lines[2], "{\"packet_count\":12,\"arp_count\":2,\"requests_sent\":254,\"send_errors\":2,\"duration_ms\":2500}");
+    }
+
+    #[test]
+    fn should_write_output_file() {
+
+        let file_path = env::temp_dir().join(format!("arp-scan-output-{}.json", std::process::id()));
+        let file_path = file_path.to_str().unwrap();
+
+        write_output(Some(file_path), b"first").unwrap();
+        write_output(Some(file_path), b"second").unwrap();
+        let content = fs::read_to_string(file_path).unwrap();
+        fs::remove_file(file_path).unwrap();
+
+        assert_eq!(content, "second");
+    }
+
+    #[test]
+    fn should_fail_unwritable_output_file() {
+
+        let result = write_output(Some("/nonexistent-directory/output.json"), b"content");
+
+        assert!(result.unwrap_err().starts_with("Could not write output file /nonexistent-directory/output.json"));
+    }
+
+    /**
+     * Validate a JSON value against the keywords used by the export schema
+     * (type, const, minimum & maximum, required, properties, items, closed
+     * objects and references to the definitions of the export schema), each
+     * violation is reported with its JSON pointer.
+     */
+    fn validate_schema(value: &serde_json::Value, schema: &serde_json::Value, pointer: &str, violations: &mut Vec<String>) {
+
+        if let Some(reference) = schema.get("$ref").and_then(|reference| reference.as_str()) {
+            let export_schema: serde_json::Value = serde_json::from_str(EXPORT_SCHEMA).unwrap();
+            let definition = export_schema.pointer(reference.trim_start_matches('#')).unwrap_or_else(|| panic!("Unknown reference {}", reference));
+            return validate_schema(value, definition, pointer, violations);
+        }
+
+        if let Some(expected_type) = schema.get("type") {
+            let expected_types: Vec<&str> = match expected_type {
+                serde_json::Value::Array(types) => types.iter().filter_map(|value| value.as_str()).collect(),
+                other => other.as_str().into_iter().collect()
+            };
+            let actual_type = match value {
+                serde_json::Value::Null => "null",
+                serde_json::Value::Bool(_) => "boolean",
+                serde_json::Value::Number(number) if number.is_u64() || number.is_i64() => "integer",
+                serde_json::Value::Number(_) => "number",
+                serde_json::Value::String(_) => "string",
+                serde_json::Value::Array(_) => "array",
+                serde_json::Value::Object(_) => "object"
+            };
+            if !expected_types.contains(&actual_type) {
+                violations.push(format!("{} should be {:?}, not {}", pointer, expected_types, actual_type));
+            }
+        }
+        if let Some(expected_value) = schema.get("const") {
+            if value != expected_value {
+                violations.push(format!("{} should be {}", pointer, expected_value));
+            }
+        }
+        if let Some(number) = value.as_f64() {
+            if schema.get("minimum").and_then(|minimum| minimum.as_f64()).is_some_and(|minimum| number < minimum) {
+                violations.push(format!("{} is below its minimum", pointer));
+            }
+            if schema.get("maximum").and_then(|maximum| maximum.as_f64()).is_some_and(|maximum| number > maximum) {
+                violations.push(format!("{} is above its maximum", pointer));
+            }
+        }
+
+        if let Some(object) = value.as_object() {
+            let properties = schema.get("properties").and_then(|properties| properties.as_object());
+            for required_key in schema.get("required").and_then(|required| required.as_array()).into_iter().flatten() {
+                if let Some(required_key) = required_key.as_str().filter(|key| !object.contains_key(*key)) {
+                    violations.push(format!("{}/{} is missing", pointer, required_key));
+                }
+            }
+            for (key, property_value) in object {
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(property_schema) => validate_schema(property_value, property_schema, &format!("{}/{}", pointer, key), violations),
+                    None if schema.get("additionalProperties") == Some(&serde_json::Value::Bool(false)) => {
+                        violations.push(format!("{}/{} is not in the schema", pointer, key));
+                    },
+                    None => {}
+                }
+            }
+        }
+        if let (Some(items), Some(item_schema)) = (value.as_array(), schema.get("items")) {
+            for (index, item) in items.iter().enumerate() {
+                validate_schema(item, item_schema, &format!("{}/{}", pointer, index), violations);
+            }
+        }
+    }
+
+    #[test]
+    fn should_match_the_export_schema() {
+
+        // All optional fields are exported: conflicts (with a VLAN), shared
+        // MACs, the vendor summary, unsolicited hosts, hosts grouped by MAC,
+        // scan flags and every host detail.
+        let mut target_details = build_target_details();
+        target_details[0].ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        for target_detail in target_details.iter_mut() {
+            target_detail.vlan_id = Some(42);
+            target_detail.verify_status = Some(VerifyStatus::Alive);
+            target_detail.origin = Some(HostOrigin::Scan);
+            target_detail.hostname_source = Some(HostnameSource::Mdns);
+            target_detail.hostname_ascii = Some("xn--rter-bva.home".to_string());
+            target_detail.virtualization = Some("QEMU/KVM".to_string());
+            target_detail.note = Some("Office".to_string());
+            target_detail.frame_src_mac = Some(target_detail.mac);
+        }
+        let mut shared_host = build_target_details()[1].clone();
+        shared_host.ipv4 = Ipv4Addr::new(192, 168, 1, 2);
+        target_details.push(shared_host);
+        let summary = ResponseSummary { truncated: true, cancelled: true, unsolicited_hosts: build_target_details(), ..build_summary() };
+
+        let schema: serde_json::Value = serde_json::from_str(EXPORT_SCHEMA).unwrap();
+        assert_eq!(schema["properties"]["schema_version"]["const"], EXPORT_SCHEMA_VERSION);
+
+        let exports = [
+            export_to_json(summary.clone(), target_details.clone(), &build_metadata(), false, Some(1), true).unwrap(),
+            export_to_json(build_summary(), vec![], &build_metadata(), false, None, false).unwrap(),
+            serde_json::to_string(&serde_yaml::from_str::<serde_json::Value>(&export_to_yaml(summary, target_details, &build_metadata(), Some(1), true).unwrap()).unwrap()).unwrap()
+        ];
+        for export in exports.iter() {
+            let value: serde_json::Value = serde_json::from_str(export).unwrap();
+            let mut violations: Vec<String> = vec![];
+            validate_schema(&value, &schema, "", &mut violations);
+            assert_eq!(violations, Vec::<String>::new(), "{}", export);
+        }
+        assert!(exports[0].contains("\"conflicts\"") && exports[0].contains("\"shared_macs\"") && exports[0].contains("\"vendor_summary\"") && exports[0].contains("\"unsolicited_hosts\"") && exports[0].contains("\"hosts_by_mac\""));
+    }
+
+    #[test]
+    fn should_report_schema_violations() {
+
+        let schema: serde_json::Value = serde_json::from_str(EXPORT_SCHEMA).unwrap();
+        let json = export_to_json(build_summary(), build_target_details(), &build_metadata(), false, None, false).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["schema_version"] = serde_json::json!(EXPORT_SCHEMA_VERSION + 1);
+        value["results"][0]["latency"] = serde_json::json!(14);
+        let mut unsolicited_host = value["results"][1].clone();
+        unsolicited_host.as_object_mut().unwrap().remove("interface");
+        value["unsolicited_hosts"] = serde_json::json!([unsolicited_host]);
+
+        let mut violations: Vec<String> = vec![];
+        validate_schema(&value, &schema, "", &mut violations);
+
+        assert_eq!(violations, vec!["/results/0/latency is not in the schema".to_string(), format!("/schema_version should be {}", EXPORT_SCHEMA_VERSION), "/unsolicited_hosts/0/interface is missing".to_string()]);
+    }
+
+    #[test]
+    fn should_export_json_metadata() {
+
+        let json = export_to_json(build_summary(), build_target_details(), &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["schema_version"], EXPORT_SCHEMA_VERSION);
+        assert_eq!(value["packet_count"], 12);
+        assert_eq!(value["results"][0]["ipv4"], "192.168.1.1");
+        assert_eq!(value["metadata"]["started_at"], "2022-09-17T10:00:00+00:00");
+        assert_eq!(value["metadata"]["interface_name"], "eth0");
+        assert_eq!(value["metadata"]["interface_mac"], "d2:71:d8:29:a8:72");
+        assert_eq!(value["metadata"]["source_ipv4"], "192.168.1.21");
+        assert_eq!(value["metadata"]["target_networks"][0], "192.168.1.0/24");
+        assert_eq!(value["metadata"]["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn should_export_conflicts() {
+
+        let mut target_details = build_target_details();
+        target_details[0].ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+
+        let json = export_to_json(build_summary(), target_details, &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["conflicts"], serde_json::json!([{ "ipv4": "192.168.1.1", "macs": ["00:22:72:d7:b5:23", "40:55:82:c3:e5:5b"] }]));
+
+        let json = export_to_json(build_summary(), build_target_details(), &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert!(value.get("conflicts").is_none());
+    }
+
+    #[test]
+    fn should_export_csv_conflict_column() {
+
+        let mut target_details = build_target_details();
+        target_details[0].ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        target_details.push(TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, 30), MacAddr::new(0xcc, 0x9d, 0xa2, 0x14, 0x2e, 0x6f)));
+
+        let csv = export_to_csv(build_summary(), target_details, true, &[OutputColumn::Ipv4, OutputColumn::Mac], b',', false).unwrap();
+
+        assert_eq!(csv, "ipv4,mac,conflict\n192.168.1.1,40:55:82:c3:e5:5b,true\n192.168.1.1,00:22:72:d7:b5:23,true\n192.168.1.30,cc:9d:a2:14:2e:6f,false\n");
+    }
+
+    #[test]
+    fn should_export_yaml_metadata() {
+
+        let yaml = export_to_yaml(build_summary(), build_target_details(), &build_metadata(), None, false).unwrap();
+        let parsed: SerializableGlobalResult = serde_yaml::from_str(&yaml).unwrap();
+
+        assert_eq!(parsed.packet_count, 12);
+        assert_eq!(parsed.metadata.unwrap().interface_name, "eth0");
+    }
+
+    #[test]
+    fn should_export_greppable_lines() {
+
+        let mut target_details = build_target_details();
+        target_details.push(TargetDetails {
+            hostname: Some("printer.lan".to_string()),
+            ..TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, 30), MacAddr::new(0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff))
+        });
+
+        let grep = export_to_grep(build_summary(), target_details);
+        let lines: Vec<&str> = grep.lines().collect();
+
+        assert_eq!(lines, vec![
+            "Host: 192.168.1.1 (router.home) MAC: 00:22:72:d7:b5:23 (Vendor & Co.)",
+            "Host: 192.168.1.20 () MAC: 40:55:82:c3:e5:5b ()",
+            "Host: 192.168.1.30 (printer.lan) MAC: aa:bb:cc:dd:ee:ff ()",
+            "# Summary: 3 hosts found, 12 packets received, 2 ARP packets filtered, 254 requests sent, 2 send errors, 2500 ms"
+        ]);
+    }
+
+    #[test]
+    fn should_export_junit_without_allowlist() {
+
+        let mut target_details = build_target_details();
+        target_details[0].hostname = Some("<evil>&host".to_string());
+
+        let junit = export_to_junit(build_summary(), target_details, None);
+
+        assert!(junit.contains("<testsuite name=\"arp-scan\" tests=\"2\" failures=\"0\" errors=\"0\" time=\"2.500\">"));
+        assert!(junit.contains("name=\"192.168.1.20 &lt;evil&gt;&amp;host (40:55:82:c3:e5:5b)\""));
+        assert!(!junit.contains("<failure"));
+    }
+
+    #[test]
+    fn should_export_junit_with_allowlist() {
+
+        let allowlist = vec!["00:22:72:d7:b5:23".to_string(), "192.168.1.99".to_string()];
+
+        let junit = export_to_junit(build_summary(), build_target_details(), Some(&allowlist));
+
+        assert!(junit.contains("<testsuite name=\"arp-scan\" tests=\"3\" failures=\"2\" errors=\"0\" time=\"2.500\">"));
+        assert!(junit.contains("<testcase classname=\"arp-scan.hosts\" name=\"192.168.1.1 router.home (00:22:72:d7:b5:23)\" time=\"0\"/>"));
+        assert!(junit.contains("name=\"192.168.1.20 (40:55:82:c3:e5:5b)\" time=\"0\"><failure type=\"unexpected\""));
+        assert!(junit.contains("name=\"192.168.1.99\" time=\"0\"><failure type=\"missing\""));
+    }
+
+    #[test]
+    fn should_export_dot_graph() {
+
+        let mut target_details = build_target_details();
+        target_details[0].vendor = Some("Quote \"Corp\"".to_string());
+
+        let dot = export_to_dot(build_summary(), target_details, &build_metadata());
+
+        assert!(dot.starts_with("graph arp_scan {\n"));
+        assert!(dot.contains("  scanner [label=\"eth0\\n192.168.1.21\", shape=box];\n"));
+        assert!(dot.contains("  mac_00_22_72_d7_b5_23 [label=\"192.168.1.1\\n00:22:72:d7:b5:23\\nrouter.home\\nVendor & Co.\"];\n"));
+        assert!(dot.contains("  mac_40_55_82_c3_e5_5b [label=\"192.168.1.20\\n40:55:82:c3:e5:5b\\nQuote \\\"Corp\\\"\"];\n"));
+        assert!(dot.contains("  scanner -- mac_40_55_82_c3_e5_5b;\n"));
+        assert!(!dot.contains("subgraph"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn should_group_dot_graph_by_network() {
+
+        let mut metadata = build_metadata();
+        metadata.target_networks.push("10.0.0.0/16".parse().unwrap());
+
+        let mut target_details = build_target_details();
+        target_details[0].ipv4 = Ipv4Addr::new(10, 0, 4, 2);
+
+        let dot = export_to_dot(build_summary(), target_details, &metadata);
+
+        assert!(dot.contains("  subgraph cluster_10_0_4_0_24 {\n    label=\"10.0.4.0/24\";\n    mac_40_55_82_c3_e5_5b"));
+        assert!(dot.contains("  subgraph cluster_192_168_1_0_24 {\n    label=\"192.168.1.0/24\";\n    mac_00_22_72_d7_b5_23"));
+    }
+
+    #[test]
+    fn should_parse_template_escapes() {
+
+        let parts = parse_template("{ipv4}\\t{mac}\\n{{literal}}\\\\", &HOST_TEMPLATE_FIELDS);
+
+        assert_eq!(parts, Ok(vec![
+            TemplatePart::Field("ipv4".to_string()),
+            TemplatePart::Text("\t".to_string()),
+            TemplatePart::Field("mac".to_string()),
+            TemplatePart::Text("\n{literal}\\".to_string())
+        ]));
+    }
+
+    #[test]
+    fn should_deny_unknown_template_placeholder() {
+
+        let parts = parse_template("{ipv4} {duration_ms}", &HOST_TEMPLATE_FIELDS);
+
+        assert_eq!(parts, Err("Unknown placeholder {duration_ms} in template (expected ipv4, mac, hostname, vendor, response_time_ms, interface, note)".to_string()));
+    }
+
+    #[test]
+    fn should_deny_unbalanced_template_brace() {
+
+        assert!(parse_template("{ipv4}}", &HOST_TEMPLATE_FIELDS).is_err());
+        assert!(parse_template("{ipv4", &HOST_TEMPLATE_FIELDS).is_err());
+    }
+
+    #[test]
+    fn should_export_to_template() {
+
+        let host_template = parse_template("{ipv4}\\t{mac}\\t{vendor}", &HOST_TEMPLATE_FIELDS).unwrap();
+        let summary_template = parse_template("{host_count} hosts in {duration_ms}ms", &SUMMARY_TEMPLATE_FIELDS).unwrap();
+
+        let output = export_to_template(build_summary(), build_target_details(), &host_template, Some(&summary_template));
+
+        assert_eq!(output, "192.168.1.1\t00:22:72:d7:b5:23\tVendor & Co.\n192.168.1.20\t40:55:82:c3:e5:5b\t\n2 hosts in 2500ms\n");
+    }
+
+    #[test]
+    fn should_export_ansible_inventory() {
+
+        let inventory = export_to_ansible(build_summary(), build_target_details(), &build_metadata(), None).unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&inventory).unwrap();
+
+        let hosts = &value["all"]["hosts"];
+        assert_eq!(hosts["router.home"]["ansible_host"].as_str(), Some("192.168.1.1"));
+        assert_eq!(hosts["router.home"]["vendor"].as_str(), Some("Vendor & Co."));
+        assert_eq!(hosts["192.168.1.20"]["mac"].as_str(), Some("40:55:82:c3:e5:5b"));
+        assert!(hosts["192.168.1.20"]["vendor"].is_null());
+    }
+
+    #[test]
+    fn should_disambiguate_ansible_hostnames() {
+
+        let mut target_details = build_target_details();
+        target_details[0].hostname = Some("router.home".to_string());
+
+        let inventory = export_to_ansible(build_summary(), target_details, &build_metadata(), None).unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&inventory).unwrap();
+
+        let hosts = &value["all"]["hosts"];
+        assert_eq!(hosts["router.home-1"]["ansible_host"].as_str(), Some("192.168.1.1"));
+        assert_eq!(hosts["router.home-20"]["ansible_host"].as_str(), Some("192.168.1.20"));
+    }
+
+    #[test]
+    fn should_group_ansible_inventory() {
+
+        let by_vendor = export_to_ansible(build_summary(), build_target_details(), &build_metadata(), Some(&AnsibleGrouping::Vendor)).unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&by_vendor).unwrap();
+
+        let children = &value["all"]["children"];
+        assert!(children["vendor_vendor_co"]["hosts"]["router.home"].is_mapping());
+        assert!(children["vendor_unknown"]["hosts"]["192.168.1.20"].is_mapping());
+
+        let by_subnet = export_to_ansible(build_summary(), build_target_details(), &build_metadata(), Some(&AnsibleGrouping::Subnet)).unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&by_subnet).unwrap();
+
+        let hosts = &value["all"]["children"]["subnet_192_168_1_0_24"]["hosts"];
+        assert_eq!(hosts.as_mapping().map(|hosts| hosts.len()), Some(2));
+    }
+
+    #[test]
+    fn should_sanitize_hostnames() {
+
+        assert_eq!(sanitize_hostname("Router.Home"), "router.home");
+        assert_eq!(sanitize_hostname("my_nas box.local."), "my-nas-box.local");
+        assert_eq!(sanitize_hostname("-_-"), "");
+    }
+
+    #[test]
+    fn should_export_hosts_file() {
+
+        let hosts = export_to_hosts(build_summary(), build_target_details());
+
+        assert_eq!(hosts, "192.168.1.1\trouter.home\n");
+    }
+
+    #[test]
+    fn should_export_dnsmasq_leases() {
+
+        let leases = export_to_dnsmasq(build_summary(), build_target_details(), false);
+        assert_eq!(leases, "dhcp-host=00:22:72:d7:b5:23,192.168.1.1,router.home\ndhcp-host=40:55:82:c3:e5:5b,192.168.1.20\n");
+
+        let resolved_leases = export_to_dnsmasq(build_summary(), build_target_details(), true);
+        assert_eq!(resolved_leases, "dhcp-host=00:22:72:d7:b5:23,192.168.1.1,router.home\n");
+    }
+
+    #[test]
+    fn should_export_ascii_hostname_to_hosts_file() {
+
+        let mut target_details = build_target_details();
+        target_details[0].hostname = Some("münchen.example".to_string());
+        target_details[0].hostname_ascii = Some("xn--mnchen-3ya.example".to_string());
+
+        let hosts = export_to_hosts(build_summary(), target_details);
+
+        assert_eq!(hosts, "192.168.1.1\trouter.home\n192.168.1.20\txn--mnchen-3ya.example\n");
+    }
+
+    #[test]
+    fn should_export_ascii_hostname_to_dnsmasq_leases() {
+
+        let mut target_details = build_target_details();
+        target_details[0].hostname = Some("münchen.example".to_string());
+        target_details[0].hostname_ascii = Some("xn--mnchen-3ya.example".to_string());
+
+        let leases = export_to_dnsmasq(build_summary(), target_details, true);
+
+        assert_eq!(leases, "dhcp-host=00:22:72:d7:b5:23,192.168.1.1,router.home\ndhcp-host=40:55:82:c3:e5:5b,192.168.1.20,xn--mnchen-3ya.example\n");
+    }
+
+    #[test]
+    fn should_export_influx_line_protocol() {
+
+        let mut target_details = build_target_details();
+        target_details[0].vendor = Some("Acme, Inc. = Best".to_string());
+
+        let influx = export_to_influx(build_summary(), target_details, &build_metadata());
+
+        assert_eq!(influx, r"arp_host,hostname=router.home,ipv4=192.168.1.1,mac=00:22:72:d7:b5:23,vendor=Vendor\ &\ Co. up=1i 1663408800000000000
+arp_host,ipv4=192.168.1.20,mac=40:55:82:c3:e5:5b,vendor=Acme\,\ Inc.\ \=\ Best up=1i 1663408800000000000
+arp_scan hosts_found=2i,duration_ms=2500i,packets_received=12i,requests_sent=254i,send_errors=2i 1663408800000000000
+");
+    }
+
+    #[test]
+    fn should_compute_network_size_with_exclusions() {
+
+        let network = IpNetwork::V4(Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 0), 24).unwrap());
+        let excluded_networks = vec![
+            Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 0), 30).unwrap(),
+            Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 2), 32).unwrap(),
+            Ipv4Network::new(Ipv4Addr::new(192, 168, 1, 200), 32).unwrap(),
+            Ipv4Network::new(Ipv4Addr::new(10, 0, 0, 1), 32).unwrap()
+        ];
+
+        assert_eq!(compute_network_size(&[&network], &[]).unwrap(), 256);
+        assert_eq!(compute_network_size(&[&network], &excluded_networks).unwrap(), 251);
+    }
+
+    #[test]
+    fn should_reject_ipv6_network_size() {
+
+        let ipv4_network: IpNetwork = "192.168.1.0/24".parse().unwrap();
+        let ipv6_network: IpNetwork = "fe80::/64".parse().unwrap();
+
+        assert_eq!(compute_network_size(&[&ipv4_network, &ipv6_network], &[]), Err(SizeError::Ipv6Network(ipv6_network)));
+    }
+
+    #[test]
+    fn should_compute_size_of_whole_ipv4_space() {
+
+        let any_network: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        let excluded_networks = vec![Ipv4Network::new(Ipv4Addr::new(10, 0, 0, 0), 8).unwrap()];
+
+        assert_eq!(compute_network_size(&[&any_network, &any_network], &[]).unwrap(), 1u128 << 33);
+        assert_eq!(compute_network_size(&[&any_network], &excluded_networks).unwrap(), (1u128 << 32) - (1 << 24));
+    }
+
+    #[test]
+    fn should_return_export_errors() {
+
+        // A delimiter byte outside of ASCII does not produce valid UTF-8, the
+        // export fails instead of stopping the process.
+        let result = export_to_csv(build_summary(), build_target_details(), true, &[OutputColumn::Ipv4, OutputColumn::Mac], 0xff, false);
+
+        assert!(result.is_err_and(|err| err.to_string().starts_with("Could not convert final CSV result to text")));
+    }
+
+    #[test]
+    fn should_flag_truncated_scan_in_exports() {
+
+        let mut summary = build_summary();
+        summary.truncated = true;
+
+        let json = export_to_json(summary, build_target_details(), &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["truncated"], true);
+
+        let complete_json = export_to_json(build_summary(), build_target_details(), &build_metadata(), false, None, false).unwrap();
+        assert!(!complete_json.contains("truncated"));
+    }
+
+    #[test]
+    fn should_flag_cancelled_scan_in_exports() {
+
+        let mut summary = build_summary();
+        summary.cancelled = true;
+
+        let json = export_to_json(summary, build_target_details(), &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["cancelled"], true);
+
+        let complete_json = export_to_json(build_summary(), build_target_details(), &build_metadata(), false, None, false).unwrap();
+        assert!(!complete_json.contains("cancelled"));
+    }
+
+    #[test]
+    fn should_report_woken_hosts() {
+
+        let wake_macs = [MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23), MacAddr::new(0xbb, 0xbb, 0xbb, 0xd2, 0xf5, 0xb6)];
+        let report = format_wake_report(&wake_macs, &build_target_details());
+
+        assert_eq!(report, "Woken host 00:22:72:d7:b5:23 appeared as 192.168.1.1\nWoken host bb:bb:bb:d2:f5:b6 did not appear in the results\n1 of 2 woken hosts appeared in the results\n");
+    }
+
+    #[test]
+    fn should_format_scan_plans() {
+
+        let scan_plan = ScanPlan {
+            interface_name: "eth0".to_string(),
+            network: "192.168.1.0/24".parse().unwrap(),
+            vlan_id: None,
+            target_count: 256,
+            first_target: Some(Ipv4Addr::new(192, 168, 1, 0)),
+            last_target: Some(Ipv4Addr::new(192, 168, 1, 255)),
+            source_ipv4: Ipv4Addr::new(192, 168, 1, 21),
+            source_mac: Some(MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72)),
+            destination_mac: MacAddr::broadcast(),
+            example_frame: Some((0..20).collect())
+        };
+
+        assert_eq!(format_scan_plans(&[scan_plan]), concat!(
+            "Dry run, no packet will be sent\n",
+            "\neth0 192.168.1.0/24: 256 targets, from 192.168.1.0 to 192.168.1.255\n",
+            "  Source 192.168.1.21 (d2:71:d8:29:a8:72), destination ff:ff:ff:ff:ff:ff\n",
+            "  Example frame (20 bytes)\n",
+            "  0000  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n",
+            "  0010  10 11 12 13\n"
+        ));
+    }
+
+    #[test]
+    fn should_format_progress_bar() {
+
+        let progress = ScanProgress {
+            sent_count: 127,
+            target_count: 254,
+            host_count: 3,
+            elapsed: Duration::from_millis(1500),
+            estimated_remaining: Duration::from_millis(3270),
+            finished: false
+        };
+
+        assert_eq!(format_progress_bar(&progress), "[###############---------------]  50% 127/254 targets, 3 hosts found, 1s elapsed, ~3s remaining");
+    }
+
+    #[test]
+    fn should_flag_proxy_arp_answers() {
+
+        let router_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let mut target_details = build_target_details();
+        target_details[0].frame_src_mac = Some(router_mac);
+        target_details[1].frame_src_mac = Some(router_mac);
+        let options = ScanOptions {
+            columns: vec![OutputColumn::Ipv4, OutputColumn::Mac, OutputColumn::FrameSrcMac],
+            ..ScanOptions::default()
+        };
+
+        let text = format_scan_results(build_summary(), target_details.clone(), &options, false);
+
+        assert!(text.contains("| 192.168.1.1     | 00:22:72:d7:b5:23        | 00:22:72:d7:b5:23 |"), "{}", text);
+        assert!(text.contains("| 192.168.1.20    | 40:55:82:c3:e5:5b proxy? | 00:22:72:d7:b5:23 |"), "{}", text);
+
+        let json = export_to_json(build_summary(), target_details, &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!((value["results"][0]["mac"].as_str(), value["results"][0]["frame_src_mac"].as_str()), (Some("00:22:72:d7:b5:23"), Some("00:22:72:d7:b5:23")));
+        assert_eq!((value["results"][1]["mac"].as_str(), value["results"][1]["frame_src_mac"].as_str()), (Some("40:55:82:c3:e5:5b"), Some("00:22:72:d7:b5:23")));
+    }
+
+    #[test]
+    fn should_count_duplicate_replies_in_summary() {
+
+        let mut target_details = build_target_details();
+        let options = ScanOptions { columns: vec![OutputColumn::Ipv4, OutputColumn::ReplyCount], ..ScanOptions::default() };
+
+        let text = format_scan_results(build_summary(), target_details.clone(), &options, false);
+        assert!(text.trim_end().ends_with("12 packets received, 2 ARP packets filtered, 254 requests sent, 2 send errors"), "{}", text);
+
+        target_details[1].reply_count = 4;
+        let text = format_scan_results(build_summary(), target_details.clone(), &options, false);
+        assert!(text.contains("| 192.168.1.1     | 4       |"), "{}", text);
+        assert!(text.trim_end().ends_with("254 requests sent, 2 send errors, 3 duplicate replies"), "{}", text);
+
+        let csv = export_to_csv(build_summary(), target_details, true, &[OutputColumn::Ipv4, OutputColumn::ReplyCount], b',', false).unwrap();
+        assert_eq!(csv, "ipv4,reply_count\n192.168.1.1,4\n192.168.1.20,1\n");
+    }
+
+    #[test]
+    fn should_list_unsolicited_hosts_apart() {
+
+        let mut target_details = build_target_details();
+        let unsolicited_hosts = target_details.split_off(1);
+        let summary = ResponseSummary { unsolicited_hosts, ..build_summary() };
+        let options = ScanOptions { columns: vec![OutputColumn::Ipv4, OutputColumn::Mac], ..ScanOptions::default() };
+
+        let text = format_scan_results(summary.clone(), target_details.clone(), &options, false);
+        assert!(text.contains("\nUnsolicited replies\n  192.168.1.1 answered from 00:22:72:d7:b5:23 (Vendor & Co.), 1 reply\n"), "{}", text);
+        assert!(text.contains("ARP scan finished, 1 host found"), "{}", text);
+
+        let json = export_to_json(summary, target_details, &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["results"].as_array().map(|results| results.len()), Some(1));
+        assert_eq!(value["unsolicited_hosts"][0]["ipv4"].as_str(), Some("192.168.1.1"));
+        assert!(!export_to_json(build_summary(), vec![], &build_metadata(), false, None, false).unwrap().contains("unsolicited_hosts"));
+    }
+
+    #[test]
+    fn should_group_hosts_by_mac() {
+
+        let mut target_details = build_target_details();
+        let mut second_address = target_details[1].clone();
+        second_address.ipv4 = Ipv4Addr::new(192, 168, 1, 254);
+        second_address.hostname = None;
+        target_details.push(second_address);
+        let options = ScanOptions { group_by_mac: true, ..ScanOptions::default() };
+
+        let text = format_scan_results(build_summary(), target_details.clone(), &options, false);
+        assert!(text.starts_with(concat!(
+            "\n00:22:72:d7:b5:23 (Vendor & Co.), 2 IPv4 addresses\n",
+            "  192.168.1.1 (router.home)\n",
+            "  192.168.1.254\n",
+            "\n40:55:82:c3:e5:5b, 1 IPv4 address\n",
+            "  192.168.1.20\n"
+        )), "{}", text);
+
+        let json = export_to_json(build_summary(), target_details.clone(), &build_metadata(), false, None, true).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["hosts_by_mac"][0], serde_json::json!({
+            "mac": "00:22:72:d7:b5:23",
+            "vendor": "Vendor & Co.",
+            "ipv4s": ["192.168.1.1", "192.168.1.254"],
+            "hostnames": ["router.home"]
+        }));
+        assert_eq!(value["results"].as_array().map(|results| results.len()), Some(3));
+        assert!(!export_to_json(build_summary(), target_details, &build_metadata(), false, None, false).unwrap().contains("hosts_by_mac"));
+    }
+
+    #[test]
+    fn should_format_duplicates_section() {
+
+        let nat_mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+        let mut target_details = build_target_details();
+        target_details[0].ipv4 = Ipv4Addr::new(192, 168, 1, 1);
+        for last_byte in [2, 3] {
+            target_details.push(TargetDetails::for_test(Ipv4Addr::new(192, 168, 1, last_byte), nat_mac));
+        }
+        let conflicts = find_address_conflicts(&target_details);
+
+        assert_eq!(format_duplicates(&target_details, &conflicts, false), "
+Duplicates
+  MAC 00:22:72:d7:b5:23 answers for 3 IPv4 addresses: 192.168.1.1 (router.home), 192.168.1.2, 192.168.1.3
+  CONFLICT 192.168.1.1 is claimed by 2 MAC addresses: 00:22:72:d7:b5:23, 40:55:82:c3:e5:5b
+");
+
+        let json = export_to_json(build_summary(), target_details, &build_metadata(), false, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["shared_macs"], serde_json::json!([{ "mac": "00:22:72:d7:b5:23", "ipv4s": ["192.168.1.1", "192.168.1.2", "192.168.1.3"] }]));
     }
-    wtr.flush().unwrap_or_else(|err| {
-        eprintln!("Could not flush CSV writer buffer ({})", err);
-        process::exit(1);
-    });
 
-    let convert_writer = wtr.into_inner().unwrap_or_else(|err| {
-        eprintln!("Could not convert final CSV result ({})", err);
-        process::exit(1);
-    });
-    String::from_utf8(convert_writer).unwrap_or_else(|err| {
-        eprintln!("Could not convert final CSV result to text ({})", err);
-        process::exit(1);
-    })
 }
@@ -1,145 +1,944 @@
-use std::fs::File;
-use std::process;
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
 
 use pnet_datalink::MacAddr;
-use csv::{Position, Reader};
+use csv::Reader;
+use miniz_oxide::inflate::decompress_to_vec;
 
-// The Vendor structure performs search operations on a vendor database to find
-// which MAC address belongs to a specific vendor. All network vendors have a
-// dedicated MAC address range that is registered by the IEEE and maintained in
-// the OUI database. An OUI is a 24-bit globally unique assigned number
-// referenced by various standards.
-pub struct Vendor {
-    reader: Option<Reader<File>>,
+pub const SYSTEM_VENDOR_FILE_PATH: &str = "/usr/share/arp-scan/ieee-oui.csv";
+// The IEEE registries of MA-L (OUI), MA-M and MA-S assignments.
+pub const IEEE_REGISTRY_URLS: [&str; 3] = [
+    "https://standards-oui.ieee.org/oui/oui.csv",
+    "https://standards-oui.ieee.org/oui28/mam.csv",
+    "https://standards-oui.ieee.org/oui36/oui36.csv"
+];
+
+const USER_VENDOR_FILE_NAME: &str = "ieee-oui.csv";
+const DOWNLOAD_TIMEOUT_SECONDS: u64 = 120;
+const MAC_BITS: u8 = 48;
+const OUI_BITS: u8 = 24;
+
+// Snapshot of the IEEE registries embedded in the binary (built with the
+// 'vendor-snapshot.py' script), decoded on the first lookup only.
+const EMBEDDED_SNAPSHOT: &[u8] = include_bytes!("../assets/ieee-oui.bin");
+const SNAPSHOT_MAGIC: &[u8] = b"ARPVEND1";
+const COMPRESSED_SNAPSHOT_MAGIC: &[u8] = b"ARPVENDZ";
+static EMBEDDED_PREFIXES: OnceLock<PrefixIndex> = OnceLock::new();
+
+// Well-known prefixes of virtual machines & containers. Vendor files in the
+// manuf format can add hints with a 'virtualization=<name>' field.
+const VIRTUALIZATION_PREFIXES: [(u64, u8, &str); 9] = [
+    (0x005056000000, 24, "VMware"),
+    (0x000c29000000, 24, "VMware"),
+    (0x000569000000, 24, "VMware"),
+    (0x001c14000000, 24, "VMware"),
+    (0x080027000000, 24, "VirtualBox"),
+    (0x525400000000, 24, "QEMU/KVM"),
+    (0x00155d000000, 24, "Hyper-V"),
+    (0x00163e000000, 24, "Xen"),
+    (0x024200000000, 16, "Docker")
+];
+const VIRTUALIZATION_FIELD: &str = "virtualization=";
+
+/**
+ * The vendor database finds which vendor a MAC address belongs to. All
+ * network vendors have a dedicated MAC address range that is registered by
+ * the IEEE and maintained in the OUI database. An OUI is a 24-bit globally
+ * unique assigned number referenced by various standards, longer MA-M and
+ * MA-S assignments (28 and 36 bits) are also supported.
+ *
+ * ```
+ * use arp_scan::MacAddr;
+ * use arp_scan::vendor::VendorDatabase;
+ *
+ * let database = VendorDatabase::load_from_path("./data/ieee-oui.csv").unwrap();
+ * let mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
+ *
+ * assert_eq!(database.lookup(&mac), Some("American Micro-Fuel Device Corp."));
+ * ```
+ */
+pub struct VendorDatabase {
+    prefixes: VendorPrefixes,
+    virtualization: PrefixIndex
 }
 
-impl Vendor {
+// Vendor prefixes are either loaded from a file, or from the snapshot
+// embedded in the binary.
+enum VendorPrefixes {
+    Disabled,
+    File(PrefixIndex),
+    Embedded
+}
 
-    // Create a new MAC vendor search instance based on the given datebase path
-    // (absolute or relative). A failure will not throw an error, but leave the
-    // vendor search instance without database reader.
-    pub fn new(path: &str) -> Self {
+// Vendor prefixes indexed by OUI (their first 24 bits), so that most lookups
+// are a single map search. Longer MA-M and MA-S assignments are carved from a
+// few OUIs only: they are kept in secondary tables, which are only searched
+// when the OUI of the MAC address has such assignments. Shorter prefixes
+// (like the /16 of Docker) are searched last.
+#[derive(Default)]
+struct PrefixIndex {
+    ouis: HashMap<u64, OuiPrefixes>,
+    longer: Vec<PrefixTable>,
+    shorter: Vec<PrefixTable>
+}
 
-        let file_result = File::open(path);
-        
-        match file_result {
-            Ok(file) => Vendor {
-                reader: Some(Reader::from_reader(file)),
+#[derive(Default)]
+struct OuiPrefixes {
+    vendor: Option<String>,
+    has_longer: bool
+}
+
+// All the vendor prefixes of a given length (in bits), indexed by the first
+// bits of the MAC addresses they cover.
+struct PrefixTable {
+    length: u8,
+    vendors: HashMap<u64, String>
+}
+
+impl VendorDatabase {
+
+    /**
+     * Create a vendor database without any prefix, all lookups will return
+     * no vendor.
+     */
+    pub fn disabled() -> Self {
+
+        VendorDatabase {
+            prefixes: VendorPrefixes::Disabled,
+            virtualization: build_virtualization_tables(vec![])
+        }
+    }
+
+    /**
+     * Create a vendor database based on the snapshot embedded in the binary.
+     * The snapshot is only decoded by the first lookup, so that scans without
+     * vendor lookups do not pay for it.
+     */
+    pub fn embedded() -> Self {
+
+        match snapshot_entry_count(EMBEDDED_SNAPSHOT) {
+            Some(entry_count) if entry_count > 0 => VendorDatabase {
+                prefixes: VendorPrefixes::Embedded,
+                virtualization: build_virtualization_tables(vec![])
             },
-            Err(_) => Vendor {
-                reader: None,
-            }
+            _ => VendorDatabase::disabled()
         }
     }
 
+    /**
+     * Load the default vendor database, as the CLI does: the database
+     * downloaded by 'arp-scan --update-vendors' if any, otherwise the
+     * database installed with arp-scan. The snapshot embedded in the binary
+     * is used when this file cannot be loaded.
+     *
+     * ```
+     * use arp_scan::vendor::VendorDatabase;
+     *
+     * let database = VendorDatabase::load_default();
+     * println!("{} vendor prefixes loaded", database.prefix_count());
+     * ```
+     */
+    pub fn load_default() -> Self {
+
+        VendorDatabase::load_default_from(default_vendor_file_path(false)).0
+    }
+
+    /**
+     * Load the default vendor database from a given vendor file, the
+     * snapshot embedded in the binary is used when this file cannot be
+     * loaded. The reason is then returned along with the database, so that
+     * callers can report it.
+     */
+    pub fn load_default_from(path: impl AsRef<Path>) -> (Self, Option<String>) {
+
+        match VendorDatabase::load_from_path(path) {
+            Ok(database) => (database, None),
+            Err(err) => (VendorDatabase::embedded(), Some(err))
+        }
+    }
+
+    /**
+     * Load all the vendor prefixes of a database file in memory, so that
+     * each lookup is a map search per prefix length. Both the IEEE CSV
+     * layout and the Wireshark 'manuf' format are supported, the format is
+     * detected from the first entry. Unreadable files, malformed records and
+     * files without any vendor prefix are rejected.
+     */
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, String> {
+
+        let path = path.as_ref().display().to_string();
+        let content = fs::read_to_string(&path).map_err(|err| format!("Could not open vendor file {} ({})", path, err))?;
+        let is_manuf = is_manuf_format(&content);
+        let entries = match is_manuf {
+            true => parse_manuf(&content),
+            false => parse_ieee_csv(&content)
+        }.map_err(|err| format!("Malformed vendor file {} ({})", path, err))?;
+
+        if entries.is_empty() {
+            return Err(format!("Malformed vendor file {} (no vendor prefix found)", path));
+        }
+
+        let virtualization_hints = match is_manuf {
+            true => parse_manuf_virtualization(&content),
+            false => vec![]
+        };
+        Ok(VendorDatabase {
+            prefixes: VendorPrefixes::File(PrefixIndex::build(entries)),
+            virtualization: build_virtualization_tables(virtualization_hints)
+        })
+    }
+
+    /**
+     * Check if the database has vendor prefixes (or may have some, for the
+     * embedded snapshot).
+     */
     pub fn has_vendor_db(&self) -> bool {
-        self.reader.is_some()
-    }
-
-    // Find a vendor name based on a given MAC address. A vendor search
-    // operation will perform a whole read on the database for now.
-    pub fn search_by_mac(&mut self, mac_address: &MacAddr) -> Option<String> {
-
-        match &mut self.reader {
-            Some(reader) => {
-
-                // The {:02X} syntax forces to pad all numbers with zero values.
-                // This ensures that a MAC 002272... will not be printed as
-                // 02272 and therefore fails the search process.
-                let vendor_oui = format!("{:02X}{:02X}{:02X}", mac_address.0, mac_address.1, mac_address.2);
-
-                // Since we share a common instance of the CSV reader, it must be reset
-                // before each read (internal buffers will be cleared).
-                reader.seek(Position::new()).unwrap_or_else(|err| {
-                    eprintln!("Could not reset the CSV reader ({})", err);
-                    process::exit(1);
-                });
-
-                for vendor_result in reader.records() {
-            
-                    let record = vendor_result.unwrap_or_else(|err| {
-                        eprintln!("Could not read CSV record ({})", err);
-                        process::exit(1);
-                    });
-                    let potential_oui = record.get(1).unwrap_or("");
-            
-                    if vendor_oui.eq(potential_oui) {
-                        return Some(record.get(2).unwrap_or("(no vendor)").to_string())
-                    }
-                }
-
-                None
-            }
-            None => None
+        !matches!(self.prefixes, VendorPrefixes::Disabled)
+    }
+
+    /**
+     * Count the vendor prefixes of the database.
+     */
+    pub fn prefix_count(&self) -> usize {
+
+        match &self.prefixes {
+            VendorPrefixes::Disabled => 0,
+            VendorPrefixes::File(prefixes) => prefixes.len(),
+            VendorPrefixes::Embedded => snapshot_entry_count(EMBEDDED_SNAPSHOT).unwrap_or(0)
         }
     }
+
+    /**
+     * Find the vendor name of a MAC address, based on its longest known
+     * prefix: a MA-S assignment (36 bits) takes precedence over the MA-L
+     * block (24 bits) it is carved from.
+     *
+     * ```
+     * use arp_scan::{MacAddr, VendorDatabase};
+     *
+     * let database = VendorDatabase::load_from_path("./data/manuf-sample.txt").unwrap();
+     *
+     * assert_eq!(database.lookup(&MacAddr::new(0x00, 0x50, 0xc2, 0x00, 0x0a, 0x01)), Some("T.L.S. Corp."));
+     * assert_eq!(database.lookup(&MacAddr::new(0x00, 0x50, 0xc2, 0xff, 0x00, 0x01)), Some("IEEE Registration Authority"));
+     * assert_eq!(database.lookup(&MacAddr::new(0x02, 0x00, 0x00, 0x00, 0x00, 0x01)), None);
+     * ```
+     */
+    pub fn lookup(&self, mac_address: &MacAddr) -> Option<&str> {
+
+        let prefixes = match &self.prefixes {
+            VendorPrefixes::Disabled => return None,
+            VendorPrefixes::File(prefixes) => prefixes,
+            VendorPrefixes::Embedded => EMBEDDED_PREFIXES.get_or_init(|| {
+                PrefixIndex::build(decode_snapshot(EMBEDDED_SNAPSHOT).unwrap_or_default())
+            })
+        };
+
+        prefixes.search(mac_address)
+    }
+
+    /**
+     * Find the virtualization product (VMware, VirtualBox, Docker, ...) that
+     * usually owns the prefix of a MAC address. Only known prefixes are
+     * tagged, other locally administered MACs (such as randomized MACs) are
+     * not considered as virtual machines.
+     */
+    pub fn search_virtualization(&self, mac_address: &MacAddr) -> Option<String> {
+
+        self.virtualization.search(mac_address).map(|name| name.to_string())
+    }
     
 }
 
+impl PrefixIndex {
+
+    // Index vendor prefixes by OUI, then by length for the prefixes that are
+    // not 24 bits long. The first entry wins when a prefix is listed twice.
+    fn build(entries: Vec<VendorEntry>) -> Self {
+
+        let mut index = PrefixIndex::default();
+        for (value, length, name) in entries {
+
+            let oui = value >> (MAC_BITS - OUI_BITS);
+            match length.cmp(&OUI_BITS) {
+                Ordering::Equal => {
+                    index.ouis.entry(oui).or_default().vendor.get_or_insert(name);
+                },
+                Ordering::Greater => {
+                    index.ouis.entry(oui).or_default().has_longer = true;
+                    insert_prefix(&mut index.longer, value, length, name);
+                },
+                Ordering::Less => insert_prefix(&mut index.shorter, value, length, name)
+            }
+        }
+
+        // Longer prefixes are more specific, they are searched first.
+        index.longer.sort_by_key(|table| Reverse(table.length));
+        index.shorter.sort_by_key(|table| Reverse(table.length));
+        index
+    }
+
+    fn len(&self) -> usize {
+
+        let oui_count = self.ouis.values().filter(|prefixes| prefixes.vendor.is_some()).count();
+        oui_count + self.longer.iter().chain(self.shorter.iter()).map(|table| table.vendors.len()).sum::<usize>()
+    }
+
+    fn search(&self, mac_address: &MacAddr) -> Option<&str> {
+
+        let mac_value = mac_to_u64(mac_address);
+        let oui_prefixes = self.ouis.get(&(mac_value >> (MAC_BITS - OUI_BITS)));
+        let longer_vendor = match oui_prefixes {
+            Some(prefixes) if prefixes.has_longer => search_prefix_tables(&self.longer, mac_value),
+            _ => None
+        };
+
+        longer_vendor
+            .or_else(|| oui_prefixes.and_then(|prefixes| prefixes.vendor.as_deref()))
+            .or_else(|| search_prefix_tables(&self.shorter, mac_value))
+    }
+}
+
+fn insert_prefix(prefixes: &mut Vec<PrefixTable>, value: u64, length: u8, name: String) {
+
+    let table_index = match prefixes.iter().position(|table| table.length == length) {
+        Some(table_index) => table_index,
+        None => {
+            prefixes.push(PrefixTable { length, vendors: HashMap::new() });
+            prefixes.len() - 1
+        }
+    };
+    prefixes[table_index].vendors.entry(value >> (MAC_BITS - length)).or_insert(name);
+}
+
+// The built-in virtualization prefixes, completed by the hints of a vendor
+// file (which take precedence).
+fn build_virtualization_tables(mut hints: Vec<VendorEntry>) -> PrefixIndex {
+
+    hints.extend(VIRTUALIZATION_PREFIXES.iter().map(|(value, length, name)| (*value, *length, name.to_string())));
+    PrefixIndex::build(hints)
+}
+
+fn search_prefix_tables(prefixes: &[PrefixTable], mac_value: u64) -> Option<&str> {
+
+    prefixes.iter().find_map(|table| {
+        table.vendors.get(&(mac_value >> (MAC_BITS - table.length))).map(|name| name.as_str())
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<usize> {
+
+    bytes.get(offset..offset + 4).map(|value| u32::from_le_bytes([value[0], value[1], value[2], value[3]]) as usize)
+}
+
+// Both snapshot layouts start with the entry count, so that it is known
+// without decompressing the snapshot.
+fn snapshot_entry_count(snapshot: &[u8]) -> Option<usize> {
+
+    match snapshot.starts_with(SNAPSHOT_MAGIC) || snapshot.starts_with(COMPRESSED_SNAPSHOT_MAGIC) {
+        true => read_u32(snapshot, SNAPSHOT_MAGIC.len()),
+        false => None
+    }
+}
+
+// Decode a vendor snapshot, as written by the 'vendor-snapshot.py' script: a
+// table of deduplicated vendor names, followed by the prefixes (prefix length,
+// prefix bytes & name index). Compressed snapshots hold the same layout as a
+// raw DEFLATE stream.
+fn decode_snapshot(snapshot: &[u8]) -> Result<Vec<VendorEntry>, String> {
+
+    let invalid_snapshot = || "invalid vendor snapshot".to_string();
+    if snapshot.starts_with(COMPRESSED_SNAPSHOT_MAGIC) {
+        let compressed = snapshot.get(COMPRESSED_SNAPSHOT_MAGIC.len() + 4..).ok_or_else(invalid_snapshot)?;
+        let decompressed = decompress_to_vec(compressed).map_err(|err| format!("invalid vendor snapshot ({})", err))?;
+        if !decompressed.starts_with(SNAPSHOT_MAGIC) {
+            return Err(invalid_snapshot());
+        }
+        return decode_snapshot(&decompressed);
+    }
+    let entry_count = snapshot_entry_count(snapshot).ok_or_else(invalid_snapshot)?;
+    let name_count = read_u32(snapshot, SNAPSHOT_MAGIC.len() + 4).ok_or_else(invalid_snapshot)?;
+
+    let mut offset = SNAPSHOT_MAGIC.len() + 8;
+    let mut names: Vec<String> = Vec::with_capacity(name_count);
+    for _ in 0..name_count {
+        let name_size = usize::from(*snapshot.get(offset).ok_or_else(invalid_snapshot)?);
+        let name = snapshot.get(offset + 1..offset + 1 + name_size).ok_or_else(invalid_snapshot)?;
+        names.push(String::from_utf8_lossy(name).to_string());
+        offset += 1 + name_size;
+    }
+
+    let mut entries: Vec<VendorEntry> = Vec::with_capacity(entry_count);
+    for _ in 0..entry_count {
+        let length = *snapshot.get(offset).ok_or_else(invalid_snapshot)?;
+        if length == 0 || length > MAC_BITS {
+            return Err(invalid_snapshot());
+        }
+        let prefix_size = usize::from(length).div_ceil(8);
+        let prefix = snapshot.get(offset + 1..offset + 1 + prefix_size).ok_or_else(invalid_snapshot)?;
+        let value = prefix.iter().enumerate().fold(0, |value, (byte_index, byte)| value | (u64::from(*byte) << (40 - byte_index * 8)));
+        let name_index = snapshot.get(offset + 1 + prefix_size..offset + 3 + prefix_size).map(|index| usize::from(u16::from_le_bytes([index[0], index[1]]))).ok_or_else(invalid_snapshot)?;
+        entries.push((value, length, names.get(name_index).ok_or_else(invalid_snapshot)?.clone()));
+        offset += 3 + prefix_size;
+    }
+    Ok(entries)
+}
+
+fn mac_to_u64(mac_address: &MacAddr) -> u64 {
+
+    [mac_address.0, mac_address.1, mac_address.2, mac_address.3, mac_address.4, mac_address.5].iter()
+        .fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+// A vendor prefix: the MAC value of the first covered address, the prefix
+// length (in bits) and the vendor name.
+type VendorEntry = (u64, u8, String);
+
+// Parse the IEEE CSV layout, where assignments are written in hexadecimal:
+// 6 digits for an OUI (MA-L, such as 002272), 7 digits for a MA-M block and
+// 9 digits for a MA-S block. Header lines of concatenated registries are
+// ignored like any other invalid assignment.
+fn parse_ieee_csv(content: &str) -> Result<Vec<VendorEntry>, String> {
+
+    let mut reader = Reader::from_reader(content.as_bytes());
+    let mut entries: Vec<VendorEntry> = vec![];
+    for vendor_result in reader.records() {
+
+        let record = vendor_result.map_err(|err| err.to_string())?;
+        if let Some((value, length)) = record.get(1).and_then(parse_assignment) {
+            entries.push((value, length, record.get(2).unwrap_or("(no vendor)").to_string()));
+        }
+    }
+    Ok(entries)
+}
+
+fn parse_assignment(assignment: &str) -> Option<(u64, u8)> {
+
+    let assignment = assignment.trim();
+    let length: u8 = match assignment.len() {
+        6 => 24,
+        7 => 28,
+        9 => 36,
+        _ => return None
+    };
+    if !assignment.chars().all(|character| character.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u64::from_str_radix(assignment, 16).ok().map(|value| (value << (MAC_BITS - length), length))
+}
+
+// A Wireshark 'manuf' file starts with comments, its first entry begins with
+// a prefix such as 00:00:0C (while the IEEE CSV starts with a header line).
+fn is_manuf_format(content: &str) -> bool {
+
+    content.lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| parse_manuf_prefix(line.split_whitespace().next().unwrap_or_default()).is_some())
+        .unwrap_or(false)
+}
+
+// Parse the Wireshark 'manuf' format: each line holds a prefix, a short name
+// and an optional long name, separated by tabs. Prefixes are written with
+// 3 bytes (a /24 OUI) or with an explicit length like 00:50:C2:00:00:00/36.
+// The long name is preferred when present.
+fn parse_manuf(content: &str) -> Result<Vec<VendorEntry>, String> {
+
+    let mut entries: Vec<VendorEntry> = vec![];
+    for (line_index, line) in content.lines().enumerate() {
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split('\t').map(|field| field.trim()).filter(|field| !field.is_empty());
+        let (value, length) = fields.next().and_then(parse_manuf_prefix).ok_or_else(|| {
+            format!("invalid prefix at line {}", line_index + 1)
+        })?;
+        let short_name = fields.next().ok_or_else(|| format!("missing vendor name at line {}", line_index + 1))?;
+
+        // Older files write the long name as a trailing comment.
+        let long_name = fields.find(|field| !field.starts_with(VIRTUALIZATION_FIELD)).map(|name| name.trim_start_matches('#').trim()).filter(|name| !name.is_empty());
+        entries.push((value, length, long_name.unwrap_or(short_name).to_string()));
+    }
+    Ok(entries)
+}
+
+// Parse the virtualization hints of a 'manuf' file, given as an additional
+// 'virtualization=<name>' field (such as 00:16:3E, Xensourc & virtualization=Xen).
+fn parse_manuf_virtualization(content: &str) -> Vec<VendorEntry> {
+
+    content.lines().filter(|line| !line.trim().starts_with('#')).filter_map(|line| {
+        let mut fields = line.split('\t').map(|field| field.trim());
+        let (value, length) = fields.next().and_then(parse_manuf_prefix)?;
+        let name = fields.find_map(|field| field.strip_prefix(VIRTUALIZATION_FIELD)).filter(|name| !name.is_empty())?;
+        Some((value, length, name.to_string()))
+    }).collect()
+}
+
+fn parse_manuf_prefix(prefix: &str) -> Option<(u64, u8)> {
+
+    let (address, length) = match prefix.split_once('/') {
+        Some((address, length)) => (address, Some(length.parse::<u8>().ok()?)),
+        None => (prefix, None)
+    };
+
+    let bytes: Vec<u8> = address.split([':', '-', '.']).map(|byte| {
+        match byte.len() == 2 {
+            true => u8::from_str_radix(byte, 16).ok(),
+            false => None
+        }
+    }).collect::<Option<Vec<u8>>>()?;
+    if bytes.is_empty() || bytes.len() > 6 {
+        return None;
+    }
+
+    let length = length.unwrap_or(bytes.len() as u8 * 8);
+    if length == 0 || length > MAC_BITS {
+        return None;
+    }
+    let value = bytes.iter().enumerate().fold(0, |value, (byte_index, byte)| value | (u64::from(*byte) << (40 - byte_index * 8)));
+    Some((value, length))
+}
+
+// The per-user data directory of arp-scan, where the downloaded vendor
+// database is stored. The XDG base directory is used on Linux.
+fn user_data_directory() -> Option<PathBuf> {
+
+    if cfg!(target_os = "windows") {
+        return env::var_os("LOCALAPPDATA").map(|path| PathBuf::from(path).join("arp-scan"));
+    }
+    if cfg!(target_os = "macos") {
+        return env::var_os("HOME").map(|path| PathBuf::from(path).join("Library/Application Support/arp-scan"));
+    }
+
+    env::var_os("XDG_DATA_HOME").map(PathBuf::from).filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|path| PathBuf::from(path).join(".local/share")))
+        .map(|path| path.join("arp-scan"))
+}
+
+/**
+ * Path of the vendor database downloaded with '--update-vendors'.
+ */
+pub fn user_vendor_file_path() -> Option<PathBuf> {
+
+    user_data_directory().map(|directory| directory.join(USER_VENDOR_FILE_NAME))
+}
+
+/**
+ * Path of the default vendor file: the database downloaded with
+ * '--update-vendors' if it exists (unless offline), otherwise the database
+ * installed with arp-scan.
+ */
+pub fn default_vendor_file_path(offline: bool) -> PathBuf {
+
+    match offline {
+        true => None,
+        false => user_vendor_file_path().filter(|path| path.is_file())
+    }.unwrap_or_else(|| PathBuf::from(SYSTEM_VENDOR_FILE_PATH))
+}
+
+/**
+ * Age of a vendor database, based on its last modification.
+ */
+pub fn vendor_file_age(path: &Path) -> Option<Duration> {
+
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok().and_then(|modified| modified.elapsed().ok())
+}
+
+/**
+ * The result of a vendor database update: the amount of loaded prefixes and
+ * the age of the replaced database (if any).
+ */
+pub struct VendorUpdate {
+    pub prefix_count: usize,
+    pub previous_age: Option<Duration>
+}
+
+/**
+ * Download the IEEE registries (MA-L, MA-M & MA-S) to the given path, as a
+ * single CSV file. The file is written next to the previous copy, then
+ * validated and atomically moved in place: a network error or an invalid
+ * download leaves the previous copy untouched.
+ */
+pub fn update_vendor_file(path: &Path) -> Result<VendorUpdate, String> {
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|err| format!("Could not create directory {} ({})", directory.display(), err))?;
+    }
+    let previous_age = vendor_file_age(path);
+
+    let mut content: Vec<u8> = vec![];
+    for registry_url in IEEE_REGISTRY_URLS {
+        content.extend(download(registry_url)?);
+        if !content.ends_with(b"\n") {
+            content.push(b'\n');
+        }
+    }
+
+    let download_path = path.with_extension("csv.download");
+    let validation_result = fs::write(&download_path, &content)
+        .map_err(|err| format!("Could not write vendor file {} ({})", download_path.display(), err))
+        .and_then(|_| VendorDatabase::load_from_path(&download_path));
+
+    let prefix_count = validation_result.map(|vendor_list| vendor_list.prefix_count()).inspect_err(|_| {
+        fs::remove_file(&download_path).unwrap_or(());
+    })?;
+    fs::rename(&download_path, path).map_err(|err| {
+        fs::remove_file(&download_path).unwrap_or(());
+        format!("Could not replace vendor file {} ({})", path.display(), err)
+    })?;
+
+    Ok(VendorUpdate {
+        prefix_count,
+        previous_age
+    })
+}
+
+// The registries are downloaded over HTTPS in process (rustls), without
+// depending on a system TLS library or download tool.
+fn download(url: &str) -> Result<Vec<u8>, String> {
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECONDS))
+        .user_agent(concat!("arp-scan/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let response = agent.get(url).call().map_err(|err| format!("Could not download {} ({})", url, err))?;
+
+    let mut content: Vec<u8> = vec![];
+    response.into_reader().read_to_end(&mut content).map_err(|err| format!("Could not download {} ({})", url, err))?;
+    Ok(content)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
 
     #[test]
     fn should_create_vendor_resolver() {
         
-        let vendor = Vendor::new("./data/ieee-oui.csv");
+        let vendor = VendorDatabase::load_from_path("./data/ieee-oui.csv").ok().unwrap();
 
-        assert_eq!(vendor.has_vendor_db(), true);
+        assert!(vendor.has_vendor_db());
     }
 
     #[test]
     fn should_handle_unresolved_database() {
         
-        let vendor = Vendor::new("./unknown.csv");
+        let vendor = VendorDatabase::disabled();
+
+        assert!(VendorDatabase::load_from_path("./unknown.csv").is_err());
+        assert!(!vendor.has_vendor_db());
+    }
+
+    #[test]
+    fn should_load_vendor_prefixes() {
+
+        let vendor = VendorDatabase::load_from_path("./data/ieee-oui.csv").unwrap();
+
+        assert_eq!(vendor.prefix_count(), 32);
+    }
+
+    #[test]
+    fn should_reject_invalid_vendor_files() {
+
+        assert!(VendorDatabase::load_from_path("./unknown.csv").err().unwrap().starts_with("Could not open vendor file ./unknown.csv"));
+        assert_eq!(VendorDatabase::load_from_path("./data/ip-list.txt").err(), Some("Malformed vendor file ./data/ip-list.txt (no vendor prefix found)".to_string()));
+    }
+
+    #[test]
+    fn should_parse_manuf_prefixes() {
+
+        assert_eq!(parse_manuf_prefix("00:00:0C"), Some((0x00000c000000, 24)));
+        assert_eq!(parse_manuf_prefix("00:50:C2:00:00:00/36"), Some((0x0050c2000000, 36)));
+        assert_eq!(parse_manuf_prefix("00-1B-C5-00-00/28"), Some((0x001bc5000000, 28)));
+        assert_eq!(parse_manuf_prefix("00:50:C2:00:00:00/49"), None);
+        assert_eq!(parse_manuf_prefix("Registry"), None);
+    }
+
+    #[test]
+    fn should_load_manuf_file() {
+
+        let vendor = VendorDatabase::load_from_path("./data/manuf-sample.txt").ok().unwrap();
+
+        assert_eq!(vendor.prefix_count(), 7);
+        assert_eq!(vendor.lookup(&MacAddr::new(0x00, 0x00, 0x0c, 0x12, 0x34, 0x56)), Some("Cisco Systems, Inc"));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x00, 0x00, 0x01, 0x12, 0x34, 0x56)), Some("Xerox"));
+    }
+
+    #[test]
+    fn should_prefer_longest_manuf_prefix() {
+
+        let vendor = VendorDatabase::load_from_path("./data/manuf-sample.txt").ok().unwrap();
+
+        assert_eq!(vendor.lookup(&MacAddr::new(0x00, 0x50, 0xc2, 0x00, 0x0a, 0x01)), Some("T.L.S. Corp."));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x70, 0xb3, 0xd5, 0x0e, 0x12, 0x34)), Some("Kontron Europe GmbH"));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x00, 0x50, 0xc2, 0xff, 0x00, 0x01)), Some("IEEE Registration Authority"));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x70, 0xb3, 0xd5, 0xff, 0x00, 0x01)), Some("IEEE Registration Authority"));
+    }
+
+    #[test]
+    fn should_reject_malformed_manuf_file() {
+
+        assert_eq!(parse_manuf("00:00:0C\tCisco\nnot-a-prefix\tVendor"), Err("invalid prefix at line 2".to_string()));
+        assert_eq!(parse_manuf("00:00:0C"), Err("missing vendor name at line 1".to_string()));
+    }
+
+    #[test]
+    fn should_store_vendors_in_user_data_directory() {
+
+        if let Some(path) = user_vendor_file_path() {
+            assert!(path.ends_with("arp-scan/ieee-oui.csv"));
+        }
+    }
+
+    #[test]
+    fn should_decode_vendor_snapshot() {
+
+        let snapshot = fs::read("./data/vendor-snapshot.bin").unwrap();
+        let prefixes = PrefixIndex::build(decode_snapshot(&snapshot).unwrap());
+
+        assert_eq!(snapshot_entry_count(&snapshot), Some(32));
+        assert_eq!(prefixes.search(&MacAddr::new(0x40, 0x55, 0x82, 0x00, 0x00, 0x01)), Some("Nokia"));
+    }
+
+    #[test]
+    fn should_reject_truncated_vendor_snapshot() {
+
+        let snapshot = fs::read("./data/vendor-snapshot.bin").unwrap();
+
+        assert!(decode_snapshot(&snapshot[..snapshot.len() - 1]).is_err());
+        assert!(decode_snapshot(b"ARPVEND").is_err());
+    }
+
+    #[test]
+    fn should_decode_compressed_vendor_snapshot() {
+
+        let snapshot = fs::read("./data/vendor-snapshot.bin").unwrap();
+        let compressed = fs::read("./data/vendor-snapshot-compressed.bin").unwrap();
+
+        assert_eq!(snapshot_entry_count(&compressed), Some(32));
+        assert_eq!(decode_snapshot(&compressed).unwrap(), decode_snapshot(&snapshot).unwrap());
+        assert!(decode_snapshot(&compressed[..compressed.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn should_look_up_embedded_vendors() {
+
+        let vendor = VendorDatabase::embedded();
+
+        assert!(vendor.has_vendor_db());
+        assert_eq!(vendor.prefix_count(), snapshot_entry_count(EMBEDDED_SNAPSHOT).unwrap());
+        assert!(vendor.prefix_count() > 40_000);
+        assert_eq!(vendor.lookup(&MacAddr::new(0xf4, 0xbd, 0x9e, 0x12, 0x34, 0x56)), Some("Cisco Systems, Inc"));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x9c, 0x69, 0xb4, 0x30, 0x00, 0x01)), Some("Appareo Systems, Llc"));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x70, 0xb3, 0xd5, 0x44, 0xd0, 0x01)), Some("Vessel Tech Ltd"));
+    }
+
+    #[test]
+    fn should_load_default_vendors_from_given_file() {
+
+        let (vendor, file_error) = VendorDatabase::load_default_from("./data/ieee-oui.csv");
+        assert_eq!(file_error, None);
+        assert_eq!(vendor.lookup(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)), Some("American Micro-Fuel Device Corp."));
+
+        let (vendor, file_error) = VendorDatabase::load_default_from("./unknown.csv");
+        assert!(file_error.unwrap().starts_with("Could not open vendor file ./unknown.csv"));
+        assert_eq!(vendor.prefix_count(), VendorDatabase::embedded().prefix_count());
+        assert_eq!(default_vendor_file_path(true), PathBuf::from(SYSTEM_VENDOR_FILE_PATH));
+    }
+
+    #[test]
+    fn should_parse_ieee_assignments() {
+
+        assert_eq!(parse_assignment("002272"), Some((0x002272000000, 24)));
+        assert_eq!(parse_assignment("F8B568F"), Some((0xf8b568f00000, 28)));
+        assert_eq!(parse_assignment("70B3D5E12"), Some((0x70b3d5e12000, 36)));
+        assert_eq!(parse_assignment("Assignment"), None);
+        assert_eq!(parse_assignment("70B3D5E1"), None);
+    }
+
+    #[test]
+    fn should_prefer_ma_s_assignee_over_registry_owner() {
 
-        assert_eq!(vendor.has_vendor_db(), false);
+        let vendor = VendorDatabase::load_from_path("./data/ieee-registries.csv").ok().unwrap();
+
+        assert_eq!(vendor.prefix_count(), 4);
+        assert_eq!(vendor.lookup(&MacAddr::new(0x70, 0xb3, 0xd5, 0xe1, 0x2a, 0xbc)), Some("Smart Plug Devices Co."));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x70, 0xb3, 0xd5, 0xe1, 0x3a, 0xbc)), Some("IEEE Registration Authority"));
+    }
+
+    #[test]
+    fn should_prefer_ma_m_assignee_over_registry_owner() {
+
+        let vendor = VendorDatabase::load_from_path("./data/ieee-registries.csv").ok().unwrap();
+
+        assert_eq!(vendor.lookup(&MacAddr::new(0xf8, 0xb5, 0x68, 0xf1, 0x00, 0x01)), Some("Camera Works Ltd."));
+        assert_eq!(vendor.lookup(&MacAddr::new(0xf8, 0xb5, 0x68, 0x01, 0x00, 0x01)), Some("IEEE Registration Authority"));
+    }
+
+    #[test]
+    fn should_find_virtualization_prefixes() {
+
+        let vendor = VendorDatabase::disabled();
+
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x00, 0x50, 0x56, 0xa1, 0x02, 0x03)), Some("VMware".to_string()));
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x08, 0x00, 0x27, 0x4e, 0x1f, 0x20)), Some("VirtualBox".to_string()));
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x52, 0x54, 0x00, 0x12, 0x34, 0x56)), Some("QEMU/KVM".to_string()));
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x02, 0x42, 0xac, 0x11, 0x00, 0x02)), Some("Docker".to_string()));
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23)), None);
+    }
+
+    #[test]
+    fn should_not_tag_randomized_macs_as_virtual() {
+
+        let vendor = VendorDatabase::disabled();
+
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0xda, 0xa1, 0x19, 0x2b, 0x3c, 0x4d)), None);
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x02, 0x43, 0xac, 0x11, 0x00, 0x02)), None);
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x52, 0x54, 0x01, 0x12, 0x34, 0x56)), None);
+    }
+
+    #[test]
+    fn should_extend_virtualization_prefixes_with_manuf_file() {
+
+        let vendor = VendorDatabase::load_from_path("./data/manuf-sample.txt").ok().unwrap();
+
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x00, 0x16, 0x3e, 0x00, 0x00, 0x01)), Some("XCP-ng".to_string()));
+        assert_eq!(vendor.lookup(&MacAddr::new(0x00, 0x16, 0x3e, 0x00, 0x00, 0x01)), Some("XenSource, Inc."));
+        assert_eq!(vendor.search_virtualization(&MacAddr::new(0x00, 0x0c, 0x29, 0x00, 0x00, 0x01)), Some("VMware".to_string()));
     }
 
     #[test]
     fn should_find_specific_mac_vendor() {
         
-        let mut vendor = Vendor::new("./data/ieee-oui.csv");
+        let vendor = VendorDatabase::load_from_path("./data/ieee-oui.csv").ok().unwrap();
         let mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
 
-        assert_eq!(vendor.search_by_mac(&mac), Some("Nokia".to_string()));
+        assert_eq!(vendor.lookup(&mac), Some("Nokia"));
     }
 
     #[test]
     fn should_find_first_mac_vendor() {
         
-        let mut vendor = Vendor::new("./data/ieee-oui.csv");
+        let vendor = VendorDatabase::load_from_path("./data/ieee-oui.csv").ok().unwrap();
         let mac = MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23);
 
-        assert_eq!(vendor.search_by_mac(&mac), Some("American Micro-Fuel Device Corp.".to_string()));
+        assert_eq!(vendor.lookup(&mac), Some("American Micro-Fuel Device Corp."));
     }
 
     #[test]
     fn should_find_last_mac_vendor() {
         
-        let mut vendor = Vendor::new("./data/ieee-oui.csv");
+        let vendor = VendorDatabase::load_from_path("./data/ieee-oui.csv").ok().unwrap();
         let mac = MacAddr::new(0xcc, 0x9d, 0xa2, 0x14, 0x2e, 0x6f);
 
-        assert_eq!(vendor.search_by_mac(&mac), Some("Eltex Enterprise Ltd.".to_string()));
+        assert_eq!(vendor.lookup(&mac), Some("Eltex Enterprise Ltd."));
     }
 
     #[test]
     fn should_handle_unknown_mac_vendor() {
         
-        let mut vendor = Vendor::new("./data/ieee-oui.csv");
+        let vendor = VendorDatabase::load_from_path("./data/ieee-oui.csv").ok().unwrap();
         let mac = MacAddr::new(0xbb, 0xbb, 0xbb, 0xd2, 0xf5, 0xb6);
 
-        assert_eq!(vendor.search_by_mac(&mac), None);
+        assert_eq!(vendor.lookup(&mac), None);
+    }
+
+    // Reference search over all the entries of a vendor file, which keeps the
+    // semantics of the previous per-length tables: the longest prefix wins,
+    // then the first entry of the file.
+    fn search_linear(entries: &[VendorEntry], mac_address: &MacAddr) -> Option<String> {
+
+        let mac_value = mac_to_u64(mac_address);
+        let mut best_match: Option<&VendorEntry> = None;
+        for entry in entries.iter().filter(|(value, length, _)| value >> (MAC_BITS - length) == mac_value >> (MAC_BITS - length)) {
+            if best_match.map(|(_, length, _)| entry.1 > *length).unwrap_or(true) {
+                best_match = Some(entry);
+            }
+        }
+        best_match.map(|(_, _, name)| name.clone())
+    }
+
+    fn u64_to_mac(value: u64) -> MacAddr {
+
+        let bytes = value.to_be_bytes();
+        MacAddr::new(bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7])
+    }
+
+    #[test]
+    fn should_match_linear_search_results() {
+
+        for file_path in ["./data/ieee-oui.csv", "./data/ieee-registries.csv", "./data/manuf-sample.txt"] {
+
+            let content = fs::read_to_string(file_path).unwrap();
+            let entries = match is_manuf_format(&content) {
+                true => parse_manuf(&content).unwrap(),
+                false => parse_ieee_csv(&content).unwrap()
+            };
+            let index = PrefixIndex::build(entries.clone());
+
+            // The first and last addresses of each prefix, the addresses
+            // right outside of it and a spread of arbitrary addresses.
+            let mut mac_values: Vec<u64> = (0..1000u64).map(|step| step.wrapping_mul(0x9e3779b97f4a7c15) >> 16).collect();
+            for (value, length, _) in entries.iter() {
+                let block_size = 1u64 << (MAC_BITS - length);
+                mac_values.extend([*value, value + block_size - 1, value.wrapping_sub(1) & 0xffffffffffff, (value + block_size) & 0xffffffffffff]);
+            }
+
+            for mac_value in mac_values {
+                let mac = u64_to_mac(mac_value);
+                assert_eq!(index.search(&mac).map(|name| name.to_string()), search_linear(&entries, &mac), "{} in {}", mac, file_path);
+            }
+        }
     }
 
     #[test]
     fn should_pad_correctly_with_zeroes() {
         
-        let mut vendor = Vendor::new("./data/ieee-oui.csv");
+        let vendor = VendorDatabase::load_from_path("./data/ieee-oui.csv").ok().unwrap();
         let mac = MacAddr::new(0x01, 0x01, 0x01, 0x67, 0xb2, 0x1d);
 
-        assert_eq!(vendor.search_by_mac(&mac), Some("SomeCorp".to_string()));
+        assert_eq!(vendor.lookup(&mac), Some("SomeCorp"));
+    }
+
+    #[test]
+    fn should_download_registries_in_process() {
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/oui.csv", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let body = "Registry,Assignment,Organization Name,Organization Address\nMA-L,002272,American Micro-Fuel Device Corp.,Ferndale\n";
+            let (mut stream, _) = listener.accept().unwrap();
+            let request_size = stream.read(&mut [0u8; 1024]).unwrap();
+            assert!(request_size > 0);
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).unwrap();
+        });
+
+        let content = download(&url).unwrap();
+        server.join().unwrap();
+
+        assert!(content.starts_with(b"Registry,Assignment"));
+        assert!(content.ends_with(b"Ferndale\n"));
+    }
+
+    #[test]
+    fn should_report_download_errors() {
+
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        assert!(download(&format!("http://{}/oui.csv", address)).unwrap_err().starts_with(&format!("Could not download http://{}/oui.csv", address)));
     }
 
 }
@@ -0,0 +1,96 @@
+use pnet_datalink::MacAddr;
+use pnet::packet::ethernet::{EtherType, MutableEthernetPacket};
+
+use crate::datalink::DataLink;
+
+const MAGIC_SYNC_SIZE: usize = 6;
+const MAGIC_MAC_REPEAT: usize = 16;
+pub const MAGIC_PACKET_SIZE: usize = MAGIC_SYNC_SIZE + MAGIC_MAC_REPEAT * 6;
+
+// EtherType registered for Wake-on-LAN magic packets sent directly over
+// Ethernet (without IP & UDP headers).
+const WOL_ETHERTYPE: u16 = 0x0842;
+const ETHERNET_HEADER_SIZE: usize = 14;
+
+/**
+ * Build a Wake-on-LAN magic packet payload: a synchronization stream of six
+ * 0xff bytes, followed by sixteen repetitions of the target MAC address.
+ */
+pub fn build_magic_packet(mac: &MacAddr) -> [u8; MAGIC_PACKET_SIZE] {
+
+    let mut payload = [0xffu8; MAGIC_PACKET_SIZE];
+    let mac_bytes = mac.octets();
+
+    for repeat_index in 0..MAGIC_MAC_REPEAT {
+        let offset = MAGIC_SYNC_SIZE + repeat_index * mac_bytes.len();
+        payload[offset..offset + mac_bytes.len()].copy_from_slice(&mac_bytes);
+    }
+    payload
+}
+
+/**
+ * Build a broadcasted Ethernet frame holding the magic packet of the target
+ * MAC address, the source MAC being the one of the sending interface.
+ */
+pub fn build_wol_frame(source_mac: MacAddr, target_mac: &MacAddr) -> Vec<u8> {
+
+    let mut ethernet_buffer = vec![0u8; ETHERNET_HEADER_SIZE + MAGIC_PACKET_SIZE];
+    if let Some(mut ethernet_packet) = MutableEthernetPacket::new(&mut ethernet_buffer) {
+        ethernet_packet.set_destination(MacAddr::broadcast());
+        ethernet_packet.set_source(source_mac);
+        ethernet_packet.set_ethertype(EtherType::new(WOL_ETHERTYPE));
+        ethernet_packet.set_payload(&build_magic_packet(target_mac));
+    }
+    ethernet_buffer
+}
+
+/**
+ * Send a Wake-on-LAN magic packet for the target MAC address on the given
+ * interface. Sending errors are returned so that each MAC can be reported.
+ */
+pub fn send_magic_packet(link: &dyn DataLink, source_mac: MacAddr, target_mac: &MacAddr) -> Result<(), String> {
+
+    let ethernet_buffer = build_wol_frame(source_mac, target_mac);
+    link.send(&ethernet_buffer).map_err(|err| format!("Could not send magic packet to {} ({})", target_mac, err))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_start_magic_packet_with_sync_stream() {
+
+        let payload = build_magic_packet(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23));
+
+        assert_eq!(payload.len(), 102);
+        assert_eq!(&payload[0..6], &[0xff; 6]);
+    }
+
+    #[test]
+    fn should_repeat_mac_sixteen_times() {
+
+        let payload = build_magic_packet(&MacAddr::new(0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23));
+
+        for repeat in payload[6..].chunks(6) {
+            assert_eq!(repeat, &[0x00, 0x22, 0x72, 0xd7, 0xb5, 0x23]);
+        }
+        assert_eq!(payload[6..].chunks(6).count(), 16);
+    }
+
+    #[test]
+    fn should_broadcast_wol_frame() {
+
+        let source_mac = MacAddr::new(0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72);
+        let target_mac = MacAddr::new(0x40, 0x55, 0x82, 0xc3, 0xe5, 0x5b);
+        let frame = build_wol_frame(source_mac, &target_mac);
+
+        assert_eq!(frame.len(), 116);
+        assert_eq!(&frame[0..6], &[0xff; 6]);
+        assert_eq!(&frame[6..12], &[0xd2, 0x71, 0xd8, 0x29, 0xa8, 0x72]);
+        assert_eq!(&frame[12..14], &[0x08, 0x42]);
+        assert_eq!(&frame[14..], &build_magic_packet(&target_mac));
+    }
+
+}
@@ -0,0 +1,85 @@
+#!/usr/bin/env python3
+
+# VENDOR SNAPSHOT
+# This script builds the vendor database embedded in the binary, from the IEEE
+# registry CSV files (MA-L, MA-M & MA-S). The registries are downloaded when
+# no file is given:
+#
+#   ./vendor-snapshot.py > assets/ieee-oui.bin
+#   ./vendor-snapshot.py oui.csv mam.csv oui36.csv > assets/ieee-oui.bin
+#
+# The IEEE assignment export (a single CSV file with 'oui', 'companyName' and
+# 'assignmentBlockSize' columns, covering all registries) is accepted as well.
+#
+# Layout (little endian): the 'ARPVEND1' magic, the entry count (u32) and the
+# name count (u32), then each name (u8 size & UTF-8 bytes), then each entry
+# (u8 prefix length in bits, the prefix bytes & the u16 name index). With
+# '--uncompressed' this layout is written as is, otherwise it is written after
+# the 'ARPVENDZ' magic and the entry count (u32) as a raw DEFLATE stream.
+
+import csv
+import io
+import struct
+import sys
+import urllib.request
+import zlib
+
+IEEE_REGISTRY_URLS = [
+    'https://standards-oui.ieee.org/oui/oui.csv',
+    'https://standards-oui.ieee.org/oui28/mam.csv',
+    'https://standards-oui.ieee.org/oui36/oui36.csv'
+]
+
+ASSIGNMENT_BITS = {6: 24, 7: 28, 9: 36}
+EXPORTED_REGISTRIES = {'MA-L', 'MA-M', 'MA-S'}
+
+def read_assignments(content):
+
+    for record in csv.DictReader(io.StringIO(content, newline='')):
+        if 'Assignment' in record:
+            yield record['Assignment'], record['Organization Name']
+        elif record.get('assignmentBlockSize') in EXPORTED_REGISTRIES:
+            yield record['oui'].replace(':', ''), record['companyName']
+
+def read_registries(file_paths):
+
+    if file_paths:
+        for file_path in file_paths:
+            with open(file_path, newline='', encoding='utf-8') as csv_file:
+                yield csv_file.read()
+    else:
+        for url in IEEE_REGISTRY_URLS:
+            request = urllib.request.Request(url, headers={'User-Agent': 'arp-scan'})
+            with urllib.request.urlopen(request, timeout=120) as response:
+                yield response.read().decode('utf-8')
+
+arguments = [argument for argument in sys.argv[1:] if argument != '--uncompressed']
+is_compressed = '--uncompressed' not in sys.argv[1:]
+
+entries = {}
+for content in read_registries(arguments):
+    for assignment, organization_name in read_assignments(content):
+        assignment = assignment.strip().upper()
+        if len(assignment) not in ASSIGNMENT_BITS:
+            continue
+        prefix_bits = ASSIGNMENT_BITS[len(assignment)]
+        prefix_value = int(assignment, 16) << (48 - prefix_bits)
+        name = organization_name.strip().encode('utf-8')[:255]
+        entries.setdefault((prefix_bits, prefix_value), name.decode('utf-8', 'ignore').encode('utf-8'))
+
+names = sorted(set(entries.values()))
+name_indexes = {name: index for index, name in enumerate(names)}
+
+output = bytearray(b'ARPVEND1')
+output += struct.pack('<II', len(entries), len(names))
+for name in names:
+    output += struct.pack('<B', len(name)) + name
+for (prefix_bits, prefix_value), name in sorted(entries.items()):
+    prefix_bytes = prefix_value.to_bytes(6, 'big')[:(prefix_bits + 7) // 8]
+    output += struct.pack('<B', prefix_bits) + prefix_bytes + struct.pack('<H', name_indexes[name])
+
+if is_compressed:
+    compressor = zlib.compressobj(9, zlib.DEFLATED, -15)
+    output = b'ARPVENDZ' + struct.pack('<I', len(entries)) + compressor.compress(bytes(output)) + compressor.flush()
+
+sys.stdout.buffer.write(bytes(output))